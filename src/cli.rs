@@ -1,5 +1,8 @@
 use clap::{Parser, Subcommand};
 
+use crate::export::ExportFormat;
+use crate::protocol::messages::SignalTarget;
+
 /// Minimum allowed `--rows` value. Anything smaller is rejected; many TUIs
 /// behave badly below ~10 rows.
 pub const MIN_ROWS: u16 = 10;
@@ -18,6 +21,105 @@ fn parse_rows(s: &str) -> Result<u16, String> {
     Ok(n)
 }
 
+/// Parse a byte size such as `512M` or `4G` (binary units; a bare number
+/// is bytes).
+fn parse_size(s: &str) -> Result<u64, String> {
+    let t = s.trim();
+    let (digits, shift) = match t.char_indices().last() {
+        Some((i, c)) if c.is_ascii_alphabetic() => {
+            let shift = match c.to_ascii_uppercase() {
+                'K' => 10,
+                'M' => 20,
+                'G' => 30,
+                'T' => 40,
+                _ => return Err(format!("'{}' has an unknown size suffix (use K, M, G or T)", s)),
+            };
+            (&t[..i], shift)
+        }
+        _ => (t, 0),
+    };
+    let n: f64 = digits
+        .parse()
+        .map_err(|_| format!("'{}' is not a valid size", s))?;
+    if !(n > 0.0 && n.is_finite()) {
+        return Err(format!("size must be positive, got '{}'", s));
+    }
+    Ok((n * (1u64 << shift) as f64) as u64)
+}
+
+/// Parse a signal given as a name (`INT`, `SIGINT`, case-insensitive) or a
+/// number into its signal number.
+fn parse_signal(s: &str) -> Result<i32, String> {
+    use std::str::FromStr;
+    let sig = match s.parse::<i32>() {
+        Ok(n) => nix::sys::signal::Signal::try_from(n).ok(),
+        Err(_) => {
+            let upper = s.to_ascii_uppercase();
+            let name = if upper.starts_with("SIG") {
+                upper
+            } else {
+                format!("SIG{}", upper)
+            };
+            nix::sys::signal::Signal::from_str(&name).ok()
+        }
+    };
+    sig.map(|sig| sig as i32)
+        .ok_or_else(|| format!("'{}' is not a known signal", s))
+}
+
+fn parse_signal_target(s: &str) -> Result<SignalTarget, String> {
+    match s {
+        "child" => Ok(SignalTarget::Child),
+        "pgrp" => Ok(SignalTarget::Pgrp),
+        "tree" => Ok(SignalTarget::Tree),
+        _ => Err(format!("'{}' is not one of child, pgrp, tree", s)),
+    }
+}
+
+fn parse_export_format(s: &str) -> Result<ExportFormat, String> {
+    match s {
+        "html" => Ok(ExportFormat::Html),
+        "svg" => Ok(ExportFormat::Svg),
+        _ => Err(format!("'{}' is not one of html, svg", s)),
+    }
+}
+
+/// Parse a duration such as `10s`, `500ms` or `2m` into milliseconds. A
+/// bare number is seconds.
+fn parse_duration_ms(s: &str) -> Result<u64, String> {
+    let t = s.trim();
+    let (num, scale) = if let Some(n) = t.strip_suffix("ms") {
+        (n, 1.0)
+    } else if let Some(n) = t.strip_suffix('s') {
+        (n, 1000.0)
+    } else if let Some(n) = t.strip_suffix('m') {
+        (n, 60_000.0)
+    } else {
+        (t, 1000.0)
+    };
+    let n: f64 = num
+        .parse()
+        .map_err(|_| format!("'{}' is not a valid duration (e.g. 10s, 500ms)", s))?;
+    if !(n >= 0.0 && n.is_finite()) {
+        return Err(format!("duration must not be negative, got '{}'", s));
+    }
+    Ok((n * scale).round() as u64)
+}
+
+/// Shortest `amux top --json --interval`: each tick is a full
+/// `ListSessions` round trip.
+const MIN_TOP_INTERVAL_MS: u64 = 100;
+
+/// Parse `amux top --interval`, refusing anything that would poll the
+/// daemon in a tight loop.
+fn parse_top_interval_ms(s: &str) -> Result<u64, String> {
+    let ms = parse_duration_ms(s)?;
+    if ms < MIN_TOP_INTERVAL_MS {
+        return Err(format!("interval must be at least {}ms, got '{}'", MIN_TOP_INTERVAL_MS, s));
+    }
+    Ok(ms)
+}
+
 #[derive(Parser)]
 #[command(name = "amux", about = "AI Agent Multiplexer", version)]
 pub struct Cli {
@@ -64,6 +166,25 @@ pub enum Command {
         /// starting point, not a ceiling.
         #[arg(short = 'r', long = "rows", value_parser = parse_rows)]
         rows: Option<u16>,
+        /// Memory limit for the session's process tree, e.g. 512M or 4G.
+        /// Enforced via a cgroup when the daemon has a delegated cgroup v2
+        /// subtree, otherwise approximated with RLIMIT_AS.
+        #[arg(long = "memory", value_name = "SIZE", value_parser = parse_size)]
+        memory: Option<u64>,
+        /// CPU limit in cores (e.g. 0.5, 2). Requires cgroup v2.
+        #[arg(long = "cpus", value_name = "N")]
+        cpus: Option<f64>,
+        /// Maximum number of processes/threads (cgroup pids.max, or
+        /// RLIMIT_NPROC without a cgroup)
+        #[arg(long = "pids", value_name = "N")]
+        pids: Option<u64>,
+        /// Maximum open file descriptors per process (RLIMIT_NOFILE)
+        #[arg(long = "nofile", value_name = "N")]
+        nofile: Option<u64>,
+        /// Mark the session as needing attention (like a bell) whenever
+        /// its output contains PATTERN, e.g. "Do you want to proceed?"
+        #[arg(long = "attention", value_name = "PATTERN")]
+        attention: Vec<String>,
         /// Command to run
         #[arg(last = true, required = true)]
         cmd: Vec<String>,
@@ -74,6 +195,10 @@ pub enum Command {
         /// Target session name
         #[arg(short = 't', long = "target")]
         name: String,
+        /// Output chunks the daemon may queue for this client before it
+        /// skips ahead and repaints the screen (default 256)
+        #[arg(long, value_name = "CHUNKS")]
+        buffer: Option<u32>,
     },
     /// Follow session output (read-only streaming, no stdin)
     Follow {
@@ -86,6 +211,14 @@ pub enum Command {
         /// Strip ANSI escape sequences (default behavior, kept for backwards compat)
         #[arg(long, hide = true)]
         plain: bool,
+        /// Output chunks the daemon may queue for this client before it
+        /// skips ahead (default 256)
+        #[arg(long, value_name = "CHUNKS")]
+        buffer: Option<u32>,
+        /// Have the daemon compress output (LZ4), for slow links such as an
+        /// SSH-forwarded socket; the compression ratio is printed on exit
+        #[arg(long)]
+        compress: bool,
     },
     /// List sessions
     Ls {
@@ -102,10 +235,20 @@ pub enum Command {
         #[arg(long)]
         json: bool,
     },
-    /// Wait for a session to exit
+    /// Show resource usage history for a session (CPU, memory, threads,
+    /// fds and child processes, sampled every 2s for the last 10 minutes)
+    Stats {
+        /// Target session name
+        #[arg(short = 't', long = "target")]
+        name: String,
+        /// Output in JSON format
+        #[arg(long)]
+        json: bool,
+    },
+    /// Wait for a session to exit (or, with --attention, to need a human)
     Wait {
         /// Target session name (single session mode)
-        #[arg(short = 't', long = "target", required_unless_present = "any")]
+        #[arg(short = 't', long = "target", required_unless_present_any = ["any", "attention"])]
         name: Option<String>,
         /// Wait for any of the given sessions to exit
         #[arg(long, num_args = 1.., value_name = "SESSION")]
@@ -116,6 +259,11 @@ pub enum Command {
         /// Print the exit code after the session exits
         #[arg(long)]
         exit_code: bool,
+        /// Wait until a session rings the bell, raises a notification or
+        /// prints an attention pattern, then print its name (the target,
+        /// the --any sessions, or every live session)
+        #[arg(long, conflicts_with = "exit_code")]
+        attention: bool,
     },
     /// Watch multiple sessions and print exit events as they occur
     Watch {
@@ -130,7 +278,18 @@ pub enum Command {
         #[arg(long)]
         on_exit: Option<String>,
     },
-    /// Kill a session (or all sessions with --all)
+    /// Stream window-title changes and desktop notifications (OSC 0/2,
+    /// OSC 9/777) as sessions emit them
+    Events {
+        /// Sessions to watch (default: all, including ones created later)
+        sessions: Vec<String>,
+        /// Output one JSON object per event
+        #[arg(long)]
+        json: bool,
+    },
+    /// Kill a session (or all sessions with --all). The signal goes to the
+    /// session's whole process tree; anything still running after the
+    /// grace period is SIGKILLed and reported.
     Kill {
         /// Target session name
         #[arg(short = 't', long = "target", required_unless_present = "all")]
@@ -138,17 +297,128 @@ pub enum Command {
         /// Kill all sessions
         #[arg(long)]
         all: bool,
+        /// Signal to send first, by name (INT, SIGHUP) or number [default: TERM]
+        #[arg(
+            short = 's',
+            long = "signal",
+            value_parser = parse_signal,
+            conflicts_with = "all"
+        )]
+        signal: Option<i32>,
+        /// How long to wait before SIGKILLing survivors, e.g. 10s or 500ms
+        /// [default: 2s]
+        #[arg(
+            long = "grace",
+            value_name = "DURATION",
+            value_parser = parse_duration_ms,
+            conflicts_with = "all"
+        )]
+        grace: Option<u64>,
     },
     /// Kill all sessions
     KillAll,
-    /// Send keys to a session
-    Send {
+    /// Send a signal to a session (e.g. `amux signal -t w INT`)
+    Signal {
         /// Target session name
         #[arg(short = 't', long = "target")]
         name: String,
+        /// Signal name (INT, SIGUSR1, ...) or number
+        #[arg(value_parser = parse_signal)]
+        signal: i32,
+        /// Which processes get it: the main process, its process group, or
+        /// the whole process tree
+        #[arg(
+            long = "to",
+            value_name = "child|pgrp|tree",
+            default_value = "child",
+            value_parser = parse_signal_target
+        )]
+        to: SignalTarget,
+    },
+    /// Pause a session: SIGSTOP its whole process tree
+    Pause {
+        /// Target session name
+        #[arg(short = 't', long = "target")]
+        name: String,
+    },
+    /// Resume a paused session: SIGCONT its whole process tree
+    Resume {
+        /// Target session name
+        #[arg(short = 't', long = "target")]
+        name: String,
+    },
+    /// Send keys to a session
+    Send {
+        /// Target session name
+        #[arg(
+            short = 't',
+            long = "target",
+            required_unless_present_any = ["all", "any", "pattern"],
+            conflicts_with_all = ["all", "any", "pattern"]
+        )]
+        name: Option<String>,
+        /// Send to every live session
+        #[arg(long, conflicts_with_all = ["any", "pattern"])]
+        all: bool,
+        /// Send to each of these sessions (end the list with `--` before
+        /// the text)
+        #[arg(long, num_args = 1.., value_name = "SESSION", conflicts_with = "pattern")]
+        any: Vec<String>,
+        /// Send to every live session whose name matches this glob
+        /// (`*` and `?` wildcards)
+        #[arg(long = "match", value_name = "GLOB")]
+        pattern: Option<String>,
         /// Send literal text without trailing newline
         #[arg(short = 'l', long = "literal")]
         literal: bool,
+        /// Treat each argument as a key name (C-c, Escape, Up, Enter, F5,
+        /// M-x, ...); arguments that are not key names are sent as literal
+        /// text. No trailing Enter is added.
+        #[arg(
+            short = 'k',
+            long = "keys",
+            conflicts_with_all = ["literal", "all", "any", "pattern"]
+        )]
+        keys: bool,
+        /// Deliver the text as a paste: wrapped in bracketed-paste markers
+        /// when the session's program has enabled that mode, otherwise fed
+        /// line by line with --line-delay between lines
+        #[arg(
+            short = 'p',
+            long = "paste",
+            conflicts_with_all = ["keys", "all", "any", "pattern"]
+        )]
+        paste: bool,
+        /// Milliseconds to wait between lines when --paste has to fall back
+        /// to line-by-line delivery
+        #[arg(long = "line-delay", default_value = "50", requires = "paste")]
+        line_delay: u64,
+        /// Type the text at this many characters per second instead of
+        /// writing it all at once
+        #[arg(
+            long = "cps",
+            conflicts_with_all = ["keys", "paste", "all", "any", "pattern"]
+        )]
+        cps: Option<u32>,
+        /// Write at most this many characters per PTY write
+        #[arg(
+            long = "chunk-size",
+            conflicts_with_all = ["keys", "paste", "all", "any", "pattern"]
+        )]
+        chunk_size: Option<usize>,
+        /// Pause this many milliseconds after each newline
+        #[arg(
+            long = "newline-delay",
+            conflicts_with_all = ["keys", "paste", "all", "any", "pattern"]
+        )]
+        newline_delay: Option<u64>,
+        /// Return only once the daemon has written the last byte to the
+        /// session's PTY, not merely queued it
+        #[arg(
+            long = "wait-written",
+            conflicts_with_all = ["keys", "paste", "all", "any", "pattern"]
+        )]
+        wait_written: bool,
         /// Text to send
         text: Vec<String>,
     },
@@ -172,6 +442,26 @@ pub enum Command {
         /// Strip ANSI escape sequences (default behavior, kept for backwards compat)
         #[arg(long, hide = true)]
         plain: bool,
+        /// Print the screen as JSON: per-row text, styled runs, cursor,
+        /// alternate-screen flag and window title
+        #[arg(long, conflicts_with = "raw")]
+        json: bool,
+        /// Write a styled snapshot instead of text: `html` or `svg`
+        #[arg(
+            long,
+            value_name = "FORMAT",
+            value_parser = parse_export_format,
+            conflicts_with_all = ["raw", "json"]
+        )]
+        format: Option<ExportFormat>,
+        /// Color theme for --format: dark, light, solarized-dark, or a JSON
+        /// file with `foreground`, `background` and a 16-color `palette`
+        #[arg(long, default_value = "dark", requires = "format")]
+        theme: String,
+        /// Have the daemon LZ4-compress the capture in transit, for slow
+        /// links such as an SSH-forwarded socket
+        #[arg(long)]
+        compress: bool,
     },
     /// Get or set session-level environment variables
     Env {
@@ -179,7 +469,12 @@ pub enum Command {
         action: EnvAction,
     },
     /// Start the daemon server
-    StartServer,
+    StartServer {
+        /// Serve Prometheus metrics on ADDR: a TCP `host:port` or a Unix
+        /// socket path (also read from $AMUX_METRICS_ADDR)
+        #[arg(long, value_name = "ADDR")]
+        metrics: Option<String>,
+    },
     /// Stop daemon (use --force to kill sessions first)
     KillServer {
         /// Kill all sessions before stopping the server
@@ -191,6 +486,23 @@ pub enum Command {
         /// Print a single snapshot and exit (no TUI)
         #[arg(long)]
         once: bool,
+        /// Print one JSON document per tick instead of the TUI: every
+        /// session's info plus its output rate in bytes/sec
+        #[arg(long)]
+        json: bool,
+        /// Time between JSON documents, e.g. 2, 500ms, 1m; at least 100ms
+        /// [default: 2s]
+        #[arg(long, value_name = "DURATION", requires = "json", value_parser = parse_top_interval_ms)]
+        interval: Option<u64>,
+    },
+    /// Rename a session. The program inside keeps its old
+    /// `$AMUX_SESSION` until it is respawned.
+    Rename {
+        /// Current session name
+        #[arg(short = 't', long = "target")]
+        name: String,
+        /// New session name
+        new_name: String,
     },
     /// Atomically replace a session's child process with a new command,
     /// preserving the session name and any attached clients' output
@@ -332,6 +644,145 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_new_resource_limits() {
+        let cli = super::Cli::try_parse_from([
+            "amux", "new", "--memory", "4G", "--cpus", "1.5", "--pids", "512", "--nofile",
+            "4096", "--", "bash",
+        ])
+        .unwrap();
+        match cli.command.unwrap() {
+            super::Command::New {
+                memory,
+                cpus,
+                pids,
+                nofile,
+                ..
+            } => {
+                assert_eq!(memory, Some(4 << 30));
+                assert_eq!(cpus, Some(1.5));
+                assert_eq!(pids, Some(512));
+                assert_eq!(nofile, Some(4096));
+            }
+            other => panic!("expected New, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_wait_attention() {
+        let cli = super::Cli::try_parse_from(["amux", "wait", "--attention", "--timeout", "30"])
+            .unwrap();
+        match cli.command.unwrap() {
+            super::Command::Wait {
+                name,
+                attention,
+                timeout,
+                ..
+            } => {
+                assert!(name.is_none());
+                assert!(attention);
+                assert_eq!(timeout, 30);
+            }
+            other => panic!("expected Wait, got {:?}", other),
+        }
+        // Plain `wait` still needs a target.
+        assert!(super::Cli::try_parse_from(["amux", "wait"]).is_err());
+        assert!(
+            super::Cli::try_parse_from(["amux", "wait", "--attention", "--exit-code"]).is_err()
+        );
+
+        let cli = super::Cli::try_parse_from([
+            "amux", "new", "--attention", "proceed?", "--attention", "[y/n]", "--", "bash",
+        ])
+        .unwrap();
+        match cli.command.unwrap() {
+            super::Command::New { attention, .. } => {
+                assert_eq!(attention, vec!["proceed?", "[y/n]"]);
+            }
+            other => panic!("expected New, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_kill_signal_and_grace() {
+        let cli = super::Cli::try_parse_from([
+            "amux", "kill", "-t", "w", "--signal", "INT", "--grace", "10s",
+        ])
+        .unwrap();
+        match cli.command.unwrap() {
+            super::Command::Kill {
+                name,
+                signal,
+                grace,
+                ..
+            } => {
+                assert_eq!(name.as_deref(), Some("w"));
+                assert_eq!(signal, Some(nix::sys::signal::Signal::SIGINT as i32));
+                assert_eq!(grace, Some(10_000));
+            }
+            other => panic!("expected Kill, got {:?}", other),
+        }
+        let all_with_signal = ["amux", "kill", "--all", "--signal", "INT"];
+        assert!(super::Cli::try_parse_from(all_with_signal).is_err());
+    }
+
+    #[test]
+    fn test_parse_signal_and_duration() {
+        use nix::sys::signal::Signal;
+        assert_eq!(super::parse_signal("sighup"), Ok(Signal::SIGHUP as i32));
+        assert_eq!(super::parse_signal("kill"), Ok(Signal::SIGKILL as i32));
+        assert_eq!(super::parse_signal("15"), Ok(Signal::SIGTERM as i32));
+        assert!(super::parse_signal("NOPE").is_err());
+        assert!(super::parse_signal("999").is_err());
+        assert_eq!(super::parse_duration_ms("500ms"), Ok(500));
+        assert_eq!(super::parse_duration_ms("1.5s"), Ok(1500));
+        assert_eq!(super::parse_duration_ms("2m"), Ok(120_000));
+        assert_eq!(super::parse_duration_ms("3"), Ok(3000));
+        assert!(super::parse_duration_ms("soon").is_err());
+        assert_eq!(super::parse_top_interval_ms("100ms"), Ok(100));
+        assert!(super::parse_top_interval_ms("0").is_err());
+        assert!(super::parse_top_interval_ms("10ms").is_err());
+        let zero = ["amux", "top", "--json", "--interval", "0"];
+        assert!(super::Cli::try_parse_from(zero).is_err());
+    }
+
+    #[test]
+    fn test_signal_command() {
+        use nix::sys::signal::Signal;
+        let cli = super::Cli::try_parse_from(["amux", "signal", "-t", "w", "USR1"]).unwrap();
+        match cli.command.unwrap() {
+            super::Command::Signal { name, signal, to } => {
+                assert_eq!(name, "w");
+                assert_eq!(signal, Signal::SIGUSR1 as i32);
+                assert_eq!(to, super::SignalTarget::Child);
+            }
+            other => panic!("expected Signal, got {:?}", other),
+        }
+        let cli =
+            super::Cli::try_parse_from(["amux", "signal", "-t", "w", "INT", "--to", "tree"])
+                .unwrap();
+        assert!(matches!(
+            cli.command.unwrap(),
+            super::Command::Signal {
+                to: super::SignalTarget::Tree,
+                ..
+            }
+        ));
+        assert!(super::Cli::try_parse_from(["amux", "signal", "-t", "w", "INT", "--to", "all"])
+            .is_err());
+        assert!(super::Cli::try_parse_from(["amux", "pause", "-t", "w"]).is_ok());
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(super::parse_size("1024"), Ok(1024));
+        assert_eq!(super::parse_size("512M"), Ok(512 << 20));
+        assert_eq!(super::parse_size("1.5k"), Ok(1536));
+        assert!(super::parse_size("4X").is_err());
+        assert!(super::parse_size("0").is_err());
+        assert!(super::parse_size("G").is_err());
+    }
+
     #[test]
     fn test_capture_defaults_to_plain() {
         let cli = super::Cli::try_parse_from(["amux", "capture", "-t", "mysession"]).unwrap();
@@ -532,6 +983,17 @@ mod tests {
         assert!(result.is_err(), "respawn must require a command");
     }
 
+    #[test]
+    fn test_rename_parses() {
+        let cli = super::Cli::try_parse_from(["amux", "rename", "-t", "old", "new"]).unwrap();
+        match cli.command.unwrap() {
+            super::Command::Rename { name, new_name } => {
+                assert_eq!((name.as_str(), new_name.as_str()), ("old", "new"));
+            }
+            other => panic!("expected Rename, got {:?}", other),
+        }
+    }
+
     #[test]
     fn test_current_parses() {
         let cli = super::Cli::try_parse_from(["amux", "current"]).unwrap();
@@ -631,4 +1093,200 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_send_keys_flag() {
+        let cli = super::Cli::try_parse_from([
+            "amux", "send", "-t", "w", "--keys", "C-c", "Escape", "Up", "Enter", "text",
+        ])
+        .unwrap();
+        match cli.command.unwrap() {
+            super::Command::Send { keys, text, .. } => {
+                assert!(keys);
+                assert_eq!(text, vec!["C-c", "Escape", "Up", "Enter", "text"]);
+            }
+            other => panic!("expected Send, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_send_keys_conflicts_with_literal() {
+        let result = super::Cli::try_parse_from(["amux", "send", "-t", "w", "-k", "-l", "Enter"]);
+        assert!(result.is_err(), "--keys and --literal are mutually exclusive");
+    }
+
+    #[test]
+    fn test_send_paste_flags() {
+        let cli = super::Cli::try_parse_from([
+            "amux", "send", "-t", "w", "--paste", "--line-delay", "200", "hi",
+        ])
+        .unwrap();
+        match cli.command.unwrap() {
+            super::Command::Send {
+                paste, line_delay, ..
+            } => {
+                assert!(paste);
+                assert_eq!(line_delay, 200);
+            }
+            other => panic!("expected Send, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_send_line_delay_requires_paste() {
+        let result =
+            super::Cli::try_parse_from(["amux", "send", "-t", "w", "--line-delay", "10", "hi"]);
+        assert!(result.is_err(), "--line-delay only makes sense with --paste");
+    }
+
+    #[test]
+    fn test_send_pacing_flags() {
+        let cli = super::Cli::try_parse_from([
+            "amux",
+            "send",
+            "-t",
+            "w",
+            "--cps",
+            "30",
+            "--chunk-size",
+            "4",
+            "--newline-delay",
+            "500",
+            "--wait-written",
+            "hi",
+        ])
+        .unwrap();
+        match cli.command.unwrap() {
+            super::Command::Send {
+                cps,
+                chunk_size,
+                newline_delay,
+                wait_written,
+                ..
+            } => {
+                assert_eq!(cps, Some(30));
+                assert_eq!(chunk_size, Some(4));
+                assert_eq!(newline_delay, Some(500));
+                assert!(wait_written);
+            }
+            other => panic!("expected Send, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_send_pacing_conflicts_with_paste() {
+        let result =
+            super::Cli::try_parse_from(["amux", "send", "-t", "w", "--paste", "--cps", "10", "x"]);
+        assert!(result.is_err(), "--cps does not apply to --paste");
+    }
+
+    #[test]
+    fn test_send_all() {
+        let cli = super::Cli::try_parse_from(["amux", "send", "--all", "stop and commit"]).unwrap();
+        match cli.command.unwrap() {
+            super::Command::Send {
+                name, all, text, ..
+            } => {
+                assert!(name.is_none());
+                assert!(all);
+                assert_eq!(text, vec!["stop and commit"]);
+            }
+            other => panic!("expected Send, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_send_any_list_ends_at_double_dash() {
+        let cli =
+            super::Cli::try_parse_from(["amux", "send", "--any", "s1", "s2", "--", "hello"])
+                .unwrap();
+        match cli.command.unwrap() {
+            super::Command::Send { any, text, .. } => {
+                assert_eq!(any, vec!["s1", "s2"]);
+                assert_eq!(text, vec!["hello"]);
+            }
+            other => panic!("expected Send, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_send_match_glob() {
+        let cli =
+            super::Cli::try_parse_from(["amux", "send", "--match", "worker-*", "hi"]).unwrap();
+        match cli.command.unwrap() {
+            super::Command::Send { pattern, .. } => {
+                assert_eq!(pattern.as_deref(), Some("worker-*"));
+            }
+            other => panic!("expected Send, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_send_requires_some_target() {
+        assert!(super::Cli::try_parse_from(["amux", "send", "hi"]).is_err());
+        assert!(
+            super::Cli::try_parse_from(["amux", "send", "-t", "w", "--all", "hi"]).is_err(),
+            "-t and --all are mutually exclusive"
+        );
+    }
+
+    #[test]
+    fn test_start_server_metrics() {
+        let cli = super::Cli::try_parse_from([
+            "amux",
+            "start-server",
+            "--metrics",
+            "127.0.0.1:9464",
+        ])
+        .unwrap();
+        match cli.command.unwrap() {
+            super::Command::StartServer { metrics } => {
+                assert_eq!(metrics.as_deref(), Some("127.0.0.1:9464"));
+            }
+            other => panic!("expected StartServer, got {:?}", other),
+        }
+        let cli = super::Cli::try_parse_from(["amux", "start-server"]).unwrap();
+        assert!(matches!(
+            cli.command.unwrap(),
+            super::Command::StartServer { metrics: None }
+        ));
+    }
+
+    #[test]
+    fn test_capture_json() {
+        let cli = super::Cli::try_parse_from(["amux", "capture", "-t", "w", "--json"]).unwrap();
+        assert!(matches!(
+            cli.command.unwrap(),
+            super::Command::Capture { json: true, raw: false, .. }
+        ));
+        assert!(super::Cli::try_parse_from(["amux", "capture", "-t", "w", "--json", "--raw"])
+            .is_err());
+    }
+
+    #[test]
+    fn test_capture_format_and_theme() {
+        let cli = super::Cli::try_parse_from([
+            "amux", "capture", "-t", "w", "--format", "svg", "--theme", "light",
+        ])
+        .unwrap();
+        match cli.command.unwrap() {
+            super::Command::Capture { format, theme, .. } => {
+                assert_eq!(format, Some(super::ExportFormat::Svg));
+                assert_eq!(theme, "light");
+            }
+            other => panic!("expected Capture, got {:?}", other),
+        }
+        let cli = super::Cli::try_parse_from(["amux", "capture", "-t", "w"]).unwrap();
+        assert!(matches!(
+            cli.command.unwrap(),
+            super::Command::Capture { format: None, .. }
+        ));
+        assert!(super::Cli::try_parse_from(["amux", "capture", "-t", "w", "--format", "png"])
+            .is_err());
+        assert!(super::Cli::try_parse_from([
+            "amux", "capture", "-t", "w", "--format", "html", "--raw"
+        ])
+        .is_err());
+        assert!(super::Cli::try_parse_from(["amux", "capture", "-t", "w", "--theme", "light"])
+            .is_err());
+    }
 }
@@ -62,6 +62,8 @@ pub async fn run_attach(
 enum DaemonEvent {
     /// PTY output data to display.
     Output(Vec<u8>),
+    /// Screen repaint replacing output we fell too far behind to receive.
+    Resync { skipped: u64, screen: Vec<u8> },
     /// Session ended normally.
     SessionEnded,
     /// Error from daemon.
@@ -93,6 +95,12 @@ async fn attach_loop(
                         break;
                     }
                 }
+                Some(Ok(DaemonMessage::Resync { skipped, screen })) => {
+                    let event = DaemonEvent::Resync { skipped, screen };
+                    if daemon_msg_tx.send(event).await.is_err() {
+                        break;
+                    }
+                }
                 Some(Ok(DaemonMessage::SessionEnded)) => {
                     let _ = daemon_msg_tx.send(DaemonEvent::SessionEnded).await;
                     break;
@@ -134,6 +142,13 @@ async fn attach_loop(
                         write_all_retry(&mut stdout, &data)?;
                         flush_retry(&mut stdout)?;
                     }
+                    Some(DaemonEvent::Resync { skipped, screen }) => {
+                        // The repaint replaces the skipped output; printing a
+                        // notice here would only scribble over it.
+                        if debug { eprintln!("\r\namux-debug: resync after {} skipped chunks", skipped); }
+                        write_all_retry(&mut stdout, &screen)?;
+                        flush_retry(&mut stdout)?;
+                    }
                     Some(DaemonEvent::SessionEnded) => {
                         eprintln!("\r\namux: session ended");
                         break Ok(());
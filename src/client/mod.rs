@@ -1,4 +1,6 @@
 pub mod attach;
+pub mod mux;
+pub mod session_list;
 
 use std::os::unix::net::UnixStream;
 use std::time::Duration;
@@ -6,8 +8,8 @@ use std::time::Duration;
 use anyhow::Context;
 
 use crate::common;
-use crate::protocol::codec::{read_frame, write_frame};
-use crate::protocol::messages::{ClientMessage, DaemonMessage};
+use crate::protocol::codec::{read_frame, split_chunks, write_frame, Reassembly, CHUNK_SIZE};
+use crate::protocol::messages::{ClientMessage, Compression, DaemonMessage};
 
 /// Deadline applied to simple request/response RPCs so a hung daemon can
 /// never freeze a client indefinitely. Streaming connections (attach,
@@ -27,7 +29,56 @@ pub fn connect() -> anyhow::Result<UnixStream> {
 /// Applies `REQUEST_TIMEOUT` to reads and writes so a hung or unresponsive
 /// daemon produces a clear error instead of hanging the client forever.
 pub fn request(req: &ClientMessage) -> anyhow::Result<DaemonMessage> {
+    request_with_timeout(req, REQUEST_TIMEOUT)
+}
+
+/// Like `request`, but with a caller-chosen deadline. For requests the
+/// daemon deliberately takes a while to answer — e.g. a paced paste that
+/// sleeps between lines — where `REQUEST_TIMEOUT` would misfire.
+///
+/// Requests share one multiplexed connection per process (see `mux`).
+pub fn request_with_timeout(
+    req: &ClientMessage,
+    timeout: Duration,
+) -> anyhow::Result<DaemonMessage> {
+    if let Ok(conn) = mux::shared(&common::socket_path()) {
+        match conn.request(req.clone(), timeout) {
+            Ok(resp) => return Ok(resp),
+            // A daemon from before multiplexing hangs up without a word;
+            // anything else is a real failure.
+            Err(e) if conn.is_alive() || conn.has_answered() => return Err(e),
+            Err(_) => {}
+        }
+    }
+    request_unshared(req, timeout)
+}
+
+/// One request on a connection of its own.
+fn request_unshared(req: &ClientMessage, timeout: Duration) -> anyhow::Result<DaemonMessage> {
     let mut stream = connect().context("is the server running? try: amux start-server")?;
+    stream
+        .set_read_timeout(Some(timeout))
+        .context("failed to set read timeout")?;
+    stream
+        .set_write_timeout(Some(timeout))
+        .context("failed to set write timeout")?;
+    do_request(&mut stream, req)
+}
+
+/// Like `request`, on a connection of its own that first negotiates
+/// compression, for answers big enough to be worth it (`amux capture
+/// --compress` over a slow link).
+pub fn request_compressed(req: &ClientMessage) -> anyhow::Result<DaemonMessage> {
+    let stream = connect().context("is the server running? try: amux start-server")?;
+    request_compressed_on(stream, req)
+}
+
+/// `request_compressed` over an already open `stream`.
+pub fn request_compressed_on(
+    stream: UnixStream,
+    req: &ClientMessage,
+) -> anyhow::Result<DaemonMessage> {
+    let mut stream = negotiate_compression(stream)?;
     stream
         .set_read_timeout(Some(REQUEST_TIMEOUT))
         .context("failed to set read timeout")?;
@@ -37,15 +88,85 @@ pub fn request(req: &ClientMessage) -> anyhow::Result<DaemonMessage> {
     do_request(&mut stream, req)
 }
 
+/// Offer compression on a fresh connection. A daemon that predates
+/// negotiation hangs up on the offer, so carry on uncompressed over a new
+/// connection then.
+pub fn negotiate_compression(mut stream: UnixStream) -> anyhow::Result<UnixStream> {
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    write_frame(
+        &mut stream,
+        &ClientMessage::Negotiate {
+            compression: vec![Compression::Lz4],
+        },
+    )?;
+    match read_frame::<_, DaemonMessage>(&mut stream) {
+        Ok(DaemonMessage::Negotiated { compression: Some(_) }) => {
+            stream.set_read_timeout(None)?;
+            Ok(stream)
+        }
+        Ok(_) => {
+            eprintln!("amux: server declined compression; continuing uncompressed");
+            stream.set_read_timeout(None)?;
+            Ok(stream)
+        }
+        Err(_) => {
+            eprintln!("amux: server doesn't support compression; continuing uncompressed");
+            connect().context("is the server running?")
+        }
+    }
+}
+
 /// Core request/response cycle, shared by `request` and tests.
 fn do_request(stream: &mut UnixStream, req: &ClientMessage) -> anyhow::Result<DaemonMessage> {
-    write_frame(stream, req).map_err(|e| map_io_timeout(e, "write"))?;
-    read_frame(stream).map_err(|e| map_io_timeout(e, "read"))
+    let timeout = stream
+        .read_timeout()
+        .ok()
+        .flatten()
+        .unwrap_or(REQUEST_TIMEOUT);
+    for frame in with_upload(req) {
+        write_frame(stream, &frame).map_err(|e| map_io_timeout(e, "write", timeout))?;
+    }
+    read_answer(|| read_frame(stream).map_err(|e| map_io_timeout(e, "read", timeout)))
+}
+
+/// The frames that carry `req`: input too big for one frame goes ahead
+/// of it as `InputChunk`s, and the request itself follows without it.
+fn with_upload(req: &ClientMessage) -> Vec<ClientMessage> {
+    let mut req = req.clone();
+    let mut frames: Vec<ClientMessage> = match req.input_mut() {
+        Some(data) if data.len() > CHUNK_SIZE => split_chunks(&std::mem::take(data))
+            .into_iter()
+            .map(ClientMessage::InputChunk)
+            .collect(),
+        _ => Vec::new(),
+    };
+    frames.push(req);
+    frames
+}
+
+/// Read an answer with `next`, putting a chunked capture back together
+/// into the `CaptureOutput` it stands for.
+fn read_answer(
+    mut next: impl FnMut() -> anyhow::Result<DaemonMessage>,
+) -> anyhow::Result<DaemonMessage> {
+    let mut chunk = match next()? {
+        DaemonMessage::CaptureChunk(chunk) => chunk,
+        other => return Ok(other),
+    };
+    let mut capture = Reassembly::default();
+    while !capture.push(chunk).map_err(anyhow::Error::msg)? {
+        chunk = match next()? {
+            DaemonMessage::CaptureChunk(chunk) => chunk,
+            other => anyhow::bail!("unexpected response in a chunked capture: {:?}", other),
+        };
+    }
+    let data = capture.finish().map_err(anyhow::Error::msg)?;
+    Ok(DaemonMessage::CaptureOutput(data))
 }
 
 /// If `e` wraps a socket timeout (`WouldBlock` or `TimedOut`), rewrite it
 /// into a clear, actionable message; otherwise pass it through.
-fn map_io_timeout(e: anyhow::Error, op: &str) -> anyhow::Error {
+fn map_io_timeout(e: anyhow::Error, op: &str, timeout: Duration) -> anyhow::Error {
     let is_timeout = e.chain().any(|cause| {
         cause
             .downcast_ref::<std::io::Error>()
@@ -62,7 +183,7 @@ fn map_io_timeout(e: anyhow::Error, op: &str) -> anyhow::Error {
             "daemon unresponsive (possibly suspended; was the system asleep?) — \
              {} timed out after {}s; try: amux kill-server && amux start-server",
             op,
-            REQUEST_TIMEOUT.as_secs()
+            timeout.as_secs()
         )
     } else {
         e
@@ -141,6 +262,29 @@ mod tests {
         let _ = std::fs::remove_file(&sock);
     }
 
+    #[test]
+    fn mux_connection_reports_a_daemon_that_hangs_up() {
+        use crate::protocol::codec::read_frame;
+
+        let sock = unique_sock_path("old-daemon");
+        let _ = std::fs::remove_file(&sock);
+        let listener = UnixListener::bind(&sock).unwrap();
+        // A daemon that can't decode `Tagged` drops the connection.
+        std::thread::spawn(move || {
+            if let Ok((mut stream, _)) = listener.accept() {
+                let _ = read_frame::<_, ClientMessage>(&mut stream);
+            }
+        });
+
+        let conn = mux::MuxConnection::connect_to(&sock).unwrap();
+        let result = conn.request(ClientMessage::Ping, Duration::from_secs(2));
+        assert!(result.is_err());
+        assert!(!conn.is_alive());
+        assert!(!conn.has_answered(), "caller should retry without multiplexing");
+
+        let _ = std::fs::remove_file(&sock);
+    }
+
     #[test]
     fn do_request_returns_response_when_peer_replies() {
         use crate::protocol::codec::{read_frame, write_frame};
@@ -171,4 +315,71 @@ mod tests {
 
         let _ = std::fs::remove_file(&sock);
     }
+
+    #[test]
+    fn do_request_chunks_payloads_too_big_for_a_frame() {
+        use crate::protocol::codec::{read_frame, write_frame};
+
+        let sock = unique_sock_path("chunks");
+        let _ = std::fs::remove_file(&sock);
+        let listener = UnixListener::bind(&sock).unwrap();
+        let payload: Vec<u8> = (0..3 * CHUNK_SIZE + 7).map(|i| (i % 251) as u8).collect();
+
+        let expected = payload.clone();
+        let peer = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            // The upload arrives as chunks ahead of a request without data.
+            let mut upload = Reassembly::default();
+            loop {
+                match read_frame::<_, ClientMessage>(&mut stream).unwrap() {
+                    ClientMessage::InputChunk(chunk) => {
+                        let _ = upload.push(chunk);
+                    }
+                    ClientMessage::SendInput { data, .. } => {
+                        assert!(data.is_empty());
+                        break;
+                    }
+                    other => panic!("unexpected request: {:?}", other),
+                }
+            }
+            assert!(upload.finish().unwrap() == expected, "upload mangled");
+            write_frame(&mut stream, &DaemonMessage::InputSent).unwrap();
+
+            let _: ClientMessage = read_frame(&mut stream).unwrap();
+            for chunk in split_chunks(&expected) {
+                write_frame(&mut stream, &DaemonMessage::CaptureChunk(chunk)).unwrap();
+            }
+        });
+
+        let mut stream = UnixStream::connect(&sock).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        stream
+            .set_write_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+
+        let send = ClientMessage::SendInput {
+            name: "big".to_string(),
+            data: payload.clone(),
+            newline: false,
+            pacing: None,
+            wait_written: false,
+        };
+        let resp = do_request(&mut stream, &send).unwrap();
+        assert!(matches!(resp, DaemonMessage::InputSent), "{:?}", resp);
+
+        let capture = ClientMessage::CaptureScrollback {
+            name: "big".to_string(),
+            lines: 1_000_000,
+            mode: crate::protocol::messages::CaptureMode::Plain,
+        };
+        match do_request(&mut stream, &capture).unwrap() {
+            DaemonMessage::CaptureOutput(data) => assert!(data == payload, "capture mangled"),
+            other => panic!("expected CaptureOutput, got: {:?}", other),
+        }
+        peer.join().unwrap();
+
+        let _ = std::fs::remove_file(&sock);
+    }
 }
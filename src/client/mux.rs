@@ -0,0 +1,258 @@
+//! Client side of multiplexed connections (see `daemon::mux`).
+//!
+//! One `MuxConnection` carries any number of concurrent requests and
+//! streams, each on its own channel id. A reader thread routes the
+//! daemon's tagged answers to whoever is waiting on that id. `client::request`
+//! goes through one such connection per process, so commands that poll
+//! (top, `wait_for_session_ready`, watch callbacks) stop paying a connect
+//! per request.
+
+use std::collections::HashMap;
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use anyhow::Context;
+
+use crate::protocol::codec::{read_frame, write_frame};
+use crate::protocol::messages::{ClientMessage, DaemonMessage};
+
+struct Shared {
+    /// Write side of the socket; one frame at a time.
+    writer: Mutex<UnixStream>,
+    /// Open channels and where their answers go.
+    routes: Mutex<HashMap<u64, Sender<DaemonMessage>>>,
+    next_id: AtomicU64,
+    /// Cleared when the reader thread sees the connection end.
+    alive: AtomicBool,
+    /// Set once any answer has arrived, i.e. the daemon speaks this
+    /// protocol.
+    answered: AtomicBool,
+}
+
+impl Shared {
+    fn send(&self, msg: &ClientMessage) -> anyhow::Result<()> {
+        let mut writer = self
+            .writer
+            .lock()
+            .map_err(|_| anyhow::anyhow!("connection poisoned"))?;
+        write_frame(&mut *writer, msg)
+    }
+
+    fn open(&self, msg: ClientMessage) -> anyhow::Result<(u64, Receiver<DaemonMessage>)> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (tx, rx) = mpsc::channel();
+        if let Ok(mut routes) = self.routes.lock() {
+            routes.insert(id, tx);
+        }
+        if let Err(e) = self.send(&ClientMessage::Tagged {
+            id,
+            msg: Box::new(msg),
+        }) {
+            self.close(id);
+            return Err(e);
+        }
+        Ok((id, rx))
+    }
+
+    /// Stop routing channel `id`; returns whether it was still open.
+    fn forget(&self, id: u64) -> bool {
+        self.routes
+            .lock()
+            .map(|mut routes| routes.remove(&id).is_some())
+            .unwrap_or(false)
+    }
+
+    /// Forget channel `id` and tell the daemon to drop it.
+    fn close(&self, id: u64) {
+        if self.forget(id) && self.alive.load(Ordering::Relaxed) {
+            let _ = self.send(&ClientMessage::Cancel { id });
+        }
+    }
+}
+
+/// A daemon connection carrying concurrent requests and streams.
+#[derive(Clone)]
+pub struct MuxConnection {
+    shared: Arc<Shared>,
+}
+
+impl MuxConnection {
+    /// Connect to the daemon socket at `path`.
+    pub fn connect_to(path: &Path) -> anyhow::Result<Self> {
+        let stream = UnixStream::connect(path)
+            .with_context(|| format!("failed to connect to server at {}", path.display()))?;
+        stream.set_write_timeout(Some(super::REQUEST_TIMEOUT))?;
+        let reader = stream.try_clone()?;
+        let shared = Arc::new(Shared {
+            writer: Mutex::new(stream),
+            routes: Mutex::new(HashMap::new()),
+            next_id: AtomicU64::new(1),
+            alive: AtomicBool::new(true),
+            answered: AtomicBool::new(false),
+        });
+        let thread_shared = shared.clone();
+        thread::spawn(move || route_answers(reader, thread_shared));
+        Ok(Self { shared })
+    }
+
+    /// Whether the connection is still up.
+    pub fn is_alive(&self) -> bool {
+        self.shared.alive.load(Ordering::Relaxed)
+    }
+
+    /// Whether the daemon has answered anything on this connection yet.
+    /// Until it has, a dead connection may just mean the daemon predates
+    /// multiplexing.
+    pub fn has_answered(&self) -> bool {
+        self.shared.answered.load(Ordering::Relaxed)
+    }
+
+    /// Send `req` on a fresh channel and wait up to `timeout` for its
+    /// answer. Other requests on the connection proceed meanwhile.
+    pub fn request(&self, req: ClientMessage, timeout: Duration) -> anyhow::Result<DaemonMessage> {
+        // Never empty: the request itself comes last.
+        let mut frames = super::with_upload(&req);
+        let uploading = frames.len() > 1;
+        let (id, rx) = self.shared.open(frames.remove(0))?;
+        for msg in frames {
+            if let Err(e) = self.shared.send(&ClientMessage::Tagged {
+                id,
+                msg: Box::new(msg),
+            }) {
+                self.shared.close(id);
+                return Err(e);
+            }
+        }
+        let answer = super::read_answer(|| match rx.recv_timeout(timeout) {
+            Ok(msg) => Ok(msg),
+            Err(RecvTimeoutError::Timeout) => Err(super::map_io_timeout(
+                std::io::Error::from(std::io::ErrorKind::TimedOut).into(),
+                "read",
+                timeout,
+            )),
+            Err(RecvTimeoutError::Disconnected) => {
+                anyhow::bail!("connection to server closed")
+            }
+        });
+        if answer.is_ok() && !uploading {
+            // The daemon closes an answered channel by itself.
+            self.shared.forget(id);
+        } else {
+            // An upload's channel stays open for more chunks.
+            self.shared.close(id);
+        }
+        answer
+    }
+
+    /// Start a streaming request (`Follow`, `WatchEvents`,
+    /// `SubscribeSessionList`, ...) on a fresh channel.
+    pub fn stream(&self, req: ClientMessage) -> anyhow::Result<MuxStream> {
+        let (id, rx) = self.shared.open(req)?;
+        Ok(MuxStream {
+            id,
+            rx: Mutex::new(rx),
+            shared: self.shared.clone(),
+        })
+    }
+}
+
+/// One streaming channel. Dropping it cancels the stream on the daemon.
+pub struct MuxStream {
+    id: u64,
+    /// Behind a lock so one thread can read while another sends.
+    rx: Mutex<Receiver<DaemonMessage>>,
+    shared: Arc<Shared>,
+}
+
+impl MuxStream {
+    /// Next message, or `None` once the stream (or connection) ended.
+    pub fn recv(&self) -> Option<DaemonMessage> {
+        self.rx.lock().ok()?.recv().ok()
+    }
+
+    /// Like `recv`, giving up after `timeout` with an error.
+    pub fn recv_timeout(&self, timeout: Duration) -> anyhow::Result<Option<DaemonMessage>> {
+        let rx = self
+            .rx
+            .lock()
+            .map_err(|_| anyhow::anyhow!("stream poisoned"))?;
+        match rx.recv_timeout(timeout) {
+            Ok(msg) => Ok(Some(msg)),
+            Err(RecvTimeoutError::Disconnected) => Ok(None),
+            Err(RecvTimeoutError::Timeout) => Err(super::map_io_timeout(
+                std::io::Error::from(std::io::ErrorKind::TimedOut).into(),
+                "read",
+                timeout,
+            )),
+        }
+    }
+
+    /// Send a follow-up (`SetPreview`, `AttachInput`, ...) on this channel.
+    pub fn send(&self, msg: ClientMessage) -> anyhow::Result<()> {
+        self.shared.send(&ClientMessage::Tagged {
+            id: self.id,
+            msg: Box::new(msg),
+        })
+    }
+
+    /// Cancel the stream now; a blocked `recv` returns `None`.
+    pub fn close(&self) {
+        self.shared.close(self.id);
+    }
+}
+
+impl Drop for MuxStream {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+fn route_answers(mut reader: UnixStream, shared: Arc<Shared>) {
+    while let Ok(msg) = read_frame::<_, DaemonMessage>(&mut reader) {
+        shared.answered.store(true, Ordering::Relaxed);
+        let Ok(mut routes) = shared.routes.lock() else { break };
+        match msg {
+            DaemonMessage::Tagged { id, msg } => {
+                if let Some(tx) = routes.get(&id) {
+                    if tx.send(*msg).is_err() {
+                        routes.remove(&id);
+                    }
+                }
+            }
+            DaemonMessage::Closed { id } => {
+                routes.remove(&id);
+            }
+            other => tracing::debug!("untagged message on multiplexed connection: {:?}", other),
+        }
+    }
+    shared.alive.store(false, Ordering::Relaxed);
+    // Dropping the senders wakes everyone still waiting.
+    if let Ok(mut routes) = shared.routes.lock() {
+        routes.clear();
+    }
+}
+
+/// The process-wide connection behind `client::request`, with the socket
+/// it was opened for.
+static SHARED: Mutex<Option<(PathBuf, MuxConnection)>> = Mutex::new(None);
+
+/// The shared connection to the daemon at `path`, (re)connecting when
+/// there is none, it died, or it was opened for another socket.
+pub fn shared(path: &Path) -> anyhow::Result<MuxConnection> {
+    let mut slot = SHARED
+        .lock()
+        .map_err(|_| anyhow::anyhow!("shared connection poisoned"))?;
+    if let Some((opened_for, conn)) = slot.as_ref() {
+        if opened_for == path && conn.is_alive() {
+            return Ok(conn.clone());
+        }
+    }
+    let conn = MuxConnection::connect_to(path)?;
+    *slot = Some((path.to_path_buf(), conn.clone()));
+    Ok(conn)
+}
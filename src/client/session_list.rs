@@ -0,0 +1,144 @@
+//! Client side of `SubscribeSessionList`: a local copy of the daemon's
+//! session list (and one session's preview) kept current by the daemon's
+//! pushed diffs, so dashboards read it without a request per refresh.
+//! The subscription is a stream on the shared multiplexed connection, so
+//! a dashboard's other requests travel alongside it.
+
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use anyhow::Context;
+
+use super::mux::{self, MuxStream};
+use crate::common;
+use crate::protocol::messages::{ClientMessage, DaemonMessage, SessionInfo};
+use crate::protocol::session_list::SessionListDiff;
+
+#[derive(Default)]
+struct FeedState {
+    sessions: Vec<SessionInfo>,
+    /// The previewed session and its formatted screen lines.
+    preview: Option<(String, Vec<String>)>,
+    /// Cleared when the stream ends (daemon gone or protocol error).
+    connected: bool,
+}
+
+impl FeedState {
+    fn apply(&mut self, mut diff: SessionListDiff) {
+        diff.apply_sessions(&mut self.sessions);
+        if let Some(update) = diff.preview {
+            let mut lines = match self.preview.take() {
+                Some((name, lines)) if name == update.name => lines,
+                _ => Vec::new(),
+            };
+            let name = update.name.clone();
+            update.apply(&mut lines);
+            self.preview = Some((name, lines));
+        }
+    }
+}
+
+/// A subscription to the daemon's session list. A background thread
+/// applies each update as it arrives; dropping the feed cancels the
+/// stream and ends the thread.
+pub struct SessionListFeed {
+    state: Arc<Mutex<FeedState>>,
+    stream: Arc<MuxStream>,
+    preview: Option<String>,
+    preview_lines: u32,
+}
+
+impl SessionListFeed {
+    /// Subscribe, previewing the last `preview_lines` lines of `preview`.
+    /// Returns once the first update (the full list) has arrived.
+    pub fn subscribe(preview: Option<&str>, preview_lines: u32) -> anyhow::Result<Self> {
+        let conn = mux::shared(&common::socket_path())
+            .context("is the server running? try: amux start-server")?;
+        let stream = Arc::new(conn.stream(ClientMessage::SubscribeSessionList {
+            preview: preview.map(str::to_string),
+            preview_lines,
+        })?);
+        let mut state = FeedState {
+            connected: true,
+            ..FeedState::default()
+        };
+        match stream.recv_timeout(super::REQUEST_TIMEOUT)? {
+            Some(DaemonMessage::SessionListUpdate(diff)) => state.apply(diff),
+            Some(DaemonMessage::Error(e)) => anyhow::bail!(e),
+            Some(other) => anyhow::bail!("unexpected response: {:?}", other),
+            None => anyhow::bail!("connection to server closed"),
+        }
+
+        let state = Arc::new(Mutex::new(state));
+        let thread_stream = stream.clone();
+        let thread_state = state.clone();
+        thread::spawn(move || read_updates(&thread_stream, thread_state));
+        Ok(Self {
+            state,
+            stream,
+            preview: preview.map(str::to_string),
+            preview_lines,
+        })
+    }
+
+    /// Whether updates are still arriving.
+    pub fn is_connected(&self) -> bool {
+        self.state.lock().map(|s| s.connected).unwrap_or(false)
+    }
+
+    /// The current session list, in no particular order.
+    pub fn sessions(&self) -> Vec<SessionInfo> {
+        self.state
+            .lock()
+            .map(|s| s.sessions.clone())
+            .unwrap_or_default()
+    }
+
+    /// The preview of `name`, as `CaptureScrollback` in formatted mode
+    /// would return it, if it is the session being previewed and its
+    /// first update has arrived.
+    pub fn preview(&self, name: &str) -> Option<Vec<u8>> {
+        let state = self.state.lock().ok()?;
+        match &state.preview {
+            Some((previewed, lines)) if previewed == name => Some(lines.join("\n").into_bytes()),
+            _ => None,
+        }
+    }
+
+    /// Preview a different session (or more lines). A no-op when nothing
+    /// changed.
+    pub fn set_preview(&mut self, name: Option<&str>, lines: u32) -> anyhow::Result<()> {
+        if self.preview.as_deref() == name && self.preview_lines == lines {
+            return Ok(());
+        }
+        self.stream.send(ClientMessage::SetPreview {
+            name: name.map(str::to_string),
+            lines,
+        })?;
+        self.preview = name.map(str::to_string);
+        self.preview_lines = lines;
+        Ok(())
+    }
+}
+
+impl Drop for SessionListFeed {
+    fn drop(&mut self) {
+        // Wakes the reader thread out of its blocking recv.
+        self.stream.close();
+    }
+}
+
+fn read_updates(stream: &MuxStream, state: Arc<Mutex<FeedState>>) {
+    loop {
+        let update = stream.recv();
+        let Ok(mut state) = state.lock() else { return };
+        match update {
+            Some(DaemonMessage::SessionListUpdate(diff)) => state.apply(diff),
+            Some(_) => {}
+            None => {
+                state.connected = false;
+                return;
+            }
+        }
+    }
+}
@@ -6,7 +6,7 @@ use crate::protocol::messages::{ClientMessage, DaemonMessage};
 use crate::client;
 
 /// Attach to a named session.
-pub fn do_attach(name: &str) -> anyhow::Result<()> {
+pub fn do_attach(name: &str, buffer: Option<u32>) -> anyhow::Result<()> {
     use crate::protocol::codec::write_frame;
     let debug = std::env::var("AMUX_DEBUG").is_ok();
 
@@ -35,6 +35,7 @@ pub fn do_attach(name: &str) -> anyhow::Result<()> {
             name: name.to_string(),
             cols,
             rows,
+            buffer,
         },
     )?;
     if debug { eprintln!("amux-debug: Attach frame sent"); }
@@ -66,9 +67,18 @@ pub fn do_attach(name: &str) -> anyhow::Result<()> {
     })
 }
 
-/// Follow a session's output (read-only streaming, no stdin).
-pub fn do_follow(name: &str, plain: bool) -> anyhow::Result<()> {
-    use crate::protocol::codec::{try_read_frame_async, write_frame, write_frame_async};
+/// Follow a session's output (read-only streaming, no stdin). With
+/// `compress`, output arrives compressed and what that saved over the
+/// whole connection is reported on exit.
+pub fn do_follow(
+    name: &str,
+    plain: bool,
+    buffer: Option<u32>,
+    compress: bool,
+) -> anyhow::Result<()> {
+    use crate::protocol::codec::{
+        try_read_frame_counted_async, write_frame, write_frame_async, TransferStats,
+    };
     use crate::util::{clean_control_chars, strip_ansi};
     use std::io::Write;
 
@@ -82,12 +92,16 @@ pub fn do_follow(name: &str, plain: bool) -> anyhow::Result<()> {
     }
 
     let mut stream = client::connect().context("is the server running?")?;
+    if compress {
+        stream = client::negotiate_compression(stream)?;
+    }
 
     // Send Follow message.
     write_frame(
         &mut stream,
         &ClientMessage::Follow {
             name: name.to_string(),
+            buffer,
         },
     )?;
 
@@ -117,9 +131,10 @@ pub fn do_follow(name: &str, plain: bool) -> anyhow::Result<()> {
         )?;
 
         let mut stdout = std::io::stdout();
+        let mut stats = TransferStats::default();
         loop {
             tokio::select! {
-                msg = try_read_frame_async::<DaemonMessage>(&mut reader) => {
+                msg = try_read_frame_counted_async::<DaemonMessage>(&mut reader, &mut stats) => {
                     match msg {
                         Some(Ok(DaemonMessage::Output(data))) => {
                             if plain {
@@ -134,6 +149,15 @@ pub fn do_follow(name: &str, plain: bool) -> anyhow::Result<()> {
                                 let _ = stdout.flush();
                             }
                         }
+                        Some(Ok(DaemonMessage::Resync { skipped, screen })) => {
+                            // A plain-text log can't be repainted; say where
+                            // the gap is instead.
+                            eprintln!("amux: fell behind, skipped {} output chunks", skipped);
+                            if !plain {
+                                let _ = stdout.write_all(&screen);
+                                let _ = stdout.flush();
+                            }
+                        }
                         Some(Ok(DaemonMessage::SessionEnded)) => {
                             break;
                         }
@@ -163,6 +187,14 @@ pub fn do_follow(name: &str, plain: bool) -> anyhow::Result<()> {
             }
         }
 
+        if compress {
+            if let Some(ratio) = stats.ratio() {
+                eprintln!(
+                    "amux: received {} frames, {} bytes of output as {} bytes on the wire ({:.1}x)",
+                    stats.frames, stats.payload_bytes, stats.wire_bytes, ratio
+                );
+            }
+        }
         Ok(())
     })
 }
@@ -188,7 +220,7 @@ mod tests {
 
         let server_shutdown = shutdown_tx.clone();
         tokio::spawn(async move {
-            crate::daemon::server::run_server(listener, server_shutdown).await;
+            crate::daemon::server::run_server(listener, server_shutdown, None).await;
         });
 
         tokio::time::sleep(std::time::Duration::from_millis(100)).await;
@@ -198,14 +230,7 @@ mod tests {
 
         write_frame_async(
             &mut writer,
-            &ClientMessage::CreateSession {
-                name: Some("input-test".to_string()),
-                command: vec!["cat".to_string()],
-                env: None,
-                cwd: None,
-                cols: None,
-                rows: None,
-            },
+            &ClientMessage::create_session("input-test", vec!["cat".to_string()]),
         )
         .await
         .unwrap();
@@ -225,6 +250,7 @@ mod tests {
                 name: "input-test".to_string(),
                 cols: 80,
                 rows: 24,
+                buffer: None,
             },
         )
         .await
@@ -340,7 +366,7 @@ mod tests {
 
         let server_shutdown = shutdown_tx.clone();
         tokio::spawn(async move {
-            crate::daemon::server::run_server(listener, server_shutdown).await;
+            crate::daemon::server::run_server(listener, server_shutdown, None).await;
         });
 
         tokio::time::sleep(std::time::Duration::from_millis(100)).await;
@@ -350,14 +376,7 @@ mod tests {
 
         write_frame_async(
             &mut writer,
-            &ClientMessage::CreateSession {
-                name: Some("keys-test".to_string()),
-                command: vec!["cat".to_string()],
-                env: None,
-                cwd: None,
-                cols: None,
-                rows: None,
-            },
+            &ClientMessage::create_session("keys-test", vec!["cat".to_string()]),
         )
         .await
         .unwrap();
@@ -373,6 +392,7 @@ mod tests {
                 name: "keys-test".to_string(),
                 cols: 80,
                 rows: 24,
+                buffer: None,
             },
         )
         .await
@@ -494,7 +514,7 @@ mod tests {
 
         let server_shutdown = shutdown_tx.clone();
         tokio::spawn(async move {
-            crate::daemon::server::run_server(listener, server_shutdown).await;
+            crate::daemon::server::run_server(listener, server_shutdown, None).await;
         });
 
         tokio::time::sleep(std::time::Duration::from_millis(100)).await;
@@ -503,14 +523,7 @@ mod tests {
         let (mut r, mut w) = stream.into_split();
         write_frame_async(
             &mut w,
-            &ClientMessage::CreateSession {
-                name: Some("sync-attach-test".to_string()),
-                command: vec!["cat".to_string()],
-                env: None,
-                cwd: None,
-                cols: None,
-                rows: None,
-            },
+            &ClientMessage::create_session("sync-attach-test", vec!["cat".to_string()]),
         )
         .await
         .unwrap();
@@ -532,6 +545,7 @@ mod tests {
                 name: "sync-attach-test".to_string(),
                 cols: 80,
                 rows: 24,
+                buffer: None,
             },
         )
         .unwrap();
@@ -621,21 +635,20 @@ mod tests {
         let (shutdown_tx, _) = broadcast::channel::<()>(1);
         let server_shutdown = shutdown_tx.clone();
         tokio::spawn(async move {
-            crate::daemon::server::run_server(listener, server_shutdown).await;
+            crate::daemon::server::run_server(listener, server_shutdown, None).await;
         });
         tokio::time::sleep(std::time::Duration::from_millis(100)).await;
         let stream = tokio::net::UnixStream::connect(&sock_path).await.unwrap();
         let (mut r, mut w) = stream.into_split();
-        write_frame_async(&mut w, &ClientMessage::CreateSession {
-            name: Some("follow-test".to_string()),
-            command: vec!["cat".to_string()],
-            env: None, cwd: None, cols: None, rows: None,
-        }).await.unwrap();
+        write_frame_async(&mut w, &ClientMessage::create_session("follow-test", vec!["cat".to_string()]))
+            .await
+            .unwrap();
         let resp: DaemonMessage = try_read_frame_async(&mut r).await.unwrap().unwrap();
         assert!(matches!(resp, DaemonMessage::SessionCreated { .. }));
         write_frame_async(&mut w, &ClientMessage::SendInput {
             name: "follow-test".to_string(),
             data: b"hello-follow".to_vec(), newline: true,
+            pacing: None, wait_written: false,
         }).await.unwrap();
         let resp: DaemonMessage = try_read_frame_async(&mut r).await.unwrap().unwrap();
         assert!(matches!(resp, DaemonMessage::InputSent));
@@ -644,6 +657,7 @@ mod tests {
         let (mut fr, mut fw) = stream2.into_split();
         write_frame_async(&mut fw, &ClientMessage::Follow {
             name: "follow-test".to_string(),
+            buffer: None,
         }).await.unwrap();
         let mut output = Vec::new();
         let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(3);
@@ -668,6 +682,7 @@ mod tests {
         write_frame_async(&mut w, &ClientMessage::SendInput {
             name: "follow-test".to_string(),
             data: b"live-data".to_vec(), newline: true,
+            pacing: None, wait_written: false,
         }).await.unwrap();
         let _: DaemonMessage = try_read_frame_async(&mut r).await.unwrap().unwrap();
         let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(3);
@@ -704,16 +719,16 @@ mod tests {
         let (shutdown_tx, _) = broadcast::channel::<()>(1);
         let server_shutdown = shutdown_tx.clone();
         tokio::spawn(async move {
-            crate::daemon::server::run_server(listener, server_shutdown).await;
+            crate::daemon::server::run_server(listener, server_shutdown, None).await;
         });
         tokio::time::sleep(std::time::Duration::from_millis(100)).await;
         let stream = tokio::net::UnixStream::connect(&sock_path).await.unwrap();
         let (mut r, mut w) = stream.into_split();
-        write_frame_async(&mut w, &ClientMessage::CreateSession {
-            name: Some("follow-end-test".to_string()),
-            command: vec!["echo".to_string(), "bye".to_string()],
-            env: None, cwd: None, cols: None, rows: None,
-        }).await.unwrap();
+        let create = ClientMessage::create_session(
+            "follow-end-test",
+            vec!["echo".to_string(), "bye".to_string()],
+        );
+        write_frame_async(&mut w, &create).await.unwrap();
         let resp: DaemonMessage = try_read_frame_async(&mut r).await.unwrap().unwrap();
         assert!(matches!(resp, DaemonMessage::SessionCreated { .. }));
         tokio::time::sleep(std::time::Duration::from_millis(500)).await;
@@ -721,6 +736,7 @@ mod tests {
         let (mut fr, mut fw) = stream2.into_split();
         write_frame_async(&mut fw, &ClientMessage::Follow {
             name: "follow-end-test".to_string(),
+            buffer: None,
         }).await.unwrap();
         let mut got_ended = false;
         let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(3);
@@ -807,13 +823,14 @@ mod tests {
         let (shutdown_tx, _) = broadcast::channel::<()>(1);
         let server_shutdown = shutdown_tx.clone();
         tokio::spawn(async move {
-            crate::daemon::server::run_server(listener, server_shutdown).await;
+            crate::daemon::server::run_server(listener, server_shutdown, None).await;
         });
         tokio::time::sleep(std::time::Duration::from_millis(100)).await;
         let stream = tokio::net::UnixStream::connect(&sock_path).await.unwrap();
         let (mut fr, mut fw) = stream.into_split();
         write_frame_async(&mut fw, &ClientMessage::Follow {
             name: "nonexistent".to_string(),
+            buffer: None,
         }).await.unwrap();
         let resp: DaemonMessage = try_read_frame_async(&mut fr).await.unwrap().unwrap();
         match resp {
@@ -841,7 +858,7 @@ mod tests {
         let (shutdown_tx, _) = broadcast::channel::<()>(1);
         let server_shutdown = shutdown_tx.clone();
         tokio::spawn(async move {
-            crate::daemon::server::run_server(listener, server_shutdown).await;
+            crate::daemon::server::run_server(listener, server_shutdown, None).await;
         });
         tokio::time::sleep(std::time::Duration::from_millis(100)).await;
 
@@ -874,6 +891,8 @@ mod tests {
                 cwd: None,
                 cols: Some(80),
                 rows: Some(24),
+                limits: None,
+                attention: Vec::new(),
             },
         )
         .await
@@ -894,6 +913,7 @@ mod tests {
                 name: "ac".to_string(),
                 cols: 80,
                 rows: 24,
+                buffer: None,
             },
         )
         .await
@@ -930,7 +950,7 @@ mod tests {
         let (shutdown_tx, _) = broadcast::channel::<()>(1);
         let server_shutdown = shutdown_tx.clone();
         tokio::spawn(async move {
-            crate::daemon::server::run_server(listener, server_shutdown).await;
+            crate::daemon::server::run_server(listener, server_shutdown, None).await;
         });
         tokio::time::sleep(std::time::Duration::from_millis(100)).await;
 
@@ -951,6 +971,8 @@ mod tests {
                 cwd: None,
                 cols: Some(80),
                 rows: Some(24),
+                limits: None,
+                attention: Vec::new(),
             },
         )
         .await
@@ -1044,7 +1066,7 @@ mod tests {
         let (shutdown_tx, _) = broadcast::channel::<()>(1);
         let server_shutdown = shutdown_tx.clone();
         tokio::spawn(async move {
-            crate::daemon::server::run_server(listener, server_shutdown).await;
+            crate::daemon::server::run_server(listener, server_shutdown, None).await;
         });
         tokio::time::sleep(std::time::Duration::from_millis(100)).await;
 
@@ -1090,7 +1112,7 @@ mod tests {
         let (shutdown_tx, _) = broadcast::channel::<()>(1);
         let server_shutdown = shutdown_tx.clone();
         tokio::spawn(async move {
-            crate::daemon::server::run_server(listener, server_shutdown).await;
+            crate::daemon::server::run_server(listener, server_shutdown, None).await;
         });
         tokio::time::sleep(std::time::Duration::from_millis(100)).await;
 
@@ -1112,6 +1134,8 @@ mod tests {
                 cwd: None,
                 cols: Some(80),
                 rows: Some(60),
+                limits: None,
+                attention: Vec::new(),
             },
         )
         .await
@@ -1131,6 +1155,7 @@ mod tests {
                 name: "resize-test".to_string(),
                 cols: 80,
                 rows: 60,
+                buffer: None,
             },
         )
         .await
@@ -1199,4 +1224,198 @@ mod tests {
         let _ = shutdown_tx.send(());
         let _ = std::fs::remove_dir_all(&dir);
     }
+
+    /// Integration test: an attached client that stops reading while the
+    /// session floods output is resynced with a screen repaint instead of
+    /// being left with a gap in the byte stream.
+    #[tokio::test]
+    async fn test_slow_attach_client_gets_resync() {
+        use crate::daemon::vterm::VirtualTerminal;
+        use tokio::sync::broadcast;
+
+        let dir = std::env::temp_dir().join(format!("amux-test-resync-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let sock_path = dir.join("test.sock");
+        let _ = std::fs::remove_file(&sock_path);
+
+        let listener = tokio::net::UnixListener::bind(&sock_path).unwrap();
+        let (shutdown_tx, _) = broadcast::channel::<()>(1);
+        let server_shutdown = shutdown_tx.clone();
+        tokio::spawn(async move {
+            crate::daemon::server::run_server(listener, server_shutdown, None).await;
+        });
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+        let stream = tokio::net::UnixStream::connect(&sock_path).await.unwrap();
+        let (mut reader, mut writer) = stream.into_split();
+        write_frame_async(
+            &mut writer,
+            &ClientMessage::create_session(
+                "flood",
+                vec![
+                    "sh".to_string(),
+                    "-c".to_string(),
+                    "sleep 0.3; seq 1 300000; sleep 10".to_string(),
+                ],
+            ),
+        )
+        .await
+        .unwrap();
+        let resp: DaemonMessage = try_read_frame_async(&mut reader).await.unwrap().unwrap();
+        assert!(matches!(resp, DaemonMessage::SessionCreated { .. }), "{:?}", resp);
+
+        let stream = tokio::net::UnixStream::connect(&sock_path).await.unwrap();
+        let (mut reader, mut writer) = stream.into_split();
+        write_frame_async(
+            &mut writer,
+            &ClientMessage::Attach {
+                name: "flood".to_string(),
+                cols: 80,
+                rows: 24,
+                buffer: Some(1),
+            },
+        )
+        .await
+        .unwrap();
+
+        // Don't read while the session floods.
+        tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+
+        let mut screen = VirtualTerminal::new(24, 80);
+        let mut skipped = None;
+        let read_all = async {
+            loop {
+                match try_read_frame_async::<DaemonMessage>(&mut reader).await {
+                    Some(Ok(DaemonMessage::Output(data))) => screen.process(&data),
+                    Some(Ok(DaemonMessage::Resync { skipped: n, screen: repaint })) => {
+                        skipped = Some(n);
+                        screen.process(&repaint);
+                    }
+                    other => panic!("unexpected {:?}", other),
+                }
+                if skipped.is_some() && screen.rendered_screen().contains("300000") {
+                    break;
+                }
+            }
+        };
+        let finished = tokio::time::timeout(std::time::Duration::from_secs(10), read_all).await;
+        assert!(finished.is_ok(), "never caught up; skipped={:?}", skipped);
+        assert!(skipped.unwrap() > 0);
+        // The client's screen is the session's, not a spliced stream.
+        let lines: Vec<String> = screen
+            .rendered_screen()
+            .lines()
+            .filter(|l| !l.is_empty())
+            .map(str::to_string)
+            .collect();
+        let expected: Vec<String> =
+            ((300000 - lines.len() + 1)..=300000).map(|n| n.to_string()).collect();
+        assert_eq!(lines, expected);
+
+        let _ = write_frame_async(&mut writer, &ClientMessage::Detach).await;
+        let _ = shutdown_tx.send(());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    /// Integration test: attaching to a TUI whose last full redraw has been
+    /// evicted from the raw scrollback ring still shows its whole screen.
+    #[tokio::test]
+    async fn test_attach_paints_live_screen_not_scrollback() {
+        use crate::daemon::vterm::VirtualTerminal;
+        use tokio::sync::broadcast;
+
+        let dir = std::env::temp_dir().join(format!("amux-test-repaint-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let sock_path = dir.join("test.sock");
+        let _ = std::fs::remove_file(&sock_path);
+
+        let listener = tokio::net::UnixListener::bind(&sock_path).unwrap();
+        let (shutdown_tx, _) = broadcast::channel::<()>(1);
+        let server_shutdown = shutdown_tx.clone();
+        tokio::spawn(async move {
+            crate::daemon::server::run_server(listener, server_shutdown, None).await;
+        });
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+        // One full redraw, then >64KB of cursor-addressed partial updates.
+        let script = r#"printf '\033[?1049h\033[2J\033[1;1HTITLE BAR\033[3;1Hstatus:'
+            i=0
+            while [ $i -lt 6000 ]; do
+                printf '\033[3;9H%-12s' "tick $i"
+                i=$((i+1))
+            done
+            printf '\033[3;9Hdone        '
+            sleep 10"#;
+        let stream = tokio::net::UnixStream::connect(&sock_path).await.unwrap();
+        let (mut reader, mut writer) = stream.into_split();
+        write_frame_async(
+            &mut writer,
+            &ClientMessage::CreateSession {
+                name: Some("tui".to_string()),
+                command: vec!["sh".to_string(), "-c".to_string(), script.to_string()],
+                env: None,
+                cwd: None,
+                cols: Some(80),
+                rows: Some(24),
+                limits: None,
+                attention: Vec::new(),
+            },
+        )
+        .await
+        .unwrap();
+        let resp: DaemonMessage = try_read_frame_async(&mut reader).await.unwrap().unwrap();
+        assert!(matches!(resp, DaemonMessage::SessionCreated { .. }), "{:?}", resp);
+
+        // Wait for the loop to finish.
+        let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(10);
+        loop {
+            write_frame_async(
+                &mut writer,
+                &ClientMessage::CaptureScrollback {
+                    name: "tui".to_string(),
+                    lines: 5,
+                    mode: CaptureMode::Plain,
+                },
+            )
+            .await
+            .unwrap();
+            match try_read_frame_async::<DaemonMessage>(&mut reader).await {
+                Some(Ok(DaemonMessage::CaptureOutput(data)))
+                    if String::from_utf8_lossy(&data).contains("done") =>
+                {
+                    break
+                }
+                _ => {}
+            }
+            assert!(tokio::time::Instant::now() < deadline, "session never finished");
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        }
+
+        let stream = tokio::net::UnixStream::connect(&sock_path).await.unwrap();
+        let (mut reader, mut writer) = stream.into_split();
+        write_frame_async(
+            &mut writer,
+            &ClientMessage::Attach {
+                name: "tui".to_string(),
+                cols: 80,
+                rows: 24,
+                buffer: None,
+            },
+        )
+        .await
+        .unwrap();
+        let first = match try_read_frame_async::<DaemonMessage>(&mut reader).await {
+            Some(Ok(DaemonMessage::Output(data))) => data,
+            other => panic!("expected initial Output, got {:?}", other),
+        };
+        let mut client = VirtualTerminal::new(24, 80);
+        client.process(&first);
+        let screen = client.rendered_screen();
+        assert!(screen.contains("TITLE BAR"), "screen: {:?}", screen);
+        assert!(screen.contains("status: done"), "screen: {:?}", screen);
+
+        let _ = write_frame_async(&mut writer, &ClientMessage::Detach).await;
+        let _ = shutdown_tx.send(());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
 }
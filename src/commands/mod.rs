@@ -8,14 +8,17 @@ mod session;
 mod top;
 
 use crate::cli::{Command, EnvAction};
-use crate::protocol::messages::{ClientMessage, DaemonMessage};
+use crate::protocol::messages::{
+    CaptureMode, ClientMessage, DaemonMessage, InputPacing, ResourceLimits, SessionSelector, SignalTarget,
+};
+use nix::sys::signal::Signal;
 use crate::util::ensure_daemon_running;
 use crate::client;
 
 pub fn dispatch(command: Command) -> anyhow::Result<()> {
     match command {
-        Command::StartServer => {
-            server::start_server()?;
+        Command::StartServer { metrics } => {
+            server::start_server(metrics)?;
         }
         Command::KillServer { force } => {
             server::kill_server(force)?;
@@ -23,8 +26,10 @@ pub fn dispatch(command: Command) -> anyhow::Result<()> {
         Command::Ping => {
             server::ping()?;
         }
-        Command::Top { once } => {
-            if once {
+        Command::Top { once, json, interval } => {
+            if json {
+                top::do_top_json(interval, once)?;
+            } else if once {
                 top::do_top_once()?;
             } else {
                 top::do_top()?;
@@ -38,17 +43,50 @@ pub fn dispatch(command: Command) -> anyhow::Result<()> {
             worktree,
             init_message,
             rows,
+            memory,
+            cpus,
+            pids,
+            nofile,
+            attention,
             cmd,
         } => {
-            session::new_session(name, detached, env, cwd, worktree, init_message, rows, cmd)?;
+            let limits = if memory.is_some() || cpus.is_some() || pids.is_some() || nofile.is_some()
+            {
+                Some(ResourceLimits {
+                    memory_bytes: memory,
+                    cpus,
+                    pids,
+                    nofile,
+                })
+            } else {
+                None
+            };
+            session::new_session(
+                name,
+                detached,
+                env,
+                cwd,
+                worktree,
+                init_message,
+                rows,
+                limits,
+                attention,
+                cmd,
+            )?;
         }
-        Command::Attach { name } => {
+        Command::Attach { name, buffer } => {
             ensure_daemon_running()?;
-            attach::do_attach(&name)?;
+            attach::do_attach(&name, buffer)?;
         }
-        Command::Follow { name, raw, plain: _ } => {
+        Command::Follow {
+            name,
+            raw,
+            plain: _,
+            buffer,
+            compress,
+        } => {
             ensure_daemon_running()?;
-            attach::do_follow(&name, !raw)?;
+            attach::do_follow(&name, !raw, buffer, compress)?;
         }
         Command::Ls { json } => {
             query::list_sessions(json)?;
@@ -56,52 +94,126 @@ pub fn dispatch(command: Command) -> anyhow::Result<()> {
         Command::Info { name, json } => {
             query::session_info(&name, json)?;
         }
+        Command::Stats { name, json } => {
+            query::session_stats(&name, json)?;
+        }
         Command::Wait {
             name,
             any,
             timeout,
             exit_code,
+            attention,
         } => {
-            query::wait_session(name, any, timeout, exit_code)?;
+            if attention {
+                let sessions = name.into_iter().chain(any).collect();
+                query::wait_attention(sessions, timeout)?;
+            } else {
+                query::wait_session(name, any, timeout, exit_code)?;
+            }
         }
         Command::Watch { sessions, json, on_exit } => {
             ensure_daemon_running()?;
             query::do_watch(&sessions, json, on_exit.as_deref())?;
         }
-        Command::Kill { name, all } => {
+        Command::Events { sessions, json } => {
+            ensure_daemon_running()?;
+            query::do_watch_events(&sessions, json)?;
+        }
+        Command::Kill {
+            name,
+            all,
+            signal,
+            grace,
+        } => {
             ensure_daemon_running()?;
             if all {
                 session::do_kill_all()?;
             } else {
-                let name = name.unwrap();
-                let resp =
-                    client::request(&ClientMessage::KillSession { name: name.clone() })?;
-                match resp {
-                    DaemonMessage::Ok => eprintln!("amux: killed session '{}'", name),
-                    DaemonMessage::Error(e) => {
-                        eprintln!("amux: error: {}", e);
-                        std::process::exit(1);
-                    }
-                    other => eprintln!("amux: unexpected: {:?}", other),
-                }
+                session::kill_session(&name.unwrap(), signal, grace)?;
             }
         }
+        Command::Signal { name, signal, to } => {
+            ensure_daemon_running()?;
+            session::signal_session(&name, signal, to)?;
+        }
+        Command::Pause { name } => {
+            ensure_daemon_running()?;
+            session::signal_session(&name, Signal::SIGSTOP as i32, SignalTarget::Tree)?;
+        }
+        Command::Resume { name } => {
+            ensure_daemon_running()?;
+            session::signal_session(&name, Signal::SIGCONT as i32, SignalTarget::Tree)?;
+        }
         Command::KillAll => {
             ensure_daemon_running()?;
             session::do_kill_all()?;
         }
         Command::Send {
             name,
+            all,
+            any,
+            pattern,
             literal,
+            keys,
+            paste,
+            line_delay,
+            cps,
+            chunk_size,
+            newline_delay,
+            wait_written,
             text,
         } => {
-            session::send_keys(&name, literal, &text)?;
+            // clap guarantees exactly one of -t / --all / --any / --match.
+            let selector = if all {
+                Some(SessionSelector::All)
+            } else if !any.is_empty() {
+                Some(SessionSelector::Names(any))
+            } else {
+                pattern.map(SessionSelector::Pattern)
+            };
+            let name = name.unwrap_or_default();
+            if let Some(selector) = selector {
+                session::broadcast_keys(selector, literal, &text)?;
+            } else if keys {
+                session::send_key_names(&name, &text)?;
+            } else {
+                let pacing = if cps.is_some() || chunk_size.is_some() || newline_delay.is_some() {
+                    Some(InputPacing {
+                        chars_per_sec: cps.unwrap_or(0),
+                        chunk_size: chunk_size.unwrap_or(0),
+                        newline_delay_ms: newline_delay.unwrap_or(0),
+                    })
+                } else {
+                    None
+                };
+                let opts = session::SendOptions {
+                    literal,
+                    paste: paste.then_some(line_delay),
+                    pacing,
+                    wait_written,
+                };
+                session::send_keys(&name, &opts, &text)?;
+            }
         }
         Command::Has { name } => {
             session::has_session(&name)?;
         }
-        Command::Capture { name, lines, raw, plain: _ } => {
-            session::capture_scrollback(&name, lines, !raw)?;
+        Command::Capture { name, lines, format: Some(format), theme, compress, .. } => {
+            session::capture_export(&name, lines, format, &theme, compress)?;
+        }
+        Command::Capture { name, lines, raw, plain: _, json, compress, .. } => {
+            let mode = if json {
+                CaptureMode::Structured
+            } else if raw {
+                CaptureMode::Raw
+            } else {
+                CaptureMode::Plain
+            };
+            session::capture_scrollback(&name, lines, mode, compress)?;
+        }
+        Command::Rename { name, new_name } => {
+            ensure_daemon_running()?;
+            session::rename_session(&name, &new_name)?;
         }
         Command::Respawn { name, cwd, env, cmd } => {
             ensure_daemon_running()?;
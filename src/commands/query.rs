@@ -1,6 +1,8 @@
 use crate::protocol::codec::{read_frame, write_frame};
-use crate::protocol::messages::{ClientMessage, DaemonMessage};
-use crate::util::{ensure_daemon_running, truncate};
+use crate::protocol::messages::{
+    ClientMessage, DaemonMessage, LimitStatus, Notification, ResourceSample, TerminalEvent,
+};
+use crate::util::{cpu_sparkline, ensure_daemon_running, format_size, truncate};
 use crate::client;
 
 use anyhow::Context;
@@ -18,7 +20,11 @@ pub fn list_sessions(json: bool) -> anyhow::Result<()> {
                 );
             } else if !sessions.is_empty() {
                 for s in &sessions {
-                    let status = if s.alive {
+                    let status = if s.alive && s.paused {
+                        " (paused)".to_string()
+                    } else if s.alive && s.needs_attention {
+                        " (needs attention)".to_string()
+                    } else if s.alive {
                         String::new()
                     } else {
                         match s.exit_code {
@@ -26,8 +32,17 @@ pub fn list_sessions(json: bool) -> anyhow::Result<()> {
                             None => " (dead)".to_string(),
                         }
                     };
+                    let title = if s.title.is_empty() {
+                        String::new()
+                    } else {
+                        format!(" \"{}\"", truncate(&s.title, 40))
+                    };
+                    let notified = match s.notifications.last() {
+                        Some(n) => format!(" [notify: {}]", truncate(&format_notification(n), 40)),
+                        None => String::new(),
+                    };
                     println!(
-                        "{}: {} (pid {}, up {}s, idle {}s, created {}){}", s.name, truncate(&s.command, 60), s.pid, s.uptime_secs, s.idle_secs, s.created_at, status
+                        "{}: {} (pid {}, up {}s, idle {}s, created {}){}{}{}", s.name, truncate(&s.command, 60), s.pid, s.uptime_secs, s.idle_secs, s.created_at, status, title, notified
                     );
                 }
             }
@@ -55,7 +70,9 @@ pub fn session_info(name: &str, json: bool) -> anyhow::Result<()> {
                         .unwrap_or_else(|e| format!("{{\"error\":\"{}\"}}", e))
                 );
             } else {
-                let status = if info.alive {
+                let status = if info.alive && info.paused {
+                    "paused".to_string()
+                } else if info.alive {
                     "alive".to_string()
                 } else {
                     match info.exit_code {
@@ -71,6 +88,80 @@ pub fn session_info(name: &str, json: bool) -> anyhow::Result<()> {
                 println!("uptime: {}s", info.uptime_secs);
                 println!("last_activity: {}", info.last_activity);
                 println!("idle: {}s", info.idle_secs);
+                if !info.title.is_empty() {
+                    println!("title: {}", info.title);
+                }
+                if info.attention_count > 0 {
+                    println!(
+                        "attention: {} ({} total)",
+                        if info.needs_attention { "needed" } else { "cleared" },
+                        info.attention_count
+                    );
+                }
+                for n in &info.notifications {
+                    println!("notification: {}", format_notification(n));
+                }
+                if let Some(ref u) = info.usage {
+                    println!("resources: {}", format_usage(u));
+                }
+                if let Some(ref status) = info.limits {
+                    let (limits, usage) = format_limit_status(status);
+                    println!("limits: {}", limits);
+                    if let Some(usage) = usage {
+                        println!("usage: {}", usage);
+                    }
+                }
+            }
+        }
+        DaemonMessage::Error(e) => {
+            eprintln!("amux: error: {}", e);
+            std::process::exit(1);
+        }
+        other => eprintln!("amux: unexpected: {:?}", other),
+    }
+    Ok(())
+}
+
+/// `title: body`, or just the body for notifications without a title.
+fn format_notification(n: &Notification) -> String {
+    match &n.title {
+        Some(title) => format!("{}: {}", title, n.body),
+        None => n.body.clone(),
+    }
+}
+
+/// One-line summary of a telemetry sample for `amux info`.
+fn format_usage(u: &ResourceSample) -> String {
+    format!(
+        "cpu {:.1}%, rss {}, threads {}, fds {}, children {}",
+        u.cpu_percent,
+        format_size(u.rss_bytes),
+        u.threads,
+        u.fds,
+        u.children
+    )
+}
+
+/// Print a session's resource usage history (`amux stats`).
+pub fn session_stats(name: &str, json: bool) -> anyhow::Result<()> {
+    ensure_daemon_running()?;
+    let resp = client::request(&ClientMessage::GetSessionStats {
+        name: name.to_string(),
+    })?;
+    match resp {
+        DaemonMessage::SessionStats(samples) => {
+            if json {
+                println!(
+                    "{}",
+                    serde_json::to_string(&samples)
+                        .unwrap_or_else(|e| format!("{{\"error\":\"{}\"}}", e))
+                );
+            } else if samples.is_empty() {
+                eprintln!("amux: no samples for '{}' yet", name);
+            } else {
+                for line in render_stats(&samples) {
+                    println!("{}", line);
+                }
             }
         }
         DaemonMessage::Error(e) => {
@@ -82,6 +173,78 @@ pub fn session_info(name: &str, json: bool) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Width of the CPU sparkline above the `amux stats` table.
+const STATS_SPARKLINE_WIDTH: usize = 60;
+
+/// Render the `amux stats` view: a CPU sparkline over the most recent
+/// samples followed by one row per sample, newest last. Times are relative
+/// to the newest sample.
+fn render_stats(samples: &[ResourceSample]) -> Vec<String> {
+    let mut lines = Vec::new();
+    let cpu: Vec<f32> = samples.iter().map(|s| s.cpu_percent).collect();
+    let spark_from = cpu.len().saturating_sub(STATS_SPARKLINE_WIDTH);
+    let peak = cpu.iter().copied().fold(0.0f32, f32::max);
+    lines.push(format!(
+        "cpu {} (peak {:.1}%)",
+        cpu_sparkline(&cpu[spark_from..]),
+        peak
+    ));
+    lines.push(format!(
+        "{:>6} {:>7} {:>8} {:>7} {:>5} {:>8}",
+        "AGO", "CPU%", "RSS", "THREADS", "FDS", "CHILDREN"
+    ));
+    let newest = samples.last().map(|s| s.unix_ms).unwrap_or(0);
+    for s in samples {
+        lines.push(format!(
+            "{:>6} {:>7.1} {:>8} {:>7} {:>5} {:>8}",
+            format!("-{}s", newest.saturating_sub(s.unix_ms) / 1000),
+            s.cpu_percent,
+            format_size(s.rss_bytes),
+            s.threads,
+            s.fds,
+            s.children
+        ));
+    }
+    lines
+}
+
+/// The `limits:` and (when anything is measurable) `usage:` lines of
+/// `amux info`.
+fn format_limit_status(status: &LimitStatus) -> (String, Option<String>) {
+    let l = &status.limits;
+    let mut parts = Vec::new();
+    if let Some(bytes) = l.memory_bytes {
+        parts.push(format!("memory {}", format_size(bytes)));
+    }
+    if let Some(cpus) = l.cpus {
+        let note = if status.cgroup.is_none() { " (unenforced)" } else { "" };
+        parts.push(format!("cpus {}{}", cpus, note));
+    }
+    if let Some(pids) = l.pids {
+        parts.push(format!("pids {}", pids));
+    }
+    if let Some(nofile) = l.nofile {
+        parts.push(format!("nofile {}", nofile));
+    }
+    let via = match status.cgroup {
+        Some(ref path) => format!("cgroup {}", path),
+        None => "rlimit".to_string(),
+    };
+    let limits = format!("{} ({})", parts.join(", "), via);
+
+    let mut usage = Vec::new();
+    if let Some(bytes) = status.memory_bytes {
+        usage.push(format!("memory {}", format_size(bytes)));
+    }
+    if let Some(pids) = status.pids {
+        usage.push(format!("pids {}", pids));
+    }
+    if let Some(usec) = status.cpu_usage_usec {
+        usage.push(format!("cpu {:.1}s", usec as f64 / 1_000_000.0));
+    }
+    (limits, (!usage.is_empty()).then(|| usage.join(", ")))
+}
+
 pub fn wait_session(
     name: Option<String>,
     any: Vec<String>,
@@ -162,6 +325,42 @@ pub fn wait_session(
     Ok(())
 }
 
+/// Block until a session needs attention and print its name.
+pub fn wait_attention(sessions: Vec<String>, timeout: u64) -> anyhow::Result<()> {
+    ensure_daemon_running()?;
+    let resp = client::request_with_timeout(
+        &ClientMessage::WaitAttention {
+            sessions,
+            timeout_secs: timeout,
+        },
+        attention_deadline(timeout),
+    )?;
+    match resp {
+        DaemonMessage::AttentionNeeded { session } => println!("{}", session),
+        DaemonMessage::Error(e) => {
+            if e == "timeout" {
+                eprintln!("amux: wait --attention timed out");
+                std::process::exit(2);
+            }
+            eprintln!("amux: error: {}", e);
+            std::process::exit(1);
+        }
+        other => eprintln!("amux: unexpected: {:?}", other),
+    }
+    Ok(())
+}
+
+/// How long to wait for the answer to a `WaitAttention` of `timeout_secs`.
+/// The daemon reports its own timeout, so allow a margin past it; a wait
+/// of 0 is unbounded and gets no deadline at all.
+fn attention_deadline(timeout_secs: u64) -> std::time::Duration {
+    if timeout_secs == 0 {
+        std::time::Duration::MAX
+    } else {
+        std::time::Duration::from_secs(timeout_secs) + client::REQUEST_TIMEOUT
+    }
+}
+
 /// Expand an on-exit template, substituting `{name}`, `{code}`, `{pid}`, `{duration}`.
 fn expand_on_exit_template(
     template: &str,
@@ -277,9 +476,84 @@ pub fn do_watch(sessions: &[String], json: bool, on_exit: Option<&str>) -> anyho
     Ok(())
 }
 
+/// JSON line for one `amux events --json` event.
+fn event_json(session: &str, event: &TerminalEvent) -> serde_json::Value {
+    match event {
+        TerminalEvent::TitleChanged { title } => serde_json::json!({
+            "event": "title",
+            "session": session,
+            "title": title,
+        }),
+        TerminalEvent::Notification(n) => serde_json::json!({
+            "event": "notification",
+            "session": session,
+            "unix_ms": n.unix_ms,
+            "title": n.title,
+            "body": n.body,
+        }),
+        TerminalEvent::Bell => serde_json::json!({
+            "event": "bell",
+            "session": session,
+        }),
+        TerminalEvent::AttentionPattern { pattern } => serde_json::json!({
+            "event": "attention",
+            "session": session,
+            "pattern": pattern,
+        }),
+    }
+}
+
+/// Stream title changes and notifications until interrupted.
+pub fn do_watch_events(sessions: &[String], json: bool) -> anyhow::Result<()> {
+    let mut stream =
+        client::connect().context("is the server running? try: amux start-server")?;
+    write_frame(
+        &mut stream,
+        &ClientMessage::WatchEvents {
+            sessions: sessions.to_vec(),
+        },
+    )?;
+
+    loop {
+        let resp: DaemonMessage = read_frame(&mut stream)?;
+        match resp {
+            DaemonMessage::SessionEvent { session, event } => {
+                if json {
+                    println!("{}", event_json(&session, &event));
+                } else {
+                    match &event {
+                        TerminalEvent::TitleChanged { title } => {
+                            println!("{}: title: {}", session, title)
+                        }
+                        TerminalEvent::Notification(n) => {
+                            println!("{}: notification: {}", session, format_notification(n))
+                        }
+                        TerminalEvent::Bell => println!("{}: bell", session),
+                        TerminalEvent::AttentionPattern { pattern } => {
+                            println!("{}: attention: {}", session, pattern)
+                        }
+                    }
+                }
+            }
+            DaemonMessage::Error(e) => {
+                eprintln!("amux: error: {}", e);
+                std::process::exit(1);
+            }
+            other => {
+                eprintln!("amux: unexpected: {:?}", other);
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::expand_on_exit_template;
+    use super::{
+        attention_deadline, event_json, expand_on_exit_template, format_limit_status, render_stats,
+    };
+    use crate::client;
+    use crate::util::format_size;
     use crate::protocol::codec::{try_read_frame_async, write_frame_async};
     use crate::protocol::messages::{ClientMessage, DaemonMessage};
 
@@ -295,6 +569,58 @@ mod tests {
         assert_eq!(result, "echo worker-1 exited with 0, pid=1234, dur=60");
     }
 
+    #[test]
+    fn test_format_limit_status() {
+        use crate::protocol::messages::{LimitStatus, ResourceLimits};
+        assert_eq!(format_size(512), "512B");
+        assert_eq!(format_size(4 << 30), "4.0G");
+
+        let status = LimitStatus {
+            limits: ResourceLimits {
+                memory_bytes: Some(512 << 20),
+                cpus: Some(2.0),
+                pids: None,
+                nofile: Some(4096),
+            },
+            cgroup: None,
+            memory_bytes: Some(3 << 20),
+            pids: None,
+            cpu_usage_usec: None,
+        };
+        let (limits, usage) = format_limit_status(&status);
+        assert_eq!(limits, "memory 512.0M, cpus 2 (unenforced), nofile 4096 (rlimit)");
+        assert_eq!(usage.as_deref(), Some("memory 3.0M"));
+
+        let status = LimitStatus {
+            cgroup: Some("/sys/fs/cgroup/amux-w".into()),
+            pids: Some(3),
+            cpu_usage_usec: Some(1_500_000),
+            memory_bytes: None,
+            ..status
+        };
+        let (limits, usage) = format_limit_status(&status);
+        assert!(limits.ends_with("(cgroup /sys/fs/cgroup/amux-w)"), "{}", limits);
+        assert_eq!(usage.as_deref(), Some("pids 3, cpu 1.5s"));
+    }
+
+    #[test]
+    fn test_render_stats() {
+        use crate::protocol::messages::ResourceSample;
+        let sample = |unix_ms, cpu_percent| ResourceSample {
+            unix_ms,
+            cpu_percent,
+            rss_bytes: 2 << 20,
+            threads: 3,
+            fds: 9,
+            children: 1,
+        };
+        let lines = render_stats(&[sample(10_000, 0.0), sample(12_000, 100.0)]);
+        assert_eq!(lines[0], "cpu ▁█ (peak 100.0%)");
+        assert!(lines[1].contains("CHILDREN"));
+        assert_eq!(lines[2], "   -2s     0.0     2.0M       3     9        1");
+        assert!(lines[3].starts_with("   -0s   100.0"), "{:?}", lines[3]);
+    }
+
     #[test]
     fn test_expand_on_exit_template_none_values() {
         let result = expand_on_exit_template(
@@ -340,7 +666,7 @@ mod tests {
 
         let server_shutdown = shutdown_tx.clone();
         tokio::spawn(async move {
-            crate::daemon::server::run_server(listener, server_shutdown).await;
+            crate::daemon::server::run_server(listener, server_shutdown, None).await;
         });
 
         tokio::time::sleep(std::time::Duration::from_millis(100)).await;
@@ -350,14 +676,10 @@ mod tests {
 
         write_frame_async(
             &mut writer,
-            &ClientMessage::CreateSession {
-                name: Some("watch-a".to_string()),
-                command: vec!["sh".to_string(), "-c".to_string(), "exit 0".to_string()],
-                env: None,
-                cwd: None,
-                cols: None,
-                rows: None,
-            },
+            &ClientMessage::create_session(
+                "watch-a",
+                vec!["sh".to_string(), "-c".to_string(), "exit 0".to_string()],
+            ),
         )
         .await
         .unwrap();
@@ -366,14 +688,10 @@ mod tests {
 
         write_frame_async(
             &mut writer,
-            &ClientMessage::CreateSession {
-                name: Some("watch-b".to_string()),
-                command: vec!["sh".to_string(), "-c".to_string(), "exit 42".to_string()],
-                env: None,
-                cwd: None,
-                cols: None,
-                rows: None,
-            },
+            &ClientMessage::create_session(
+                "watch-b",
+                vec!["sh".to_string(), "-c".to_string(), "exit 42".to_string()],
+            ),
         )
         .await
         .unwrap();
@@ -437,7 +755,7 @@ mod tests {
 
         let server_shutdown = shutdown_tx.clone();
         tokio::spawn(async move {
-            crate::daemon::server::run_server(listener, server_shutdown).await;
+            crate::daemon::server::run_server(listener, server_shutdown, None).await;
         });
 
         tokio::time::sleep(std::time::Duration::from_millis(100)).await;
@@ -447,18 +765,14 @@ mod tests {
 
         write_frame_async(
             &mut writer,
-            &ClientMessage::CreateSession {
-                name: Some("watch-live".to_string()),
-                command: vec![
+            &ClientMessage::create_session(
+                "watch-live",
+                vec![
                     "sh".to_string(),
                     "-c".to_string(),
                     "sleep 0.3; exit 7".to_string(),
                 ],
-                env: None,
-                cwd: None,
-                cols: None,
-                rows: None,
-            },
+            ),
         )
         .await
         .unwrap();
@@ -520,7 +834,7 @@ mod tests {
 
         let server_shutdown = shutdown_tx.clone();
         tokio::spawn(async move {
-            crate::daemon::server::run_server(listener, server_shutdown).await;
+            crate::daemon::server::run_server(listener, server_shutdown, None).await;
         });
 
         tokio::time::sleep(std::time::Duration::from_millis(100)).await;
@@ -548,6 +862,568 @@ mod tests {
         let _ = std::fs::remove_dir_all(&dir);
     }
 
+    #[test]
+    fn test_event_json() {
+        use crate::protocol::messages::{Notification, TerminalEvent};
+        let title = TerminalEvent::TitleChanged {
+            title: "busy".to_string(),
+        };
+        assert_eq!(
+            event_json("w1", &title).to_string(),
+            r#"{"event":"title","session":"w1","title":"busy"}"#
+        );
+        let note = TerminalEvent::Notification(Notification {
+            unix_ms: 5,
+            title: None,
+            body: "Needs input".to_string(),
+        });
+        assert_eq!(
+            event_json("w1", &note).to_string(),
+            r#"{"body":"Needs input","event":"notification","session":"w1","title":null,"unix_ms":5}"#
+        );
+    }
+
+    /// Integration test: titles and notifications reach a WatchEvents
+    /// client (even for a session created after it subscribed) and show
+    /// up in SessionInfo.
+    #[tokio::test]
+    async fn test_watch_events_title_and_notification() {
+        use crate::protocol::messages::TerminalEvent;
+        use std::time::Duration;
+        use tokio::sync::broadcast;
+
+        let dir =
+            std::env::temp_dir().join(format!("amux-test-events-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let sock_path = dir.join("test.sock");
+        let _ = std::fs::remove_file(&sock_path);
+
+        let listener = tokio::net::UnixListener::bind(&sock_path).unwrap();
+        let (shutdown_tx, _) = broadcast::channel::<()>(1);
+        let server_shutdown = shutdown_tx.clone();
+        tokio::spawn(async move {
+            crate::daemon::server::run_server(listener, server_shutdown, None).await;
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let watch = tokio::net::UnixStream::connect(&sock_path).await.unwrap();
+        let (mut watch_reader, mut watch_writer) = watch.into_split();
+        write_frame_async(&mut watch_writer, &ClientMessage::WatchEvents { sessions: vec![] })
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let stream = tokio::net::UnixStream::connect(&sock_path).await.unwrap();
+        let (mut reader, mut writer) = stream.into_split();
+        write_frame_async(
+            &mut writer,
+            &ClientMessage::create_session(
+                "notify-test",
+                vec![
+                    "sh".to_string(),
+                    "-c".to_string(),
+                    r"sleep 0.2; printf ']2;agent: busy]9;Needs input'; sleep 10"
+                        .to_string(),
+                ],
+            ),
+        )
+        .await
+        .unwrap();
+        let resp: DaemonMessage = try_read_frame_async(&mut reader).await.unwrap().unwrap();
+        assert!(matches!(resp, DaemonMessage::SessionCreated { .. }), "{:?}", resp);
+
+        let mut events = Vec::new();
+        while events.len() < 2 {
+            let resp: DaemonMessage =
+                tokio::time::timeout(Duration::from_secs(5), try_read_frame_async(&mut watch_reader))
+                    .await
+                    .expect("no event within 5s")
+                    .unwrap()
+                    .unwrap();
+            match resp {
+                DaemonMessage::SessionEvent { session, event } => {
+                    assert_eq!(session, "notify-test");
+                    events.push(event);
+                }
+                other => panic!("expected SessionEvent, got: {:?}", other),
+            }
+        }
+        assert_eq!(
+            events[0],
+            TerminalEvent::TitleChanged {
+                title: "agent: busy".to_string()
+            }
+        );
+        assert!(
+            matches!(&events[1], TerminalEvent::Notification(n) if n.body == "Needs input"),
+            "{:?}",
+            events[1]
+        );
+
+        write_frame_async(
+            &mut writer,
+            &ClientMessage::GetSessionInfo {
+                name: "notify-test".to_string(),
+            },
+        )
+        .await
+        .unwrap();
+        match try_read_frame_async(&mut reader).await.unwrap().unwrap() {
+            DaemonMessage::SessionDetail(info) => {
+                assert_eq!(info.title, "agent: busy");
+                assert_eq!(info.notifications.len(), 1);
+                assert_eq!(info.notifications[0].body, "Needs input");
+            }
+            other => panic!("expected SessionDetail, got: {:?}", other),
+        }
+
+        let _ = shutdown_tx.send(());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    /// Integration test: a SubscribeSessionList client gets the list, then
+    /// additions, preview lines for the session it picks (no more than
+    /// the daemon allows), and removals.
+    #[tokio::test]
+    async fn test_subscribe_session_list_pushes_diffs() {
+        use crate::protocol::session_list::SessionListDiff;
+        use std::time::Duration;
+        use tokio::sync::broadcast;
+
+        let dir =
+            std::env::temp_dir().join(format!("amux-test-list-feed-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let sock_path = dir.join("test.sock");
+        let _ = std::fs::remove_file(&sock_path);
+
+        let listener = tokio::net::UnixListener::bind(&sock_path).unwrap();
+        let (shutdown_tx, _) = broadcast::channel::<()>(1);
+        let server_shutdown = shutdown_tx.clone();
+        tokio::spawn(async move {
+            crate::daemon::server::run_server(listener, server_shutdown, None).await;
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let feed = tokio::net::UnixStream::connect(&sock_path).await.unwrap();
+        let (mut feed_reader, mut feed_writer) = feed.into_split();
+        write_frame_async(
+            &mut feed_writer,
+            &ClientMessage::SubscribeSessionList {
+                preview: None,
+                preview_lines: 0,
+            },
+        )
+        .await
+        .unwrap();
+        async fn next_update(
+            reader: &mut tokio::net::unix::OwnedReadHalf,
+        ) -> SessionListDiff {
+            let resp: DaemonMessage =
+                tokio::time::timeout(Duration::from_secs(5), try_read_frame_async(reader))
+                    .await
+                    .expect("no update within 5s")
+                    .unwrap()
+                    .unwrap();
+            match resp {
+                DaemonMessage::SessionListUpdate(diff) => diff,
+                other => panic!("expected SessionListUpdate, got: {:?}", other),
+            }
+        }
+        let first = next_update(&mut feed_reader).await;
+        assert!(first.added.is_empty() && first.preview.is_none());
+
+        let stream = tokio::net::UnixStream::connect(&sock_path).await.unwrap();
+        let (mut reader, mut writer) = stream.into_split();
+        write_frame_async(
+            &mut writer,
+            &ClientMessage::create_session(
+                "list-test",
+                vec![
+                    "sh".to_string(),
+                    "-c".to_string(),
+                    "seq 300; echo feed-hello; sleep 10".to_string(),
+                ],
+            ),
+        )
+        .await
+        .unwrap();
+        let resp: DaemonMessage = try_read_frame_async(&mut reader).await.unwrap().unwrap();
+        assert!(matches!(resp, DaemonMessage::SessionCreated { .. }), "{:?}", resp);
+
+        let mut sessions = Vec::new();
+        while sessions.is_empty() {
+            next_update(&mut feed_reader).await.apply_sessions(&mut sessions);
+        }
+        assert_eq!(sessions[0].name, "list-test");
+
+        // Choose the preview on the same connection.
+        write_frame_async(
+            &mut feed_writer,
+            &ClientMessage::SetPreview {
+                name: Some("list-test".to_string()),
+                lines: 10,
+            },
+        )
+        .await
+        .unwrap();
+        let mut preview = Vec::new();
+        while !preview.iter().any(|l: &String| l.contains("feed-hello")) {
+            let mut diff = next_update(&mut feed_reader).await;
+            diff.apply_sessions(&mut sessions);
+            if let Some(update) = diff.preview {
+                assert_eq!(update.name, "list-test");
+                update.apply(&mut preview);
+            }
+        }
+        assert!(preview.len() <= 10, "{}", preview.len());
+
+        // Asking for everything gets a screenful's worth, in one frame.
+        write_frame_async(
+            &mut feed_writer,
+            &ClientMessage::SetPreview {
+                name: Some("list-test".to_string()),
+                lines: u32::MAX,
+            },
+        )
+        .await
+        .unwrap();
+        loop {
+            let mut diff = next_update(&mut feed_reader).await;
+            diff.apply_sessions(&mut sessions);
+            if let Some(update) = diff.preview {
+                update.apply(&mut preview);
+                break;
+            }
+        }
+        assert!(preview.len() > 100 && preview.len() <= 200, "{}", preview.len());
+
+        write_frame_async(
+            &mut writer,
+            &ClientMessage::KillSession {
+                name: "list-test".to_string(),
+                signal: None,
+                grace_ms: None,
+            },
+        )
+        .await
+        .unwrap();
+        let _: DaemonMessage = try_read_frame_async(&mut reader).await.unwrap().unwrap();
+        while !sessions.is_empty() {
+            next_update(&mut feed_reader).await.apply_sessions(&mut sessions);
+        }
+
+        let _ = shutdown_tx.send(());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    /// Integration test: one multiplexed connection carries a blocking
+    /// WaitSession and later requests side by side, and the client's
+    /// MuxConnection routes each answer to its caller.
+    #[tokio::test]
+    async fn test_tagged_requests_share_a_connection() {
+        use std::time::Duration;
+        use tokio::sync::broadcast;
+
+        let dir = std::env::temp_dir().join(format!("amux-test-mux-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let sock_path = dir.join("test.sock");
+        let _ = std::fs::remove_file(&sock_path);
+
+        let listener = tokio::net::UnixListener::bind(&sock_path).unwrap();
+        let (shutdown_tx, _) = broadcast::channel::<()>(1);
+        let server_shutdown = shutdown_tx.clone();
+        tokio::spawn(async move {
+            crate::daemon::server::run_server(listener, server_shutdown, None).await;
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let stream = tokio::net::UnixStream::connect(&sock_path).await.unwrap();
+        let (mut reader, mut writer) = stream.into_split();
+        let tagged = |id, msg| ClientMessage::Tagged {
+            id,
+            msg: Box::new(msg),
+        };
+        async fn next(reader: &mut tokio::net::unix::OwnedReadHalf) -> DaemonMessage {
+            tokio::time::timeout(Duration::from_secs(5), try_read_frame_async(reader))
+                .await
+                .expect("no answer within 5s")
+                .unwrap()
+                .unwrap()
+        }
+
+        write_frame_async(
+            &mut writer,
+            &tagged(
+                1,
+                ClientMessage::create_session(
+                    "mux-test",
+                    vec!["sleep".to_string(), "10".to_string()],
+                ),
+            ),
+        )
+        .await
+        .unwrap();
+        match next(&mut reader).await {
+            DaemonMessage::Tagged { id: 1, msg } => {
+                assert!(matches!(*msg, DaemonMessage::SessionCreated { .. }), "{:?}", msg)
+            }
+            other => panic!("expected tagged SessionCreated, got: {:?}", other),
+        }
+        assert!(matches!(next(&mut reader).await, DaemonMessage::Closed { id: 1 }));
+
+        // A wait that won't finish for a while must not hold up the ping
+        // sent after it.
+        write_frame_async(
+            &mut writer,
+            &tagged(
+                2,
+                ClientMessage::WaitSession {
+                    name: "mux-test".to_string(),
+                    timeout_secs: 0,
+                },
+            ),
+        )
+        .await
+        .unwrap();
+        write_frame_async(&mut writer, &tagged(3, ClientMessage::Ping))
+            .await
+            .unwrap();
+        match next(&mut reader).await {
+            DaemonMessage::Tagged { id: 3, msg } => assert!(matches!(*msg, DaemonMessage::Pong)),
+            other => panic!("expected tagged Pong, got: {:?}", other),
+        }
+        assert!(matches!(next(&mut reader).await, DaemonMessage::Closed { id: 3 }));
+
+        // Untagged requests are refused once the connection is multiplexed.
+        write_frame_async(&mut writer, &ClientMessage::Ping).await.unwrap();
+        assert!(matches!(next(&mut reader).await, DaemonMessage::Error(_)));
+
+        // Cancelling the wait closes its channel.
+        write_frame_async(&mut writer, &ClientMessage::Cancel { id: 2 })
+            .await
+            .unwrap();
+        assert!(matches!(next(&mut reader).await, DaemonMessage::Closed { id: 2 }));
+
+        // The blocking client gets the same treatment from the other side.
+        let path = sock_path.clone();
+        tokio::task::spawn_blocking(move || {
+            let conn = client::mux::MuxConnection::connect_to(&path).unwrap();
+            let waiter = conn.clone();
+            let wait = std::thread::spawn(move || {
+                waiter.request(
+                    ClientMessage::WaitSession {
+                        name: "mux-test".to_string(),
+                        timeout_secs: 0,
+                    },
+                    Duration::from_secs(5),
+                )
+            });
+            for _ in 0..3 {
+                let resp = conn.request(ClientMessage::ListSessions, Duration::from_secs(5));
+                assert!(matches!(resp, Ok(DaemonMessage::SessionList(_))), "{:?}", resp);
+            }
+            let resp = conn.request(
+                ClientMessage::KillSession {
+                    name: "mux-test".to_string(),
+                    signal: None,
+                    grace_ms: None,
+                },
+                Duration::from_secs(5),
+            );
+            assert!(matches!(resp, Ok(DaemonMessage::SessionKilled { .. })), "{:?}", resp);
+            let resp = wait.join().unwrap();
+            assert!(matches!(resp, Ok(DaemonMessage::SessionExited)), "{:?}", resp);
+            assert!(conn.is_alive() && conn.has_answered());
+        })
+        .await
+        .unwrap();
+
+        let _ = shutdown_tx.send(());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    /// Integration test: an attention pattern on screen answers
+    /// WaitAttention and sets `needs_attention`; attaching clears it.
+    #[tokio::test]
+    async fn test_wait_attention_pattern_then_attach_clears() {
+        use std::time::Duration;
+        use tokio::sync::broadcast;
+
+        let dir =
+            std::env::temp_dir().join(format!("amux-test-attention-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let sock_path = dir.join("test.sock");
+        let _ = std::fs::remove_file(&sock_path);
+
+        let listener = tokio::net::UnixListener::bind(&sock_path).unwrap();
+        let (shutdown_tx, _) = broadcast::channel::<()>(1);
+        let server_shutdown = shutdown_tx.clone();
+        tokio::spawn(async move {
+            crate::daemon::server::run_server(listener, server_shutdown, None).await;
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let stream = tokio::net::UnixStream::connect(&sock_path).await.unwrap();
+        let (mut reader, mut writer) = stream.into_split();
+        for (name, script) in [
+            ("quiet", "sleep 10"),
+            ("asker", "sleep 0.3; printf 'Do you want to proceed?'; sleep 10"),
+        ] {
+            write_frame_async(
+                &mut writer,
+                &ClientMessage::CreateSession {
+                    name: Some(name.to_string()),
+                    command: vec!["sh".to_string(), "-c".to_string(), script.to_string()],
+                    env: None,
+                    cwd: None,
+                    cols: None,
+                    rows: None,
+                    limits: None,
+                    attention: vec!["want to proceed".to_string()],
+                },
+            )
+            .await
+            .unwrap();
+            let resp: DaemonMessage = try_read_frame_async(&mut reader).await.unwrap().unwrap();
+            assert!(matches!(resp, DaemonMessage::SessionCreated { .. }), "{:?}", resp);
+        }
+
+        write_frame_async(
+            &mut writer,
+            &ClientMessage::WaitAttention {
+                sessions: vec![],
+                timeout_secs: 5,
+            },
+        )
+        .await
+        .unwrap();
+        match try_read_frame_async(&mut reader).await.unwrap().unwrap() {
+            DaemonMessage::AttentionNeeded { session } => assert_eq!(session, "asker"),
+            other => panic!("expected AttentionNeeded, got: {:?}", other),
+        }
+
+        let info = |name: &str| ClientMessage::GetSessionInfo {
+            name: name.to_string(),
+        };
+        write_frame_async(&mut writer, &info("asker")).await.unwrap();
+        match try_read_frame_async(&mut reader).await.unwrap().unwrap() {
+            DaemonMessage::SessionDetail(info) => {
+                assert!(info.needs_attention);
+                assert_eq!(info.attention_count, 1);
+            }
+            other => panic!("expected SessionDetail, got: {:?}", other),
+        }
+
+        // Already flagged: a second wait answers at once.
+        write_frame_async(
+            &mut writer,
+            &ClientMessage::WaitAttention {
+                sessions: vec!["asker".to_string()],
+                timeout_secs: 1,
+            },
+        )
+        .await
+        .unwrap();
+        let resp: DaemonMessage = try_read_frame_async(&mut reader).await.unwrap().unwrap();
+        assert!(matches!(resp, DaemonMessage::AttentionNeeded { .. }), "{:?}", resp);
+
+        let attach = tokio::net::UnixStream::connect(&sock_path).await.unwrap();
+        let (mut attach_reader, mut attach_writer) = attach.into_split();
+        write_frame_async(
+            &mut attach_writer,
+            &ClientMessage::Attach {
+                name: "asker".to_string(),
+                cols: 80,
+                rows: 24,
+                buffer: None,
+            },
+        )
+        .await
+        .unwrap();
+        let _: DaemonMessage = try_read_frame_async(&mut attach_reader).await.unwrap().unwrap();
+
+        write_frame_async(&mut writer, &info("asker")).await.unwrap();
+        match try_read_frame_async(&mut reader).await.unwrap().unwrap() {
+            DaemonMessage::SessionDetail(info) => {
+                assert!(!info.needs_attention);
+                assert_eq!(info.attention_count, 1);
+            }
+            other => panic!("expected SessionDetail, got: {:?}", other),
+        }
+
+        // Nobody else needs a human.
+        write_frame_async(
+            &mut writer,
+            &ClientMessage::WaitAttention {
+                sessions: vec!["quiet".to_string()],
+                timeout_secs: 1,
+            },
+        )
+        .await
+        .unwrap();
+        match try_read_frame_async(&mut reader).await.unwrap().unwrap() {
+            DaemonMessage::Error(e) => assert_eq!(e, "timeout"),
+            other => panic!("expected timeout, got: {:?}", other),
+        }
+
+        let _ = shutdown_tx.send(());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    /// Integration test: `wait --attention` with no timeout outlasts the
+    /// client's usual request deadline.
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_wait_attention_outlasts_request_timeout() {
+        use std::time::Duration;
+        use tokio::sync::broadcast;
+
+        let dir =
+            std::env::temp_dir().join(format!("amux-test-attention-slow-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let sock_path = dir.join("test.sock");
+        let _ = std::fs::remove_file(&sock_path);
+
+        let listener = tokio::net::UnixListener::bind(&sock_path).unwrap();
+        let (shutdown_tx, _) = broadcast::channel::<()>(1);
+        let server_shutdown = shutdown_tx.clone();
+        tokio::spawn(async move {
+            crate::daemon::server::run_server(listener, server_shutdown, None).await;
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let conn = client::mux::MuxConnection::connect_to(&sock_path).unwrap();
+        let script = format!(
+            "sleep {}; printf 'Do you want to proceed?'; sleep 10",
+            client::REQUEST_TIMEOUT.as_secs() + 1
+        );
+        let create = ClientMessage::CreateSession {
+            name: Some("slow-asker".to_string()),
+            command: vec!["sh".to_string(), "-c".to_string(), script],
+            env: None,
+            cwd: None,
+            cols: None,
+            rows: None,
+            limits: None,
+            attention: vec!["want to proceed".to_string()],
+        };
+        let resp = conn.request(create, Duration::from_secs(5)).unwrap();
+        assert!(matches!(resp, DaemonMessage::SessionCreated { .. }), "{:?}", resp);
+
+        let started = std::time::Instant::now();
+        let wait = ClientMessage::WaitAttention {
+            sessions: vec![],
+            timeout_secs: 0,
+        };
+        match conn.request(wait, attention_deadline(0)) {
+            Ok(DaemonMessage::AttentionNeeded { session }) => assert_eq!(session, "slow-asker"),
+            other => panic!("expected AttentionNeeded, got: {:?}", other),
+        }
+        assert!(started.elapsed() > client::REQUEST_TIMEOUT);
+
+        let _ = shutdown_tx.send(());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
     /// Integration test: SessionInfo includes exit_code for exited sessions.
     #[tokio::test]
     async fn test_ls_shows_exit_code() {
@@ -564,7 +1440,7 @@ mod tests {
 
         let server_shutdown = shutdown_tx.clone();
         tokio::spawn(async move {
-            crate::daemon::server::run_server(listener, server_shutdown).await;
+            crate::daemon::server::run_server(listener, server_shutdown, None).await;
         });
 
         tokio::time::sleep(std::time::Duration::from_millis(100)).await;
@@ -574,14 +1450,10 @@ mod tests {
 
         write_frame_async(
             &mut writer,
-            &ClientMessage::CreateSession {
-                name: Some("exit-test".to_string()),
-                command: vec!["sh".to_string(), "-c".to_string(), "exit 42".to_string()],
-                env: None,
-                cwd: None,
-                cols: None,
-                rows: None,
-            },
+            &ClientMessage::create_session(
+                "exit-test",
+                vec!["sh".to_string(), "-c".to_string(), "exit 42".to_string()],
+            ),
         )
         .await
         .unwrap();
@@ -637,7 +1509,7 @@ mod tests {
 
         let server_shutdown = shutdown_tx.clone();
         tokio::spawn(async move {
-            crate::daemon::server::run_server(listener, server_shutdown).await;
+            crate::daemon::server::run_server(listener, server_shutdown, None).await;
         });
 
         tokio::time::sleep(std::time::Duration::from_millis(100)).await;
@@ -647,14 +1519,10 @@ mod tests {
 
         write_frame_async(
             &mut writer,
-            &ClientMessage::CreateSession {
-                name: Some("alive-test".to_string()),
-                command: vec!["sleep".to_string(), "60".to_string()],
-                env: None,
-                cwd: None,
-                cols: None,
-                rows: None,
-            },
+            &ClientMessage::create_session(
+                "alive-test",
+                vec!["sleep".to_string(), "60".to_string()],
+            ),
         )
         .await
         .unwrap();
@@ -683,6 +1551,8 @@ mod tests {
             &mut writer,
             &ClientMessage::KillSession {
                 name: "alive-test".to_string(),
+                signal: None,
+                grace_ms: None,
             },
         )
         .await
@@ -692,4 +1562,559 @@ mod tests {
         let _ = shutdown_tx.send(());
         let _ = std::fs::remove_dir_all(&dir);
     }
+
+    /// Integration test: a connection that negotiates compression gets its
+    /// capture back as a compressed frame, which the codec reads as usual;
+    /// a multiplexed channel declines.
+    #[tokio::test]
+    async fn test_negotiated_compression() {
+        use crate::protocol::messages::{CaptureMode, Compression};
+        use std::time::Duration;
+        use tokio::io::AsyncReadExt;
+        use tokio::sync::broadcast;
+
+        let dir = std::env::temp_dir().join(format!("amux-test-zip-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let sock_path = dir.join("test.sock");
+        let _ = std::fs::remove_file(&sock_path);
+
+        let listener = tokio::net::UnixListener::bind(&sock_path).unwrap();
+        let (shutdown_tx, _) = broadcast::channel::<()>(1);
+        let server_shutdown = shutdown_tx.clone();
+        tokio::spawn(async move {
+            crate::daemon::server::run_server(listener, server_shutdown, None).await;
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let stream = tokio::net::UnixStream::connect(&sock_path).await.unwrap();
+        let (mut reader, mut writer) = stream.into_split();
+        let create = ClientMessage::create_session(
+            "zip",
+            vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                "yes 'a very compressible log line' | head -n 500; sleep 10".to_string(),
+            ],
+        );
+        write_frame_async(&mut writer, &create).await.unwrap();
+        let resp = try_read_frame_async::<DaemonMessage>(&mut reader).await;
+        assert!(matches!(resp, Some(Ok(DaemonMessage::SessionCreated { .. }))), "{:?}", resp);
+        tokio::time::sleep(Duration::from_millis(500)).await;
+
+        let offer = ClientMessage::Negotiate {
+            compression: vec![Compression::Lz4],
+        };
+        write_frame_async(&mut writer, &offer).await.unwrap();
+        let resp = try_read_frame_async::<DaemonMessage>(&mut reader).await;
+        assert!(
+            matches!(
+                resp,
+                Some(Ok(DaemonMessage::Negotiated {
+                    compression: Some(Compression::Lz4)
+                }))
+            ),
+            "{:?}",
+            resp
+        );
+
+        let capture = ClientMessage::CaptureScrollback {
+            name: "zip".to_string(),
+            lines: 500,
+            mode: CaptureMode::Plain,
+        };
+        write_frame_async(&mut writer, &capture).await.unwrap();
+        let mut frame = vec![0u8; 4];
+        reader.read_exact(&mut frame).await.unwrap();
+        let word = u32::from_be_bytes([frame[0], frame[1], frame[2], frame[3]]);
+        assert!(word & (1 << 31) != 0, "capture frame not compressed");
+        frame.resize(4 + (word & !(1 << 31)) as usize, 0);
+        reader.read_exact(&mut frame[4..]).await.unwrap();
+        match crate::protocol::codec::read_frame::<_, DaemonMessage>(&mut &frame[..]).unwrap() {
+            DaemonMessage::CaptureOutput(data) => {
+                let text = String::from_utf8_lossy(&data);
+                assert!(text.matches("a very compressible log line").count() > 100, "{}", text);
+                assert!(frame.len() * 4 < data.len(), "{} -> {}", data.len(), frame.len());
+            }
+            other => panic!("expected CaptureOutput, got: {:?}", other),
+        }
+
+        // Channels are re-framed by the multiplexer, so they decline.
+        let stream = tokio::net::UnixStream::connect(&sock_path).await.unwrap();
+        let (mut reader, mut writer) = stream.into_split();
+        let tagged = ClientMessage::Tagged {
+            id: 1,
+            msg: Box::new(offer),
+        };
+        write_frame_async(&mut writer, &tagged).await.unwrap();
+        match try_read_frame_async::<DaemonMessage>(&mut reader).await {
+            Some(Ok(DaemonMessage::Tagged { id: 1, msg })) => assert!(
+                matches!(*msg, DaemonMessage::Negotiated { compression: None }),
+                "{:?}",
+                msg
+            ),
+            other => panic!("expected tagged Negotiated, got: {:?}", other),
+        }
+
+        let _ = shutdown_tx.send(());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    /// Integration test: `capture --compress` negotiates compression on
+    /// its own connection and gets the capture back intact.
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_compressed_capture_request() {
+        use crate::protocol::codec::compression_stats;
+        use crate::protocol::messages::CaptureMode;
+        use std::time::Duration;
+        use tokio::sync::broadcast;
+
+        let dir = std::env::temp_dir().join(format!("amux-test-zip-cap-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let sock_path = dir.join("test.sock");
+        let _ = std::fs::remove_file(&sock_path);
+
+        let listener = tokio::net::UnixListener::bind(&sock_path).unwrap();
+        let (shutdown_tx, _) = broadcast::channel::<()>(1);
+        let server_shutdown = shutdown_tx.clone();
+        tokio::spawn(async move {
+            crate::daemon::server::run_server(listener, server_shutdown, None).await;
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let stream = std::os::unix::net::UnixStream::connect(&sock_path).unwrap();
+        let create = ClientMessage::create_session(
+            "zip-cap",
+            vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                "yes 'a very compressible log line' | head -n 500; sleep 10".to_string(),
+            ],
+        );
+        let resp = client::request_compressed_on(stream, &create).unwrap();
+        assert!(matches!(resp, DaemonMessage::SessionCreated { .. }), "{:?}", resp);
+        tokio::time::sleep(Duration::from_millis(500)).await;
+
+        let compressed_before = compression_stats().frames();
+        let stream = std::os::unix::net::UnixStream::connect(&sock_path).unwrap();
+        let capture = ClientMessage::CaptureScrollback {
+            name: "zip-cap".to_string(),
+            lines: 500,
+            mode: CaptureMode::Plain,
+        };
+        match client::request_compressed_on(stream, &capture).unwrap() {
+            DaemonMessage::CaptureOutput(data) => {
+                let text = String::from_utf8_lossy(&data);
+                assert!(text.matches("a very compressible log line").count() > 100, "{}", text);
+            }
+            other => panic!("expected CaptureOutput, got: {:?}", other),
+        }
+        assert!(compression_stats().frames() > compressed_before, "capture not compressed");
+
+        let _ = shutdown_tx.send(());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    /// Integration test: input and captures bigger than a frame travel as
+    /// chunks, both ways, through the client's multiplexed connection.
+    #[tokio::test]
+    async fn test_chunked_upload_and_capture() {
+        use crate::protocol::messages::CaptureMode;
+        use std::time::{Duration, Instant};
+        use tokio::sync::broadcast;
+
+        let dir = std::env::temp_dir().join(format!("amux-test-chunks-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let sock_path = dir.join("test.sock");
+        let _ = std::fs::remove_file(&sock_path);
+        let sink = dir.join("upload");
+
+        let listener = tokio::net::UnixListener::bind(&sock_path).unwrap();
+        let (shutdown_tx, _) = broadcast::channel::<()>(1);
+        let server_shutdown = shutdown_tx.clone();
+        tokio::spawn(async move {
+            crate::daemon::server::run_server(listener, server_shutdown, None).await;
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let path = sock_path.clone();
+        tokio::task::spawn_blocking(move || {
+            let conn = client::mux::MuxConnection::connect_to(&path).unwrap();
+            let create = |name: &str, script: String, size: Option<u16>| {
+                let resp = conn.request(
+                    ClientMessage::CreateSession {
+                        name: Some(name.to_string()),
+                        command: vec!["sh".to_string(), "-c".to_string(), script],
+                        env: None,
+                        cwd: None,
+                        cols: size,
+                        rows: size,
+                        limits: None,
+                        attention: Vec::new(),
+                    },
+                    Duration::from_secs(5),
+                );
+                assert!(matches!(resp, Ok(DaemonMessage::SessionCreated { .. })), "{:?}", resp);
+            };
+
+            // A 1000x1000 screen of 999-column lines is ~1MB of capture.
+            create(
+                "wide",
+                "yes $(printf 'x%.0s' $(seq 999)) | head -n 1200; sleep 10".to_string(),
+                Some(1000),
+            );
+            // Raw mode, so the PTY passes every byte through unechoed.
+            create(
+                "sink",
+                format!("stty raw -echo; head -c 3000000 > {}; sleep 10", sink.display()),
+                None,
+            );
+            std::thread::sleep(Duration::from_millis(1000));
+
+            let payload: Vec<u8> = (0..3_000_000).map(|i| b'a' + (i % 26) as u8).collect();
+            let resp = conn.request(
+                ClientMessage::SendInput {
+                    name: "sink".to_string(),
+                    data: payload.clone(),
+                    newline: false,
+                    pacing: None,
+                    wait_written: true,
+                },
+                Duration::from_secs(10),
+            );
+            assert!(
+                matches!(resp, Ok(DaemonMessage::InputWritten { bytes: 3_000_000 })),
+                "{:?}",
+                resp
+            );
+            let deadline = Instant::now() + Duration::from_secs(10);
+            while std::fs::metadata(&sink).map(|m| m.len()).unwrap_or(0) < 3_000_000 {
+                assert!(Instant::now() < deadline, "upload never reached the session");
+                std::thread::sleep(Duration::from_millis(50));
+            }
+            assert!(std::fs::read(&sink).unwrap() == payload, "upload mangled");
+
+            let resp = conn.request(
+                ClientMessage::CaptureScrollback {
+                    name: "wide".to_string(),
+                    lines: 1_000_000,
+                    mode: CaptureMode::Plain,
+                },
+                Duration::from_secs(10),
+            );
+            match resp {
+                Ok(DaemonMessage::CaptureOutput(data)) => {
+                    assert!(data.len() > 1024 * 1024, "capture only {} bytes", data.len());
+                    let text = String::from_utf8_lossy(&data);
+                    assert!(text.lines().filter(|l| l.len() == 999).count() >= 1000);
+                }
+                other => panic!("expected CaptureOutput, got: {:?}", other),
+            }
+
+            // Both channels are done with; the connection carries on.
+            let resp = conn.request(ClientMessage::Ping, Duration::from_secs(5));
+            assert!(matches!(resp, Ok(DaemonMessage::Pong)), "{:?}", resp);
+        })
+        .await
+        .unwrap();
+
+        let _ = shutdown_tx.send(());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    /// Benchmark: latency of `ls`, `new` and `attach` while one session is
+    /// hammered with deep captures and another has a PTY nobody reads, so
+    /// writes to it stall. Neither may hold up requests for other sessions.
+    /// Run with
+    /// `cargo test --release -- --ignored --nocapture bench_registry_contention`.
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    #[ignore = "benchmark"]
+    async fn bench_registry_contention() {
+        use std::path::Path;
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::sync::Arc;
+        use std::time::{Duration, Instant};
+        use tokio::sync::broadcast;
+
+        const ROUNDS: usize = 40;
+        const LIMIT: Duration = Duration::from_secs(10);
+
+        let dir = std::env::temp_dir().join(format!("amux-bench-registry-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let sock_path = dir.join("test.sock");
+        let _ = std::fs::remove_file(&sock_path);
+
+        let listener = tokio::net::UnixListener::bind(&sock_path).unwrap();
+        let (shutdown_tx, _) = broadcast::channel::<()>(1);
+        let server_shutdown = shutdown_tx.clone();
+        tokio::spawn(async move {
+            crate::daemon::server::run_server(listener, server_shutdown, None).await;
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        /// One request on its own connection; `None` if it took over `LIMIT`.
+        async fn request(sock: &Path, msg: &ClientMessage) -> Option<DaemonMessage> {
+            let exchange = async {
+                let stream = tokio::net::UnixStream::connect(sock).await.ok()?;
+                let (mut reader, mut writer) = stream.into_split();
+                write_frame_async(&mut writer, msg).await.ok()?;
+                try_read_frame_async(&mut reader).await?.ok()
+            };
+            tokio::time::timeout(LIMIT, exchange).await.ok().flatten()
+        }
+        fn create(name: &str, script: &str) -> ClientMessage {
+            ClientMessage::create_session(
+                name,
+                vec!["sh".to_string(), "-c".to_string(), script.to_string()],
+            )
+        }
+        fn report(what: &str, mut samples: Vec<Duration>) -> Duration {
+            samples.sort();
+            let at = |q: f64| samples[((samples.len() - 1) as f64 * q) as usize];
+            println!(
+                "{:<8} p50 {:>9.2?}  p99 {:>9.2?}  max {:>9.2?}",
+                what,
+                at(0.5),
+                at(0.99),
+                samples[samples.len() - 1]
+            );
+            at(0.99)
+        }
+
+        let sock = sock_path.as_path();
+        // Out of canonical mode the tty stops taking input once its buffer
+        // is full, instead of dropping it.
+        for (name, script) in [
+            ("deep", "seq 1 20000; sleep 120"),
+            ("stuck", "stty -icanon -echo; sleep 120"),
+        ] {
+            let resp = request(sock, &create(name, script)).await;
+            assert!(matches!(resp, Some(DaemonMessage::SessionCreated { .. })), "{:?}", resp);
+        }
+        tokio::time::sleep(Duration::from_secs(1)).await;
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let mut load = Vec::new();
+        // Deep captures of one session, back to back.
+        for _ in 0..2 {
+            let (stop, sock) = (stop.clone(), sock_path.clone());
+            load.push(tokio::spawn(async move {
+                let capture = ClientMessage::CaptureScrollback {
+                    name: "deep".to_string(),
+                    lines: 20000,
+                    mode: crate::protocol::messages::CaptureMode::Formatted,
+                };
+                while !stop.load(Ordering::Relaxed) {
+                    request(&sock, &capture).await;
+                }
+            }));
+        }
+        // Input for a program that never reads it: the PTY fills up, then
+        // the session's input queue, then every further write waits.
+        for i in 0..4 {
+            let (stop, sock) = (stop.clone(), sock_path.clone());
+            load.push(tokio::spawn(async move {
+                let send = match i {
+                    0 => ClientMessage::SendInput {
+                        name: "stuck".to_string(),
+                        data: vec![b'x'; 4096],
+                        newline: false,
+                        pacing: None,
+                        wait_written: false,
+                    },
+                    _ => ClientMessage::SendKeys {
+                        name: "stuck".to_string(),
+                        keys: vec!["x".repeat(4096)],
+                    },
+                };
+                while !stop.load(Ordering::Relaxed) {
+                    request(&sock, &send).await;
+                }
+            }));
+        }
+        tokio::time::sleep(Duration::from_millis(500)).await;
+
+        let mut ls = Vec::new();
+        let mut new = Vec::new();
+        let mut attach = Vec::new();
+        for round in 0..ROUNDS {
+            let start = Instant::now();
+            let resp = request(sock, &ClientMessage::ListSessions).await;
+            ls.push(start.elapsed());
+            assert!(matches!(resp, Some(DaemonMessage::SessionList(_))), "ls: {:?}", resp);
+
+            let name = format!("bench-{}", round);
+            let start = Instant::now();
+            let resp = request(sock, &create(&name, "sleep 120")).await;
+            new.push(start.elapsed());
+            assert!(matches!(resp, Some(DaemonMessage::SessionCreated { .. })), "new: {:?}", resp);
+
+            let attach_msg = ClientMessage::Attach {
+                name: name.clone(),
+                cols: 80,
+                rows: 24,
+                buffer: None,
+            };
+            let start = Instant::now();
+            let resp = request(sock, &attach_msg).await;
+            attach.push(start.elapsed());
+            assert!(matches!(resp, Some(DaemonMessage::Output(_))), "attach: {:?}", resp);
+
+            request(
+                sock,
+                &ClientMessage::KillSession {
+                    name,
+                    signal: None,
+                    grace_ms: Some(0),
+                },
+            )
+            .await;
+        }
+        stop.store(true, Ordering::Relaxed);
+
+        println!("under load, {} rounds:", ROUNDS);
+        let worst = [report("ls", ls), report("new", new), report("attach", attach)];
+        let _ = shutdown_tx.send(());
+        for task in load {
+            task.abort();
+        }
+        let _ = std::fs::remove_dir_all(&dir);
+        for p99 in worst {
+            assert!(p99 < Duration::from_millis(500), "p99 {:?}", p99);
+        }
+    }
+
+    /// Benchmark: output throughput to N followers of a session printing a
+    /// build log as fast as it can. Reports the aggregate delivery rate and
+    /// the frames each follower needed. Run with
+    /// `cargo test --release -- --ignored --nocapture bench_follow_throughput`.
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    #[ignore = "benchmark"]
+    async fn bench_follow_throughput() {
+        use std::time::{Duration, Instant};
+        use tokio::sync::broadcast;
+
+        const LOG_BYTES: usize = 16 * 1024 * 1024;
+
+        let dir = std::env::temp_dir().join(format!("amux-bench-follow-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let sock_path = dir.join("test.sock");
+        let _ = std::fs::remove_file(&sock_path);
+
+        let listener = tokio::net::UnixListener::bind(&sock_path).unwrap();
+        let (shutdown_tx, _) = broadcast::channel::<()>(1);
+        let server_shutdown = shutdown_tx.clone();
+        tokio::spawn(async move {
+            crate::daemon::server::run_server(listener, server_shutdown, None).await;
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        /// What one follower saw: output bytes, Output frames, resyncs, and
+        /// when its first and last output arrived.
+        struct Seen {
+            bytes: usize,
+            frames: usize,
+            resyncs: usize,
+            first: Option<Instant>,
+            last: Option<Instant>,
+        }
+
+        for followers in [1usize, 8, 32] {
+            let name = format!("log-{}", followers);
+            // Give the followers a moment to subscribe before the log starts.
+            let script = format!(
+                "sleep 1; yes 'Compiling amux v0.1.0 (/src/amux) warning: unused variable `x`' | head -c {}; sleep 2",
+                LOG_BYTES
+            );
+            let stream = tokio::net::UnixStream::connect(&sock_path).await.unwrap();
+            let (mut reader, mut writer) = stream.into_split();
+            let create = ClientMessage::CreateSession {
+                name: Some(name.clone()),
+                command: vec!["sh".to_string(), "-c".to_string(), script],
+                env: None,
+                cwd: None,
+                cols: Some(200),
+                rows: Some(50),
+                limits: None,
+                attention: Vec::new(),
+            };
+            write_frame_async(&mut writer, &create).await.unwrap();
+            let resp = try_read_frame_async::<DaemonMessage>(&mut reader).await;
+            assert!(matches!(resp, Some(Ok(DaemonMessage::SessionCreated { .. }))), "{:?}", resp);
+
+            let mut tasks = Vec::new();
+            for _ in 0..followers {
+                let (sock, name) = (sock_path.clone(), name.clone());
+                tasks.push(tokio::spawn(async move {
+                    let stream = tokio::net::UnixStream::connect(&sock).await.unwrap();
+                    let (mut reader, mut writer) = stream.into_split();
+                    let follow = ClientMessage::Follow {
+                        name,
+                        buffer: Some(crate::daemon::fanout::MAX_CLIENT_BUFFER as u32),
+                    };
+                    write_frame_async(&mut writer, &follow).await.unwrap();
+                    let mut seen = Seen {
+                        bytes: 0,
+                        frames: 0,
+                        resyncs: 0,
+                        first: None,
+                        last: None,
+                    };
+                    while seen.bytes < LOG_BYTES {
+                        match try_read_frame_async::<DaemonMessage>(&mut reader).await {
+                            Some(Ok(DaemonMessage::Output(data))) => {
+                                let now = Instant::now();
+                                seen.first.get_or_insert(now);
+                                seen.last = Some(now);
+                                seen.bytes += data.len();
+                                seen.frames += 1;
+                            }
+                            Some(Ok(DaemonMessage::Resync { .. })) => seen.resyncs += 1,
+                            _ => break,
+                        }
+                    }
+                    seen
+                }));
+            }
+
+            let mut seen = Vec::new();
+            for task in tasks {
+                seen.push(task.await.unwrap());
+            }
+            let start = seen.iter().filter_map(|s| s.first).min().unwrap();
+            let end = seen.iter().filter_map(|s| s.last).max().unwrap();
+            let elapsed = end.duration_since(start);
+            let bytes: usize = seen.iter().map(|s| s.bytes).sum();
+            let frames: usize = seen.iter().map(|s| s.frames).sum();
+            let resyncs: usize = seen.iter().map(|s| s.resyncs).sum();
+            println!(
+                "{:>2} followers: {:>8.1} MB/s delivered in {:>8.2?}, {:>6} frames/follower ({:>6} B avg), {} resyncs",
+                followers,
+                bytes as f64 / elapsed.as_secs_f64() / 1e6,
+                elapsed,
+                frames / followers,
+                bytes / frames.max(1),
+                resyncs
+            );
+            for s in &seen {
+                assert!(s.bytes >= LOG_BYTES || s.resyncs > 0, "follower stopped at {} bytes", s.bytes);
+            }
+
+            let stream = tokio::net::UnixStream::connect(&sock_path).await.unwrap();
+            let (mut reader, mut writer) = stream.into_split();
+            write_frame_async(
+                &mut writer,
+                &ClientMessage::KillSession {
+                    name,
+                    signal: None,
+                    grace_ms: Some(0),
+                },
+            )
+            .await
+            .unwrap();
+            let _ = try_read_frame_async::<DaemonMessage>(&mut reader).await;
+        }
+
+        let _ = shutdown_tx.send(());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
 }
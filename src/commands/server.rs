@@ -1,7 +1,7 @@
 use crate::protocol::messages::{ClientMessage, DaemonMessage};
 use crate::{client, common, daemon};
 
-pub fn start_server() -> anyhow::Result<()> {
+pub fn start_server(metrics: Option<String>) -> anyhow::Result<()> {
     if common::daemon_alive() {
         eprintln!("amux: server is already running");
         return Ok(());
@@ -15,7 +15,7 @@ pub fn start_server() -> anyhow::Result<()> {
             common::pid_file_path().display()
         );
     }
-    daemon::fork_daemon()?;
+    daemon::fork_daemon(metrics)?;
     Ok(())
 }
 
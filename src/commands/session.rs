@@ -1,6 +1,12 @@
 use std::collections::HashMap;
 
-use crate::protocol::messages::{CaptureMode, ClientMessage, DaemonMessage};
+use crate::export::{self, ExportFormat, Theme};
+use crate::daemon::pacing;
+use crate::daemon::session::KillRequest;
+use crate::protocol::messages::{
+    CaptureMode, ClientMessage, DaemonMessage, InputDelivery, InputPacing, ResourceLimits,
+    SessionSelector, SignalTarget,
+};
 use crate::util::{create_git_worktree, ensure_daemon_running, parse_env_vars};
 use crate::client;
 
@@ -14,6 +20,8 @@ pub fn new_session(
     worktree: Option<String>,
     init_message: Option<String>,
     rows: Option<u16>,
+    limits: Option<ResourceLimits>,
+    attention: Vec<String>,
     cmd: Vec<String>,
 ) -> anyhow::Result<()> {
     if std::env::var("AMUX_DEBUG").is_ok() {
@@ -57,6 +65,8 @@ pub fn new_session(
             cwd: cwd.clone(),
             cols: spawn_cols,
             rows: spawn_rows,
+            limits,
+            attention,
         })?;
         let session_name = match resp {
             DaemonMessage::SessionCreated { name } => {
@@ -76,7 +86,7 @@ pub fn new_session(
         if let Some(msg) = init_message {
             // Wait for the session to produce some output (indicating readiness)
             wait_for_session_ready(&session_name)?;
-            send_keys(&session_name, false, &[msg])?;
+            send_keys(&session_name, &SendOptions::default(), &[msg])?;
         }
     } else {
         // Create then attach.
@@ -98,6 +108,8 @@ pub fn new_session(
             cwd,
             cols: Some(term_cols),
             rows: Some(spawn_rows),
+            limits,
+            attention,
         })?;
         let session_name = match resp {
             DaemonMessage::SessionCreated { name } => {
@@ -115,7 +127,7 @@ pub fn new_session(
                 std::process::exit(1);
             }
         };
-        do_attach(&session_name)?;
+        do_attach(&session_name, None)?;
     }
     Ok(())
 }
@@ -142,6 +154,81 @@ fn wait_for_session_ready(name: &str) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Send a signal to a session (`amux signal`, `amux pause`, `amux resume`).
+pub fn signal_session(name: &str, signal: i32, target: SignalTarget) -> anyhow::Result<()> {
+    let resp = client::request(&ClientMessage::SignalSession {
+        name: name.to_string(),
+        signal,
+        target,
+    })?;
+    let sig_name = nix::sys::signal::Signal::try_from(signal)
+        .map(|s| s.as_str().to_string())
+        .unwrap_or_else(|_| signal.to_string());
+    match resp {
+        DaemonMessage::SignalSent { pids } => match sig_name.as_str() {
+            "SIGSTOP" => eprintln!("amux: paused session '{}'", name),
+            "SIGCONT" => eprintln!("amux: resumed session '{}'", name),
+            _ => eprintln!(
+                "amux: sent {} to '{}' ({} process{})",
+                sig_name,
+                name,
+                pids.len(),
+                if pids.len() == 1 { "" } else { "es" }
+            ),
+        },
+        DaemonMessage::Error(e) => {
+            eprintln!("amux: error: {}", e);
+            std::process::exit(1);
+        }
+        other => eprintln!("amux: unexpected: {:?}", other),
+    }
+    Ok(())
+}
+
+/// Kill one session's process tree and report anything that had to be
+/// SIGKILLed after the grace period.
+pub fn kill_session(
+    name: &str,
+    signal: Option<i32>,
+    grace_ms: Option<u64>,
+) -> anyhow::Result<()> {
+    // The daemon answers once the tree is down, which can take the whole
+    // grace period.
+    let grace = grace_ms
+        .map(std::time::Duration::from_millis)
+        .unwrap_or(KillRequest::DEFAULT_GRACE);
+    let resp = client::request_with_timeout(
+        &ClientMessage::KillSession {
+            name: name.to_string(),
+            signal,
+            grace_ms,
+        },
+        client::REQUEST_TIMEOUT + grace,
+    )?;
+    match resp {
+        DaemonMessage::SessionKilled { survivors } => {
+            eprintln!("amux: killed session '{}'", name);
+            if !survivors.is_empty() {
+                let list: Vec<String> = survivors
+                    .iter()
+                    .map(|s| format!("{} ({})", s.pid, s.command))
+                    .collect();
+                eprintln!(
+                    "amux: still running after {:.1}s, sent SIGKILL: {}",
+                    grace.as_secs_f64(),
+                    list.join(", ")
+                );
+            }
+        }
+        DaemonMessage::Error(e) => {
+            eprintln!("amux: error: {}", e);
+            std::process::exit(1);
+        }
+        other => eprintln!("amux: unexpected: {:?}", other),
+    }
+    Ok(())
+}
+
 pub fn do_kill_all() -> anyhow::Result<()> {
     let resp = client::request(&ClientMessage::KillAllSessions)?;
     match resp {
@@ -180,7 +267,22 @@ fn build_send_payload<R: std::io::Read>(
     }
 }
 
-pub fn send_keys(name: &str, literal: bool, text: &[String]) -> anyhow::Result<()> {
+/// How `amux send` delivers its payload.
+#[derive(Debug, Default)]
+pub struct SendOptions {
+    /// Don't follow the text with Enter.
+    pub literal: bool,
+    /// `--paste`: deliver as a paste; carries the fallback inter-line
+    /// delay in ms for programs without bracketed paste.
+    pub paste: Option<u64>,
+    /// Daemon-side typing rate / chunking for the payload.
+    pub pacing: Option<InputPacing>,
+    /// Wait until the daemon confirms the bytes reached the PTY.
+    pub wait_written: bool,
+}
+
+/// Send text to a session.
+pub fn send_keys(name: &str, opts: &SendOptions, text: &[String]) -> anyhow::Result<()> {
     use std::io::IsTerminal;
     ensure_daemon_running()?;
     if text.is_empty() && std::io::stdin().is_terminal() {
@@ -190,11 +292,153 @@ pub fn send_keys(name: &str, literal: bool, text: &[String]) -> anyhow::Result<(
             "amux send: no text given and stdin is a terminal — pipe input or pass text args"
         );
     }
+    let (data, needs_enter) =
+        build_send_payload(opts.literal, text, &mut std::io::stdin().lock())?;
+    let resp = match opts.paste {
+        Some(line_delay_ms) => {
+            // The daemon only answers after the last line of an unbracketed
+            // paste is queued, so budget for the worst-case pacing on top of
+            // the normal deadline.
+            let lines = data.iter().filter(|&&b| b == b'\n').count() as u64;
+            let timeout =
+                client::REQUEST_TIMEOUT + std::time::Duration::from_millis(lines * line_delay_ms);
+            client::request_with_timeout(
+                &ClientMessage::PasteInput {
+                    name: name.to_string(),
+                    data,
+                    line_delay_ms,
+                },
+                timeout,
+            )?
+        }
+        None => {
+            // Likewise a paced send replies only after its last chunk.
+            let timeout = client::REQUEST_TIMEOUT
+                + opts
+                    .pacing
+                    .as_ref()
+                    .map(|p| pacing::duration(&data, p))
+                    .unwrap_or_default();
+            client::request_with_timeout(
+                &ClientMessage::SendInput {
+                    name: name.to_string(),
+                    data,
+                    newline: false,
+                    pacing: opts.pacing.clone(),
+                    wait_written: opts.wait_written,
+                },
+                timeout,
+            )?
+        }
+    };
+    check_input_response(resp);
+    if needs_enter {
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        let resp = client::request(&ClientMessage::SendInput {
+            name: name.to_string(),
+            data: vec![b'\r'],
+            newline: false,
+            pacing: None,
+            wait_written: opts.wait_written,
+        })?;
+        check_input_response(resp);
+    }
+    Ok(())
+}
+
+fn check_input_response(resp: DaemonMessage) {
+    match resp {
+        DaemonMessage::InputSent | DaemonMessage::InputWritten { .. } => {}
+        DaemonMessage::Error(e) => {
+            eprintln!("amux: error: {}", e);
+            std::process::exit(1);
+        }
+        other => eprintln!("amux: unexpected: {:?}", other),
+    }
+}
+
+/// `amux send --all` / `--any` / `--match`: deliver the same text to
+/// several sessions with one `BroadcastInput` and print each session's
+/// outcome. Exits non-zero if any targeted session failed or none matched.
+pub fn broadcast_keys(
+    target: SessionSelector,
+    literal: bool,
+    text: &[String],
+) -> anyhow::Result<()> {
+    use std::io::IsTerminal;
+    ensure_daemon_running()?;
+    if text.is_empty() && std::io::stdin().is_terminal() {
+        anyhow::bail!(
+            "amux send: no text given and stdin is a terminal — pipe input or pass text args"
+        );
+    }
     let (data, needs_enter) = build_send_payload(literal, text, &mut std::io::stdin().lock())?;
-    let resp = client::request(&ClientMessage::SendInput {
-        name: name.to_string(),
+    let mut results = request_broadcast(target, data)?;
+    if needs_enter {
+        // Same split as single-session send: Enter goes in a separate
+        // write so TUIs don't mistake text+CR for a paste. Only sessions
+        // that took the text get the Enter.
+        let delivered: Vec<String> = results
+            .iter()
+            .filter(|r| r.error.is_none())
+            .map(|r| r.session.clone())
+            .collect();
+        if !delivered.is_empty() {
+            std::thread::sleep(std::time::Duration::from_millis(100));
+            for enter in request_broadcast(SessionSelector::Names(delivered), vec![b'\r'])? {
+                if let Some(r) = results.iter_mut().find(|r| r.session == enter.session) {
+                    r.error = enter.error;
+                }
+            }
+        }
+    }
+    if results.is_empty() {
+        eprintln!("amux: error: no sessions matched");
+        std::process::exit(1);
+    }
+    let mut failed = false;
+    for r in &results {
+        match &r.error {
+            None => println!("sent to {}", r.session),
+            Some(e) => {
+                eprintln!("amux: {}: {}", r.session, e);
+                failed = true;
+            }
+        }
+    }
+    if failed {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+fn request_broadcast(
+    target: SessionSelector,
+    data: Vec<u8>,
+) -> anyhow::Result<Vec<InputDelivery>> {
+    match client::request(&ClientMessage::BroadcastInput {
+        target,
         data,
         newline: false,
+        keys: Vec::new(),
+    })? {
+        DaemonMessage::BroadcastResult(results) => Ok(results),
+        DaemonMessage::Error(e) => anyhow::bail!(e),
+        other => anyhow::bail!("unexpected response: {:?}", other),
+    }
+}
+
+/// `amux send --keys`: ship the key names to the daemon, which translates
+/// them against the session's live terminal modes (application cursor keys
+/// change what `Up`/`Down`/... must send).
+pub fn send_key_names(name: &str, keys: &[String]) -> anyhow::Result<()> {
+    ensure_daemon_running()?;
+    if keys.is_empty() {
+        anyhow::bail!("amux send --keys: no keys given");
+    }
+    let resp = client::request(&ClientMessage::SendKeys {
+        name: name.to_string(),
+        keys: keys.to_vec(),
     })?;
     match resp {
         DaemonMessage::InputSent => {}
@@ -204,22 +448,6 @@ pub fn send_keys(name: &str, literal: bool, text: &[String]) -> anyhow::Result<(
         }
         other => eprintln!("amux: unexpected: {:?}", other),
     }
-    if needs_enter {
-        std::thread::sleep(std::time::Duration::from_millis(100));
-        let resp = client::request(&ClientMessage::SendInput {
-            name: name.to_string(),
-            data: vec![b'\r'],
-            newline: false,
-        })?;
-        match resp {
-            DaemonMessage::InputSent => {}
-            DaemonMessage::Error(e) => {
-                eprintln!("amux: error: {}", e);
-                std::process::exit(1);
-            }
-            other => eprintln!("amux: unexpected: {:?}", other),
-        }
-    }
     Ok(())
 }
 
@@ -238,18 +466,44 @@ pub fn has_session(name: &str) -> anyhow::Result<()> {
     }
 }
 
-pub fn capture_scrollback(name: &str, lines: usize, plain: bool) -> anyhow::Result<()> {
+pub fn rename_session(name: &str, new_name: &str) -> anyhow::Result<()> {
+    let resp = client::request(&ClientMessage::RenameSession {
+        name: name.to_string(),
+        new_name: new_name.to_string(),
+    })?;
+    match resp {
+        DaemonMessage::Ok => {
+            eprintln!("amux: renamed session '{}' to '{}'", name, new_name);
+        }
+        DaemonMessage::Error(e) => {
+            eprintln!("amux: error: {}", e);
+            std::process::exit(1);
+        }
+        other => eprintln!("amux: unexpected: {:?}", other),
+    }
+    Ok(())
+}
+
+pub fn capture_scrollback(
+    name: &str,
+    lines: usize,
+    mode: CaptureMode,
+    compress: bool,
+) -> anyhow::Result<()> {
     ensure_daemon_running()?;
     // Plain mode (the default) asks the daemon for the rendered virtual
     // terminal screen, which correctly handles TUI apps that redraw with
     // cursor-addressed escape sequences. Raw mode streams the PTY bytes
     // verbatim so callers can re-render them in their own terminal.
-    let mode = if plain { CaptureMode::Plain } else { CaptureMode::Raw };
-    let resp = client::request(&ClientMessage::CaptureScrollback {
-        name: name.to_string(),
-        lines,
-        mode,
-    })?;
+    // Structured mode gets the same screen as JSON.
+    let resp = request_capture(
+        &ClientMessage::CaptureScrollback {
+            name: name.to_string(),
+            lines,
+            mode,
+        },
+        compress,
+    )?;
     match resp {
         DaemonMessage::CaptureOutput(data) => {
             use std::io::Write;
@@ -258,7 +512,7 @@ pub fn capture_scrollback(name: &str, lines: usize, plain: bool) -> anyhow::Resu
             std::io::stdout().write_all(&data)?;
             // The rendered screen has no trailing newline; add one for
             // nicer shell output so the prompt lands on a fresh line.
-            if plain && !data.ends_with(b"\n") {
+            if mode != CaptureMode::Raw && !data.ends_with(b"\n") {
                 std::io::stdout().write_all(b"\n")?;
             }
         }
@@ -271,10 +525,66 @@ pub fn capture_scrollback(name: &str, lines: usize, plain: bool) -> anyhow::Resu
     Ok(())
 }
 
+/// Send a `CaptureScrollback`, compressed in transit with `compress`.
+fn request_capture(req: &ClientMessage, compress: bool) -> anyhow::Result<DaemonMessage> {
+    if compress {
+        client::request_compressed(req)
+    } else {
+        client::request(req)
+    }
+}
+
+/// `amux capture --format html|svg`: render the last `lines` lines of the
+/// session's screen as a styled, self-contained snapshot on stdout.
+pub fn capture_export(
+    name: &str,
+    lines: usize,
+    format: ExportFormat,
+    theme: &str,
+    compress: bool,
+) -> anyhow::Result<()> {
+    let theme = Theme::load(theme)?;
+    ensure_daemon_running()?;
+    let cols = match client::request(&ClientMessage::GetSessionInfo {
+        name: name.to_string(),
+    })? {
+        DaemonMessage::SessionDetail(info) => info.cols,
+        DaemonMessage::Error(e) => {
+            eprintln!("amux: error: {}", e);
+            std::process::exit(1);
+        }
+        other => anyhow::bail!("unexpected: {:?}", other),
+    };
+    let resp = request_capture(
+        &ClientMessage::CaptureScrollback {
+            name: name.to_string(),
+            lines,
+            mode: CaptureMode::Formatted,
+        },
+        compress,
+    )?;
+    match resp {
+        DaemonMessage::CaptureOutput(data) => {
+            use std::io::Write;
+            let doc = export::render(&data, cols, format, &theme, name);
+            std::io::stdout().write_all(doc.as_bytes())?;
+        }
+        DaemonMessage::Error(e) => {
+            eprintln!("amux: error: {}", e);
+            std::process::exit(1);
+        }
+        other => eprintln!("amux: unexpected: {:?}", other),
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use crate::protocol::codec::{try_read_frame_async, write_frame_async};
-    use crate::protocol::messages::{CaptureMode, ClientMessage, DaemonMessage};
+    use crate::protocol::messages::{
+        CaptureMode, ClientMessage, DaemonMessage, InputPacing, ResourceLimits, SessionSelector,
+        SignalTarget,
+    };
     use crate::util::strip_ansi;
 
     /// Args path: text gets joined and `--literal` controls whether we
@@ -360,7 +670,7 @@ mod tests {
 
         let server_shutdown = shutdown_tx.clone();
         tokio::spawn(async move {
-            crate::daemon::server::run_server(listener, server_shutdown).await;
+            crate::daemon::server::run_server(listener, server_shutdown, None).await;
         });
 
         tokio::time::sleep(std::time::Duration::from_millis(100)).await;
@@ -370,14 +680,7 @@ mod tests {
 
         write_frame_async(
             &mut writer,
-            &ClientMessage::CreateSession {
-                name: Some("send-test".to_string()),
-                command: vec!["cat".to_string()],
-                env: None,
-                cwd: None,
-                cols: None,
-                rows: None,
-            },
+            &ClientMessage::create_session("send-test", vec!["cat".to_string()]),
         )
         .await
         .unwrap();
@@ -393,6 +696,8 @@ mod tests {
                 name: "send-test".to_string(),
                 data: b"hello".to_vec(),
                 newline: true,
+                pacing: None,
+                wait_written: false,
             },
         )
         .await
@@ -436,13 +741,15 @@ mod tests {
         let _ = std::fs::remove_dir_all(&dir);
     }
 
-    /// Integration test: CaptureScrollback on an active session returns its output.
+    /// Integration test: SendKeys translates cursor keys against the
+    /// session's live DECCKM mode — `Up` becomes `ESC O A` once the program
+    /// has enabled application cursor keys.
     #[tokio::test]
-    async fn test_capture_active_session() {
+    async fn test_send_keys_honors_application_cursor() {
         use tokio::sync::broadcast;
 
         let dir =
-            std::env::temp_dir().join(format!("amux-test-cap-active-{}", std::process::id()));
+            std::env::temp_dir().join(format!("amux-test-sendkeys-{}", std::process::id()));
         std::fs::create_dir_all(&dir).unwrap();
         let sock_path = dir.join("test.sock");
         let _ = std::fs::remove_file(&sock_path);
@@ -452,7 +759,7 @@ mod tests {
 
         let server_shutdown = shutdown_tx.clone();
         tokio::spawn(async move {
-            crate::daemon::server::run_server(listener, server_shutdown).await;
+            crate::daemon::server::run_server(listener, server_shutdown, None).await;
         });
 
         tokio::time::sleep(std::time::Duration::from_millis(100)).await;
@@ -462,43 +769,48 @@ mod tests {
 
         write_frame_async(
             &mut writer,
-            &ClientMessage::CreateSession {
-                name: Some("cap-active".to_string()),
-                command: vec!["cat".to_string()],
-                env: None,
-                cwd: None,
-                cols: None,
-                rows: None,
-            },
+            &ClientMessage::create_session(
+                "keys-test",
+                vec![
+                    "sh".to_string(),
+                    "-c".to_string(),
+                    "printf '\\033[?1h'; exec cat -v".to_string(),
+                ],
+            ),
         )
         .await
         .unwrap();
+
         let resp: DaemonMessage = try_read_frame_async(&mut reader).await.unwrap().unwrap();
         assert!(matches!(resp, DaemonMessage::SessionCreated { .. }));
 
-        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+        tokio::time::sleep(std::time::Duration::from_millis(300)).await;
 
         write_frame_async(
             &mut writer,
-            &ClientMessage::SendInput {
-                name: "cap-active".to_string(),
-                data: b"capture-test-data".to_vec(),
-                newline: true,
+            &ClientMessage::SendKeys {
+                name: "keys-test".to_string(),
+                keys: vec!["Up".to_string(), "Enter".to_string()],
             },
         )
         .await
         .unwrap();
+
         let resp: DaemonMessage = try_read_frame_async(&mut reader).await.unwrap().unwrap();
-        assert!(matches!(resp, DaemonMessage::InputSent));
+        assert!(
+            matches!(resp, DaemonMessage::InputSent),
+            "expected InputSent, got {:?}",
+            resp
+        );
 
         tokio::time::sleep(std::time::Duration::from_millis(500)).await;
 
         write_frame_async(
             &mut writer,
             &ClientMessage::CaptureScrollback {
-                name: "cap-active".to_string(),
-                lines: 50,
-                mode: CaptureMode::Raw,
+                name: "keys-test".to_string(),
+                lines: 10,
+                mode: CaptureMode::Plain,
             },
         )
         .await
@@ -507,11 +819,10 @@ mod tests {
         let resp: DaemonMessage = try_read_frame_async(&mut reader).await.unwrap().unwrap();
         match resp {
             DaemonMessage::CaptureOutput(data) => {
-                let plain = strip_ansi(&data);
-                let output_str = String::from_utf8_lossy(&plain);
+                let output_str = String::from_utf8_lossy(&data);
                 assert!(
-                    output_str.contains("capture-test-data"),
-                    "expected 'capture-test-data' in capture output, got: {:?}",
+                    output_str.contains("^[OA"),
+                    "SendKeys: expected application-mode Up (^[OA), got: {:?}",
                     output_str
                 );
             }
@@ -522,13 +833,14 @@ mod tests {
         let _ = std::fs::remove_dir_all(&dir);
     }
 
-    /// Integration test: CaptureScrollback on a dead session still returns its scrollback.
+    /// Integration test: CaptureMode::Structured returns the live screen
+    /// as a JSON ScreenSnapshot, including styled runs and the title.
     #[tokio::test]
-    async fn test_capture_dead_session() {
+    async fn test_capture_structured_json() {
+        use crate::protocol::messages::ScreenSnapshot;
         use tokio::sync::broadcast;
 
-        let dir =
-            std::env::temp_dir().join(format!("amux-test-cap-dead-{}", std::process::id()));
+        let dir = std::env::temp_dir().join(format!("amux-test-structured-{}", std::process::id()));
         std::fs::create_dir_all(&dir).unwrap();
         let sock_path = dir.join("test.sock");
         let _ = std::fs::remove_file(&sock_path);
@@ -538,7 +850,7 @@ mod tests {
 
         let server_shutdown = shutdown_tx.clone();
         tokio::spawn(async move {
-            crate::daemon::server::run_server(listener, server_shutdown).await;
+            crate::daemon::server::run_server(listener, server_shutdown, None).await;
         });
 
         tokio::time::sleep(std::time::Duration::from_millis(100)).await;
@@ -549,61 +861,67 @@ mod tests {
         write_frame_async(
             &mut writer,
             &ClientMessage::CreateSession {
-                name: Some("cap-dead".to_string()),
+                name: Some("menu".to_string()),
                 command: vec![
                     "sh".to_string(),
                     "-c".to_string(),
-                    "echo dead-session-output; exit 0".to_string(),
+                    "printf '\\033]2;Pick one\\007\\033[2J\\033[1;1H  Yes\\r\\n\\033[7m  No \\033[0m\\r\\n'; sleep 10"
+                        .to_string(),
                 ],
                 env: None,
                 cwd: None,
-                cols: None,
-                rows: None,
+                cols: Some(40),
+                rows: Some(10),
+                limits: None,
+                attention: Vec::new(),
             },
         )
         .await
         .unwrap();
+
         let resp: DaemonMessage = try_read_frame_async(&mut reader).await.unwrap().unwrap();
         assert!(matches!(resp, DaemonMessage::SessionCreated { .. }));
 
-        tokio::time::sleep(std::time::Duration::from_millis(800)).await;
+        tokio::time::sleep(std::time::Duration::from_millis(500)).await;
 
         write_frame_async(
             &mut writer,
             &ClientMessage::CaptureScrollback {
-                name: "cap-dead".to_string(),
+                name: "menu".to_string(),
                 lines: 50,
-                mode: CaptureMode::Raw,
+                mode: CaptureMode::Structured,
             },
         )
         .await
         .unwrap();
 
         let resp: DaemonMessage = try_read_frame_async(&mut reader).await.unwrap().unwrap();
-        match resp {
-            DaemonMessage::CaptureOutput(data) => {
-                let plain = strip_ansi(&data);
-                let output_str = String::from_utf8_lossy(&plain);
-                assert!(
-                    output_str.contains("dead-session-output"),
-                    "expected 'dead-session-output' in capture output, got: {:?}",
-                    output_str
-                );
-            }
+        let snapshot: ScreenSnapshot = match resp {
+            DaemonMessage::CaptureOutput(data) => serde_json::from_slice(&data).unwrap(),
             other => panic!("expected CaptureOutput, got {:?}", other),
-        }
+        };
+        assert_eq!((snapshot.rows, snapshot.cols), (10, 40));
+        assert_eq!(snapshot.lines.len(), 10);
+        assert_eq!(snapshot.title, "Pick one");
+        assert_eq!(snapshot.lines[0].text, "  Yes");
+        assert!(snapshot.lines[0].runs.is_empty());
+        // The highlighted option is the inverse run on row 1.
+        let selected = &snapshot.lines[1].runs[0];
+        assert!(selected.inverse);
+        assert_eq!(selected.text.trim(), "No");
+        assert_eq!((snapshot.cursor.row, snapshot.cursor.col), (2, 0));
 
         let _ = shutdown_tx.send(());
         let _ = std::fs::remove_dir_all(&dir);
     }
 
-    /// Integration test: CaptureScrollback on a nonexistent session returns an error.
+    /// Integration test: PasteInput wraps the payload in bracketed-paste
+    /// markers once the session's program has enabled DECSET 2004.
     #[tokio::test]
-    async fn test_capture_nonexistent_session() {
+    async fn test_paste_input_uses_bracketed_paste() {
         use tokio::sync::broadcast;
 
-        let dir =
-            std::env::temp_dir().join(format!("amux-test-cap-ne-{}", std::process::id()));
+        let dir = std::env::temp_dir().join(format!("amux-test-paste-{}", std::process::id()));
         std::fs::create_dir_all(&dir).unwrap();
         let sock_path = dir.join("test.sock");
         let _ = std::fs::remove_file(&sock_path);
@@ -613,7 +931,7 @@ mod tests {
 
         let server_shutdown = shutdown_tx.clone();
         tokio::spawn(async move {
-            crate::daemon::server::run_server(listener, server_shutdown).await;
+            crate::daemon::server::run_server(listener, server_shutdown, None).await;
         });
 
         tokio::time::sleep(std::time::Duration::from_millis(100)).await;
@@ -623,10 +941,29 @@ mod tests {
 
         write_frame_async(
             &mut writer,
-            &ClientMessage::CaptureScrollback {
-                name: "nonexistent".to_string(),
-                lines: 10,
-                mode: CaptureMode::Plain,
+            &ClientMessage::create_session(
+                "paste-test",
+                vec![
+                    "sh".to_string(),
+                    "-c".to_string(),
+                    "printf '\\033[?2004h'; exec cat -v".to_string(),
+                ],
+            ),
+        )
+        .await
+        .unwrap();
+
+        let resp: DaemonMessage = try_read_frame_async(&mut reader).await.unwrap().unwrap();
+        assert!(matches!(resp, DaemonMessage::SessionCreated { .. }));
+
+        tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+
+        write_frame_async(
+            &mut writer,
+            &ClientMessage::PasteInput {
+                name: "paste-test".to_string(),
+                data: b"first\nsecond\n".to_vec(),
+                line_delay_ms: 0,
             },
         )
         .await
@@ -634,11 +971,866 @@ mod tests {
 
         let resp: DaemonMessage = try_read_frame_async(&mut reader).await.unwrap().unwrap();
         assert!(
-            matches!(resp, DaemonMessage::Error(ref e) if e.contains("not found")),
-            "expected Error(not found), got: {:?}",
+            matches!(resp, DaemonMessage::InputSent),
+            "expected InputSent, got {:?}",
             resp
         );
 
+        tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+
+        write_frame_async(
+            &mut writer,
+            &ClientMessage::CaptureScrollback {
+                name: "paste-test".to_string(),
+                lines: 10,
+                mode: CaptureMode::Plain,
+            },
+        )
+        .await
+        .unwrap();
+
+        let resp: DaemonMessage = try_read_frame_async(&mut reader).await.unwrap().unwrap();
+        match resp {
+            DaemonMessage::CaptureOutput(data) => {
+                let output_str = String::from_utf8_lossy(&data);
+                assert!(
+                    output_str.contains("^[[200~first"),
+                    "PasteInput: expected paste start marker, got: {:?}",
+                    output_str
+                );
+            }
+            other => panic!("expected CaptureOutput, got {:?}", other),
+        }
+
+        let _ = shutdown_tx.send(());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    /// Integration test: a paced SendInput with `wait_written` takes about
+    /// as long as its typing rate implies and replies `InputWritten` only
+    /// after the last chunk reached the PTY.
+    #[tokio::test]
+    async fn test_paced_send_input_acks_written() {
+        use tokio::sync::broadcast;
+
+        let dir = std::env::temp_dir().join(format!("amux-test-paced-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let sock_path = dir.join("test.sock");
+        let _ = std::fs::remove_file(&sock_path);
+
+        let listener = tokio::net::UnixListener::bind(&sock_path).unwrap();
+        let (shutdown_tx, _) = broadcast::channel::<()>(1);
+
+        let server_shutdown = shutdown_tx.clone();
+        tokio::spawn(async move {
+            crate::daemon::server::run_server(listener, server_shutdown, None).await;
+        });
+
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+        let stream = tokio::net::UnixStream::connect(&sock_path).await.unwrap();
+        let (mut reader, mut writer) = stream.into_split();
+
+        write_frame_async(
+            &mut writer,
+            &ClientMessage::create_session("paced-test", vec!["cat".to_string()]),
+        )
+        .await
+        .unwrap();
+
+        let resp: DaemonMessage = try_read_frame_async(&mut reader).await.unwrap().unwrap();
+        assert!(matches!(resp, DaemonMessage::SessionCreated { .. }));
+
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+
+        let start = std::time::Instant::now();
+        write_frame_async(
+            &mut writer,
+            &ClientMessage::SendInput {
+                name: "paced-test".to_string(),
+                data: b"typed".to_vec(),
+                newline: true,
+                pacing: Some(InputPacing {
+                    chars_per_sec: 20,
+                    ..Default::default()
+                }),
+                wait_written: true,
+            },
+        )
+        .await
+        .unwrap();
+
+        let resp: DaemonMessage = try_read_frame_async(&mut reader).await.unwrap().unwrap();
+        let elapsed = start.elapsed();
+        match resp {
+            DaemonMessage::InputWritten { bytes } => assert_eq!(bytes, 6),
+            other => panic!("expected InputWritten, got {:?}", other),
+        }
+        // Six characters at 20/s, with no pause after the last one.
+        assert!(
+            elapsed >= std::time::Duration::from_millis(240),
+            "paced send finished too quickly: {:?}",
+            elapsed
+        );
+
+        let _ = shutdown_tx.send(());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    /// Integration test: BroadcastInput reaches every targeted session and
+    /// reports unknown names individually instead of failing the batch.
+    #[tokio::test]
+    async fn test_broadcast_input_reports_per_session() {
+        use tokio::sync::broadcast;
+
+        let dir =
+            std::env::temp_dir().join(format!("amux-test-broadcast-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let sock_path = dir.join("test.sock");
+        let _ = std::fs::remove_file(&sock_path);
+
+        let listener = tokio::net::UnixListener::bind(&sock_path).unwrap();
+        let (shutdown_tx, _) = broadcast::channel::<()>(1);
+
+        let server_shutdown = shutdown_tx.clone();
+        tokio::spawn(async move {
+            crate::daemon::server::run_server(listener, server_shutdown, None).await;
+        });
+
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+        let stream = tokio::net::UnixStream::connect(&sock_path).await.unwrap();
+        let (mut reader, mut writer) = stream.into_split();
+
+        for name in ["bcast-a", "bcast-b"] {
+            write_frame_async(
+                &mut writer,
+                &ClientMessage::create_session(name, vec!["cat".to_string()]),
+            )
+            .await
+            .unwrap();
+            let resp: DaemonMessage = try_read_frame_async(&mut reader).await.unwrap().unwrap();
+            assert!(matches!(resp, DaemonMessage::SessionCreated { .. }));
+        }
+
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+
+        write_frame_async(
+            &mut writer,
+            &ClientMessage::BroadcastInput {
+                target: SessionSelector::Names(vec![
+                    "bcast-b".to_string(),
+                    "bcast-a".to_string(),
+                    "bcast-missing".to_string(),
+                ]),
+                data: b"all-".to_vec(),
+                newline: false,
+                keys: vec!["hands".to_string(), "Enter".to_string()],
+            },
+        )
+        .await
+        .unwrap();
+
+        let resp: DaemonMessage = try_read_frame_async(&mut reader).await.unwrap().unwrap();
+        match resp {
+            DaemonMessage::BroadcastResult(results) => {
+                let names: Vec<&str> = results.iter().map(|r| r.session.as_str()).collect();
+                assert_eq!(names, vec!["bcast-a", "bcast-b", "bcast-missing"]);
+                assert!(results[0].error.is_none());
+                assert!(results[1].error.is_none());
+                assert!(results[2].error.as_deref().unwrap().contains("not found"));
+            }
+            other => panic!("expected BroadcastResult, got {:?}", other),
+        }
+
+        tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+
+        for name in ["bcast-a", "bcast-b"] {
+            write_frame_async(
+                &mut writer,
+                &ClientMessage::CaptureScrollback {
+                    name: name.to_string(),
+                    lines: 10,
+                    mode: CaptureMode::Plain,
+                },
+            )
+            .await
+            .unwrap();
+            let resp: DaemonMessage = try_read_frame_async(&mut reader).await.unwrap().unwrap();
+            match resp {
+                DaemonMessage::CaptureOutput(data) => {
+                    let output_str = String::from_utf8_lossy(&data);
+                    assert!(
+                        output_str.contains("all-hands"),
+                        "{}: expected broadcast text, got: {:?}",
+                        name,
+                        output_str
+                    );
+                }
+                other => panic!("expected CaptureOutput, got {:?}", other),
+            }
+        }
+
+        let _ = shutdown_tx.send(());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    /// Integration test: CaptureScrollback on an active session returns its output.
+    #[tokio::test]
+    async fn test_capture_active_session() {
+        use tokio::sync::broadcast;
+
+        let dir =
+            std::env::temp_dir().join(format!("amux-test-cap-active-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let sock_path = dir.join("test.sock");
+        let _ = std::fs::remove_file(&sock_path);
+
+        let listener = tokio::net::UnixListener::bind(&sock_path).unwrap();
+        let (shutdown_tx, _) = broadcast::channel::<()>(1);
+
+        let server_shutdown = shutdown_tx.clone();
+        tokio::spawn(async move {
+            crate::daemon::server::run_server(listener, server_shutdown, None).await;
+        });
+
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+        let stream = tokio::net::UnixStream::connect(&sock_path).await.unwrap();
+        let (mut reader, mut writer) = stream.into_split();
+
+        write_frame_async(
+            &mut writer,
+            &ClientMessage::create_session("cap-active", vec!["cat".to_string()]),
+        )
+        .await
+        .unwrap();
+        let resp: DaemonMessage = try_read_frame_async(&mut reader).await.unwrap().unwrap();
+        assert!(matches!(resp, DaemonMessage::SessionCreated { .. }));
+
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+
+        write_frame_async(
+            &mut writer,
+            &ClientMessage::SendInput {
+                name: "cap-active".to_string(),
+                data: b"capture-test-data".to_vec(),
+                newline: true,
+                pacing: None,
+                wait_written: false,
+            },
+        )
+        .await
+        .unwrap();
+        let resp: DaemonMessage = try_read_frame_async(&mut reader).await.unwrap().unwrap();
+        assert!(matches!(resp, DaemonMessage::InputSent));
+
+        tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+
+        write_frame_async(
+            &mut writer,
+            &ClientMessage::CaptureScrollback {
+                name: "cap-active".to_string(),
+                lines: 50,
+                mode: CaptureMode::Raw,
+            },
+        )
+        .await
+        .unwrap();
+
+        let resp: DaemonMessage = try_read_frame_async(&mut reader).await.unwrap().unwrap();
+        match resp {
+            DaemonMessage::CaptureOutput(data) => {
+                let plain = strip_ansi(&data);
+                let output_str = String::from_utf8_lossy(&plain);
+                assert!(
+                    output_str.contains("capture-test-data"),
+                    "expected 'capture-test-data' in capture output, got: {:?}",
+                    output_str
+                );
+            }
+            other => panic!("expected CaptureOutput, got {:?}", other),
+        }
+
+        let _ = shutdown_tx.send(());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    /// Integration test: CaptureScrollback on a dead session still returns its scrollback.
+    #[tokio::test]
+    async fn test_capture_dead_session() {
+        use tokio::sync::broadcast;
+
+        let dir =
+            std::env::temp_dir().join(format!("amux-test-cap-dead-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let sock_path = dir.join("test.sock");
+        let _ = std::fs::remove_file(&sock_path);
+
+        let listener = tokio::net::UnixListener::bind(&sock_path).unwrap();
+        let (shutdown_tx, _) = broadcast::channel::<()>(1);
+
+        let server_shutdown = shutdown_tx.clone();
+        tokio::spawn(async move {
+            crate::daemon::server::run_server(listener, server_shutdown, None).await;
+        });
+
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+        let stream = tokio::net::UnixStream::connect(&sock_path).await.unwrap();
+        let (mut reader, mut writer) = stream.into_split();
+
+        write_frame_async(
+            &mut writer,
+            &ClientMessage::create_session(
+                "cap-dead",
+                vec![
+                    "sh".to_string(),
+                    "-c".to_string(),
+                    "echo dead-session-output; exit 0".to_string(),
+                ],
+            ),
+        )
+        .await
+        .unwrap();
+        let resp: DaemonMessage = try_read_frame_async(&mut reader).await.unwrap().unwrap();
+        assert!(matches!(resp, DaemonMessage::SessionCreated { .. }));
+
+        tokio::time::sleep(std::time::Duration::from_millis(800)).await;
+
+        write_frame_async(
+            &mut writer,
+            &ClientMessage::CaptureScrollback {
+                name: "cap-dead".to_string(),
+                lines: 50,
+                mode: CaptureMode::Raw,
+            },
+        )
+        .await
+        .unwrap();
+
+        let resp: DaemonMessage = try_read_frame_async(&mut reader).await.unwrap().unwrap();
+        match resp {
+            DaemonMessage::CaptureOutput(data) => {
+                let plain = strip_ansi(&data);
+                let output_str = String::from_utf8_lossy(&plain);
+                assert!(
+                    output_str.contains("dead-session-output"),
+                    "expected 'dead-session-output' in capture output, got: {:?}",
+                    output_str
+                );
+            }
+            other => panic!("expected CaptureOutput, got {:?}", other),
+        }
+
+        let _ = shutdown_tx.send(());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    /// Integration test: CaptureScrollback on a nonexistent session returns an error.
+    #[tokio::test]
+    async fn test_capture_nonexistent_session() {
+        use tokio::sync::broadcast;
+
+        let dir =
+            std::env::temp_dir().join(format!("amux-test-cap-ne-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let sock_path = dir.join("test.sock");
+        let _ = std::fs::remove_file(&sock_path);
+
+        let listener = tokio::net::UnixListener::bind(&sock_path).unwrap();
+        let (shutdown_tx, _) = broadcast::channel::<()>(1);
+
+        let server_shutdown = shutdown_tx.clone();
+        tokio::spawn(async move {
+            crate::daemon::server::run_server(listener, server_shutdown, None).await;
+        });
+
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+        let stream = tokio::net::UnixStream::connect(&sock_path).await.unwrap();
+        let (mut reader, mut writer) = stream.into_split();
+
+        write_frame_async(
+            &mut writer,
+            &ClientMessage::CaptureScrollback {
+                name: "nonexistent".to_string(),
+                lines: 10,
+                mode: CaptureMode::Plain,
+            },
+        )
+        .await
+        .unwrap();
+
+        let resp: DaemonMessage = try_read_frame_async(&mut reader).await.unwrap().unwrap();
+        assert!(
+            matches!(resp, DaemonMessage::Error(ref e) if e.contains("not found")),
+            "expected Error(not found), got: {:?}",
+            resp
+        );
+
+        let _ = shutdown_tx.send(());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    /// Integration test: a session created with `nofile` runs under that
+    /// RLIMIT_NOFILE, and GetSessionInfo reports the limits back.
+    #[tokio::test]
+    async fn test_create_session_applies_resource_limits() {
+        use tokio::sync::broadcast;
+
+        let dir =
+            std::env::temp_dir().join(format!("amux-test-limits-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let sock_path = dir.join("test.sock");
+        let _ = std::fs::remove_file(&sock_path);
+
+        let listener = tokio::net::UnixListener::bind(&sock_path).unwrap();
+        let (shutdown_tx, _) = broadcast::channel::<()>(1);
+
+        let server_shutdown = shutdown_tx.clone();
+        tokio::spawn(async move {
+            crate::daemon::server::run_server(listener, server_shutdown, None).await;
+        });
+
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+        let stream = tokio::net::UnixStream::connect(&sock_path).await.unwrap();
+        let (mut reader, mut writer) = stream.into_split();
+
+        let limits = ResourceLimits {
+            nofile: Some(64),
+            ..Default::default()
+        };
+        write_frame_async(
+            &mut writer,
+            &ClientMessage::CreateSession {
+                name: Some("limits-test".to_string()),
+                command: vec![
+                    "sh".to_string(),
+                    "-c".to_string(),
+                    "echo nofile=$(ulimit -n); exec sleep 60".to_string(),
+                ],
+                env: None,
+                cwd: None,
+                cols: None,
+                rows: None,
+                limits: Some(limits.clone()),
+                attention: Vec::new(),
+            },
+        )
+        .await
+        .unwrap();
+
+        let resp: DaemonMessage = try_read_frame_async(&mut reader).await.unwrap().unwrap();
+        assert!(
+            matches!(resp, DaemonMessage::SessionCreated { .. }),
+            "expected SessionCreated, got {:?}",
+            resp
+        );
+
+        tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+
+        write_frame_async(
+            &mut writer,
+            &ClientMessage::CaptureScrollback {
+                name: "limits-test".to_string(),
+                lines: 10,
+                mode: CaptureMode::Plain,
+            },
+        )
+        .await
+        .unwrap();
+        let resp: DaemonMessage = try_read_frame_async(&mut reader).await.unwrap().unwrap();
+        match resp {
+            DaemonMessage::CaptureOutput(data) => {
+                let output_str = String::from_utf8_lossy(&data);
+                assert!(
+                    output_str.contains("nofile=64"),
+                    "expected RLIMIT_NOFILE of 64, got: {:?}",
+                    output_str
+                );
+            }
+            other => panic!("expected CaptureOutput, got {:?}", other),
+        }
+
+        write_frame_async(
+            &mut writer,
+            &ClientMessage::GetSessionInfo {
+                name: "limits-test".to_string(),
+            },
+        )
+        .await
+        .unwrap();
+        let resp: DaemonMessage = try_read_frame_async(&mut reader).await.unwrap().unwrap();
+        match resp {
+            DaemonMessage::SessionDetail(info) => {
+                let status = info.limits.expect("limits should be reported");
+                assert_eq!(status.limits, limits);
+                assert!(status.cgroup.is_none(), "nofile alone never needs a cgroup");
+            }
+            other => panic!("expected SessionDetail, got {:?}", other),
+        }
+
+        write_frame_async(
+            &mut writer,
+            &ClientMessage::KillSession {
+                name: "limits-test".to_string(),
+                signal: None,
+                grace_ms: None,
+            },
+        )
+        .await
+        .unwrap();
+        let _ = try_read_frame_async::<DaemonMessage>(&mut reader).await;
+
+        let _ = shutdown_tx.send(());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    /// Integration test: the telemetry sampler records the session's
+    /// process tree, visible through GetSessionStats and SessionInfo.
+    #[tokio::test]
+    async fn test_session_stats_sampled() {
+        use tokio::sync::broadcast;
+
+        let dir =
+            std::env::temp_dir().join(format!("amux-test-stats-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let sock_path = dir.join("test.sock");
+        let _ = std::fs::remove_file(&sock_path);
+
+        let listener = tokio::net::UnixListener::bind(&sock_path).unwrap();
+        let (shutdown_tx, _) = broadcast::channel::<()>(1);
+
+        let server_shutdown = shutdown_tx.clone();
+        tokio::spawn(async move {
+            crate::daemon::server::run_server(listener, server_shutdown, None).await;
+        });
+
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+        let stream = tokio::net::UnixStream::connect(&sock_path).await.unwrap();
+        let (mut reader, mut writer) = stream.into_split();
+
+        write_frame_async(
+            &mut writer,
+            &ClientMessage::create_session(
+                "stats-test",
+                vec![
+                    "sh".to_string(),
+                    "-c".to_string(),
+                    "sleep 60 & sleep 60".to_string(),
+                ],
+            ),
+        )
+        .await
+        .unwrap();
+        let resp: DaemonMessage = try_read_frame_async(&mut reader).await.unwrap().unwrap();
+        assert!(matches!(resp, DaemonMessage::SessionCreated { .. }));
+
+        // Two sampler ticks.
+        let ticks = crate::daemon::telemetry::SAMPLE_INTERVAL * 2;
+        tokio::time::sleep(ticks + std::time::Duration::from_millis(500)).await;
+
+        write_frame_async(
+            &mut writer,
+            &ClientMessage::GetSessionStats {
+                name: "stats-test".to_string(),
+            },
+        )
+        .await
+        .unwrap();
+        let resp: DaemonMessage = try_read_frame_async(&mut reader).await.unwrap().unwrap();
+        match resp {
+            DaemonMessage::SessionStats(samples) => {
+                assert!(samples.len() >= 2, "expected 2+ samples, got {:?}", samples);
+                let last = samples.last().unwrap();
+                assert_eq!(last.children, 2, "sh should have two sleep children");
+                assert!(last.rss_bytes > 0);
+                assert!(last.threads >= 3);
+            }
+            other => panic!("expected SessionStats, got {:?}", other),
+        }
+
+        write_frame_async(
+            &mut writer,
+            &ClientMessage::GetSessionInfo {
+                name: "stats-test".to_string(),
+            },
+        )
+        .await
+        .unwrap();
+        let resp: DaemonMessage = try_read_frame_async(&mut reader).await.unwrap().unwrap();
+        match resp {
+            DaemonMessage::SessionDetail(info) => {
+                assert!(info.usage.is_some(), "info should carry the latest sample");
+                assert!(!info.cpu_recent.is_empty());
+            }
+            other => panic!("expected SessionDetail, got {:?}", other),
+        }
+
+        write_frame_async(
+            &mut writer,
+            &ClientMessage::GetSessionStats {
+                name: "no-such-session".to_string(),
+            },
+        )
+        .await
+        .unwrap();
+        let resp: DaemonMessage = try_read_frame_async(&mut reader).await.unwrap().unwrap();
+        assert!(matches!(resp, DaemonMessage::Error(_)), "got {:?}", resp);
+
+        write_frame_async(
+            &mut writer,
+            &ClientMessage::KillSession {
+                name: "stats-test".to_string(),
+                signal: None,
+                grace_ms: None,
+            },
+        )
+        .await
+        .unwrap();
+        let _ = try_read_frame_async::<DaemonMessage>(&mut reader).await;
+
+        let _ = shutdown_tx.send(());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    /// Integration test: KillSession takes down the whole process tree,
+    /// and processes that ignore the signal are SIGKILLed after the grace
+    /// period and reported back.
+    #[tokio::test]
+    async fn test_kill_session_reaps_tree_and_reports_survivors() {
+        use crate::daemon::proctree;
+        use tokio::sync::broadcast;
+
+        let dir =
+            std::env::temp_dir().join(format!("amux-test-killtree-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let sock_path = dir.join("test.sock");
+        let _ = std::fs::remove_file(&sock_path);
+
+        let listener = tokio::net::UnixListener::bind(&sock_path).unwrap();
+        let (shutdown_tx, _) = broadcast::channel::<()>(1);
+
+        let server_shutdown = shutdown_tx.clone();
+        tokio::spawn(async move {
+            crate::daemon::server::run_server(listener, server_shutdown, None).await;
+        });
+
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+        let stream = tokio::net::UnixStream::connect(&sock_path).await.unwrap();
+        let (mut reader, mut writer) = stream.into_split();
+
+        // The shell and its background sleep both ignore SIGTERM.
+        write_frame_async(
+            &mut writer,
+            &ClientMessage::create_session(
+                "killtree-test",
+                vec![
+                    "sh".to_string(),
+                    "-c".to_string(),
+                    "trap '' TERM; sleep 300 & wait".to_string(),
+                ],
+            ),
+        )
+        .await
+        .unwrap();
+        let resp: DaemonMessage = try_read_frame_async(&mut reader).await.unwrap().unwrap();
+        assert!(matches!(resp, DaemonMessage::SessionCreated { .. }));
+
+        write_frame_async(
+            &mut writer,
+            &ClientMessage::GetSessionInfo {
+                name: "killtree-test".to_string(),
+            },
+        )
+        .await
+        .unwrap();
+        let leader = match try_read_frame_async(&mut reader).await.unwrap().unwrap() {
+            DaemonMessage::SessionDetail(info) => info.pid,
+            other => panic!("expected SessionDetail, got {:?}", other),
+        };
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(2);
+        let mut members = Vec::new();
+        while members.len() < 2 && std::time::Instant::now() < deadline {
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            members = proctree::session_members(leader, &proctree::snapshot());
+        }
+        assert_eq!(members.len(), 2, "expected sh + sleep, got {:?}", members);
+
+        write_frame_async(
+            &mut writer,
+            &ClientMessage::KillSession {
+                name: "killtree-test".to_string(),
+                signal: None,
+                grace_ms: Some(300),
+            },
+        )
+        .await
+        .unwrap();
+        let resp: DaemonMessage = try_read_frame_async(&mut reader).await.unwrap().unwrap();
+        match resp {
+            DaemonMessage::SessionKilled { survivors } => {
+                let mut pids: Vec<u32> = survivors.iter().map(|s| s.pid).collect();
+                pids.sort();
+                assert_eq!(pids, members, "both TERM-ignoring processes should be reported");
+                assert!(survivors.iter().any(|s| s.command == "sleep"), "{:?}", survivors);
+            }
+            other => panic!("expected SessionKilled, got {:?}", other),
+        }
+
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+        let remaining = proctree::session_members(leader, &proctree::snapshot());
+        assert!(remaining.is_empty(), "processes outlived the kill: {:?}", remaining);
+
+        let _ = shutdown_tx.send(());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    /// Integration test: SignalSession delivers arbitrary signals, and
+    /// SIGSTOP/SIGCONT to the tree pause and resume the session with the
+    /// state reflected in SessionInfo.
+    #[tokio::test]
+    async fn test_signal_session_and_pause_resume() {
+        use crate::daemon::proctree;
+        use nix::sys::signal::Signal;
+        use tokio::sync::broadcast;
+
+        let dir =
+            std::env::temp_dir().join(format!("amux-test-signal-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let sock_path = dir.join("test.sock");
+        let _ = std::fs::remove_file(&sock_path);
+
+        let listener = tokio::net::UnixListener::bind(&sock_path).unwrap();
+        let (shutdown_tx, _) = broadcast::channel::<()>(1);
+
+        let server_shutdown = shutdown_tx.clone();
+        tokio::spawn(async move {
+            crate::daemon::server::run_server(listener, server_shutdown, None).await;
+        });
+
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+        let stream = tokio::net::UnixStream::connect(&sock_path).await.unwrap();
+        let (mut reader, mut writer) = stream.into_split();
+
+        write_frame_async(
+            &mut writer,
+            &ClientMessage::create_session(
+                "signal-test",
+                vec![
+                    "sh".to_string(),
+                    "-c".to_string(),
+                    "trap 'echo got-usr1' USR1; while :; do sleep 0.1; done".to_string(),
+                ],
+            ),
+        )
+        .await
+        .unwrap();
+        let resp: DaemonMessage = try_read_frame_async(&mut reader).await.unwrap().unwrap();
+        assert!(matches!(resp, DaemonMessage::SessionCreated { .. }));
+        tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+
+        let send = |signal: Signal, target: SignalTarget| ClientMessage::SignalSession {
+            name: "signal-test".to_string(),
+            signal: signal as i32,
+            target,
+        };
+        let info = ClientMessage::GetSessionInfo {
+            name: "signal-test".to_string(),
+        };
+
+        write_frame_async(&mut writer, &send(Signal::SIGUSR1, SignalTarget::Child))
+            .await
+            .unwrap();
+        let resp: DaemonMessage = try_read_frame_async(&mut reader).await.unwrap().unwrap();
+        assert!(matches!(resp, DaemonMessage::SignalSent { .. }), "got {:?}", resp);
+        tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+        write_frame_async(
+            &mut writer,
+            &ClientMessage::CaptureScrollback {
+                name: "signal-test".to_string(),
+                lines: 10,
+                mode: CaptureMode::Plain,
+            },
+        )
+        .await
+        .unwrap();
+        match try_read_frame_async(&mut reader).await.unwrap().unwrap() {
+            DaemonMessage::CaptureOutput(data) => {
+                let out = String::from_utf8_lossy(&data);
+                assert!(out.contains("got-usr1"), "USR1 trap didn't fire: {:?}", out);
+            }
+            other => panic!("expected CaptureOutput, got {:?}", other),
+        }
+
+        // Pause: every process in the tree is stopped.
+        write_frame_async(&mut writer, &send(Signal::SIGSTOP, SignalTarget::Tree))
+            .await
+            .unwrap();
+        let pids = match try_read_frame_async(&mut reader).await.unwrap().unwrap() {
+            DaemonMessage::SignalSent { pids } => pids,
+            other => panic!("expected SignalSent, got {:?}", other),
+        };
+        assert!(!pids.is_empty());
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+        let stats = proctree::snapshot();
+        for pid in &pids {
+            if let Some(st) = stats.get(pid) {
+                assert_eq!(st.state, 'T', "pid {} ({}) not stopped", pid, st.comm);
+            }
+        }
+        write_frame_async(&mut writer, &info).await.unwrap();
+        match try_read_frame_async(&mut reader).await.unwrap().unwrap() {
+            DaemonMessage::SessionDetail(info) => assert!(info.paused),
+            other => panic!("expected SessionDetail, got {:?}", other),
+        }
+
+        // Resume.
+        write_frame_async(&mut writer, &send(Signal::SIGCONT, SignalTarget::Tree))
+            .await
+            .unwrap();
+        let _ = try_read_frame_async::<DaemonMessage>(&mut reader).await;
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+        write_frame_async(&mut writer, &info).await.unwrap();
+        match try_read_frame_async(&mut reader).await.unwrap().unwrap() {
+            DaemonMessage::SessionDetail(info) => {
+                assert!(!info.paused);
+                let st = &proctree::snapshot()[&info.pid];
+                assert_ne!(st.state, 'T', "session still stopped after resume");
+            }
+            other => panic!("expected SessionDetail, got {:?}", other),
+        }
+
+        write_frame_async(
+            &mut writer,
+            &ClientMessage::SignalSession {
+                name: "no-such".to_string(),
+                signal: Signal::SIGINT as i32,
+                target: SignalTarget::Child,
+            },
+        )
+        .await
+        .unwrap();
+        let resp: DaemonMessage = try_read_frame_async(&mut reader).await.unwrap().unwrap();
+        assert!(matches!(resp, DaemonMessage::Error(_)), "got {:?}", resp);
+
+        write_frame_async(
+            &mut writer,
+            &ClientMessage::KillSession {
+                name: "signal-test".to_string(),
+                signal: None,
+                grace_ms: None,
+            },
+        )
+        .await
+        .unwrap();
+        let _ = try_read_frame_async::<DaemonMessage>(&mut reader).await;
+
         let _ = shutdown_tx.send(());
         let _ = std::fs::remove_dir_all(&dir);
     }
@@ -1,11 +1,22 @@
 use crate::client;
-use crate::common::resolved_instance;
-use crate::protocol::messages::{CaptureMode, ClientMessage, DaemonMessage, SessionInfo};
-use crate::util::{ensure_daemon_running, truncate, truncate_preserving_ansi};
+use crate::client::session_list::SessionListFeed;
+use crate::common::{resolved_instance, state_dir};
+use crate::protocol::messages::{
+    CaptureMode, ClientMessage, DaemonMessage, InputDelivery, SessionInfo, SessionSelector,
+    SignalTarget,
+};
+use crate::util::{
+    cpu_sparkline, ensure_daemon_running, format_size, truncate, truncate_preserving_ansi,
+    SPARKLINE_CHARS,
+};
 
-use std::collections::HashMap;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
 use std::io::{self, Write};
-use std::time::Duration;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
 
 use crossterm::{
     cursor,
@@ -15,8 +26,6 @@ use crossterm::{
     terminal::{self, ClearType},
 };
 
-/// Sparkline characters ordered by intensity (lowest to highest).
-const SPARKLINE_CHARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
 /// Number of samples in the activity ring buffer.
 const ACTIVITY_SAMPLES: usize = 10;
 
@@ -47,6 +56,26 @@ impl ActivityTracker {
         self.last_bytes = current_bytes;
     }
 
+    /// Bytes written in the most recent tick.
+    fn last_delta(&self) -> u64 {
+        self.samples[(self.next + ACTIVITY_SAMPLES - 1) % ACTIVITY_SAMPLES]
+    }
+
+    /// Output rate over the last tick, which took `elapsed`.
+    fn bytes_per_sec(&self, elapsed: Duration) -> f64 {
+        let secs = elapsed.as_secs_f64();
+        if secs > 0.0 {
+            self.last_delta() as f64 / secs
+        } else {
+            0.0
+        }
+    }
+
+    /// Bytes written over the whole sample window.
+    fn recent_bytes(&self) -> u64 {
+        self.samples.iter().sum()
+    }
+
     /// Render the sparkline string from the ring buffer.
     fn sparkline(&self) -> String {
         let max = self.samples.iter().copied().max().unwrap_or(0);
@@ -88,11 +117,150 @@ fn format_duration(secs: u64) -> String {
 
 /// Sort sessions: alive first, then by name.
 fn sort_sessions(sessions: &mut [SessionInfo]) {
+    sort_sessions_by(sessions, SortKey::Name, &HashMap::new());
+}
+
+/// Sort sessions alive first, then by `key`, then by name. Output rate is
+/// read from `trackers`; sessions without one count as silent.
+fn sort_sessions_by(
+    sessions: &mut [SessionInfo],
+    key: SortKey,
+    trackers: &HashMap<String, ActivityTracker>,
+) {
+    let rate = |s: &SessionInfo| trackers.get(&s.name).map_or(0, |t| t.recent_bytes());
+    // Failures first, then clean exits, then sessions still running.
+    let exit_rank = |s: &SessionInfo| match s.exit_code {
+        Some(0) => 1,
+        Some(_) => 0,
+        None => 2,
+    };
     sessions.sort_by(|a, b| {
-        b.alive.cmp(&a.alive).then_with(|| a.name.cmp(&b.name))
+        let by_key = match key {
+            SortKey::Name => Ordering::Equal,
+            SortKey::Uptime => b.uptime_secs.cmp(&a.uptime_secs),
+            SortKey::Idle => b.idle_secs.cmp(&a.idle_secs),
+            SortKey::OutputRate => rate(b).cmp(&rate(a)),
+            SortKey::ExitCode => exit_rank(a)
+                .cmp(&exit_rank(b))
+                .then_with(|| a.exit_code.cmp(&b.exit_code)),
+        };
+        b.alive
+            .cmp(&a.alive)
+            .then(by_key)
+            .then_with(|| a.name.cmp(&b.name))
     });
 }
 
+/// What the table is ordered by after alive-first; cycled with `o`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum SortKey {
+    #[default]
+    Name,
+    /// Longest-running first.
+    Uptime,
+    /// Longest-idle first.
+    Idle,
+    /// Most output over the sparkline window first.
+    OutputRate,
+    /// Failed exits first, then clean ones.
+    ExitCode,
+}
+
+impl SortKey {
+    fn next(self) -> Self {
+        match self {
+            SortKey::Name => SortKey::Uptime,
+            SortKey::Uptime => SortKey::Idle,
+            SortKey::Idle => SortKey::OutputRate,
+            SortKey::OutputRate => SortKey::ExitCode,
+            SortKey::ExitCode => SortKey::Name,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            SortKey::Name => "name",
+            SortKey::Uptime => "uptime",
+            SortKey::Idle => "idle",
+            SortKey::OutputRate => "output",
+            SortKey::ExitCode => "exit",
+        }
+    }
+}
+
+/// The dashboard's filter and ordering. Saved to the state directory
+/// whenever it changes, so the next `amux top` opens the same view.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+struct TopView {
+    sort: SortKey,
+    /// Space-separated words; each must appear (case-insensitively) in the
+    /// session's name, command or window title.
+    filter: String,
+    hide_dead: bool,
+}
+
+impl TopView {
+    fn matches(&self, session: &SessionInfo) -> bool {
+        if self.hide_dead && !session.alive {
+            return false;
+        }
+        let haystack = format!("{}\n{}\n{}", session.name, session.command, session.title)
+            .to_lowercase();
+        self.filter
+            .split_whitespace()
+            .all(|word| haystack.contains(&word.to_lowercase()))
+    }
+
+    /// Drop the sessions the view hides and order the rest.
+    fn apply(
+        &self,
+        mut sessions: Vec<SessionInfo>,
+        trackers: &HashMap<String, ActivityTracker>,
+    ) -> Vec<SessionInfo> {
+        sessions.retain(|s| self.matches(s));
+        sort_sessions_by(&mut sessions, self.sort, trackers);
+        sessions
+    }
+
+    /// Title-row description, e.g. `sort: idle  filter: "build"  3 hidden`.
+    fn label(&self, hidden: usize) -> String {
+        let mut label = format!("sort: {}", self.sort.label());
+        if !self.filter.is_empty() {
+            label.push_str(&format!("  filter: {:?}", self.filter));
+        }
+        if self.hide_dead {
+            label.push_str("  hiding dead");
+        }
+        if hidden > 0 {
+            label.push_str(&format!("  {} hidden", hidden));
+        }
+        label
+    }
+
+    /// Load a saved view. A missing or unreadable file yields the default
+    /// view rather than an error — the dashboard should always open.
+    fn load_from(path: &Path) -> Self {
+        std::fs::read(path)
+            .ok()
+            .and_then(|data| serde_json::from_slice(&data).ok())
+            .unwrap_or_default()
+    }
+
+    fn save_to(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, serde_json::to_vec_pretty(self)?)
+    }
+}
+
+/// Where the dashboard view is remembered between runs.
+fn view_path() -> Option<PathBuf> {
+    state_dir().map(|dir| dir.join("top-view.json"))
+}
+
 /// Format the title-row instance label.
 ///
 /// Default instance (`None` or `Some("")`) returns an empty string so the
@@ -114,20 +282,35 @@ fn summary_line(sessions: &[SessionInfo]) -> String {
 }
 
 /// Render one frame of the dashboard to a buffer.
-fn render_frame(sessions: &[SessionInfo], term_cols: u16, trackers: &HashMap<String, ActivityTracker>) -> Vec<String> {
+///
+/// Sessions in `marked` get a `*` in front of their name so the user can
+/// see which rows synchronized input will go to.
+fn render_frame(
+    sessions: &[SessionInfo],
+    term_cols: u16,
+    trackers: &HashMap<String, ActivityTracker>,
+    marked: &HashSet<String>,
+) -> Vec<String> {
     let mut lines = Vec::new();
 
     // Header
-    // Fixed columns: name(16) + status(8) + pid(8) + uptime(8) + idle(8) + exit(6) + activity(11) + spaces(7) = 72
+    // Fixed columns: name(16) + status(8) + pid(8) + uptime(8) + idle(8) + exit(6) + activity(11)
+    //   + cpu%(6) + rss(7) + cpu sparkline(11) + spaces(10) = 99
     let header = format!(
-        "{:<16} {:<8} {:>8} {:>8} {:>8} {:>6} {:<11} {}",
-        "NAME", "STATUS", "PID", "UPTIME", "IDLE", "EXIT", "ACTIVITY", "COMMAND"
+        "{:<16} {:<8} {:>8} {:>8} {:>8} {:>6} {:<11} {:>6} {:>7} {:<11} {}",
+        "NAME", "STATUS", "PID", "UPTIME", "IDLE", "EXIT", "ACTIVITY", "CPU%", "RSS", "CPU",
+        "COMMAND"
     );
     lines.push(header);
 
     // Rows
     for s in sessions {
-        let status = if s.alive { "alive" } else { "dead" };
+        let status = match (s.alive, s.paused) {
+            (true, true) => "paused",
+            (true, false) if s.needs_attention => "attn",
+            (true, false) => "alive",
+            (false, _) => "dead",
+        };
         let exit_str = match s.exit_code {
             Some(c) => c.to_string(),
             None => "-".to_string(),
@@ -140,25 +323,53 @@ fn render_frame(sessions: &[SessionInfo], term_cols: u16, trackers: &HashMap<Str
             .map(|t| t.sparkline())
             .unwrap_or_else(|| SPARKLINE_CHARS[0].to_string().repeat(ACTIVITY_SAMPLES));
 
+        // Usage columns come from the daemon's telemetry sampler; blank
+        // until its first sample (or for exited sessions).
+        let (cpu, rss) = match (&s.usage, s.alive) {
+            (Some(u), true) => (format!("{:.1}", u.cpu_percent), format_size(u.rss_bytes)),
+            _ => ("-".to_string(), "-".to_string()),
+        };
+        let cpu_spark = if s.alive {
+            let mut spark = cpu_sparkline(&s.cpu_recent);
+            let pad = ACTIVITY_SAMPLES.saturating_sub(s.cpu_recent.len());
+            spark.insert_str(0, &SPARKLINE_CHARS[0].to_string().repeat(pad));
+            spark
+        } else {
+            String::new()
+        };
+
         // Calculate remaining space for command column
-        // Fixed columns: name(16) + status(8) + pid(8) + uptime(8) + idle(8) + exit(6) + activity(11) + spaces(7) = 72
-        let fixed_width = 72;
+        let fixed_width = 99;
         let cmd_width = if (term_cols as usize) > fixed_width + 4 {
             (term_cols as usize) - fixed_width
         } else {
             20
         };
-        let cmd = truncate(&s.command, cmd_width);
+        // The window title is usually the agent's own status line, so it
+        // leads when the program set one.
+        let cmd = if s.title.is_empty() {
+            truncate(&s.command, cmd_width)
+        } else {
+            truncate(&format!("[{}] {}", s.title, s.command), cmd_width)
+        };
 
+        let name = if marked.contains(&s.name) {
+            format!("*{}", truncate(&s.name, 15))
+        } else {
+            truncate(&s.name, 16)
+        };
         let row = format!(
-            "{:<16} {:<8} {:>8} {:>8} {:>8} {:>6} {:<11} {}",
-            truncate(&s.name, 16),
+            "{:<16} {:<8} {:>8} {:>8} {:>8} {:>6} {:<11} {:>6} {:>7} {:<11} {}",
+            name,
             status,
             s.pid,
             uptime,
             idle,
             exit_str,
             sparkline,
+            cpu,
+            rss,
+            cpu_spark,
             cmd
         );
         lines.push(row);
@@ -191,6 +402,228 @@ fn render_preview(raw: &[u8], max_lines: usize, max_cols: usize) -> Vec<String>
         .collect()
 }
 
+/// History requested for the zoomed view. The daemon clamps this to what
+/// the session's virtual terminal actually retains.
+const ZOOM_HISTORY_LINES: usize = 10_000;
+
+/// Full-screen, read-only view of one session's rendered history, opened
+/// with `z`. Nothing is sent to the session and its PTY is never resized,
+/// so it is safe to use on an agent mid-task. The whole history is
+/// captured when the view opens and again when it scrolls back or
+/// searches; while following, only the newest lines are fetched.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Zoom {
+    name: String,
+    /// The session's formatted lines as last fetched.
+    history: Vec<String>,
+    /// Set when `history` should be captured in full again.
+    stale: bool,
+    /// Lines scrolled back from the newest one; 0 follows live output.
+    offset: usize,
+    /// Line count at the last refresh, so a scrolled-back view stays put
+    /// while new output arrives below it.
+    total: usize,
+    /// `Some(buf)` while a search is being typed.
+    search_input: Option<String>,
+    /// The last search submitted; `n`/`N` repeat it.
+    query: String,
+    /// Index of the line the last search landed on.
+    matched: Option<usize>,
+    /// One-shot status text, e.g. "not found".
+    message: Option<String>,
+}
+
+/// Result of a key press in the zoomed view.
+#[derive(Debug, PartialEq, Eq)]
+enum ZoomResult {
+    Continue,
+    /// Back to the table.
+    Close,
+}
+
+impl Zoom {
+    fn new(name: String) -> Self {
+        Self {
+            name,
+            history: Vec::new(),
+            stale: true,
+            offset: 0,
+            total: 0,
+            search_input: None,
+            query: String::new(),
+            matched: None,
+            message: None,
+        }
+    }
+
+    /// Take in a fresh capture of `total` lines. While scrolled back, the
+    /// offset grows with the new output so the same lines stay on screen
+    /// (until the session's history is full and old lines start falling
+    /// off the top).
+    fn refresh(&mut self, total: usize) {
+        if self.offset > 0 && total > self.total {
+            self.offset += total - self.total;
+        }
+        self.total = total;
+    }
+
+    /// Fold the newest lines into `history`, lined up with the longest
+    /// run of lines they repeat (lines after it were redrawn). With no
+    /// overlap (more output arrived than `recent` holds), they are appended
+    /// and the lines in between are missing until the next full capture;
+    /// only the bottom of the view is on screen then.
+    fn merge_tail(&mut self, recent: Vec<String>) {
+        let len = self.history.len();
+        let start = (len.saturating_sub(recent.len())..len)
+            .rev()
+            .map(|start| {
+                let run = self.history[start..]
+                    .iter()
+                    .zip(&recent)
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                (start, run)
+            })
+            .filter(|&(_, run)| run > 0)
+            .max_by_key(|&(_, run)| run)
+            .map_or(len, |(start, _)| start);
+        self.history.truncate(start);
+        self.history.extend(recent);
+    }
+
+    /// Range of line indices visible in `height` rows.
+    fn window(&self, height: usize) -> (usize, usize) {
+        let max_offset = self.total.saturating_sub(height);
+        let end = self.total - self.offset.min(max_offset);
+        (end.saturating_sub(height), end)
+    }
+
+    fn scroll_up(&mut self, by: usize, height: usize) {
+        let max_offset = self.total.saturating_sub(height);
+        self.offset = self.offset.saturating_add(by).min(max_offset);
+    }
+
+    fn scroll_down(&mut self, by: usize) {
+        self.offset = self.offset.saturating_sub(by);
+    }
+
+    /// Scroll so line `index` is the top visible row (or as near as the
+    /// end of the history allows).
+    fn show_line(&mut self, index: usize, height: usize) {
+        self.offset = self.total.saturating_sub(index + height);
+    }
+
+    /// Jump to the next match of the current query: older lines when
+    /// `backward`, newer otherwise. Searching starts from the last match,
+    /// or from the bottom of the view for a new query.
+    fn search(&mut self, lines: &[String], backward: bool, height: usize) {
+        if self.query.is_empty() {
+            return;
+        }
+        let from = self.matched.unwrap_or_else(|| {
+            let (_, end) = self.window(height);
+            end
+        });
+        match find_line(lines, &self.query, from, backward) {
+            Some(i) => {
+                self.matched = Some(i);
+                self.show_line(i, height);
+            }
+            None => {
+                self.message = Some(format!(
+                    "{:?} not found {}",
+                    self.query,
+                    if backward { "above" } else { "below" }
+                ));
+            }
+        }
+    }
+
+    /// Apply a key. `lines` is the visible text of the current capture
+    /// (for searching) and `height` the number of content rows.
+    fn handle_key(
+        &mut self,
+        code: KeyCode,
+        modifiers: KeyModifiers,
+        lines: &[String],
+        height: usize,
+    ) -> ZoomResult {
+        self.message = None;
+        if let Some(buf) = self.search_input.as_mut() {
+            match handle_input_key(buf, code, modifiers) {
+                InputResult::Continue => {}
+                InputResult::Cancel => self.search_input = None,
+                InputResult::Submit(query) => {
+                    self.search_input = None;
+                    self.query = query;
+                    self.matched = None;
+                    self.search(lines, true, height);
+                }
+            }
+            return ZoomResult::Continue;
+        }
+        let page = height.saturating_sub(1).max(1);
+        let following = self.offset == 0;
+        match code {
+            KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('z') => return ZoomResult::Close,
+            KeyCode::Char('c') if modifiers.contains(KeyModifiers::CONTROL) => {
+                return ZoomResult::Close
+            }
+            KeyCode::PageUp | KeyCode::Char('b') => self.scroll_up(page, height),
+            KeyCode::PageDown | KeyCode::Char(' ') => self.scroll_down(page),
+            KeyCode::Up | KeyCode::Char('k') => self.scroll_up(1, height),
+            KeyCode::Down | KeyCode::Char('j') => self.scroll_down(1),
+            KeyCode::Home | KeyCode::Char('g') => self.scroll_up(usize::MAX, height),
+            KeyCode::End | KeyCode::Char('G') => self.offset = 0,
+            KeyCode::Char('/') => {
+                self.search_input = Some(String::new());
+                self.stale = true;
+            }
+            KeyCode::Char('n') => self.search(lines, true, height),
+            KeyCode::Char('N') => self.search(lines, false, height),
+            _ => {}
+        }
+        // Scrolling back reads history the tail refreshes didn't keep.
+        if following && self.offset > 0 {
+            self.stale = true;
+        }
+        ZoomResult::Continue
+    }
+}
+
+/// Index of the nearest line before (`backward`) or after `from` whose
+/// text contains `query`, ignoring case.
+fn find_line(lines: &[String], query: &str, from: usize, backward: bool) -> Option<usize> {
+    let query = query.to_lowercase();
+    let hit = |i: &usize| lines[*i].to_lowercase().contains(&query);
+    if backward {
+        (0..from.min(lines.len())).rev().find(hit)
+    } else {
+        (from.saturating_add(1)..lines.len()).find(hit)
+    }
+}
+
+/// The visible text of a formatted capture line: SGR and other CSI
+/// sequences removed, UTF-8 left intact.
+fn visible_text(line: &str) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            if chars.next() == Some('[') {
+                for c in chars.by_ref() {
+                    if ('\x40'..='\x7e').contains(&c) {
+                        break;
+                    }
+                }
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
 /// Clamp a selection index to be within [0, count-1], or 0 if count is 0.
 fn clamp_selection(selected: usize, count: usize) -> usize {
     if count == 0 {
@@ -211,6 +644,71 @@ enum TopAction {
     /// User pressed 'i' — drop into single-line input mode targeting the
     /// currently highlighted session (design A from bd-ly6).
     EnterInput,
+    /// User pressed space — toggle the mark on the highlighted session.
+    ToggleMark(String),
+    /// User pressed 'S' — drop into synchronized input mode: every key
+    /// (Tab, Ctrl keys and arrows included) is mirrored as it is typed to
+    /// every marked session (every live one if none are marked), until
+    /// Ctrl+B returns to the dashboard.
+    EnterSyncInput,
+    /// User pressed 'r' — prompt for a new name for the highlighted session.
+    EnterRename(String),
+    /// User asked for a session action; it runs on the marked sessions (or
+    /// the highlighted one) once confirmed.
+    Confirm(SessionAction),
+    /// User pressed 'p' — pause the targets, or resume them if all are
+    /// already paused (resolved against their state, then confirmed).
+    TogglePause,
+    /// User pressed '/' — edit the filter; the table narrows as they type.
+    EnterFilter,
+    /// User pressed 'o' — order the table by the next sort key.
+    CycleSort,
+    /// User pressed 'h' — hide or show dead sessions.
+    ToggleHideDead,
+    /// User pressed 'z' — open the full-screen history of the session.
+    Zoom(String),
+}
+
+/// What the status-bar line editor is collecting.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum InputKind {
+    /// A line for the highlighted session.
+    Send,
+    /// A new name for the given session.
+    Rename(String),
+    /// The table filter (replaces the saved one on Enter).
+    Filter,
+}
+
+/// A dashboard action applied to one or more sessions after a `y/N`
+/// confirmation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SessionAction {
+    /// `x` — kill the process tree (SIGTERM, then SIGKILL after the grace).
+    Kill,
+    /// `R` — rerun the session's command in place.
+    Respawn,
+    /// `C` — send Ctrl+C.
+    Interrupt,
+    Pause,
+    Resume,
+}
+
+impl SessionAction {
+    fn verb(self) -> &'static str {
+        match self {
+            SessionAction::Kill => "kill",
+            SessionAction::Respawn => "respawn",
+            SessionAction::Interrupt => "send Ctrl+C to",
+            SessionAction::Pause => "pause",
+            SessionAction::Resume => "resume",
+        }
+    }
+
+    /// Only respawn makes sense for a session whose program has exited.
+    fn needs_live_session(self) -> bool {
+        self != SessionAction::Respawn
+    }
 }
 
 /// Result of handling a key while top is in input mode.
@@ -324,7 +822,7 @@ fn compute_layout(session_count: u16, rows: u16) -> TopLayout {
 
 /// Render a plain-text snapshot of the session table (no TUI, no ANSI).
 fn render_snapshot(sessions: &[SessionInfo], term_cols: u16, trackers: &HashMap<String, ActivityTracker>) -> String {
-    let frame = render_frame(sessions, term_cols, trackers);
+    let frame = render_frame(sessions, term_cols, trackers, &HashSet::new());
     let summary = summary_line(sessions);
     let mut lines = frame;
     lines.push(summary);
@@ -357,6 +855,89 @@ pub fn do_top_once() -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Time between `amux top --json` documents when `--interval` is not given.
+const DEFAULT_JSON_INTERVAL_MS: u64 = 2000;
+
+/// Stream the dashboard as JSON: one document per line every `interval_ms`
+/// (`amux top --json`), or a single one with `once`. Ends quietly when the
+/// reader goes away, so it can be piped into `head` or a monitoring agent.
+pub fn do_top_json(interval_ms: Option<u64>, once: bool) -> anyhow::Result<()> {
+    ensure_daemon_running()?;
+    let interval = Duration::from_millis(interval_ms.unwrap_or(DEFAULT_JSON_INTERVAL_MS));
+
+    let mut trackers: HashMap<String, ActivityTracker> = HashMap::new();
+    let mut last_tick: Option<Instant> = None;
+    loop {
+        let mut sessions = fetch_sessions()?;
+        sort_sessions(&mut sessions);
+        let now = Instant::now();
+
+        // Rates need a previous sample: a session seen for the first time
+        // (and every session in the first document) reports null.
+        let elapsed = last_tick.map(|t| now.duration_since(t));
+        let mut rates = HashMap::new();
+        for s in &sessions {
+            match (trackers.get_mut(&s.name), elapsed) {
+                (Some(tracker), Some(elapsed)) => {
+                    tracker.record(s.output_bytes);
+                    rates.insert(s.name.clone(), tracker.bytes_per_sec(elapsed));
+                }
+                _ => {
+                    trackers.insert(s.name.clone(), ActivityTracker::new(s.output_bytes));
+                }
+            }
+        }
+        trackers.retain(|name, _| sessions.iter().any(|s| &s.name == name));
+        last_tick = Some(now);
+
+        let unix_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+        let doc = top_json(&sessions, &rates, unix_ms);
+        let mut out = io::stdout().lock();
+        match writeln!(out, "{}", doc).and_then(|()| out.flush()) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => return Ok(()),
+            Err(e) => return Err(e.into()),
+        }
+        drop(out);
+
+        if once {
+            return Ok(());
+        }
+        std::thread::sleep(interval.saturating_sub(now.elapsed()));
+    }
+}
+
+/// One `amux top --json` document: a summary plus every session's
+/// `SessionInfo` fields, each with a `bytes_per_sec` output rate taken
+/// from `rates` (null when absent).
+fn top_json(
+    sessions: &[SessionInfo],
+    rates: &HashMap<String, f64>,
+    unix_ms: u64,
+) -> serde_json::Value {
+    let alive = sessions.iter().filter(|s| s.alive).count();
+    let rows: Vec<serde_json::Value> = sessions
+        .iter()
+        .map(|s| {
+            let mut row = serde_json::to_value(s).unwrap_or_default();
+            if let Some(obj) = row.as_object_mut() {
+                obj.insert("bytes_per_sec".to_string(), serde_json::json!(rates.get(&s.name)));
+            }
+            row
+        })
+        .collect();
+    serde_json::json!({
+        "unix_ms": unix_ms,
+        "total": sessions.len(),
+        "alive": alive,
+        "dead": sessions.len() - alive,
+        "sessions": rows,
+    })
+}
+
 /// Run the live TUI dashboard.
 pub fn do_top() -> anyhow::Result<()> {
     ensure_daemon_running()?;
@@ -382,15 +963,51 @@ fn top_loop(stdout: &mut io::Stdout) -> anyhow::Result<()> {
     // None = normal mode; Some(buf) = input mode collecting `buf` to send
     // to the highlighted session on Enter (design A from bd-ly6).
     let mut input_mode: Option<String> = None;
+    // What the current input mode's line is for.
+    let mut input_kind = InputKind::Send;
+    // Sessions marked with space; the targets of synchronized input and
+    // of dashboard actions.
+    let mut marked: HashSet<String> = HashSet::new();
+    // Synchronized input: while on, keys go to the sync targets instead
+    // of the dashboard. Holds the last delivery problem, if any.
+    let mut syncing = false;
+    let mut sync_error: Option<String> = None;
+    // An action waiting for `y` on the status bar, with its targets.
+    let mut pending: Option<(SessionAction, Vec<String>)> = None;
     // One-shot status text shown on the bottom row right after a send,
     // so the user gets confirmation without leaving normal mode.
     let mut input_flash: Option<String> = None;
+    // Filter and ordering, restored from the last run.
+    let view_file = view_path();
+    let mut view = view_file
+        .as_deref()
+        .map(TopView::load_from)
+        .unwrap_or_default();
+    // The zoomed view, when open, replaces the whole dashboard.
+    let mut zoom: Option<Zoom> = None;
+    // Sessions and the preview are pushed by the daemon; `None` while no
+    // subscription can be made, in which case each refresh polls.
+    let mut feed: Option<SessionListFeed> = None;
 
     loop {
-        // Poll sessions from daemon
-        let sessions = match fetch_sessions() {
-            Ok(s) => s,
-            Err(_) => Vec::new(), // Show empty if daemon unreachable
+        if let Some(z) = zoom.as_mut() {
+            match zoom_tick(stdout, z, feed.as_mut()) {
+                Ok(ZoomResult::Continue) => continue,
+                Ok(ZoomResult::Close) => zoom = None,
+                Err(e) => {
+                    input_flash = Some(format!("zoom {}: {}", z.name, e));
+                    zoom = None;
+                }
+            }
+        }
+
+        if !feed.as_ref().is_some_and(|f| f.is_connected()) {
+            feed = SessionListFeed::subscribe(None, 0).ok();
+        }
+        let sessions = match &feed {
+            Some(feed) => feed.sessions(),
+            // Show empty if daemon unreachable
+            None => fetch_sessions().unwrap_or_default(),
         };
 
         // Update activity trackers for each session
@@ -401,11 +1018,21 @@ fn top_loop(stdout: &mut io::Stdout) -> anyhow::Result<()> {
                 .or_insert_with(|| ActivityTracker::new(s.output_bytes));
         }
 
-        // Remove trackers for sessions that no longer exist
+        // Remove trackers and marks for sessions that no longer exist
         trackers.retain(|name, _| sessions.iter().any(|s| &s.name == name));
-
-        let mut sorted = sessions;
-        sort_sessions(&mut sorted);
+        marked.retain(|name| sessions.iter().any(|s| &s.name == name));
+
+        // While the filter is being edited the table follows the buffer.
+        let shown_view = match (&input_mode, &input_kind) {
+            (Some(buf), InputKind::Filter) => TopView {
+                filter: buf.clone(),
+                ..view.clone()
+            },
+            _ => view.clone(),
+        };
+        let total = sessions.len();
+        let sorted = shown_view.apply(sessions, &trackers);
+        let hidden = total - sorted.len();
 
         // Clamp selection
         selected = clamp_selection(selected, sorted.len());
@@ -434,10 +1061,18 @@ fn top_loop(stdout: &mut io::Stdout) -> anyhow::Result<()> {
         // margin guards against trailing-blank filtering shrinking the
         // result below the available rows.
         let requested_lines = (layout.preview_row_count as usize).saturating_add(8).max(30);
-        let preview_raw = if !sorted.is_empty() {
-            fetch_scrollback(&sorted[selected].name, requested_lines).unwrap_or_default()
-        } else {
-            Vec::new()
+        // Right after the selection moves, the feed has no preview of the
+        // new session yet; one capture bridges the gap.
+        let preview_raw = match sorted.get(selected) {
+            Some(target) => feed
+                .as_mut()
+                .and_then(|f| {
+                    f.set_preview(Some(&target.name), requested_lines as u32).ok()?;
+                    f.preview(&target.name)
+                })
+                .or_else(|| fetch_scrollback(&target.name, requested_lines).ok())
+                .unwrap_or_default(),
+            None => Vec::new(),
         };
 
         let preview = render_preview(
@@ -463,9 +1098,12 @@ fn top_loop(stdout: &mut io::Stdout) -> anyhow::Result<()> {
         )?;
         write!(stdout, "amux top{}", suffix)?;
         execute!(stdout, ResetColor, SetAttribute(Attribute::Reset))?;
+        execute!(stdout, SetForegroundColor(Color::DarkGrey))?;
+        write!(stdout, "  {}", shown_view.label(hidden))?;
+        execute!(stdout, ResetColor)?;
 
         // Header row
-        let frame = render_frame(&sorted, cols, &trackers);
+        let frame = render_frame(&sorted, cols, &trackers, &marked);
         if let Some(header) = frame.first() {
             execute!(
                 stdout,
@@ -491,6 +1129,12 @@ fn top_loop(stdout: &mut io::Stdout) -> anyhow::Result<()> {
                     SetForegroundColor(Color::White),
                     SetAttribute(Attribute::Bold)
                 )?;
+            } else if session.alive && session.needs_attention {
+                execute!(
+                    stdout,
+                    SetForegroundColor(Color::Yellow),
+                    SetAttribute(Attribute::Bold)
+                )?;
             } else if session.alive {
                 execute!(stdout, SetForegroundColor(Color::Green))?;
             } else {
@@ -562,13 +1206,50 @@ fn top_loop(stdout: &mut io::Stdout) -> anyhow::Result<()> {
         //   3. normal:     summary + key hints
         execute!(stdout, cursor::MoveTo(0, layout.summary_row))?;
         if let Some(buf) = input_mode.as_ref() {
-            let target = sorted.get(selected).map(|s| s.name.as_str()).unwrap_or("(none)");
+            let (verb, target) = match &input_kind {
+                InputKind::Send => (
+                    "send",
+                    sorted
+                        .get(selected)
+                        .map(|s| s.name.clone())
+                        .unwrap_or_else(|| "(none)".to_string()),
+                ),
+                InputKind::Rename(name) => ("rename", name.clone()),
+                InputKind::Filter => ("filter", "name/command/title".to_string()),
+            };
+            execute!(
+                stdout,
+                SetForegroundColor(Color::Yellow),
+                SetAttribute(Attribute::Bold)
+            )?;
+            write!(stdout, "{} → {}: {}", verb, target, buf)?;
+            execute!(stdout, SetAttribute(Attribute::Reset), ResetColor)?;
+        } else if syncing {
+            let n = sync_targets(&sorted, &marked).len();
             execute!(
                 stdout,
                 SetForegroundColor(Color::Yellow),
                 SetAttribute(Attribute::Bold)
             )?;
-            write!(stdout, "send → {}: {}", target, buf)?;
+            write!(
+                stdout,
+                "sync → {} session{}: typing is mirrored live  (C-b: back)",
+                n,
+                if n == 1 { "" } else { "s" }
+            )?;
+            execute!(stdout, SetAttribute(Attribute::Reset))?;
+            if let Some(e) = sync_error.as_ref() {
+                execute!(stdout, SetForegroundColor(Color::Red))?;
+                write!(stdout, "  {}", e)?;
+            }
+            execute!(stdout, ResetColor)?;
+        } else if let Some((action, targets)) = pending.as_ref() {
+            execute!(
+                stdout,
+                SetForegroundColor(Color::Red),
+                SetAttribute(Attribute::Bold)
+            )?;
+            write!(stdout, "{}", confirm_prompt(*action, targets))?;
             execute!(stdout, SetAttribute(Attribute::Reset), ResetColor)?;
         } else if let Some(msg) = input_flash.take() {
             execute!(stdout, SetForegroundColor(Color::Green))?;
@@ -579,7 +1260,7 @@ fn top_loop(stdout: &mut io::Stdout) -> anyhow::Result<()> {
             execute!(stdout, SetForegroundColor(Color::DarkGrey))?;
             write!(
                 stdout,
-                "{}  │  j/k:select  Enter:attach  f:follow  i:input  q:quit",
+                "{}  │  j/k:select  Enter:attach  f:follow  i:input  space:mark  S:sync-input  /:filter  o:sort  h:hide-dead  x:kill  R:respawn  r:rename  C:ctrl-c  p:pause  z:zoom  q:quit",
                 summary
             )?;
             execute!(stdout, ResetColor)?;
@@ -600,7 +1281,29 @@ fn top_loop(stdout: &mut io::Stdout) -> anyhow::Result<()> {
         // responsive and the session/activity data refreshes at ~10Hz.
         if event::poll(Duration::from_millis(100))? {
             if let Event::Key(KeyEvent { code, modifiers, .. }) = event::read()? {
-                if let Some(buffer) = input_mode.as_mut() {
+                if let Some((action, targets)) = pending.take() {
+                    if confirms(code) {
+                        input_flash = Some(run_action(action, &targets));
+                    }
+                } else if syncing {
+                    if code == KeyCode::Char('b') && modifiers.contains(KeyModifiers::CONTROL) {
+                        syncing = false;
+                    } else if let Some(key) = key_name(code, modifiers) {
+                        let targets = sync_targets(&sorted, &marked);
+                        sync_error = match mirror_key(targets, key) {
+                            Ok(results) => {
+                                let failed: Vec<&str> = results
+                                    .iter()
+                                    .filter(|r| r.error.is_some())
+                                    .map(|r| r.session.as_str())
+                                    .collect();
+                                (!failed.is_empty())
+                                    .then(|| format!("not delivered to {}", failed.join(", ")))
+                            }
+                            Err(e) => Some(format!("sync send failed: {}", e)),
+                        };
+                    }
+                } else if let Some(buffer) = input_mode.as_mut() {
                     // Input mode: keys go into the buffer, not the table
                     // navigation. The selected row is frozen here so the
                     // target the user saw when they pressed 'i' is the
@@ -610,6 +1313,25 @@ fn top_loop(stdout: &mut io::Stdout) -> anyhow::Result<()> {
                         InputResult::Cancel => {
                             input_mode = None;
                         }
+                        InputResult::Submit(new_name) if matches!(input_kind, InputKind::Rename(_)) => {
+                            input_mode = None;
+                            if let InputKind::Rename(name) = &input_kind {
+                                input_flash = match rename_session(name, new_name.trim()) {
+                                    Ok(()) => {
+                                        if marked.remove(name) {
+                                            marked.insert(new_name.trim().to_string());
+                                        }
+                                        Some(format!("renamed {} to {}", name, new_name.trim()))
+                                    }
+                                    Err(e) => Some(format!("rename {} failed: {}", name, e)),
+                                };
+                            }
+                        }
+                        InputResult::Submit(filter) if input_kind == InputKind::Filter => {
+                            input_mode = None;
+                            view.filter = filter.trim().to_string();
+                            input_flash = save_view(&view, view_file.as_deref());
+                        }
                         InputResult::Submit(text) => {
                             input_mode = None;
                             if let Some(target) = sorted.get(selected) {
@@ -637,21 +1359,70 @@ fn top_loop(stdout: &mut io::Stdout) -> anyhow::Result<()> {
                             terminal::disable_raw_mode()?;
                             execute!(stdout, cursor::Show, terminal::LeaveAlternateScreen)?;
                             // Use the attach command
-                            let _ = super::attach::do_attach(&name);
+                            let _ = super::attach::do_attach(&name, None);
                             execute!(stdout, terminal::EnterAlternateScreen, cursor::Hide)?;
                             terminal::enable_raw_mode()?;
                         }
                         TopAction::Follow(name) => {
                             terminal::disable_raw_mode()?;
                             execute!(stdout, cursor::Show, terminal::LeaveAlternateScreen)?;
-                            let _ = super::attach::do_follow(&name, false);
+                            let _ = super::attach::do_follow(&name, false, None, false);
                             execute!(stdout, terminal::EnterAlternateScreen, cursor::Hide)?;
                             terminal::enable_raw_mode()?;
                         }
                         TopAction::EnterInput => {
                             input_mode = Some(String::new());
+                            input_kind = InputKind::Send;
+                            input_flash = None;
+                        }
+                        TopAction::EnterSyncInput => {
+                            syncing = true;
+                            sync_error = None;
+                            input_flash = None;
+                        }
+                        TopAction::EnterRename(name) => {
+                            input_mode = Some(String::new());
+                            input_kind = InputKind::Rename(name);
+                            input_flash = None;
+                        }
+                        TopAction::Confirm(action) => {
+                            let targets = action_targets(&sorted, &marked, selected, action);
+                            if targets.is_empty() {
+                                input_flash = Some(format!("nothing to {}", action.verb()));
+                            } else {
+                                pending = Some((action, targets));
+                            }
+                        }
+                        TopAction::TogglePause => {
+                            let targets =
+                                action_targets(&sorted, &marked, selected, SessionAction::Pause);
+                            if targets.is_empty() {
+                                input_flash = Some("nothing to pause".to_string());
+                            } else {
+                                pending = Some((pause_action(&sorted, &targets), targets));
+                            }
+                        }
+                        TopAction::Zoom(name) => {
+                            zoom = Some(Zoom::new(name));
+                        }
+                        TopAction::EnterFilter => {
+                            input_mode = Some(view.filter.clone());
+                            input_kind = InputKind::Filter;
                             input_flash = None;
                         }
+                        TopAction::CycleSort => {
+                            view.sort = view.sort.next();
+                            input_flash = save_view(&view, view_file.as_deref());
+                        }
+                        TopAction::ToggleHideDead => {
+                            view.hide_dead = !view.hide_dead;
+                            input_flash = save_view(&view, view_file.as_deref());
+                        }
+                        TopAction::ToggleMark(name) => {
+                            if !marked.remove(&name) {
+                                marked.insert(name);
+                            }
+                        }
                         TopAction::Continue => {}
                     }
                 }
@@ -706,10 +1477,153 @@ fn handle_key(
                 TopAction::Continue
             }
         }
+        KeyCode::Char(' ') => {
+            if !sessions.is_empty() {
+                TopAction::ToggleMark(sessions[*selected].name.clone())
+            } else {
+                TopAction::Continue
+            }
+        }
+        KeyCode::Char('S') => {
+            if !sessions.is_empty() {
+                TopAction::EnterSyncInput
+            } else {
+                TopAction::Continue
+            }
+        }
+        // View keys work on an empty table too: the filter may be what
+        // emptied it.
+        KeyCode::Char('/') => TopAction::EnterFilter,
+        KeyCode::Char('o') => TopAction::CycleSort,
+        KeyCode::Char('h') => TopAction::ToggleHideDead,
+        _ if sessions.is_empty() => TopAction::Continue,
+        KeyCode::Char('r') => TopAction::EnterRename(sessions[*selected].name.clone()),
+        KeyCode::Char('z') => TopAction::Zoom(sessions[*selected].name.clone()),
+        KeyCode::Char('x') => TopAction::Confirm(SessionAction::Kill),
+        KeyCode::Char('R') => TopAction::Confirm(SessionAction::Respawn),
+        KeyCode::Char('C') => TopAction::Confirm(SessionAction::Interrupt),
+        KeyCode::Char('p') => TopAction::TogglePause,
         _ => TopAction::Continue,
     }
 }
 
+/// Bring the zoomed view's history up to date: all of it when stale,
+/// otherwise (while following) the newest `tail` lines, taken from the
+/// dashboard's preview feed when there is one.
+fn zoom_fetch(
+    zoom: &mut Zoom,
+    feed: Option<&mut SessionListFeed>,
+    tail: usize,
+) -> anyhow::Result<()> {
+    let lines = |raw: &[u8]| -> Vec<String> {
+        String::from_utf8_lossy(raw).lines().map(str::to_string).collect()
+    };
+    if zoom.stale {
+        zoom.history = lines(&fetch_scrollback(&zoom.name, ZOOM_HISTORY_LINES)?);
+        zoom.stale = false;
+    } else if zoom.offset == 0 {
+        let recent = match feed {
+            Some(feed) => {
+                feed.set_preview(Some(&zoom.name), tail as u32)?;
+                // Nothing new until the daemon pushes the first preview.
+                let Some(raw) = feed.preview(&zoom.name) else {
+                    return Ok(());
+                };
+                raw
+            }
+            None => fetch_scrollback(&zoom.name, tail)?,
+        };
+        zoom.merge_tail(lines(&recent));
+    }
+    zoom.refresh(zoom.history.len());
+    Ok(())
+}
+
+/// Refresh, draw and take one key for the zoomed view. Errors (say, the
+/// session went away) close it.
+fn zoom_tick(
+    stdout: &mut io::Stdout,
+    zoom: &mut Zoom,
+    feed: Option<&mut SessionListFeed>,
+) -> anyhow::Result<ZoomResult> {
+    let (cols, rows) = terminal::size().unwrap_or((80, 24));
+    // Title row on top, status row at the bottom.
+    let height = rows.saturating_sub(2).max(1) as usize;
+    zoom_fetch(zoom, feed, 2 * height)?;
+    let (start, end) = zoom.window(height);
+
+    execute!(stdout, terminal::Clear(ClearType::All), cursor::MoveTo(0, 0))?;
+    execute!(
+        stdout,
+        SetAttribute(Attribute::Bold),
+        SetForegroundColor(Color::Yellow)
+    )?;
+    let position = if zoom.offset == 0 {
+        "following".to_string()
+    } else {
+        format!("{} lines back", zoom.offset)
+    };
+    write!(
+        stdout,
+        " {}  lines {}-{} of {}  ({})",
+        zoom.name,
+        if end == 0 { 0 } else { start + 1 },
+        end,
+        zoom.total,
+        position
+    )?;
+    execute!(stdout, ResetColor, SetAttribute(Attribute::Reset))?;
+
+    for (row, i) in (start..end).enumerate() {
+        execute!(stdout, cursor::MoveTo(0, 1 + row as u16))?;
+        if zoom.matched == Some(i) {
+            // The match is shown as plain reverse video; its own colors
+            // would hide the highlight.
+            execute!(stdout, SetAttribute(Attribute::Reverse))?;
+            write!(stdout, "{}", truncate(&visible_text(&zoom.history[i]), cols as usize))?;
+            execute!(stdout, SetAttribute(Attribute::Reset))?;
+        } else {
+            let mut line = truncate_preserving_ansi(&zoom.history[i], cols as usize);
+            line.push_str("\x1b[0m");
+            write!(stdout, "{}", line)?;
+        }
+    }
+
+    execute!(stdout, cursor::MoveTo(0, rows.saturating_sub(1)))?;
+    if let Some(buf) = zoom.search_input.as_ref() {
+        execute!(stdout, SetForegroundColor(Color::Yellow), cursor::Show)?;
+        write!(stdout, "/{}", buf)?;
+    } else if let Some(msg) = zoom.message.as_ref() {
+        execute!(stdout, SetForegroundColor(Color::Red), cursor::Hide)?;
+        write!(stdout, "{}", msg)?;
+    } else {
+        execute!(stdout, SetForegroundColor(Color::DarkGrey), cursor::Hide)?;
+        write!(
+            stdout,
+            "PgUp/PgDn:page  j/k:line  g/G:top/bottom  /:search  n/N:older/newer match  q:back"
+        )?;
+    }
+    execute!(stdout, ResetColor)?;
+    stdout.flush()?;
+
+    if event::poll(Duration::from_millis(100))? {
+        if let Event::Key(KeyEvent { code, modifiers, .. }) = event::read()? {
+            let plain: Vec<String> = zoom.history.iter().map(|l| visible_text(l)).collect();
+            return Ok(zoom.handle_key(code, modifiers, &plain, height));
+        }
+    }
+    Ok(ZoomResult::Continue)
+}
+
+/// Persist `view` for the next run. Returns a status-bar message only when
+/// that fails; the view still applies to this run either way.
+fn save_view(view: &TopView, path: Option<&Path>) -> Option<String> {
+    let path = path?;
+    view.save_to(path)
+        .err()
+        .map(|e| format!("could not save view to {}: {}", path.display(), e))
+}
+
 /// Push `text` (followed by a carriage return) to the named session via
 /// SendInput. Mirrors `amux send` semantics — `\r` is what the TTY line
 /// discipline turns into a real newline. Errors are returned to the
@@ -721,6 +1635,8 @@ fn send_to_session(name: &str, text: &str) -> anyhow::Result<()> {
         name: name.to_string(),
         data,
         newline: false,
+        pacing: None,
+        wait_written: false,
     })?;
     match resp {
         DaemonMessage::InputSent => Ok(()),
@@ -729,6 +1645,206 @@ fn send_to_session(name: &str, text: &str) -> anyhow::Result<()> {
     }
 }
 
+/// Sessions synchronized input goes to: the marked ones, or every live
+/// session when nothing is marked.
+fn sync_targets(sessions: &[SessionInfo], marked: &HashSet<String>) -> Vec<String> {
+    if marked.is_empty() {
+        sessions
+            .iter()
+            .filter(|s| s.alive)
+            .map(|s| s.name.clone())
+            .collect()
+    } else {
+        sessions
+            .iter()
+            .filter(|s| marked.contains(&s.name))
+            .map(|s| s.name.clone())
+            .collect()
+    }
+}
+
+/// Sessions a dashboard action applies to: the marked ones, or the
+/// highlighted one when nothing is marked. Actions that need a running
+/// program skip dead sessions.
+fn action_targets(
+    sessions: &[SessionInfo],
+    marked: &HashSet<String>,
+    selected: usize,
+    action: SessionAction,
+) -> Vec<String> {
+    let chosen: Vec<&SessionInfo> = if marked.is_empty() {
+        sessions.get(selected).into_iter().collect()
+    } else {
+        sessions.iter().filter(|s| marked.contains(&s.name)).collect()
+    };
+    chosen
+        .into_iter()
+        .filter(|s| s.alive || !action.needs_live_session())
+        .map(|s| s.name.clone())
+        .collect()
+}
+
+/// `p` pauses unless every target is already paused, in which case it
+/// resumes them.
+fn pause_action(sessions: &[SessionInfo], targets: &[String]) -> SessionAction {
+    let all_paused = sessions
+        .iter()
+        .filter(|s| targets.contains(&s.name))
+        .all(|s| s.paused);
+    if all_paused {
+        SessionAction::Resume
+    } else {
+        SessionAction::Pause
+    }
+}
+
+/// Short description of a target list for prompts and flashes.
+fn describe_targets(targets: &[String]) -> String {
+    if targets.len() <= 3 {
+        targets.join(", ")
+    } else {
+        format!("{} sessions ({}, …)", targets.len(), targets[..2].join(", "))
+    }
+}
+
+/// The status-bar question asked before running `action`.
+fn confirm_prompt(action: SessionAction, targets: &[String]) -> String {
+    format!("{} {}? [y/N]", action.verb(), describe_targets(targets))
+}
+
+/// Only an explicit `y` confirms; any other key cancels.
+fn confirms(code: KeyCode) -> bool {
+    matches!(code, KeyCode::Char('y') | KeyCode::Char('Y'))
+}
+
+/// Apply `action` to one session.
+fn apply_action(action: SessionAction, name: &str) -> anyhow::Result<()> {
+    let name = name.to_string();
+    let msg = match action {
+        SessionAction::Kill => ClientMessage::KillSession {
+            name,
+            signal: None,
+            grace_ms: None,
+        },
+        SessionAction::Respawn => ClientMessage::RespawnSession {
+            name,
+            command: Vec::new(),
+            cwd: None,
+            env: None,
+        },
+        SessionAction::Interrupt => ClientMessage::SendInput {
+            name,
+            data: vec![0x03],
+            newline: false,
+            pacing: None,
+            wait_written: false,
+        },
+        SessionAction::Pause | SessionAction::Resume => ClientMessage::SignalSession {
+            name,
+            signal: if action == SessionAction::Pause {
+                nix::sys::signal::Signal::SIGSTOP as i32
+            } else {
+                nix::sys::signal::Signal::SIGCONT as i32
+            },
+            target: SignalTarget::Tree,
+        },
+    };
+    match client::request(&msg)? {
+        DaemonMessage::Error(e) => anyhow::bail!(e),
+        _ => Ok(()),
+    }
+}
+
+/// Apply `action` to every target concurrently (kills wait out their grace
+/// period) and summarize the outcome for the status bar.
+fn run_action(action: SessionAction, targets: &[String]) -> String {
+    let failed: Vec<String> = std::thread::scope(|scope| {
+        let handles: Vec<_> = targets
+            .iter()
+            .map(|name| (name, scope.spawn(move || apply_action(action, name))))
+            .collect();
+        handles
+            .into_iter()
+            .filter_map(|(name, handle)| match handle.join() {
+                Ok(Ok(())) => None,
+                Ok(Err(e)) => Some(format!("{}: {}", name, e)),
+                Err(_) => Some(format!("{}: panicked", name)),
+            })
+            .collect()
+    });
+    if failed.is_empty() {
+        format!("{}: {}", action.verb(), describe_targets(targets))
+    } else {
+        format!("{} failed for {}", action.verb(), failed.join("; "))
+    }
+}
+
+/// Rename a session via `RenameSession`.
+fn rename_session(name: &str, new_name: &str) -> anyhow::Result<()> {
+    match client::request(&ClientMessage::RenameSession {
+        name: name.to_string(),
+        new_name: new_name.to_string(),
+    })? {
+        DaemonMessage::Ok => Ok(()),
+        DaemonMessage::Error(e) => anyhow::bail!(e),
+        _ => anyhow::bail!("unexpected response"),
+    }
+}
+
+/// The `SendKeys` name of a key press: `C-c`, `M-x`, `S-Up`, `Tab`,
+/// `F5`, or the character itself. `None` for keys with no name.
+fn key_name(code: KeyCode, modifiers: KeyModifiers) -> Option<String> {
+    let mut prefix = String::new();
+    if modifiers.contains(KeyModifiers::CONTROL) {
+        prefix.push_str("C-");
+    }
+    if modifiers.contains(KeyModifiers::ALT) {
+        prefix.push_str("M-");
+    }
+    let base = match code {
+        // Shift is already in the character; a plain one is literal text.
+        KeyCode::Char(c) if prefix.is_empty() => return Some(c.to_string()),
+        KeyCode::Char(c) => c.to_string(),
+        KeyCode::BackTab => return Some(format!("{}BTab", prefix)),
+        KeyCode::Enter => "Enter".to_string(),
+        KeyCode::Tab => "Tab".to_string(),
+        KeyCode::Esc => "Escape".to_string(),
+        KeyCode::Backspace => "BSpace".to_string(),
+        KeyCode::Up => "Up".to_string(),
+        KeyCode::Down => "Down".to_string(),
+        KeyCode::Left => "Left".to_string(),
+        KeyCode::Right => "Right".to_string(),
+        KeyCode::Home => "Home".to_string(),
+        KeyCode::End => "End".to_string(),
+        KeyCode::PageUp => "PageUp".to_string(),
+        KeyCode::PageDown => "PageDown".to_string(),
+        KeyCode::Insert => "Insert".to_string(),
+        KeyCode::Delete => "Delete".to_string(),
+        KeyCode::F(n) if (1..=12).contains(&n) => format!("F{}", n),
+        _ => return None,
+    };
+    if modifiers.contains(KeyModifiers::SHIFT) {
+        prefix.push_str("S-");
+    }
+    Some(format!("{}{}", prefix, base))
+}
+
+/// Mirror one key press to `names`; the daemon encodes it for each
+/// session's terminal modes.
+fn mirror_key(names: Vec<String>, key: String) -> anyhow::Result<Vec<InputDelivery>> {
+    let resp = client::request(&ClientMessage::BroadcastInput {
+        target: SessionSelector::Names(names),
+        data: Vec::new(),
+        newline: false,
+        keys: vec![key],
+    })?;
+    match resp {
+        DaemonMessage::BroadcastResult(results) => Ok(results),
+        DaemonMessage::Error(e) => anyhow::bail!(e),
+        _ => anyhow::bail!("unexpected response"),
+    }
+}
+
 fn fetch_sessions() -> anyhow::Result<Vec<SessionInfo>> {
     let resp = client::request(&ClientMessage::ListSessions)?;
     match resp {
@@ -792,6 +1908,14 @@ mod tests {
             cols: 80,
             attach_count: 0,
             respawn_count: 0,
+            limits: None,
+            usage: None,
+            cpu_recent: Vec::new(),
+            paused: false,
+            title: String::new(),
+            notifications: Vec::new(),
+            needs_attention: false,
+            attention_count: 0,
         }
     }
 
@@ -844,6 +1968,256 @@ mod tests {
         assert!(sessions.is_empty());
     }
 
+    #[test]
+    fn test_sort_sessions_by_keys() {
+        let sessions = vec![
+            make_session("a", true, 100, 5, None),
+            make_session("b", true, 300, 50, None),
+            make_session("c", false, 200, 500, Some(0)),
+            make_session("d", false, 50, 10, Some(2)),
+        ];
+        let mut trackers = HashMap::new();
+        let mut busy = ActivityTracker::new(0);
+        busy.record(4096);
+        trackers.insert("a".to_string(), busy);
+        let order = |key: SortKey| {
+            let mut sorted = sessions.clone();
+            sort_sessions_by(&mut sorted, key, &trackers);
+            sorted.into_iter().map(|s| s.name).collect::<Vec<_>>()
+        };
+        // Live sessions always lead.
+        assert_eq!(order(SortKey::Name), ["a", "b", "c", "d"]);
+        assert_eq!(order(SortKey::Uptime), ["b", "a", "c", "d"]);
+        assert_eq!(order(SortKey::Idle), ["b", "a", "c", "d"]);
+        assert_eq!(order(SortKey::OutputRate), ["a", "b", "c", "d"]);
+        assert_eq!(order(SortKey::ExitCode), ["a", "b", "d", "c"]);
+    }
+
+    #[test]
+    fn test_sort_key_cycles_through_all() {
+        let mut key = SortKey::default();
+        let mut seen = Vec::new();
+        for _ in 0..5 {
+            seen.push(key.label());
+            key = key.next();
+        }
+        assert_eq!(key, SortKey::Name);
+        assert_eq!(seen, ["name", "uptime", "idle", "output", "exit"]);
+    }
+
+    #[test]
+    fn test_view_filters_name_command_and_title() {
+        let mut build = make_session("build-1", true, 10, 0, None);
+        build.command = "cargo build".to_string();
+        let mut review = make_session("agent-2", true, 10, 0, None);
+        review.title = "Reviewing PR".to_string();
+        let dead = make_session("old", false, 10, 0, Some(1));
+        let sessions = vec![build, review, dead];
+        let names = |view: &TopView| {
+            view.apply(sessions.clone(), &HashMap::new())
+                .into_iter()
+                .map(|s| s.name)
+                .collect::<Vec<_>>()
+        };
+
+        let mut view = TopView::default();
+        assert_eq!(names(&view), ["agent-2", "build-1", "old"]);
+        view.filter = "CARGO".to_string();
+        assert_eq!(names(&view), ["build-1"]);
+        view.filter = "review agent".to_string();
+        assert_eq!(names(&view), ["agent-2"]);
+        view.filter = "review build".to_string();
+        assert!(names(&view).is_empty());
+        view.filter.clear();
+        view.hide_dead = true;
+        assert_eq!(names(&view), ["agent-2", "build-1"]);
+        assert_eq!(view.label(1), "sort: name  hiding dead  1 hidden");
+    }
+
+    #[test]
+    fn test_view_round_trips_through_file() {
+        let dir = std::env::temp_dir().join(format!("amux-top-view-{}", std::process::id()));
+        let path = dir.join("nested").join("top-view.json");
+        assert_eq!(TopView::load_from(&path), TopView::default());
+
+        let view = TopView {
+            sort: SortKey::OutputRate,
+            filter: "worker".to_string(),
+            hide_dead: true,
+        };
+        view.save_to(&path).unwrap();
+        assert_eq!(TopView::load_from(&path), view);
+
+        // A corrupt file falls back to the default view.
+        std::fs::write(&path, b"{not json").unwrap();
+        assert_eq!(TopView::load_from(&path), TopView::default());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_handle_key_view_keys_work_on_empty_table() {
+        let mut selected = 0;
+        for (key, expected) in [
+            ('/', "filter"),
+            ('o', "sort"),
+            ('h', "hide"),
+        ] {
+            let action = handle_key(KeyCode::Char(key), KeyModifiers::NONE, &[], &mut selected);
+            let got = match action {
+                TopAction::EnterFilter => "filter",
+                TopAction::CycleSort => "sort",
+                TopAction::ToggleHideDead => "hide",
+                _ => "other",
+            };
+            assert_eq!(got, expected);
+        }
+    }
+
+    fn zoom_lines(n: usize) -> Vec<String> {
+        (0..n).map(|i| format!("line {}", i)).collect()
+    }
+
+    #[test]
+    fn test_zoom_scrolls_within_history() {
+        let mut zoom = Zoom::new("a".to_string());
+        zoom.refresh(100);
+        assert_eq!(zoom.window(10), (90, 100));
+
+        let lines = zoom_lines(100);
+        zoom.handle_key(KeyCode::PageUp, KeyModifiers::NONE, &lines, 10);
+        assert_eq!(zoom.window(10), (81, 91));
+        zoom.handle_key(KeyCode::Char('k'), KeyModifiers::NONE, &lines, 10);
+        assert_eq!(zoom.window(10), (80, 90));
+        zoom.handle_key(KeyCode::Char('g'), KeyModifiers::NONE, &lines, 10);
+        assert_eq!(zoom.window(10), (0, 10));
+        zoom.handle_key(KeyCode::PageUp, KeyModifiers::NONE, &lines, 10);
+        assert_eq!(zoom.window(10), (0, 10), "clamped at the oldest line");
+        zoom.handle_key(KeyCode::End, KeyModifiers::NONE, &lines, 10);
+        assert_eq!(zoom.offset, 0);
+
+        // Short history: everything fits, nothing to scroll.
+        zoom.refresh(3);
+        zoom.handle_key(KeyCode::PageUp, KeyModifiers::NONE, &lines[..3], 10);
+        assert_eq!(zoom.window(10), (0, 3));
+        assert_eq!(
+            zoom.handle_key(KeyCode::Char('q'), KeyModifiers::NONE, &lines, 10),
+            ZoomResult::Close
+        );
+    }
+
+    #[test]
+    fn test_zoom_holds_position_while_output_arrives() {
+        let mut zoom = Zoom::new("a".to_string());
+        zoom.refresh(50);
+        zoom.scroll_up(20, 10);
+        let before = zoom.window(10);
+        zoom.refresh(55);
+        assert_eq!(zoom.window(10), before);
+
+        // Following (offset 0) keeps showing the newest lines.
+        zoom.offset = 0;
+        zoom.refresh(60);
+        assert_eq!(zoom.window(10), (50, 60));
+    }
+
+    #[test]
+    fn test_zoom_merges_tail_into_history() {
+        let mut zoom = Zoom::new("a".to_string());
+        zoom.history = zoom_lines(100);
+
+        // Three new lines, the tail overlapping the rest.
+        zoom.merge_tail(zoom_lines(103)[83..].to_vec());
+        assert_eq!(zoom.history, zoom_lines(103));
+
+        // Nothing new, and the last line redrawn in place.
+        zoom.merge_tail(zoom_lines(103)[83..].to_vec());
+        assert_eq!(zoom.history, zoom_lines(103));
+        let mut redrawn = zoom_lines(103)[93..].to_vec();
+        redrawn[9] = "line 102!".to_string();
+        zoom.merge_tail(redrawn);
+        assert_eq!(zoom.history.len(), 103);
+        assert_eq!(zoom.history[102], "line 102!");
+
+        // More output than the tail holds: the newest lines still end up
+        // at the bottom.
+        zoom.merge_tail(zoom_lines(500)[480..].to_vec());
+        assert_eq!(zoom.history.last().map(String::as_str), Some("line 499"));
+    }
+
+    #[test]
+    fn test_zoom_refetches_only_to_look_back() {
+        let lines = zoom_lines(100);
+        let mut zoom = Zoom::new("a".to_string());
+        assert!(zoom.stale, "the first draw captures everything");
+        zoom.stale = false;
+        zoom.refresh(100);
+
+        zoom.handle_key(KeyCode::End, KeyModifiers::NONE, &lines, 10);
+        zoom.handle_key(KeyCode::Char('j'), KeyModifiers::NONE, &lines, 10);
+        assert!(!zoom.stale, "following needs only the tail");
+
+        zoom.handle_key(KeyCode::PageUp, KeyModifiers::NONE, &lines, 10);
+        assert!(zoom.stale);
+        zoom.stale = false;
+        zoom.handle_key(KeyCode::PageUp, KeyModifiers::NONE, &lines, 10);
+        assert!(!zoom.stale, "already scrolled back on a full capture");
+
+        zoom.handle_key(KeyCode::Char('/'), KeyModifiers::NONE, &lines, 10);
+        assert!(zoom.stale, "a search covers the whole history");
+    }
+
+    #[test]
+    fn test_zoom_search_walks_matches() {
+        let mut lines = zoom_lines(100);
+        lines[20] = "error: first".to_string();
+        lines[70] = "ERROR: second".to_string();
+        let mut zoom = Zoom::new("a".to_string());
+        zoom.refresh(lines.len());
+
+        zoom.handle_key(KeyCode::Char('/'), KeyModifiers::NONE, &lines, 10);
+        for c in "error".chars() {
+            zoom.handle_key(KeyCode::Char(c), KeyModifiers::NONE, &lines, 10);
+        }
+        assert_eq!(zoom.search_input.as_deref(), Some("error"));
+        zoom.handle_key(KeyCode::Enter, KeyModifiers::NONE, &lines, 10);
+        // Newest match first, scrolled to the top of the view.
+        assert_eq!(zoom.matched, Some(70));
+        assert_eq!(zoom.window(10), (70, 80));
+
+        zoom.handle_key(KeyCode::Char('n'), KeyModifiers::NONE, &lines, 10);
+        assert_eq!(zoom.matched, Some(20));
+        assert_eq!(zoom.window(10), (20, 30));
+
+        zoom.handle_key(KeyCode::Char('n'), KeyModifiers::NONE, &lines, 10);
+        assert_eq!(zoom.matched, Some(20));
+        assert!(zoom.message.as_deref().unwrap().contains("not found"));
+
+        zoom.handle_key(KeyCode::Char('N'), KeyModifiers::NONE, &lines, 10);
+        assert_eq!(zoom.matched, Some(70));
+        assert!(zoom.message.is_none());
+    }
+
+    #[test]
+    fn test_visible_text_strips_sgr_keeps_unicode() {
+        assert_eq!(visible_text("\x1b[1;31mérror\x1b[0m ✓"), "érror ✓");
+        assert_eq!(visible_text("plain"), "plain");
+    }
+
+    #[test]
+    fn test_handle_key_z_zooms_selected() {
+        let sessions = vec![make_session("a", true, 1, 1, None), make_session("b", true, 1, 1, None)];
+        let mut selected = 1;
+        match handle_key(KeyCode::Char('z'), KeyModifiers::NONE, &sessions, &mut selected) {
+            TopAction::Zoom(name) => assert_eq!(name, "b"),
+            _ => panic!("expected Zoom"),
+        }
+        let mut selected = 0;
+        assert!(matches!(
+            handle_key(KeyCode::Char('z'), KeyModifiers::NONE, &[], &mut selected),
+            TopAction::Continue
+        ));
+    }
+
     #[test]
     fn test_truncate_short() {
         assert_eq!(truncate("hello", 10), "hello");
@@ -893,7 +2267,7 @@ mod tests {
     fn test_render_frame_header() {
         let sessions: Vec<SessionInfo> = vec![];
         let trackers = HashMap::new();
-        let frame = render_frame(&sessions, 120, &trackers);
+        let frame = render_frame(&sessions, 120, &trackers, &HashSet::new());
         assert_eq!(frame.len(), 1); // just header
         assert!(frame[0].contains("NAME"));
         assert!(frame[0].contains("STATUS"));
@@ -904,6 +2278,45 @@ mod tests {
         assert!(frame[0].contains("COMMAND"));
     }
 
+    #[test]
+    fn test_render_frame_usage_columns() {
+        let mut busy = make_session("busy", true, 60, 0, None);
+        busy.usage = Some(crate::protocol::messages::ResourceSample {
+            unix_ms: 0,
+            cpu_percent: 87.5,
+            rss_bytes: 3 << 20,
+            threads: 4,
+            fds: 10,
+            children: 2,
+        });
+        busy.cpu_recent = vec![0.0, 100.0];
+        let fresh = make_session("fresh", true, 1, 0, None);
+        let sessions = vec![busy, fresh];
+        let frame = render_frame(&sessions, 140, &HashMap::new(), &HashSet::new());
+        assert!(frame[0].contains("CPU%") && frame[0].contains("RSS"));
+        assert!(frame[1].contains(" 87.5 "), "{:?}", frame[1]);
+        assert!(frame[1].contains("3.0M"), "{:?}", frame[1]);
+        assert!(frame[1].contains("▁▁▁▁▁▁▁▁▁█"), "{:?}", frame[1]);
+        // No sample yet: placeholders rather than zeros.
+        assert!(frame[2].contains("     -       -"), "{:?}", frame[2]);
+    }
+
+    #[test]
+    fn test_render_frame_marks_attention() {
+        let mut agent = make_session("agent", true, 60, 0, None);
+        agent.needs_attention = true;
+        let frame = render_frame(&[agent], 140, &HashMap::new(), &HashSet::new());
+        assert!(frame[1].starts_with("agent            attn "), "{:?}", frame[1]);
+    }
+
+    #[test]
+    fn test_render_frame_shows_title() {
+        let mut agent = make_session("agent", true, 60, 0, None);
+        agent.title = "needs input".to_string();
+        let frame = render_frame(&[agent], 140, &HashMap::new(), &HashSet::new());
+        assert!(frame[1].ends_with("[needs input] bash"), "{:?}", frame[1]);
+    }
+
     #[test]
     fn test_render_frame_rows() {
         let sessions = vec![
@@ -911,7 +2324,7 @@ mod tests {
             make_session("builder", false, 600, 300, Some(1)),
         ];
         let trackers = HashMap::new();
-        let frame = render_frame(&sessions, 120, &trackers);
+        let frame = render_frame(&sessions, 120, &trackers, &HashSet::new());
         assert_eq!(frame.len(), 3); // header + 2 rows
         assert!(frame[1].contains("worker-1"));
         assert!(frame[1].contains("alive"));
@@ -921,6 +2334,15 @@ mod tests {
         assert!(frame[2].contains("1")); // exit code
     }
 
+    #[test]
+    fn test_render_frame_paused_status() {
+        let mut paused = make_session("held", true, 10, 1, None);
+        paused.paused = true;
+        let frame = render_frame(&[paused], 120, &HashMap::new(), &HashSet::new());
+        assert!(frame[1].contains("paused"), "{:?}", frame[1]);
+        assert!(!frame[1].contains("alive"), "{:?}", frame[1]);
+    }
+
     #[test]
     fn test_activity_tracker_idle() {
         let tracker = ActivityTracker::new(0);
@@ -989,7 +2411,7 @@ mod tests {
         t.record(2000);
         t.record(5000);
         trackers.insert("active".to_string(), t);
-        let frame = render_frame(&sessions, 120, &trackers);
+        let frame = render_frame(&sessions, 120, &trackers, &HashSet::new());
         // Row should contain sparkline characters
         assert!(frame[1].contains('▁') || frame[1].contains('█') || frame[1].contains('▃'));
     }
@@ -999,7 +2421,11 @@ mod tests {
         use clap::Parser;
         let cli = crate::cli::Cli::try_parse_from(["amux", "top"]).unwrap();
         match cli.command.unwrap() {
-            crate::cli::Command::Top { once } => assert!(!once),
+            crate::cli::Command::Top { once, json, interval } => {
+                assert!(!once);
+                assert!(!json);
+                assert_eq!(interval, None);
+            }
             other => panic!("expected Top, got {:?}", other),
         }
     }
@@ -1009,11 +2435,57 @@ mod tests {
         use clap::Parser;
         let cli = crate::cli::Cli::try_parse_from(["amux", "top", "--once"]).unwrap();
         match cli.command.unwrap() {
-            crate::cli::Command::Top { once } => assert!(once),
+            crate::cli::Command::Top { once, .. } => assert!(once),
             other => panic!("expected Top, got {:?}", other),
         }
     }
 
+    #[test]
+    fn test_cli_top_json_interval_parses() {
+        use clap::Parser;
+        let cli =
+            crate::cli::Cli::try_parse_from(["amux", "top", "--json", "--interval", "2"]).unwrap();
+        match cli.command.unwrap() {
+            crate::cli::Command::Top { json, interval, .. } => {
+                assert!(json);
+                assert_eq!(interval, Some(2000));
+            }
+            other => panic!("expected Top, got {:?}", other),
+        }
+        // The interval only paces the JSON stream.
+        assert!(crate::cli::Cli::try_parse_from(["amux", "top", "--interval", "2"]).is_err());
+    }
+
+    #[test]
+    fn test_activity_tracker_bytes_per_sec() {
+        let mut t = ActivityTracker::new(1000);
+        t.record(3000);
+        assert_eq!(t.bytes_per_sec(Duration::from_secs(2)), 1000.0);
+        t.record(3000);
+        assert_eq!(t.bytes_per_sec(Duration::from_secs(2)), 0.0);
+        assert_eq!(t.bytes_per_sec(Duration::ZERO), 0.0);
+    }
+
+    #[test]
+    fn test_top_json_document() {
+        let sessions = vec![
+            make_session("a", true, 10, 0, None),
+            make_session("b", false, 10, 0, Some(1)),
+        ];
+        let rates = HashMap::from([("a".to_string(), 512.0)]);
+        let doc = top_json(&sessions, &rates, 1234);
+        assert_eq!(doc["unix_ms"], 1234);
+        assert_eq!(doc["total"], 2);
+        assert_eq!(doc["alive"], 1);
+        assert_eq!(doc["dead"], 1);
+        let rows = doc["sessions"].as_array().unwrap();
+        assert_eq!(rows[0]["name"], "a");
+        assert_eq!(rows[0]["bytes_per_sec"], 512.0);
+        assert_eq!(rows[0]["uptime_secs"], 10);
+        assert_eq!(rows[1]["exit_code"], 1);
+        assert!(rows[1]["bytes_per_sec"].is_null());
+    }
+
     #[test]
     fn test_render_snapshot_empty() {
         let sessions: Vec<SessionInfo> = vec![];
@@ -1270,6 +2742,158 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn test_handle_key_space_toggles_mark_on_selected() {
+        let sessions = vec![
+            make_session("worker-1", true, 10, 1, None),
+            make_session("worker-2", true, 10, 1, None),
+        ];
+        let mut sel = 1;
+        match handle_key(KeyCode::Char(' '), KeyModifiers::NONE, &sessions, &mut sel) {
+            TopAction::ToggleMark(name) => assert_eq!(name, "worker-2"),
+            _ => panic!("expected ToggleMark"),
+        }
+    }
+
+    #[test]
+    fn test_handle_key_shift_s_enters_sync_input() {
+        let sessions = vec![make_session("worker-1", true, 10, 1, None)];
+        let mut sel = 0;
+        assert!(matches!(
+            handle_key(KeyCode::Char('S'), KeyModifiers::SHIFT, &sessions, &mut sel),
+            TopAction::EnterSyncInput
+        ));
+    }
+
+    #[test]
+    fn test_key_name_matches_send_keys_names() {
+        let name = |code, mods| key_name(code, mods);
+        assert_eq!(name(KeyCode::Char('a'), KeyModifiers::NONE).as_deref(), Some("a"));
+        assert_eq!(name(KeyCode::Char('A'), KeyModifiers::SHIFT).as_deref(), Some("A"));
+        assert_eq!(name(KeyCode::Char('c'), KeyModifiers::CONTROL).as_deref(), Some("C-c"));
+        assert_eq!(name(KeyCode::Char('x'), KeyModifiers::ALT).as_deref(), Some("M-x"));
+        assert_eq!(name(KeyCode::Up, KeyModifiers::NONE).as_deref(), Some("Up"));
+        assert_eq!(name(KeyCode::Up, KeyModifiers::SHIFT).as_deref(), Some("S-Up"));
+        assert_eq!(name(KeyCode::BackTab, KeyModifiers::SHIFT).as_deref(), Some("BTab"));
+        assert_eq!(name(KeyCode::F(5), KeyModifiers::NONE).as_deref(), Some("F5"));
+        assert_eq!(name(KeyCode::Null, KeyModifiers::NONE), None);
+        for key in ["a", "C-c", "M-x", "S-Up", "BTab", "Enter", "F5"] {
+            let encoded = crate::daemon::keys::encode_keys(&[key.to_string()], false);
+            assert!(!encoded.is_empty(), "{} encodes to nothing", key);
+        }
+    }
+
+    #[test]
+    fn test_sync_targets_prefers_marked_then_all_alive() {
+        let sessions = vec![
+            make_session("a", true, 10, 1, None),
+            make_session("b", true, 10, 1, None),
+            make_session("dead", false, 10, 1, Some(0)),
+        ];
+        let mut marked = HashSet::new();
+        assert_eq!(sync_targets(&sessions, &marked), vec!["a", "b"]);
+        marked.insert("b".to_string());
+        assert_eq!(sync_targets(&sessions, &marked), vec!["b"]);
+    }
+
+    #[test]
+    fn test_handle_key_session_actions() {
+        let sessions = vec![
+            make_session("worker-1", true, 10, 1, None),
+            make_session("worker-2", true, 10, 1, None),
+        ];
+        let mut sel = 1;
+        let mut key = |c: char| handle_key(KeyCode::Char(c), KeyModifiers::NONE, &sessions, &mut sel);
+        assert!(matches!(key('x'), TopAction::Confirm(SessionAction::Kill)));
+        assert!(matches!(key('R'), TopAction::Confirm(SessionAction::Respawn)));
+        assert!(matches!(key('C'), TopAction::Confirm(SessionAction::Interrupt)));
+        assert!(matches!(key('p'), TopAction::TogglePause));
+        match key('r') {
+            TopAction::EnterRename(name) => assert_eq!(name, "worker-2"),
+            _ => panic!("expected EnterRename"),
+        }
+        // Ctrl+C still quits rather than interrupting the session.
+        assert!(matches!(
+            handle_key(KeyCode::Char('c'), KeyModifiers::CONTROL, &sessions, &mut sel),
+            TopAction::Quit
+        ));
+        // No sessions, no actions.
+        let mut sel = 0;
+        for c in ['x', 'R', 'C', 'p', 'r'] {
+            assert!(matches!(
+                handle_key(KeyCode::Char(c), KeyModifiers::NONE, &[], &mut sel),
+                TopAction::Continue
+            ));
+        }
+    }
+
+    #[test]
+    fn test_action_targets_marked_then_selected() {
+        let sessions = vec![
+            make_session("a", true, 10, 1, None),
+            make_session("b", true, 10, 1, None),
+            make_session("dead", false, 10, 1, Some(0)),
+        ];
+        let mut marked = HashSet::new();
+        assert_eq!(action_targets(&sessions, &marked, 1, SessionAction::Kill), vec!["b"]);
+        // Dead sessions can be respawned but not killed.
+        assert!(action_targets(&sessions, &marked, 2, SessionAction::Kill).is_empty());
+        assert_eq!(
+            action_targets(&sessions, &marked, 2, SessionAction::Respawn),
+            vec!["dead"]
+        );
+        marked.insert("a".to_string());
+        marked.insert("dead".to_string());
+        assert_eq!(action_targets(&sessions, &marked, 1, SessionAction::Interrupt), vec!["a"]);
+        assert_eq!(
+            action_targets(&sessions, &marked, 1, SessionAction::Respawn),
+            vec!["a", "dead"]
+        );
+    }
+
+    #[test]
+    fn test_pause_toggles_on_target_state() {
+        let mut sessions = vec![
+            make_session("a", true, 10, 1, None),
+            make_session("b", true, 10, 1, None),
+        ];
+        sessions[0].paused = true;
+        let both = vec!["a".to_string(), "b".to_string()];
+        assert_eq!(pause_action(&sessions, &both), SessionAction::Pause);
+        assert_eq!(pause_action(&sessions, &both[..1]), SessionAction::Resume);
+    }
+
+    #[test]
+    fn test_confirm_prompt_and_keys() {
+        let names: Vec<String> = ["a", "b", "c", "d"].iter().map(|s| s.to_string()).collect();
+        assert_eq!(confirm_prompt(SessionAction::Kill, &names[..1]), "kill a? [y/N]");
+        assert_eq!(
+            confirm_prompt(SessionAction::Interrupt, &names[..2]),
+            "send Ctrl+C to a, b? [y/N]"
+        );
+        assert_eq!(
+            confirm_prompt(SessionAction::Respawn, &names),
+            "respawn 4 sessions (a, b, …)? [y/N]"
+        );
+        assert!(confirms(KeyCode::Char('y')));
+        assert!(confirms(KeyCode::Char('Y')));
+        assert!(!confirms(KeyCode::Enter));
+        assert!(!confirms(KeyCode::Char('n')));
+    }
+
+    #[test]
+    fn test_render_frame_marks_rows() {
+        let sessions = vec![
+            make_session("worker-1", true, 10, 1, None),
+            make_session("worker-2", true, 10, 1, None),
+        ];
+        let mut marked = HashSet::new();
+        marked.insert("worker-2".to_string());
+        let frame = render_frame(&sessions, 120, &HashMap::new(), &marked);
+        assert!(frame[1].starts_with("worker-1 "));
+        assert!(frame[2].starts_with("*worker-2 "));
+    }
+
     #[test]
     fn test_handle_key_i_empty_sessions() {
         // No session = nowhere to send to. 'i' must be a no-op so the
@@ -64,6 +64,28 @@ pub fn runtime_dir_for(instance: Option<&str>) -> PathBuf {
     }
 }
 
+/// Return the directory for state that should outlive the daemon, such as
+/// `amux top`'s saved view: `$XDG_STATE_HOME/amux`, or
+/// `~/.local/state/amux`. `None` when neither variable is set.
+pub fn state_dir() -> Option<PathBuf> {
+    state_dir_from(
+        std::env::var("XDG_STATE_HOME").ok().as_deref(),
+        std::env::var("HOME").ok().as_deref(),
+    )
+}
+
+/// Pure helper for `state_dir`. Relative or empty values are ignored, as
+/// the XDG spec requires.
+fn state_dir_from(xdg_state_home: Option<&str>, home: Option<&str>) -> Option<PathBuf> {
+    match (xdg_state_home, home) {
+        (Some(xdg), _) if xdg.starts_with('/') => Some(Path::new(xdg).join("amux")),
+        (_, Some(home)) if home.starts_with('/') => {
+            Some(Path::new(home).join(".local/state/amux"))
+        }
+        _ => None,
+    }
+}
+
 /// Derive an instance name from the current working directory.
 ///
 /// Two branches:
@@ -377,6 +399,23 @@ pub fn clear_stale_runtime_files() -> io::Result<()> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn state_dir_prefers_xdg_then_home() {
+        assert_eq!(
+            state_dir_from(Some("/x/state"), Some("/home/u")),
+            Some(PathBuf::from("/x/state/amux"))
+        );
+        assert_eq!(
+            state_dir_from(Some("relative"), Some("/home/u")),
+            Some(PathBuf::from("/home/u/.local/state/amux"))
+        );
+        assert_eq!(
+            state_dir_from(None, Some("/home/u")),
+            Some(PathBuf::from("/home/u/.local/state/amux"))
+        );
+        assert_eq!(state_dir_from(Some(""), None), None);
+    }
+
     #[test]
     fn pid_alive_reports_current_process() {
         assert!(pid_alive(std::process::id()));
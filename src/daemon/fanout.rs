@@ -0,0 +1,286 @@
+//! Per-client delivery of session output, and the slow-consumer policy.
+//!
+//! Every session broadcasts its PTY output on a bounded channel shared by
+//! all attached and following clients. A client that stops reading (a
+//! suspended terminal, a slow SSH link) must not hold the session back, so
+//! it is allowed to fall behind — but dropping chunks of a cursor-addressed
+//! byte stream leaves its screen corrupted. Instead, each client gets a
+//! `ClientFeed`: a pump task moves output into a per-client queue (sized
+//! by the client, `amux attach --buffer`), and once either the shared
+//! channel or that queue overflows, the chunks the client missed are
+//! replaced by a single `Feed::Resync` carrying a full repaint of the
+//! session's virtual terminal.
+//!
+//! Chunks are refcounted `Bytes`, so fanning one out to N clients copies
+//! nothing. A client with a backlog takes it in batches (`ClientFeed::batch`)
+//! that go out as one `Output` frame each, instead of a frame and a write
+//! per 4KB read.
+
+use std::sync::{Arc, Mutex as StdMutex};
+
+use bytes::Bytes;
+use tokio::sync::{broadcast, mpsc};
+use tokio::task::JoinHandle;
+
+use crate::daemon::metrics;
+use crate::daemon::vterm::VirtualTerminal;
+
+/// Output chunks queued per client when it doesn't ask for a size.
+pub const DEFAULT_CLIENT_BUFFER: usize = 256;
+
+/// Upper bound on a client-requested queue size.
+pub const MAX_CLIENT_BUFFER: usize = 64 * 1024;
+
+/// Most output bytes coalesced into one frame, well under the codec's
+/// frame limit.
+pub const MAX_BATCH_BYTES: usize = 256 * 1024;
+
+/// Most chunks coalesced into one frame, bounding the vectored write.
+pub const MAX_BATCH_CHUNKS: usize = 512;
+
+/// What the attach/follow loop should send next.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Feed {
+    Output(Bytes),
+    /// `skipped` chunks were dropped; `screen` redraws the current screen.
+    Resync { skipped: u64, screen: Vec<u8> },
+    /// The session's output channel closed.
+    Closed,
+}
+
+/// Clamp a client-requested queue size (`None` picks the default).
+pub fn client_buffer(requested: Option<u32>) -> usize {
+    requested
+        .map(|n| (n as usize).clamp(1, MAX_CLIENT_BUFFER))
+        .unwrap_or(DEFAULT_CLIENT_BUFFER)
+}
+
+/// One client's view of a session's output. Dropping it stops the pump.
+pub struct ClientFeed {
+    rx: mpsc::Receiver<Feed>,
+    /// An item `batch` took off the queue but couldn't merge.
+    held: Option<Feed>,
+    pump: JoinHandle<()>,
+}
+
+impl ClientFeed {
+    /// Start delivering `output` (a fresh subscription to the session's
+    /// broadcast) through a queue of `buffer` chunks.
+    pub fn new(
+        output: broadcast::Receiver<Bytes>,
+        vterm: Arc<StdMutex<VirtualTerminal>>,
+        buffer: usize,
+    ) -> Self {
+        let (tx, rx) = mpsc::channel(buffer.max(1));
+        let pump = tokio::spawn(pump(output, vterm, tx));
+        Self {
+            rx,
+            held: None,
+            pump,
+        }
+    }
+
+    /// Subscribe a new client to `output`, taking `snapshot` of the vterm
+    /// at the same instant: the snapshot reflects exactly the output before
+    /// the first chunk the feed delivers, with no gap or overlap.
+    pub fn subscribe(
+        output: &broadcast::Sender<Bytes>,
+        vterm: Arc<StdMutex<VirtualTerminal>>,
+        buffer: usize,
+        snapshot: impl FnOnce(&mut VirtualTerminal) -> Vec<u8>,
+    ) -> (Self, Vec<u8>) {
+        let (rx, initial) = {
+            // io_loop broadcasts under this lock (see `resync`).
+            let mut vt = vterm.lock();
+            let rx = output.subscribe();
+            let initial = vt.as_mut().map(|vt| snapshot(vt)).unwrap_or_default();
+            (rx, initial)
+        };
+        (Self::new(rx, vterm, buffer), initial)
+    }
+
+    /// Next item for the client. Cancel-safe.
+    pub async fn recv(&mut self) -> Feed {
+        if let Some(feed) = self.held.take() {
+            return feed;
+        }
+        self.rx.recv().await.unwrap_or(Feed::Closed)
+    }
+
+    /// `first` followed by the output already queued behind it, up to
+    /// `MAX_BATCH_BYTES`. A client that keeps up gets each chunk as it
+    /// comes; one with a backlog drains it in a few large frames.
+    pub fn batch(&mut self, first: Bytes) -> Vec<Bytes> {
+        let mut len = first.len();
+        let mut chunks = vec![first];
+        while len < MAX_BATCH_BYTES && chunks.len() < MAX_BATCH_CHUNKS && self.held.is_none() {
+            match self.rx.try_recv() {
+                Ok(Feed::Output(chunk)) => {
+                    len += chunk.len();
+                    chunks.push(chunk);
+                }
+                Ok(other) => self.held = Some(other),
+                Err(_) => break,
+            }
+        }
+        chunks
+    }
+}
+
+impl Drop for ClientFeed {
+    fn drop(&mut self) {
+        self.pump.abort();
+    }
+}
+
+async fn pump(
+    mut output: broadcast::Receiver<Bytes>,
+    vterm: Arc<StdMutex<VirtualTerminal>>,
+    tx: mpsc::Sender<Feed>,
+) {
+    loop {
+        let skipped = match output.recv().await {
+            Ok(data) => match tx.try_send(Feed::Output(data)) {
+                Ok(()) => continue,
+                Err(mpsc::error::TrySendError::Full(_)) => 1,
+                Err(mpsc::error::TrySendError::Closed(_)) => return,
+            },
+            Err(broadcast::error::RecvError::Lagged(n)) => n,
+            Err(broadcast::error::RecvError::Closed) => {
+                let _ = tx.send(Feed::Closed).await;
+                return;
+            }
+        };
+        // Wait for the client to make room; anything broadcast meanwhile
+        // is covered by the repaint.
+        let Ok(permit) = tx.reserve().await else { return };
+        let (feed, closed) = resync(&mut output, &vterm, skipped);
+        if let Feed::Resync { skipped, .. } = &feed {
+            tracing::warn!("client fell behind by {} chunks, resyncing", skipped);
+            metrics::global().record_lag(*skipped);
+        }
+        permit.send(feed);
+        if closed {
+            let _ = tx.send(Feed::Closed).await;
+            return;
+        }
+    }
+}
+
+/// Discard everything still queued for `output` and snapshot the screen it
+/// would have produced. The session broadcasts under the vterm lock, so
+/// with that lock held the queued chunks are exactly those already applied
+/// to the vterm. Also reports whether the channel closed.
+fn resync(
+    output: &mut broadcast::Receiver<Bytes>,
+    vterm: &StdMutex<VirtualTerminal>,
+    mut skipped: u64,
+) -> (Feed, bool) {
+    let vt = vterm.lock();
+    let mut closed = false;
+    loop {
+        match output.try_recv() {
+            Ok(_) => skipped += 1,
+            Err(broadcast::error::TryRecvError::Lagged(n)) => skipped += n,
+            Err(broadcast::error::TryRecvError::Empty) => break,
+            Err(broadcast::error::TryRecvError::Closed) => {
+                closed = true;
+                break;
+            }
+        }
+    }
+    let screen = vt.map(|vt| vt.repaint()).unwrap_or_default();
+    (Feed::Resync { skipped, screen }, closed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_client_buffer_clamps() {
+        assert_eq!(client_buffer(None), DEFAULT_CLIENT_BUFFER);
+        assert_eq!(client_buffer(Some(0)), 1);
+        assert_eq!(client_buffer(Some(16)), 16);
+        assert_eq!(client_buffer(Some(u32::MAX)), MAX_CLIENT_BUFFER);
+    }
+
+    /// Feed `data` to the vterm and broadcast it, the way io_loop does.
+    fn emit(
+        tx: &broadcast::Sender<Bytes>,
+        vterm: &StdMutex<VirtualTerminal>,
+        data: &[u8],
+    ) {
+        let mut vt = vterm.lock().unwrap();
+        vt.process(data);
+        let _ = tx.send(Bytes::copy_from_slice(data));
+    }
+
+    #[tokio::test]
+    async fn test_slow_client_gets_resync_not_gaps() {
+        let (tx, rx) = broadcast::channel(4);
+        let vterm = Arc::new(StdMutex::new(VirtualTerminal::new(5, 20)));
+        let mut feed = ClientFeed::new(rx, vterm.clone(), 2);
+
+        // The client reads nothing while 20 lines go by.
+        for i in 0..20 {
+            emit(&tx, &vterm, format!("line {}\r\n", i).as_bytes());
+            tokio::task::yield_now().await;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        let mut outputs = 0;
+        let screen = loop {
+            match tokio::time::timeout(Duration::from_secs(1), feed.recv())
+                .await
+                .expect("feed stalled")
+            {
+                Feed::Output(_) => outputs += 1,
+                Feed::Resync { skipped, screen } => {
+                    assert_eq!(outputs as u64 + skipped, 20);
+                    break screen;
+                }
+                Feed::Closed => panic!("unexpected close"),
+            }
+        };
+        // Replaying the repaint reproduces the session's screen.
+        let mut replay = VirtualTerminal::new(5, 20);
+        replay.process(&screen);
+        assert_eq!(
+            replay.rendered_screen(),
+            vterm.lock().unwrap().rendered_screen()
+        );
+        assert!(replay.rendered_screen().contains("line 19"));
+
+        // Once caught up, output flows normally again.
+        emit(&tx, &vterm, b"after\r\n");
+        assert_eq!(feed.recv().await, Feed::Output(Bytes::from_static(b"after\r\n")));
+        drop(tx);
+        assert_eq!(feed.recv().await, Feed::Closed);
+    }
+
+    #[tokio::test]
+    async fn test_backlog_is_batched() {
+        let (tx, rx) = broadcast::channel(64);
+        let vterm = Arc::new(StdMutex::new(VirtualTerminal::new(5, 20)));
+        let mut feed = ClientFeed::new(rx, vterm.clone(), 64);
+
+        for i in 0..10 {
+            emit(&tx, &vterm, format!("line {}\r\n", i).as_bytes());
+        }
+        drop(tx);
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        // Everything queued comes out in one batch, in order, and the close
+        // behind it is kept for the next `recv`.
+        let Feed::Output(first) = feed.recv().await else {
+            panic!("expected output");
+        };
+        let batch = feed.batch(first);
+        assert_eq!(batch.len(), 10);
+        let text = batch.concat();
+        assert!(text.starts_with(b"line 0\r\n") && text.ends_with(b"line 9\r\n"));
+        assert_eq!(feed.recv().await, Feed::Closed);
+    }
+}
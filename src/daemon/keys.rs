@@ -0,0 +1,288 @@
+//! Key-name grammar for `amux send --keys`.
+//!
+//! Each argument is either a key name (`Enter`, `Escape`, `Up`, `F5`,
+//! `C-c`, `M-x`, `C-S-Left`, ...) or, if it does not parse as one, literal
+//! text sent verbatim — the same rule tmux uses for `send-keys`, so
+//! `amux send -t w --keys C-c Escape Up Enter "text"` does what it reads as.
+//!
+//! Translation happens daemon-side because the bytes for the cursor keys
+//! depend on the session's DECCKM (application cursor) mode, which only the
+//! live vt100 parser knows: `Up` is `ESC [ A` normally but `ESC O A` once a
+//! program like vim or less has switched the terminal into application mode.
+
+/// Modifier bits, combined the way xterm encodes them in CSI parameters
+/// (`1 + bits`): `ESC [ 1 ; 5 A` is Ctrl+Up.
+const MOD_SHIFT: u8 = 1;
+const MOD_META: u8 = 2;
+const MOD_CTRL: u8 = 4;
+
+/// Translate a list of `--keys` arguments into the byte stream to write to
+/// the PTY. `app_cursor` is the session's current application-cursor mode.
+pub fn encode_keys(tokens: &[String], app_cursor: bool) -> Vec<u8> {
+    let mut out = Vec::new();
+    for token in tokens {
+        match encode_key(token, app_cursor) {
+            Some(bytes) => out.extend_from_slice(&bytes),
+            None => out.extend_from_slice(token.as_bytes()),
+        }
+    }
+    out
+}
+
+/// Encode a single key name, or `None` if `token` is not a key name (the
+/// caller then sends it as literal text).
+fn encode_key(token: &str, app_cursor: bool) -> Option<Vec<u8>> {
+    let (mut mods, mut base) = split_modifiers(token);
+    if base.is_empty() {
+        return None;
+    }
+    // tmux spells back-tab as its own key; it is just Shift+Tab.
+    if base.eq_ignore_ascii_case("btab") {
+        mods |= MOD_SHIFT;
+        base = "Tab";
+    }
+
+    let mut chars = base.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        // A bare single character is only a "key" when modified; a plain
+        // `a` falls through to literal text like any other word.
+        if mods == 0 {
+            return None;
+        }
+        return encode_char(c, mods);
+    }
+
+    let named = named_key(base)?;
+    let mut out = Vec::new();
+    match named {
+        Named::Byte(b) => {
+            if mods & MOD_META != 0 {
+                out.push(0x1b);
+            }
+            match (b, mods & !MOD_META) {
+                (b, 0) => out.push(b),
+                // S-Tab is the conventional back-tab.
+                (b'\t', MOD_SHIFT) => out.extend_from_slice(b"\x1b[Z"),
+                (b' ', MOD_CTRL) => out.push(0),
+                _ => return None,
+            }
+        }
+        Named::Cursor(final_byte) => {
+            if mods == 0 {
+                out.extend_from_slice(if app_cursor { b"\x1bO" } else { b"\x1b[" });
+                out.push(final_byte);
+            } else {
+                out.extend_from_slice(format!("\x1b[1;{}", 1 + mods).as_bytes());
+                out.push(final_byte);
+            }
+        }
+        Named::Ss3(final_byte) => {
+            if mods == 0 {
+                out.extend_from_slice(b"\x1bO");
+            } else {
+                out.extend_from_slice(format!("\x1b[1;{}", 1 + mods).as_bytes());
+            }
+            out.push(final_byte);
+        }
+        Named::Tilde(n) => {
+            if mods == 0 {
+                out.extend_from_slice(format!("\x1b[{}~", n).as_bytes());
+            } else {
+                out.extend_from_slice(format!("\x1b[{};{}~", n, 1 + mods).as_bytes());
+            }
+        }
+    }
+    Some(out)
+}
+
+/// Strip any leading `C-`, `M-` and `S-` prefixes (in any order), returning
+/// the accumulated modifier bits and the remaining key name.
+fn split_modifiers(token: &str) -> (u8, &str) {
+    let mut mods = 0;
+    let mut rest = token;
+    loop {
+        // Require something after the dash so `C-` alone stays literal and
+        // `M--` means Meta+'-'. Prefixes are upper-case only so ordinary
+        // words like `c-style` or `s-expr` are not mistaken for chords.
+        if rest.len() > 2 && rest.as_bytes()[1] == b'-' {
+            let bit = match rest.as_bytes()[0] {
+                b'C' => MOD_CTRL,
+                b'M' => MOD_META,
+                b'S' => MOD_SHIFT,
+                _ => break,
+            };
+            mods |= bit;
+            rest = &rest[2..];
+        } else {
+            break;
+        }
+    }
+    (mods, rest)
+}
+
+/// Encode a modified single character: `C-c` → 0x03, `M-x` → `ESC x`,
+/// `S-a` → `A`, `C-M-x` → `ESC 0x18`.
+fn encode_char(c: char, mods: u8) -> Option<Vec<u8>> {
+    let mut out = Vec::new();
+    if mods & MOD_META != 0 {
+        out.push(0x1b);
+    }
+    let c = if mods & MOD_SHIFT != 0 {
+        c.to_ascii_uppercase()
+    } else {
+        c
+    };
+    if mods & MOD_CTRL != 0 {
+        out.push(ctrl_byte(c)?);
+    } else {
+        let mut buf = [0u8; 4];
+        out.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+    }
+    Some(out)
+}
+
+/// The C0 control byte produced by Ctrl+`c`, or `None` if Ctrl has no
+/// conventional encoding for that character.
+fn ctrl_byte(c: char) -> Option<u8> {
+    match c {
+        'a'..='z' => Some(c as u8 - b'a' + 1),
+        '@' | 'A'..='Z' | '[' | '\\' | ']' | '^' | '_' => Some(c as u8 & 0x1f),
+        ' ' | '2' => Some(0),
+        '?' => Some(0x7f),
+        _ => None,
+    }
+}
+
+enum Named {
+    /// A key that produces a single byte (Enter, Tab, Escape, ...).
+    Byte(u8),
+    /// A cursor key whose unmodified form follows DECCKM:
+    /// `ESC [ x` normally, `ESC O x` in application cursor mode.
+    Cursor(u8),
+    /// F1–F4, always sent as `ESC O x` when unmodified.
+    Ss3(u8),
+    /// A `ESC [ n ~` key (PageUp, Delete, F5–F12, ...).
+    Tilde(u8),
+}
+
+/// Look up a key name. Matching is case-insensitive and accepts both the
+/// tmux spellings (`BSpace`, `PPage`, `DC`) and the long forms.
+fn named_key(name: &str) -> Option<Named> {
+    let lower = name.to_ascii_lowercase();
+    let key = match lower.as_str() {
+        "enter" | "return" | "cr" => Named::Byte(b'\r'),
+        "lf" => Named::Byte(b'\n'),
+        "tab" => Named::Byte(b'\t'),
+        "escape" | "esc" => Named::Byte(0x1b),
+        "space" => Named::Byte(b' '),
+        "bspace" | "backspace" | "bs" => Named::Byte(0x7f),
+        "up" => Named::Cursor(b'A'),
+        "down" => Named::Cursor(b'B'),
+        "right" => Named::Cursor(b'C'),
+        "left" => Named::Cursor(b'D'),
+        "home" => Named::Cursor(b'H'),
+        "end" => Named::Cursor(b'F'),
+        "insert" | "ic" => Named::Tilde(2),
+        "delete" | "dc" => Named::Tilde(3),
+        "pageup" | "ppage" | "pgup" => Named::Tilde(5),
+        "pagedown" | "npage" | "pgdn" => Named::Tilde(6),
+        "f1" => Named::Ss3(b'P'),
+        "f2" => Named::Ss3(b'Q'),
+        "f3" => Named::Ss3(b'R'),
+        "f4" => Named::Ss3(b'S'),
+        "f5" => Named::Tilde(15),
+        "f6" => Named::Tilde(17),
+        "f7" => Named::Tilde(18),
+        "f8" => Named::Tilde(19),
+        "f9" => Named::Tilde(20),
+        "f10" => Named::Tilde(21),
+        "f11" => Named::Tilde(23),
+        "f12" => Named::Tilde(24),
+        _ => return None,
+    };
+    Some(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(tokens: &[&str], app_cursor: bool) -> Vec<u8> {
+        let tokens: Vec<String> = tokens.iter().map(|s| s.to_string()).collect();
+        encode_keys(&tokens, app_cursor)
+    }
+
+    #[test]
+    fn test_request_example() {
+        assert_eq!(
+            keys(&["C-c", "Escape", "Up", "Enter", "text"], false),
+            b"\x03\x1b\x1b[A\rtext".to_vec()
+        );
+    }
+
+    #[test]
+    fn test_cursor_keys_follow_application_mode() {
+        assert_eq!(keys(&["Up"], false), b"\x1b[A");
+        assert_eq!(keys(&["Up"], true), b"\x1bOA");
+        assert_eq!(keys(&["Left"], true), b"\x1bOD");
+        assert_eq!(keys(&["Home"], false), b"\x1b[H");
+        assert_eq!(keys(&["End"], true), b"\x1bOF");
+        // Modified cursor keys use the CSI 1;m form in either mode.
+        assert_eq!(keys(&["C-Up"], true), b"\x1b[1;5A");
+        assert_eq!(keys(&["S-Right"], false), b"\x1b[1;2C");
+        assert_eq!(keys(&["C-S-Left"], false), b"\x1b[1;6D");
+    }
+
+    #[test]
+    fn test_control_chords() {
+        assert_eq!(keys(&["C-a"], false), vec![0x01]);
+        assert_eq!(keys(&["C-z"], false), vec![0x1a]);
+        assert_eq!(keys(&["C-C"], false), vec![0x03]);
+        assert_eq!(keys(&["C-["], false), vec![0x1b]);
+        assert_eq!(keys(&["C-@"], false), vec![0x00]);
+        assert_eq!(keys(&["C-Space"], false), vec![0x00]);
+        assert_eq!(keys(&["C-?"], false), vec![0x7f]);
+    }
+
+    #[test]
+    fn test_meta_prefixes_escape() {
+        assert_eq!(keys(&["M-x"], false), b"\x1bx");
+        assert_eq!(keys(&["M-C-x"], false), vec![0x1b, 0x18]);
+        assert_eq!(keys(&["M-Enter"], false), b"\x1b\r");
+        assert_eq!(keys(&["M--"], false), b"\x1b-");
+    }
+
+    #[test]
+    fn test_named_keys() {
+        assert_eq!(keys(&["Tab"], false), b"\t");
+        assert_eq!(keys(&["BTab"], false), b"\x1b[Z");
+        assert_eq!(keys(&["S-Tab"], false), b"\x1b[Z");
+        assert_eq!(keys(&["BSpace"], false), vec![0x7f]);
+        assert_eq!(keys(&["Space"], false), b" ");
+        assert_eq!(keys(&["PageUp"], false), b"\x1b[5~");
+        assert_eq!(keys(&["NPage"], false), b"\x1b[6~");
+        assert_eq!(keys(&["DC"], false), b"\x1b[3~");
+        assert_eq!(keys(&["F1"], false), b"\x1bOP");
+        assert_eq!(keys(&["F5"], false), b"\x1b[15~");
+        assert_eq!(keys(&["F12"], false), b"\x1b[24~");
+        assert_eq!(keys(&["C-F5"], false), b"\x1b[15;5~");
+        assert_eq!(keys(&["enter"], false), b"\r");
+        assert_eq!(keys(&["ESCAPE"], false), b"\x1b");
+    }
+
+    #[test]
+    fn test_unknown_tokens_are_literal() {
+        assert_eq!(keys(&["hello world"], false), b"hello world");
+        assert_eq!(keys(&["a"], false), b"a");
+        assert_eq!(keys(&["C-"], false), b"C-");
+        assert_eq!(keys(&["c-style"], false), b"c-style");
+        assert_eq!(keys(&["C-Bogus"], false), b"C-Bogus");
+        // Ctrl has no encoding for digits other than 2; keep it literal.
+        assert_eq!(keys(&["C-5"], false), b"C-5");
+    }
+
+    #[test]
+    fn test_tokens_are_not_space_joined() {
+        assert_eq!(keys(&["ls", "Space", "-la", "Enter"], false), b"ls -la\r");
+    }
+}
@@ -0,0 +1,417 @@
+//! Per-session resource limits (`amux new --memory/--cpus/--pids/--nofile`).
+//!
+//! Two enforcement layers, chosen per limit:
+//!
+//! - **cgroup v2.** When the daemon can manage a delegated cgroup v2
+//!   subtree (its own cgroup, or `$AMUX_CGROUP_ROOT`), each limited session
+//!   gets a fresh child cgroup `amux-<pid>-<n>` with `memory.max`, `cpu.max` and
+//!   `pids.max` set, and the forked child moves itself in before exec. This
+//!   covers every descendant of the agent and lets `amux info` report live
+//!   usage.
+//! - **setrlimit in the forked child.** `RLIMIT_NOFILE` is always applied
+//!   this way. Without a cgroup, `RLIMIT_AS` and `RLIMIT_NPROC` stand in for
+//!   memory and pids — both approximations (address space rather than
+//!   resident memory; NPROC counts all of the user's processes). `--cpus`
+//!   has no rlimit equivalent and is reported as unenforced.
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+
+use nix::sys::resource::{getrlimit, setrlimit, Resource, RLIM_INFINITY};
+
+use crate::protocol::messages::{LimitStatus, ResourceLimits};
+
+/// Environment variable naming a delegated cgroup v2 directory to create
+/// session cgroups under, overriding auto-detection.
+pub const CGROUP_ROOT_ENV: &str = "AMUX_CGROUP_ROOT";
+
+/// How many names to try when session cgroup directories are already
+/// taken (by another daemon or one that crashed).
+const MAX_CGROUP_ATTEMPTS: u32 = 100;
+
+/// Sequence number of the next session cgroup.
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Controllers a session cgroup needs enabled in the root's subtree.
+const CONTROLLERS: [&str; 3] = ["memory", "pids", "cpu"];
+
+/// `cpu.max` period in microseconds (the kernel default).
+const CPU_PERIOD_USEC: u64 = 100_000;
+
+/// Check limits that can be rejected up front, before anything is forked.
+pub fn validate(limits: &ResourceLimits) -> anyhow::Result<()> {
+    if let Some(cpus) = limits.cpus {
+        if !(cpus > 0.0 && cpus.is_finite()) {
+            anyhow::bail!("--cpus must be a positive number");
+        }
+    }
+    if limits.pids == Some(0) || limits.nofile == Some(0) || limits.memory_bytes == Some(0) {
+        anyhow::bail!("resource limits must be greater than zero");
+    }
+    if let Some(nofile) = limits.nofile {
+        // An unprivileged daemon can't raise the hard limit; catching it
+        // here gives a clear error instead of a child that dies on exec.
+        if let Ok((_, hard)) = getrlimit(Resource::RLIMIT_NOFILE) {
+            if hard != RLIM_INFINITY && nofile > hard && !nix::unistd::geteuid().is_root() {
+                anyhow::bail!(
+                    "--nofile {} exceeds the daemon's hard limit of {}",
+                    nofile,
+                    hard
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// A per-session cgroup. Removed (once empty) when dropped.
+#[derive(Debug)]
+pub struct SessionCgroup {
+    path: PathBuf,
+}
+
+impl SessionCgroup {
+    /// Create a fresh cgroup for a new session and write its limits.
+    /// Returns `None` when no usable cgroup v2 subtree is available or the
+    /// limits don't need one; the caller then falls back to rlimits.
+    pub fn create(limits: &ResourceLimits) -> Option<Self> {
+        if limits.memory_bytes.is_none() && limits.cpus.is_none() && limits.pids.is_none() {
+            return None;
+        }
+        Self::create_in(cgroup_root()?, limits)
+    }
+
+    fn create_in(root: &Path, limits: &ResourceLimits) -> Option<Self> {
+        // Named by daemon and sequence number rather than session name, so
+        // a rename can't leave a cgroup for the next session of that name
+        // to pick up. An existing directory may belong to another daemon,
+        // a crashed one, or a session whose removal hasn't finished; never
+        // adopt it.
+        let mut attempt = 0;
+        let path = loop {
+            let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+            let path = root.join(format!("amux-{}-{}", std::process::id(), id));
+            match std::fs::create_dir(&path) {
+                Ok(()) => break path,
+                Err(e) => {
+                    attempt += 1;
+                    if e.kind() != std::io::ErrorKind::AlreadyExists
+                        || attempt >= MAX_CGROUP_ATTEMPTS
+                    {
+                        tracing::warn!("cgroup: cannot create {}: {}", path.display(), e);
+                        return None;
+                    }
+                }
+            }
+        };
+        let cgroup = SessionCgroup { path };
+        if let Err(e) = cgroup.write_limits(limits) {
+            tracing::warn!("cgroup: cannot set limits on {}: {}", cgroup.path.display(), e);
+            return None;
+        }
+        Some(cgroup)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn write_limits(&self, limits: &ResourceLimits) -> std::io::Result<()> {
+        if let Some(bytes) = limits.memory_bytes {
+            std::fs::write(self.path.join("memory.max"), bytes.to_string())?;
+        }
+        if let Some(pids) = limits.pids {
+            std::fs::write(self.path.join("pids.max"), pids.to_string())?;
+        }
+        if let Some(cpus) = limits.cpus {
+            let quota = ((cpus * CPU_PERIOD_USEC as f64).round() as u64).max(1000);
+            std::fs::write(
+                self.path.join("cpu.max"),
+                format!("{} {}", quota, CPU_PERIOD_USEC),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Current memory, process count and cumulative CPU time.
+    fn usage(&self) -> (Option<u64>, Option<u64>, Option<u64>) {
+        let read_u64 = |file: &str| {
+            std::fs::read_to_string(self.path.join(file))
+                .ok()
+                .and_then(|s| s.trim().parse().ok())
+        };
+        let cpu_usec = std::fs::read_to_string(self.path.join("cpu.stat"))
+            .ok()
+            .and_then(|s| {
+                s.lines()
+                    .find_map(|l| l.strip_prefix("usage_usec "))
+                    .and_then(|v| v.trim().parse().ok())
+            });
+        (read_u64("memory.current"), read_u64("pids.current"), cpu_usec)
+    }
+}
+
+impl Drop for SessionCgroup {
+    fn drop(&mut self) {
+        // The session is dropped as soon as it leaves the registry, but a
+        // killed child gets a grace period before SIGKILL; the cgroup can
+        // only be removed once it is empty, so retry briefly off-thread.
+        let path = self.path.clone();
+        std::thread::spawn(move || {
+            for _ in 0..20 {
+                if std::fs::remove_dir(&path).is_ok() || !path.exists() {
+                    return;
+                }
+                std::thread::sleep(std::time::Duration::from_millis(500));
+            }
+            tracing::debug!("cgroup: {} still busy, leaving it", path.display());
+        });
+    }
+}
+
+/// Apply limits inside the forked child, before exec: join the session
+/// cgroup if there is one, then set whatever rlimits apply. On error the
+/// caller should report and exit rather than run the command unlimited.
+pub fn apply_in_child(
+    limits: &ResourceLimits,
+    cgroup: Option<&Path>,
+) -> std::io::Result<()> {
+    if let Some(path) = cgroup {
+        // "0" means "the writing process".
+        std::fs::write(path.join("cgroup.procs"), "0")?;
+    }
+    if let Some(nofile) = limits.nofile {
+        setrlimit(Resource::RLIMIT_NOFILE, nofile, nofile)?;
+    }
+    if cgroup.is_none() {
+        if let Some(bytes) = limits.memory_bytes {
+            setrlimit(Resource::RLIMIT_AS, bytes, bytes)?;
+        }
+        if let Some(pids) = limits.pids {
+            setrlimit(Resource::RLIMIT_NPROC, pids, pids)?;
+        }
+    }
+    Ok(())
+}
+
+/// Build the `amux info` view of a session's limits and current usage.
+pub fn status(
+    limits: &ResourceLimits,
+    cgroup: Option<&SessionCgroup>,
+    child_pid: u32,
+) -> LimitStatus {
+    match cgroup {
+        Some(cg) => {
+            let (memory_bytes, pids, cpu_usage_usec) = cg.usage();
+            LimitStatus {
+                limits: limits.clone(),
+                cgroup: Some(cg.path().display().to_string()),
+                memory_bytes,
+                pids,
+                cpu_usage_usec,
+            }
+        }
+        None => LimitStatus {
+            limits: limits.clone(),
+            cgroup: None,
+            memory_bytes: proc_rss_bytes(child_pid),
+            pids: None,
+            cpu_usage_usec: None,
+        },
+    }
+}
+
+/// Resident set size of a single process, from `/proc/<pid>/status`.
+fn proc_rss_bytes(pid: u32) -> Option<u64> {
+    let status = std::fs::read_to_string(format!("/proc/{}/status", pid)).ok()?;
+    let kb: u64 = status
+        .lines()
+        .find_map(|l| l.strip_prefix("VmRSS:"))?
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse()
+        .ok()?;
+    Some(kb * 1024)
+}
+
+/// The cgroup v2 directory session cgroups are created under, discovered
+/// once per daemon.
+fn cgroup_root() -> Option<&'static Path> {
+    static ROOT: OnceLock<Option<PathBuf>> = OnceLock::new();
+    ROOT.get_or_init(|| {
+        let root = match std::env::var(CGROUP_ROOT_ENV) {
+            Ok(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => own_cgroup_dir()?,
+        };
+        match prepare_root(&root) {
+            Ok(()) => {
+                tracing::info!("cgroup: session limits enforced under {}", root.display());
+                Some(root)
+            }
+            Err(e) => {
+                tracing::info!(
+                    "cgroup: {} unusable ({}); falling back to rlimits",
+                    root.display(),
+                    e
+                );
+                None
+            }
+        }
+    })
+    .as_deref()
+}
+
+/// The daemon's own cgroup v2 directory: the cgroup2 mount point joined
+/// with the `0::` entry of `/proc/self/cgroup`.
+fn own_cgroup_dir() -> Option<PathBuf> {
+    let mounts = std::fs::read_to_string("/proc/self/mounts").ok()?;
+    let mount = mounts.lines().find_map(|l| {
+        let mut fields = l.split_whitespace();
+        let (_, dir, fstype) = (fields.next()?, fields.next()?, fields.next()?);
+        (fstype == "cgroup2").then(|| PathBuf::from(dir))
+    })?;
+    let cgroups = std::fs::read_to_string("/proc/self/cgroup").ok()?;
+    let rel = cgroups.lines().find_map(|l| l.strip_prefix("0::"))?;
+    Some(mount.join(rel.trim_start_matches('/')))
+}
+
+/// Make `root` able to host session cgroups: the controllers we need must
+/// be available and enabled for its children. cgroup v2 refuses to enable
+/// controllers on a cgroup that still has member processes, so if the
+/// daemon is the only member it first moves itself into a leaf
+/// (`amux-daemon`); anything else there means the subtree isn't ours.
+fn prepare_root(root: &Path) -> std::io::Result<()> {
+    let available = std::fs::read_to_string(root.join("cgroup.controllers"))?;
+    let missing: Vec<&str> = CONTROLLERS
+        .iter()
+        .copied()
+        .filter(|c| !available.split_whitespace().any(|a| a == *c))
+        .collect();
+    if !missing.is_empty() {
+        return Err(std::io::Error::other(format!(
+            "controllers not delegated: {}",
+            missing.join(", ")
+        )));
+    }
+
+    let enable = CONTROLLERS.map(|c| format!("+{}", c)).join(" ");
+    let subtree = root.join("cgroup.subtree_control");
+    if std::fs::write(&subtree, &enable).is_ok() {
+        return Ok(());
+    }
+
+    let procs = std::fs::read_to_string(root.join("cgroup.procs"))?;
+    let me = std::process::id().to_string();
+    if procs.lines().any(|p| p.trim() != me) {
+        return Err(std::io::Error::other(
+            "cgroup has other member processes; cannot enable controllers",
+        ));
+    }
+    let leaf = root.join("amux-daemon");
+    if let Err(e) = std::fs::create_dir(&leaf) {
+        if e.kind() != std::io::ErrorKind::AlreadyExists {
+            return Err(e);
+        }
+    }
+    std::fs::write(leaf.join("cgroup.procs"), &me)?;
+    std::fs::write(&subtree, &enable)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_rejects_nonsense() {
+        let bad_cpus = ResourceLimits {
+            cpus: Some(0.0),
+            ..Default::default()
+        };
+        assert!(validate(&bad_cpus).is_err());
+        let zero_pids = ResourceLimits {
+            pids: Some(0),
+            ..Default::default()
+        };
+        assert!(validate(&zero_pids).is_err());
+        assert!(validate(&ResourceLimits::default()).is_ok());
+    }
+
+    #[test]
+    fn test_no_cgroup_needed_for_nofile_only() {
+        let limits = ResourceLimits {
+            nofile: Some(256),
+            ..Default::default()
+        };
+        assert!(SessionCgroup::create(&limits).is_none());
+    }
+
+    #[test]
+    fn test_create_never_reuses_an_existing_cgroup() {
+        let root = std::env::temp_dir().join(format!("amux-test-cgroup-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+
+        // Every directory this daemon could pick next is already taken.
+        let next = NEXT_ID.load(Ordering::Relaxed);
+        for id in next..next + 5 {
+            let stale = root.join(format!("amux-{}-{}", std::process::id(), id));
+            std::fs::create_dir(&stale).unwrap();
+            std::fs::write(stale.join("memory.max"), "max").unwrap();
+        }
+
+        let limits = ResourceLimits {
+            memory_bytes: Some(1 << 20),
+            ..Default::default()
+        };
+        let cgroup = SessionCgroup::create_in(&root, &limits).unwrap();
+        assert_eq!(std::fs::read_to_string(cgroup.path().join("memory.max")).unwrap(), "1048576");
+        let stale = root.join(format!("amux-{}-{}", std::process::id(), next));
+        assert_ne!(cgroup.path(), stale);
+        assert_eq!(std::fs::read_to_string(stale.join("memory.max")).unwrap(), "max");
+
+        // Skip Drop's background removal; it would race the cleanup below.
+        std::mem::forget(cgroup);
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    /// A session renamed away from `w1` keeps its cgroup, and the next
+    /// `amux new -t w1` gets one of its own rather than sharing it.
+    #[test]
+    fn test_cgroup_outlives_rename_without_sharing() {
+        let root =
+            std::env::temp_dir().join(format!("amux-test-cgroup-rename-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+
+        let small = ResourceLimits {
+            memory_bytes: Some(1 << 20),
+            ..Default::default()
+        };
+        let large = ResourceLimits {
+            memory_bytes: Some(1 << 30),
+            ..Default::default()
+        };
+        let renamed = SessionCgroup::create_in(&root, &small).unwrap();
+        let reused_name = SessionCgroup::create_in(&root, &large).unwrap();
+        assert_ne!(renamed.path(), reused_name.path());
+        assert_eq!(std::fs::read_to_string(renamed.path().join("memory.max")).unwrap(), "1048576");
+
+        std::mem::forget(renamed);
+        std::mem::forget(reused_name);
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_status_without_cgroup_reads_proc_rss() {
+        let limits = ResourceLimits {
+            memory_bytes: Some(1 << 30),
+            ..Default::default()
+        };
+        let status = status(&limits, None, std::process::id());
+        assert!(status.cgroup.is_none());
+        assert!(status.memory_bytes.unwrap() > 0);
+        assert_eq!(status.limits, limits);
+    }
+}
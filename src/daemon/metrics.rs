@@ -0,0 +1,510 @@
+//! Optional Prometheus/OpenMetrics exporter.
+//!
+//! Enabled by `amux start-server --metrics <ADDR>` or `$AMUX_METRICS_ADDR`
+//! (read by the command that starts the daemon, which passes it on; the
+//! daemon drops it from its environment so sessions don't inherit it).
+//! `<ADDR>` is either a TCP `host:port`
+//! or a Unix socket path (anything containing a `/`). The exporter answers
+//! `GET /metrics` with the text exposition format; it speaks just enough
+//! HTTP/1.1 for Prometheus, curl and `curl --unix-socket`.
+//!
+//! Per-session series are computed from the registry at scrape time.
+//! Daemon-wide counters live in a process-global `DaemonMetrics` that the
+//! server, the watchdog and the output fan-out bump as things happen.
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::os::unix::fs::FileTypeExt;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex as StdMutex;
+use std::time::{Duration, Instant};
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::daemon::registry::Registry;
+use crate::protocol::codec;
+use crate::protocol::SessionInfo;
+
+/// Environment variable naming the exporter's listen address.
+pub const METRICS_ADDR_ENV: &str = "AMUX_METRICS_ADDR";
+
+/// Largest request head the exporter will read before giving up.
+const MAX_REQUEST_BYTES: usize = 8 * 1024;
+
+/// How long a scraper gets to send its request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Daemon-wide counters. One instance per process, see `global()`.
+pub struct DaemonMetrics {
+    connections_total: AtomicU64,
+    connections_active: AtomicU64,
+    /// Messages received, by `ClientMessage::kind()`.
+    messages: StdMutex<BTreeMap<&'static str, u64>>,
+    /// Times an attach/follow receiver fell behind the session's output
+    /// broadcast, and how many messages were dropped in total.
+    lag_events: AtomicU64,
+    lagged_messages: AtomicU64,
+    suspensions: AtomicU64,
+}
+
+static GLOBAL: DaemonMetrics = DaemonMetrics {
+    connections_total: AtomicU64::new(0),
+    connections_active: AtomicU64::new(0),
+    messages: StdMutex::new(BTreeMap::new()),
+    lag_events: AtomicU64::new(0),
+    lagged_messages: AtomicU64::new(0),
+    suspensions: AtomicU64::new(0),
+};
+
+/// The daemon's counters.
+pub fn global() -> &'static DaemonMetrics {
+    &GLOBAL
+}
+
+impl DaemonMetrics {
+    pub fn record_message(&self, kind: &'static str) {
+        if let Ok(mut m) = self.messages.lock() {
+            *m.entry(kind).or_default() += 1;
+        }
+    }
+
+    pub fn record_lag(&self, dropped: u64) {
+        self.lag_events.fetch_add(1, Ordering::Relaxed);
+        self.lagged_messages.fetch_add(dropped, Ordering::Relaxed);
+    }
+
+    pub fn record_suspension(&self) {
+        self.suspensions.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Count a client connection for as long as the returned guard lives.
+    pub fn connection(&'static self) -> ConnectionGuard {
+        self.connections_total.fetch_add(1, Ordering::Relaxed);
+        self.connections_active.fetch_add(1, Ordering::Relaxed);
+        ConnectionGuard { metrics: self }
+    }
+}
+
+/// Keeps `amux_connections_active` accurate however a connection ends.
+pub struct ConnectionGuard {
+    metrics: &'static DaemonMetrics,
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        self.metrics.connections_active.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Escape a label value per the exposition format.
+fn escape_label(v: &str) -> String {
+    v.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// Writes `# HELP`/`# TYPE` once per family, then its samples.
+struct Exposition {
+    out: String,
+}
+
+impl Exposition {
+    fn family(&mut self, name: &str, kind: &str, help: &str) {
+        let _ = writeln!(self.out, "# HELP {} {}", name, help);
+        let _ = writeln!(self.out, "# TYPE {} {}", name, kind);
+    }
+
+    fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: impl std::fmt::Display) {
+        self.out.push_str(name);
+        if !labels.is_empty() {
+            let rendered: Vec<String> = labels
+                .iter()
+                .map(|(k, v)| format!("{}=\"{}\"", k, escape_label(v)))
+                .collect();
+            let _ = write!(self.out, "{{{}}}", rendered.join(","));
+        }
+        let _ = writeln!(self.out, " {}", value);
+    }
+
+    /// A per-session family: one sample per session where `value` yields
+    /// something.
+    fn per_session<V: std::fmt::Display>(
+        &mut self,
+        name: &str,
+        kind: &str,
+        help: &str,
+        sessions: &[SessionInfo],
+        value: impl Fn(&SessionInfo) -> Option<V>,
+    ) {
+        self.family(name, kind, help);
+        for s in sessions {
+            if let Some(v) = value(s) {
+                self.sample(name, &[("session", &s.name)], v);
+            }
+        }
+    }
+}
+
+/// Render the full exposition for a scrape.
+pub fn render(sessions: &[SessionInfo], daemon: &DaemonMetrics, uptime: Duration) -> String {
+    let mut e = Exposition { out: String::new() };
+
+    e.family("amux_uptime_seconds", "gauge", "Seconds since the daemon started.");
+    e.sample("amux_uptime_seconds", &[], uptime.as_secs());
+    e.family("amux_sessions", "gauge", "Sessions in the registry.");
+    e.sample("amux_sessions", &[], sessions.len());
+    e.family("amux_connections_active", "gauge", "Open client connections.");
+    e.sample(
+        "amux_connections_active",
+        &[],
+        daemon.connections_active.load(Ordering::Relaxed),
+    );
+    e.family("amux_connections_total", "counter", "Client connections accepted.");
+    e.sample(
+        "amux_connections_total",
+        &[],
+        daemon.connections_total.load(Ordering::Relaxed),
+    );
+    e.family(
+        "amux_client_messages_total",
+        "counter",
+        "Client requests received, by message type.",
+    );
+    if let Ok(messages) = daemon.messages.lock() {
+        for (kind, count) in messages.iter() {
+            e.sample("amux_client_messages_total", &[("kind", kind)], count);
+        }
+    }
+    e.family(
+        "amux_broadcast_lag_events_total",
+        "counter",
+        "Times an attached or following client fell behind session output.",
+    );
+    e.sample(
+        "amux_broadcast_lag_events_total",
+        &[],
+        daemon.lag_events.load(Ordering::Relaxed),
+    );
+    e.family(
+        "amux_broadcast_lagged_messages_total",
+        "counter",
+        "Output messages dropped for clients that fell behind.",
+    );
+    e.sample(
+        "amux_broadcast_lagged_messages_total",
+        &[],
+        daemon.lagged_messages.load(Ordering::Relaxed),
+    );
+    e.family(
+        "amux_watchdog_suspensions_total",
+        "counter",
+        "Host suspensions detected by the watchdog.",
+    );
+    e.sample(
+        "amux_watchdog_suspensions_total",
+        &[],
+        daemon.suspensions.load(Ordering::Relaxed),
+    );
+    let compression = codec::compression_stats();
+    e.family(
+        "amux_compressed_frames_total",
+        "counter",
+        "Output frames sent compressed to clients that negotiated it.",
+    );
+    e.sample("amux_compressed_frames_total", &[], compression.frames());
+    e.family(
+        "amux_compression_input_bytes_total",
+        "counter",
+        "Bytes of those frames before compression.",
+    );
+    e.sample("amux_compression_input_bytes_total", &[], compression.raw_bytes());
+    e.family(
+        "amux_compression_output_bytes_total",
+        "counter",
+        "Bytes of those frames as sent.",
+    );
+    e.sample("amux_compression_output_bytes_total", &[], compression.wire_bytes());
+
+    e.per_session(
+        "amux_session_alive",
+        "gauge",
+        "1 if the session's process is running.",
+        sessions,
+        |s| Some(s.alive as u8),
+    );
+    e.per_session(
+        "amux_session_output_bytes_total",
+        "counter",
+        "Bytes of PTY output produced.",
+        sessions,
+        |s| Some(s.output_bytes),
+    );
+    e.per_session(
+        "amux_session_idle_seconds",
+        "gauge",
+        "Seconds since the last PTY output.",
+        sessions,
+        |s| Some(s.idle_secs),
+    );
+    e.per_session(
+        "amux_session_attached_clients",
+        "gauge",
+        "Interactive clients attached.",
+        sessions,
+        |s| Some(s.attach_count),
+    );
+    e.per_session(
+        "amux_session_respawns_total",
+        "counter",
+        "Times the session was respawned.",
+        sessions,
+        |s| Some(s.respawn_count),
+    );
+    e.per_session(
+        "amux_session_exit_code",
+        "gauge",
+        "Exit code of sessions whose process has exited.",
+        sessions,
+        |s| s.exit_code,
+    );
+    e.per_session(
+        "amux_session_cpu_percent",
+        "gauge",
+        "CPU use of the session's process tree, percent of one core.",
+        sessions,
+        |s| s.usage.as_ref().map(|u| u.cpu_percent),
+    );
+    e.per_session(
+        "amux_session_rss_bytes",
+        "gauge",
+        "Resident memory of the session's process tree.",
+        sessions,
+        |s| s.usage.as_ref().map(|u| u.rss_bytes),
+    );
+    e.out
+}
+
+/// Answer one HTTP request on `stream`.
+async fn serve_one<S: AsyncRead + AsyncWrite + Unpin>(
+    mut stream: S,
+    registry: &Registry,
+    started: Instant,
+) -> std::io::Result<()> {
+    let mut head = Vec::new();
+    let mut buf = [0u8; 1024];
+    let read_head = async {
+        while !head.windows(4).any(|w| w == b"\r\n\r\n") {
+            let n = stream.read(&mut buf).await?;
+            if n == 0 || head.len() + n > MAX_REQUEST_BYTES {
+                break;
+            }
+            head.extend_from_slice(&buf[..n]);
+        }
+        Ok::<_, std::io::Error>(())
+    };
+    if tokio::time::timeout(REQUEST_TIMEOUT, read_head).await.is_err() {
+        return Ok(());
+    }
+
+    let request_line = String::from_utf8_lossy(&head);
+    let mut parts = request_line.lines().next().unwrap_or("").split_whitespace();
+    let (method, path) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+    let path = path.split('?').next().unwrap_or("");
+
+    let (status, content_type, body) = match (method, path) {
+        ("GET", "/metrics") | ("GET", "/") => {
+            let sessions = registry.list().await;
+            let body = render(&sessions, global(), started.elapsed());
+            ("200 OK", "text/plain; version=0.0.4; charset=utf-8", body)
+        }
+        ("GET", _) => ("404 Not Found", "text/plain", "not found\n".to_string()),
+        _ => (
+            "405 Method Not Allowed",
+            "text/plain",
+            "method not allowed\n".to_string(),
+        ),
+    };
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+/// Make `path` free to bind, like the daemon does with its own socket:
+/// only a socket nobody is listening on is removed. Anything else at the
+/// path (a file named by a typo, another daemon's live exporter) is left
+/// alone and reported.
+async fn claim_socket_path(path: &Path) -> Result<(), String> {
+    let meta = match std::fs::symlink_metadata(path) {
+        Ok(meta) => meta,
+        Err(_) => return Ok(()),
+    };
+    if !meta.file_type().is_socket() {
+        return Err("exists and is not a socket".to_string());
+    }
+    if tokio::net::UnixStream::connect(path).await.is_ok() {
+        return Err("is already being served".to_string());
+    }
+    std::fs::remove_file(path).map_err(|e| format!("cannot remove stale socket: {}", e))
+}
+
+/// Serve metrics on `addr` until the task is dropped. Errors binding are
+/// logged, not fatal: the daemon runs fine without its exporter.
+pub async fn run(addr: String, registry: Registry) {
+    let started = Instant::now();
+    if addr.contains('/') {
+        let path = std::path::PathBuf::from(&addr);
+        if let Err(e) = claim_socket_path(&path).await {
+            tracing::error!("metrics: not listening on {}: it {}", addr, e);
+            return;
+        }
+        let listener = match tokio::net::UnixListener::bind(&path) {
+            Ok(l) => l,
+            Err(e) => {
+                tracing::error!("metrics: cannot listen on {}: {}", addr, e);
+                return;
+            }
+        };
+        tracing::info!("metrics: serving on unix:{}", addr);
+        while let Ok((stream, _)) = listener.accept().await {
+            let registry = registry.clone();
+            tokio::spawn(async move {
+                let _ = serve_one(stream, &registry, started).await;
+            });
+        }
+    } else {
+        let listener = match tokio::net::TcpListener::bind(&addr).await {
+            Ok(l) => l,
+            Err(e) => {
+                tracing::error!("metrics: cannot listen on {}: {}", addr, e);
+                return;
+            }
+        };
+        tracing::info!("metrics: serving on http://{}/metrics", addr);
+        while let Ok((stream, _)) = listener.accept().await {
+            let registry = registry.clone();
+            tokio::spawn(async move {
+                let _ = serve_one(stream, &registry, started).await;
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(name: &str, alive: bool, exit_code: Option<i32>) -> SessionInfo {
+        SessionInfo {
+            name: name.to_string(),
+            command: "bash".to_string(),
+            pid: 1,
+            alive,
+            created_at: String::new(),
+            uptime_secs: 10,
+            last_activity: String::new(),
+            idle_secs: 4,
+            exit_code,
+            output_bytes: 1234,
+            rows: 24,
+            cols: 80,
+            attach_count: 1,
+            respawn_count: 2,
+            limits: None,
+            usage: None,
+            cpu_recent: Vec::new(),
+            paused: false,
+            title: String::new(),
+            notifications: Vec::new(),
+            needs_attention: false,
+            attention_count: 0,
+        }
+    }
+
+    #[test]
+    fn test_render_exposition() {
+        let daemon = DaemonMetrics {
+            connections_total: AtomicU64::new(5),
+            connections_active: AtomicU64::new(1),
+            messages: StdMutex::new(BTreeMap::new()),
+            lag_events: AtomicU64::new(2),
+            lagged_messages: AtomicU64::new(40),
+            suspensions: AtomicU64::new(0),
+        };
+        daemon.record_message("ListSessions");
+        daemon.record_message("ListSessions");
+        let sessions = vec![session("w1", true, None), session("say \"hi\"", false, Some(3))];
+        let text = render(&sessions, &daemon, Duration::from_secs(60));
+
+        assert!(text.contains("# TYPE amux_session_alive gauge\n"));
+        assert!(text.contains("# TYPE amux_compressed_frames_total counter\n"));
+        assert!(text.contains("amux_session_alive{session=\"w1\"} 1\n"));
+        assert!(text.contains("amux_session_alive{session=\"say \\\"hi\\\"\"} 0\n"));
+        assert!(text.contains("amux_session_output_bytes_total{session=\"w1\"} 1234\n"));
+        assert!(text.contains("amux_session_respawns_total{session=\"w1\"} 2\n"));
+        // Exit code only for the exited session.
+        assert!(!text.contains("amux_session_exit_code{session=\"w1\"}"));
+        assert!(text.contains("amux_session_exit_code{session=\"say \\\"hi\\\"\"} 3\n"));
+        assert!(text.contains("amux_client_messages_total{kind=\"ListSessions\"} 2\n"));
+        assert!(text.contains("amux_connections_total 5\n"));
+        assert!(text.contains("amux_broadcast_lagged_messages_total 40\n"));
+        assert!(text.contains("amux_uptime_seconds 60\n"));
+        // Every sample line belongs to a declared family.
+        for line in text.lines().filter(|l| !l.starts_with('#')) {
+            let name = line.split(['{', ' ']).next().unwrap();
+            assert!(text.contains(&format!("# TYPE {} ", name)), "undeclared: {}", line);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_serve_one_routes() {
+        let registry = Registry::new();
+        let started = Instant::now();
+
+        let (mut client, server) = tokio::io::duplex(64 * 1024);
+        client
+            .write_all(b"GET /metrics HTTP/1.1\r\nHost: x\r\n\r\n")
+            .await
+            .unwrap();
+        serve_one(server, &registry, started).await.unwrap();
+        let mut resp = String::new();
+        client.read_to_string(&mut resp).await.unwrap();
+        assert!(resp.starts_with("HTTP/1.1 200 OK\r\n"), "{}", resp);
+        assert!(resp.contains("amux_sessions 0\n"));
+
+        let (mut client, server) = tokio::io::duplex(64 * 1024);
+        client.write_all(b"GET /nope HTTP/1.1\r\n\r\n").await.unwrap();
+        serve_one(server, &registry, started).await.unwrap();
+        let mut resp = String::new();
+        client.read_to_string(&mut resp).await.unwrap();
+        assert!(resp.starts_with("HTTP/1.1 404"), "{}", resp);
+    }
+
+    #[tokio::test]
+    async fn test_claim_socket_path_spares_files_and_live_sockets() {
+        let dir = std::env::temp_dir().join(format!("amux-test-claim-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let notes = dir.join("notes.md");
+        std::fs::write(&notes, "keep me").unwrap();
+        assert!(claim_socket_path(&notes).await.is_err());
+        assert_eq!(std::fs::read_to_string(&notes).unwrap(), "keep me");
+
+        let live = dir.join("live.sock");
+        let _listener = tokio::net::UnixListener::bind(&live).unwrap();
+        assert!(claim_socket_path(&live).await.is_err());
+        assert!(live.exists());
+
+        let stale = dir.join("stale.sock");
+        drop(std::os::unix::net::UnixListener::bind(&stale).unwrap());
+        claim_socket_path(&stale).await.unwrap();
+        assert!(!stale.exists());
+
+        claim_socket_path(&dir.join("absent.sock")).await.unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -1,6 +1,15 @@
+pub mod fanout;
+pub mod keys;
+pub mod limits;
+pub mod metrics;
+pub mod mux;
+pub mod pacing;
+pub mod paste;
+pub mod proctree;
 pub mod registry;
 pub mod server;
 pub mod session;
+pub mod telemetry;
 pub mod vterm;
 pub mod watchdog;
 
@@ -19,7 +28,10 @@ use crate::common;
 ///
 /// The parent process returns `Ok(())` after the fork.
 /// The child process never returns (it runs the server loop).
-pub fn fork_daemon() -> anyhow::Result<()> {
+///
+/// `metrics` is the exporter address from `start-server --metrics`;
+/// without it, `$AMUX_METRICS_ADDR` is used.
+pub fn fork_daemon(metrics: Option<String>) -> anyhow::Result<()> {
     let sock_path = common::socket_path();
     let run_dir = common::runtime_dir();
 
@@ -37,6 +49,12 @@ pub fn fork_daemon() -> anyhow::Result<()> {
     common::clear_stale_runtime_files()
         .with_context(|| format!("failed to clear stale runtime files in {}", run_dir.display()))?;
 
+    let metrics = metrics.or_else(|| {
+        std::env::var(metrics::METRICS_ADDR_ENV)
+            .ok()
+            .filter(|a| !a.is_empty())
+    });
+
     // Fork: daemon must fork BEFORE tokio runtime.
     match unsafe { unistd::fork() }.context("fork failed")? {
         ForkResult::Parent { child } => {
@@ -47,6 +65,11 @@ pub fn fork_daemon() -> anyhow::Result<()> {
             // Become session leader (double-fork not needed for our use case).
             unistd::setsid().context("setsid failed")?;
 
+            // Sessions inherit our environment; an `amux start-server` in
+            // one must not pick up our exporter address and take it over.
+            // Still single-threaded here, so this is safe.
+            std::env::remove_var(metrics::METRICS_ADDR_ENV);
+
             // Redirect stdin/stdout/stderr to /dev/null.
             let devnull = fs::File::open("/dev/null").context("failed to open /dev/null")?;
             let fd = devnull.as_raw_fd();
@@ -70,7 +93,7 @@ pub fn fork_daemon() -> anyhow::Result<()> {
             disable_app_nap_macos(pid);
 
             // Now safe to create tokio runtime.
-            run_daemon(sock_path);
+            run_daemon(sock_path, metrics);
         }
     }
 }
@@ -111,7 +134,7 @@ fn setup_tracing(run_dir: &std::path::Path) {
     }
 }
 
-fn run_daemon(sock_path: PathBuf) -> ! {
+fn run_daemon(sock_path: PathBuf, metrics: Option<String>) -> ! {
     let rt = tokio::runtime::Builder::new_multi_thread()
         .enable_all()
         .build()
@@ -145,7 +168,7 @@ fn run_daemon(sock_path: PathBuf) -> ! {
             let _ = shutdown_signal.send(());
         });
 
-        server::run_server(listener, shutdown_tx).await;
+        server::run_server(listener, shutdown_tx, metrics).await;
 
         // Clean up.
         let _ = fs::remove_file(&sock_path);
@@ -0,0 +1,179 @@
+//! Multiplexed connections: many requests and streams over one socket.
+//!
+//! A client that sends `ClientMessage::Tagged` turns its connection into a
+//! set of numbered channels. Each channel is served exactly like a
+//! connection of its own — a socket pair whose far end runs the ordinary
+//! `handle_connection` — so every request and streaming mode works over a
+//! channel unchanged. Frames coming back are wrapped in
+//! `DaemonMessage::Tagged` with the channel's id, and `Closed` follows once
+//! the channel's handler is done. Channels run concurrently, so a slow
+//! `WaitSession` never holds up a `ListSessions` behind it.
+
+use std::collections::HashMap;
+
+use tokio::net::unix::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::UnixStream;
+use tokio::sync::{broadcast, mpsc};
+use tokio::task::JoinHandle;
+
+use crate::daemon::registry::Registry;
+use crate::daemon::server::serve_channel;
+use crate::protocol::codec::{try_read_frame_async, write_frame_async};
+use crate::protocol::messages::{ClientMessage, DaemonMessage};
+
+/// Frames queued for the shared writer before channels wait on it.
+const OUTBOX_SIZE: usize = 256;
+
+/// One open channel. Dropping it tears the channel down.
+struct Channel {
+    /// Where follow-ups go; `None` for requests that take none.
+    input: Option<OwnedWriteHalf>,
+    handler: JoinHandle<()>,
+    forward: JoinHandle<()>,
+}
+
+impl Drop for Channel {
+    fn drop(&mut self) {
+        self.handler.abort();
+        self.forward.abort();
+    }
+}
+
+impl Channel {
+    /// Open channel `id` with its first request.
+    async fn open(
+        id: u64,
+        first: ClientMessage,
+        registry: Registry,
+        shutdown: broadcast::Sender<()>,
+        outbox: mpsc::Sender<DaemonMessage>,
+    ) -> std::io::Result<Self> {
+        let (ours, theirs) = UnixStream::pair()?;
+        let handler = tokio::spawn(serve_channel(theirs, registry, shutdown));
+
+        let (reader, mut input) = ours.into_split();
+        let takes_follow_ups = first.takes_follow_ups();
+        write_frame_async(&mut input, &first)
+            .await
+            .map_err(std::io::Error::other)?;
+        // Without follow-ups, end-of-input lets the handler finish right
+        // after answering.
+        let input = if takes_follow_ups { Some(input) } else { None };
+        let forward = tokio::spawn(forward(id, reader, outbox));
+        Ok(Self {
+            input,
+            handler,
+            forward,
+        })
+    }
+
+    async fn follow_up(&mut self, msg: &ClientMessage) {
+        if let Some(input) = self.input.as_mut() {
+            if write_frame_async(input, msg).await.is_err() {
+                self.input = None;
+            }
+        }
+    }
+}
+
+/// Relay a channel's answers to the shared writer, then report it closed.
+async fn forward(id: u64, mut reader: OwnedReadHalf, outbox: mpsc::Sender<DaemonMessage>) {
+    while let Some(Ok(msg)) = try_read_frame_async::<DaemonMessage>(&mut reader).await {
+        let tagged = DaemonMessage::Tagged {
+            id,
+            msg: Box::new(msg),
+        };
+        if outbox.send(tagged).await.is_err() {
+            return;
+        }
+    }
+    let _ = outbox.send(DaemonMessage::Closed { id }).await;
+}
+
+/// Serve a connection whose client sent `first`, a `Tagged` or `Cancel`,
+/// until it disconnects. All its channels are torn down with it.
+pub async fn serve(
+    mut reader: OwnedReadHalf,
+    mut writer: OwnedWriteHalf,
+    registry: Registry,
+    shutdown: broadcast::Sender<()>,
+    first: ClientMessage,
+) {
+    let (outbox, mut outgoing) = mpsc::channel::<DaemonMessage>(OUTBOX_SIZE);
+    let writer_task = tokio::spawn(async move {
+        while let Some(msg) = outgoing.recv().await {
+            if write_frame_async(&mut writer, &msg).await.is_err() {
+                return;
+            }
+        }
+    });
+
+    let mut channels: HashMap<u64, Channel> = HashMap::new();
+    let mut next = Some(first);
+    loop {
+        let msg = match next.take() {
+            Some(msg) => msg,
+            None => match try_read_frame_async::<ClientMessage>(&mut reader).await {
+                Some(Ok(msg)) => msg,
+                Some(Err(e)) => {
+                    tracing::debug!("multiplexed read error: {}", e);
+                    break;
+                }
+                None => break, // Client disconnected.
+            },
+        };
+        channels.retain(|_, channel| !channel.forward.is_finished());
+
+        match msg {
+            // The reply has to be flushed before the daemon goes away, which
+            // a channel's handler can't see to.
+            ClientMessage::Tagged { id, msg } if matches!(*msg, ClientMessage::KillServer) => {
+                let _ = outbox
+                    .send(DaemonMessage::Tagged {
+                        id,
+                        msg: Box::new(DaemonMessage::Ok),
+                    })
+                    .await;
+                let _ = outbox.send(DaemonMessage::Closed { id }).await;
+                drop(channels);
+                drop(outbox);
+                let _ = writer_task.await;
+                let _ = shutdown.send(());
+                return;
+            }
+            ClientMessage::Tagged { id, msg } => match channels.get_mut(&id) {
+                Some(channel) => channel.follow_up(&msg).await,
+                None => {
+                    match Channel::open(id, *msg, registry.clone(), shutdown.clone(), outbox.clone())
+                        .await
+                    {
+                        Ok(channel) => {
+                            channels.insert(id, channel);
+                        }
+                        Err(e) => {
+                            tracing::warn!("failed to open channel {}: {}", id, e);
+                            let _ = outbox.send(DaemonMessage::Closed { id }).await;
+                        }
+                    }
+                }
+            },
+            ClientMessage::Cancel { id } => {
+                if channels.remove(&id).is_some() {
+                    let _ = outbox.send(DaemonMessage::Closed { id }).await;
+                }
+            }
+            other => {
+                let _ = outbox
+                    .send(DaemonMessage::Error(format!(
+                        "{} must be Tagged on a multiplexed connection",
+                        other.kind()
+                    )))
+                    .await;
+            }
+        }
+    }
+
+    drop(channels);
+    drop(outbox);
+    let _ = writer_task.await;
+}
@@ -0,0 +1,138 @@
+//! Chunking and timing for paced `SendInput` (see `InputPacing`).
+//!
+//! `plan` turns a payload into the sequence of PTY writes the daemon will
+//! make, each paired with the pause to take after it. Chunks never split a
+//! UTF-8 sequence, so a rate of N chars/sec means N characters, not bytes.
+
+use std::time::Duration;
+
+use crate::protocol::messages::InputPacing;
+
+/// Split `data` into `(chunk, pause_after)` pairs according to `pacing`.
+pub fn plan(data: &[u8], pacing: &InputPacing) -> Vec<(Vec<u8>, Duration)> {
+    let chunk_chars = match (pacing.chunk_size, pacing.chars_per_sec) {
+        (0, 0) => usize::MAX,
+        (0, _) => 1,
+        (n, _) => n,
+    };
+    let break_on_newline = pacing.newline_delay_ms > 0;
+
+    let mut out = Vec::new();
+    let mut chunk = Vec::new();
+    let mut chars = 0usize;
+    let mut i = 0;
+    while i < data.len() {
+        let len = utf8_len(data[i]).min(data.len() - i);
+        chunk.extend_from_slice(&data[i..i + len]);
+        chars += 1;
+        i += len;
+
+        let at_newline = ends_line(data, i);
+        if chars >= chunk_chars || (break_on_newline && at_newline) || i == data.len() {
+            let mut pause = Duration::ZERO;
+            if pacing.chars_per_sec > 0 {
+                pause += Duration::from_secs(chars as u64) / pacing.chars_per_sec;
+            }
+            if at_newline {
+                pause += Duration::from_millis(pacing.newline_delay_ms);
+            }
+            out.push((std::mem::take(&mut chunk), pause));
+            chars = 0;
+        }
+    }
+    out
+}
+
+/// Total time a paced send of `data` will take. Used client-side to size
+/// the request deadline.
+pub fn duration(data: &[u8], pacing: &InputPacing) -> Duration {
+    plan(data, pacing).iter().map(|(_, pause)| *pause).sum()
+}
+
+/// Byte length of the UTF-8 sequence starting with `lead`. Invalid lead
+/// bytes count as one-byte characters.
+fn utf8_len(lead: u8) -> usize {
+    match lead {
+        0xC0..=0xDF => 2,
+        0xE0..=0xEF => 3,
+        0xF0..=0xF7 => 4,
+        _ => 1,
+    }
+}
+
+/// Whether the byte just before `end` terminates a line. A CR immediately
+/// followed by LF does not, so `\r\n` stays in one chunk.
+fn ends_line(data: &[u8], end: usize) -> bool {
+    match data[end - 1] {
+        b'\n' => true,
+        b'\r' => data.get(end) != Some(&b'\n'),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunks(plan: &[(Vec<u8>, Duration)]) -> Vec<&[u8]> {
+        plan.iter().map(|(c, _)| c.as_slice()).collect()
+    }
+
+    #[test]
+    fn test_default_pacing_is_one_write() {
+        let p = plan(b"hello\nworld", &InputPacing::default());
+        assert_eq!(chunks(&p), vec![&b"hello\nworld"[..]]);
+        assert_eq!(p[0].1, Duration::ZERO);
+    }
+
+    #[test]
+    fn test_rate_implies_single_chars() {
+        let pacing = InputPacing {
+            chars_per_sec: 10,
+            ..Default::default()
+        };
+        let p = plan(b"abc", &pacing);
+        assert_eq!(chunks(&p), vec![&b"a"[..], b"b", b"c"]);
+        assert!(p.iter().all(|(_, d)| *d == Duration::from_millis(100)));
+        assert_eq!(duration(b"abc", &pacing), Duration::from_millis(300));
+    }
+
+    #[test]
+    fn test_chunk_size_with_rate() {
+        let pacing = InputPacing {
+            chars_per_sec: 100,
+            chunk_size: 4,
+            newline_delay_ms: 0,
+        };
+        let p = plan(b"abcdefghij", &pacing);
+        assert_eq!(chunks(&p), vec![&b"abcd"[..], b"efgh", b"ij"]);
+        assert_eq!(p[0].1, Duration::from_millis(40));
+        assert_eq!(p[2].1, Duration::from_millis(20));
+    }
+
+    #[test]
+    fn test_newline_delay_breaks_lines() {
+        let pacing = InputPacing {
+            newline_delay_ms: 250,
+            ..Default::default()
+        };
+        let p = plan(b"one\r\ntwo\nthree", &pacing);
+        assert_eq!(chunks(&p), vec![&b"one\r\n"[..], b"two\n", b"three"]);
+        assert_eq!(p[0].1, Duration::from_millis(250));
+        assert_eq!(p[1].1, Duration::from_millis(250));
+        assert_eq!(p[2].1, Duration::ZERO);
+    }
+
+    #[test]
+    fn test_chunks_never_split_utf8() {
+        let pacing = InputPacing {
+            chunk_size: 1,
+            ..Default::default()
+        };
+        let p = plan("héllo→".as_bytes(), &pacing);
+        let c = chunks(&p);
+        assert_eq!(c.len(), 6);
+        assert_eq!(c[1], "é".as_bytes());
+        assert_eq!(c[5], "→".as_bytes());
+    }
+}
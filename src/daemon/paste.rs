@@ -0,0 +1,101 @@
+//! Payload shaping for `amux send --paste`.
+//!
+//! TUI agents read a multi-line prompt typed into their PTY as a series of
+//! lines, and an embedded newline usually submits early. When the program
+//! has enabled bracketed paste (DECSET 2004) the payload is wrapped in
+//! `ESC [ 200 ~` … `ESC [ 201 ~` so it arrives as one paste; otherwise the
+//! daemon falls back to feeding it a line at a time with a pause between
+//! lines so the program has a chance to consume each one.
+//!
+//! Either way, line endings are normalised to CR the way a terminal
+//! emulator does when the user pastes: `\n` and `\r\n` both become `\r`.
+
+const PASTE_START: &[u8] = b"\x1b[200~";
+const PASTE_END: &[u8] = b"\x1b[201~";
+
+/// Wrap `data` in bracketed-paste markers. Any end marker already inside
+/// the payload is removed so it cannot terminate the paste early and have
+/// the rest interpreted as typed keys.
+pub fn bracketed(data: &[u8]) -> Vec<u8> {
+    let body = normalize_newlines(data);
+    let mut out = Vec::with_capacity(body.len() + PASTE_START.len() + PASTE_END.len());
+    out.extend_from_slice(PASTE_START);
+    let mut i = 0;
+    while i < body.len() {
+        if body[i..].starts_with(PASTE_END) {
+            i += PASTE_END.len();
+        } else {
+            out.push(body[i]);
+            i += 1;
+        }
+    }
+    out.extend_from_slice(PASTE_END);
+    out
+}
+
+/// Split `data` into per-line writes for the unbracketed fallback. Each
+/// chunk keeps its terminating CR; a final unterminated line is returned
+/// as-is.
+pub fn lines(data: &[u8]) -> Vec<Vec<u8>> {
+    let body = normalize_newlines(data);
+    body.split_inclusive(|&b| b == b'\r')
+        .map(|line| line.to_vec())
+        .collect()
+}
+
+fn normalize_newlines(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len());
+    let mut i = 0;
+    while i < data.len() {
+        match data[i] {
+            b'\r' if data.get(i + 1) == Some(&b'\n') => {
+                out.push(b'\r');
+                i += 2;
+            }
+            b'\n' => {
+                out.push(b'\r');
+                i += 1;
+            }
+            b => {
+                out.push(b);
+                i += 1;
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bracketed_wraps_and_normalizes() {
+        assert_eq!(
+            bracketed(b"line one\nline two\r\nthree"),
+            b"\x1b[200~line one\rline two\rthree\x1b[201~".to_vec()
+        );
+    }
+
+    #[test]
+    fn test_bracketed_strips_embedded_end_marker() {
+        assert_eq!(
+            bracketed(b"a\x1b[201~b"),
+            b"\x1b[200~ab\x1b[201~".to_vec()
+        );
+    }
+
+    #[test]
+    fn test_lines_keeps_terminators() {
+        assert_eq!(
+            lines(b"one\ntwo\nthree"),
+            vec![b"one\r".to_vec(), b"two\r".to_vec(), b"three".to_vec()]
+        );
+    }
+
+    #[test]
+    fn test_lines_trailing_newline_has_no_empty_tail() {
+        assert_eq!(lines(b"one\n"), vec![b"one\r".to_vec()]);
+        assert!(lines(b"").is_empty());
+    }
+}
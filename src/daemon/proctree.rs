@@ -0,0 +1,315 @@
+//! Process-tree discovery from `/proc`.
+//!
+//! Sessions fork their child with `setsid()`, so the child leads its own
+//! session and process group. Most descendants stay in that group, but
+//! job-control shells move jobs into their own groups and some tools
+//! double-fork, so "the session's processes" is the union of: the child's
+//! session, its process group, and everything still reachable through
+//! ppid links. Used by the telemetry sampler, `amux signal` and tree-aware
+//! kill.
+//!
+//! Linux only: without `/proc` every snapshot is empty, which degrades to
+//! signalling the process group alone.
+
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+use nix::sys::signal::{self, Signal};
+use nix::unistd::Pid;
+
+use crate::protocol::messages::SignalTarget;
+
+/// The fields of `/proc/<pid>/stat` amux needs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProcStat {
+    pub pid: u32,
+    pub comm: String,
+    pub state: char,
+    pub ppid: u32,
+    pub pgrp: u32,
+    pub session: u32,
+    /// utime + stime, in clock ticks.
+    pub cpu_ticks: u64,
+    pub threads: u32,
+    pub rss_pages: u64,
+}
+
+/// Parse `/proc/<pid>/stat`. The command name (field 2) may contain spaces
+/// and parentheses, so fields are counted from the last `)`.
+pub fn parse_stat(content: &str) -> Option<ProcStat> {
+    let open = content.find('(')?;
+    let close = content.rfind(')')?;
+    let pid = content[..open].trim().parse().ok()?;
+    // Fields after the comm, starting at field 3 (state).
+    let rest: Vec<&str> = content[close + 1..].split_whitespace().collect();
+    let field = |n: usize| rest.get(n - 3).and_then(|v| v.parse::<u64>().ok());
+    Some(ProcStat {
+        pid,
+        comm: content[open + 1..close].to_string(),
+        state: rest.first()?.chars().next()?,
+        ppid: field(4)? as u32,
+        pgrp: field(5)? as u32,
+        session: field(6)? as u32,
+        cpu_ticks: field(14)? + field(15)?,
+        threads: field(20)? as u32,
+        rss_pages: field(24)?,
+    })
+}
+
+/// One pass over `/proc`: every readable process, keyed by pid.
+pub fn snapshot() -> HashMap<u32, ProcStat> {
+    let mut stats = HashMap::new();
+    let Ok(dir) = std::fs::read_dir("/proc") else {
+        return stats;
+    };
+    for entry in dir.flatten() {
+        let Some(pid) = entry.file_name().to_str().and_then(|n| n.parse::<u32>().ok()) else {
+            continue;
+        };
+        if let Some(stat) = std::fs::read_to_string(format!("/proc/{}/stat", pid))
+            .ok()
+            .and_then(|s| parse_stat(&s))
+        {
+            stats.insert(pid, stat);
+        }
+    }
+    stats
+}
+
+/// ppid → children index over a snapshot.
+pub fn children_index(stats: &HashMap<u32, ProcStat>) -> HashMap<u32, Vec<u32>> {
+    let mut children: HashMap<u32, Vec<u32>> = HashMap::new();
+    for stat in stats.values() {
+        children.entry(stat.ppid).or_default().push(stat.pid);
+    }
+    children
+}
+
+/// Every pid in the tree rooted at `root` (root included).
+pub fn tree_pids(root: u32, children: &HashMap<u32, Vec<u32>>) -> Vec<u32> {
+    let mut out = vec![root];
+    let mut i = 0;
+    while i < out.len() {
+        if let Some(kids) = children.get(&out[i]) {
+            out.extend(kids);
+        }
+        i += 1;
+    }
+    out
+}
+
+/// Processes belonging to the session led by `leader`: its descendants plus
+/// anything in its session or process group, sorted by pid. Zombies are
+/// left out — they are already dead and only await reaping.
+pub fn session_members(leader: u32, stats: &HashMap<u32, ProcStat>) -> Vec<u32> {
+    let mut pids: HashSet<u32> = HashSet::new();
+    if stats.contains_key(&leader) {
+        pids.extend(tree_pids(leader, &children_index(stats)));
+    }
+    pids.extend(
+        stats
+            .values()
+            .filter(|s| s.session == leader || s.pgrp == leader)
+            .map(|s| s.pid),
+    );
+    let mut out: Vec<u32> = pids
+        .into_iter()
+        .filter(|pid| stats.get(pid).is_some_and(|s| s.state != 'Z'))
+        .collect();
+    out.sort_unstable();
+    out
+}
+
+/// Whether `pid` still exists as something other than a zombie.
+fn is_running(pid: u32) -> bool {
+    match std::fs::read_to_string(format!("/proc/{}/stat", pid)) {
+        Ok(s) => parse_stat(&s).is_some_and(|st| st.state != 'Z'),
+        // No /proc (or it vanished): fall back to a null signal.
+        Err(_) => signal::kill(Pid::from_raw(pid as i32), None).is_ok(),
+    }
+}
+
+/// Send `sig` to the session led by `leader`, scoped by `target`. Returns
+/// the pids signalled (as far as `/proc` shows them; a process group or
+/// child that `/proc` can't see is still signalled).
+pub fn signal_session(leader: u32, sig: Signal, target: SignalTarget) -> Vec<u32> {
+    let leader_pid = Pid::from_raw(leader as i32);
+    match target {
+        SignalTarget::Child => {
+            let _ = signal::kill(leader_pid, sig);
+            vec![leader]
+        }
+        SignalTarget::Pgrp => {
+            let _ = signal::killpg(leader_pid, sig);
+            let mut pids: Vec<u32> = snapshot()
+                .values()
+                .filter(|s| s.pgrp == leader && s.state != 'Z')
+                .map(|s| s.pid)
+                .collect();
+            pids.sort_unstable();
+            pids
+        }
+        SignalTarget::Tree => {
+            let members = session_members(leader, &snapshot());
+            let _ = signal::killpg(leader_pid, sig);
+            for pid in &members {
+                let _ = signal::kill(Pid::from_raw(*pid as i32), sig);
+            }
+            members
+        }
+    }
+}
+
+/// How often `kill_tree` re-checks for exits during the grace period.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Send `sig` to every process of the session led by `leader`, wait up to
+/// `grace` for them to exit, then SIGKILL whatever is left. Returns the
+/// `(pid, comm)` of each process that outlived the grace period.
+pub async fn kill_tree(leader: u32, sig: Signal, grace: Duration) -> Vec<(u32, String)> {
+    let stats = snapshot();
+    // Remember the members up front: once the leader dies its children are
+    // reparented and the ppid links back to it are gone.
+    let members: Vec<(u32, String)> = session_members(leader, &stats)
+        .into_iter()
+        .map(|pid| (pid, stats[&pid].comm.clone()))
+        .collect();
+
+    let _ = signal::killpg(Pid::from_raw(leader as i32), sig);
+    let _ = signal::kill(Pid::from_raw(leader as i32), sig);
+    for (pid, _) in &members {
+        let _ = signal::kill(Pid::from_raw(*pid as i32), sig);
+    }
+    // A paused (SIGSTOPped) tree would sit on the signal until the grace
+    // period ran out; wake it so it can act on it, like the shell's `kill`
+    // does for stopped jobs.
+    if sig != Signal::SIGCONT {
+        let _ = signal::killpg(Pid::from_raw(leader as i32), Signal::SIGCONT);
+        for (pid, _) in &members {
+            let _ = signal::kill(Pid::from_raw(*pid as i32), Signal::SIGCONT);
+        }
+    }
+
+    let deadline = Instant::now() + grace;
+    let mut alive: Vec<(u32, String)> = members;
+    loop {
+        alive.retain(|(pid, _)| is_running(*pid));
+        if alive.is_empty() && !is_running(leader) {
+            break;
+        }
+        if Instant::now() >= deadline {
+            break;
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+
+    let _ = signal::killpg(Pid::from_raw(leader as i32), Signal::SIGKILL);
+    let _ = signal::kill(Pid::from_raw(leader as i32), Signal::SIGKILL);
+    for (pid, _) in &alive {
+        let _ = signal::kill(Pid::from_raw(*pid as i32), Signal::SIGKILL);
+    }
+    alive
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stat(pid: u32, ppid: u32, pgrp: u32, session: u32, state: char) -> ProcStat {
+        ProcStat {
+            pid,
+            comm: format!("p{}", pid),
+            state,
+            ppid,
+            pgrp,
+            session,
+            cpu_ticks: 0,
+            threads: 1,
+            rss_pages: 0,
+        }
+    }
+
+    #[test]
+    fn test_parse_stat_handles_spaces_in_comm() {
+        let line = "4242 (tmux: server (1)) S 1 4240 4239 0 -1 4194560 1 0 0 0 \
+                    7 3 0 0 20 0 5 0 100 12345678 321 18446744073709551615";
+        let stat = parse_stat(line).unwrap();
+        assert_eq!(stat.pid, 4242);
+        assert_eq!(stat.comm, "tmux: server (1)");
+        assert_eq!(stat.state, 'S');
+        assert_eq!((stat.ppid, stat.pgrp, stat.session), (1, 4240, 4239));
+        assert_eq!(stat.cpu_ticks, 10);
+        assert_eq!(stat.threads, 5);
+        assert_eq!(stat.rss_pages, 321);
+        assert!(parse_stat("garbage").is_none());
+    }
+
+    #[test]
+    fn test_tree_pids_walks_descendants_only() {
+        let mut children = HashMap::new();
+        children.insert(10, vec![11, 12]);
+        children.insert(12, vec![13]);
+        children.insert(1, vec![10, 99]);
+        let mut pids = tree_pids(10, &children);
+        pids.sort();
+        assert_eq!(pids, vec![10, 11, 12, 13]);
+    }
+
+    #[test]
+    fn test_session_members_includes_escaped_and_skips_zombies() {
+        let stats: HashMap<u32, ProcStat> = [
+            stat(1, 0, 1, 1, 'S'),
+            stat(10, 1, 10, 10, 'S'),
+            // Job moved into its own group by an interactive shell.
+            stat(11, 10, 11, 10, 'S'),
+            // Its child.
+            stat(12, 11, 11, 10, 'R'),
+            // Double-forked daemon: reparented to init, still in session.
+            stat(13, 1, 13, 10, 'S'),
+            // Child that called setsid(): only reachable through ppid.
+            stat(14, 12, 14, 14, 'S'),
+            // Exited, not yet reaped.
+            stat(15, 10, 10, 10, 'Z'),
+            // Unrelated.
+            stat(20, 1, 20, 20, 'S'),
+        ]
+        .into_iter()
+        .map(|s| (s.pid, s))
+        .collect();
+        assert_eq!(session_members(10, &stats), vec![10, 11, 12, 13, 14]);
+    }
+
+    #[tokio::test]
+    async fn test_kill_tree_reports_term_ignoring_survivor() {
+        use std::os::unix::process::CommandExt;
+        // A session leader whose child ignores SIGTERM.
+        let mut leader = unsafe {
+            std::process::Command::new("sh")
+                .args(["-c", "trap '' TERM; sh -c 'trap \"\" TERM; sleep 30' & wait"])
+                .pre_exec(|| {
+                    nix::unistd::setsid().map_err(std::io::Error::from)?;
+                    Ok(())
+                })
+                .spawn()
+                .unwrap()
+        };
+        let pid = leader.id();
+        // Let the inner shell start.
+        let deadline = Instant::now() + Duration::from_secs(2);
+        while session_members(pid, &snapshot()).len() < 3 && Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert!(session_members(pid, &snapshot()).len() >= 3);
+
+        let survivors = kill_tree(pid, Signal::SIGTERM, Duration::from_millis(300)).await;
+        assert!(
+            survivors.iter().any(|(p, _)| *p == pid),
+            "TERM-ignoring leader should be reported: {:?}",
+            survivors
+        );
+        assert!(survivors.len() >= 2, "{:?}", survivors);
+        let _ = leader.wait();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(session_members(pid, &snapshot()).is_empty());
+    }
+}
@@ -1,22 +1,59 @@
 use std::collections::HashMap;
 use std::sync::atomic::{AtomicU64, Ordering as AtomicOrdering};
+use std::sync::{Arc, Mutex as StdMutex, MutexGuard};
 
-use crate::daemon::session::Session;
-use crate::protocol::SessionInfo;
+use nix::sys::signal::Signal;
+use tokio::sync::{broadcast, Mutex};
+
+use crate::daemon::limits;
+use crate::daemon::proctree;
+use crate::daemon::session::{KillRequest, Session};
+use crate::daemon::telemetry::{self, UsageHistory};
+use crate::protocol::{
+    ResourceLimits, ResourceSample, SessionInfo, SessionSelector, SignalTarget, TerminalEvent,
+};
 
 static SESSION_COUNTER: AtomicU64 = AtomicU64::new(0);
 
+/// A registered session. Handlers lock it only long enough to read or
+/// change it (cloning out the channels they need), except a respawn, which
+/// holds it throughout so nobody sees a half-swapped session.
+pub type SessionHandle = Arc<Mutex<Session>>;
+
+/// The daemon's sessions, shared by every connection and background task.
+/// Clones share the same sessions.
+///
+/// The name → session map has a lock of its own that is only held for a
+/// lookup, insert or removal — never across an await, and never while
+/// waiting for a session — so it may be taken while holding a session's
+/// lock but not the other way round. A slow PTY write, a heavy capture or a
+/// respawn therefore only delays requests for that one session.
+#[derive(Clone)]
 pub struct Registry {
-    sessions: HashMap<String, Session>,
+    sessions: Arc<StdMutex<HashMap<String, SessionHandle>>>,
+    /// Every session's terminal events, tagged with the session name.
+    events: broadcast::Sender<(String, TerminalEvent)>,
 }
 
 impl Registry {
     pub fn new() -> Self {
         Self {
-            sessions: HashMap::new(),
+            sessions: Arc::new(StdMutex::new(HashMap::new())),
+            events: broadcast::channel(256).0,
         }
     }
 
+    /// The name → session map. Nothing that can panic runs under its lock,
+    /// so a poisoned lock still holds a consistent map.
+    fn map(&self) -> MutexGuard<'_, HashMap<String, SessionHandle>> {
+        self.sessions.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Terminal events from all sessions, including ones created later.
+    pub fn subscribe_events(&self) -> broadcast::Receiver<(String, TerminalEvent)> {
+        self.events.subscribe()
+    }
+
     /// Validate a session name: must be non-empty and contain only [a-zA-Z0-9_-].
     fn validate_name(name: &str) -> anyhow::Result<()> {
         if name.is_empty() {
@@ -36,9 +73,16 @@ impl Registry {
 
     /// Allocate a session name if none provided, and validate it.
     pub fn allocate_name(&self, requested: Option<String>) -> anyhow::Result<String> {
+        Self::allocate_in(&self.map(), requested)
+    }
+
+    fn allocate_in(
+        sessions: &HashMap<String, SessionHandle>,
+        requested: Option<String>,
+    ) -> anyhow::Result<String> {
         if let Some(name) = requested {
             Self::validate_name(&name)?;
-            if self.sessions.contains_key(&name) {
+            if sessions.contains_key(&name) {
                 anyhow::bail!("session '{}' already exists", name);
             }
             return Ok(name);
@@ -46,30 +90,88 @@ impl Registry {
         loop {
             let n = SESSION_COUNTER.fetch_add(1, AtomicOrdering::Relaxed);
             let name = n.to_string();
-            if !self.sessions.contains_key(&name) {
+            if !sessions.contains_key(&name) {
                 return Ok(name);
             }
         }
     }
 
     /// Create a new session.
+    #[allow(clippy::too_many_arguments)]
     pub fn create(
-        &mut self,
+        &self,
         name: Option<String>,
         cmd: &[String],
         cols: u16,
         rows: u16,
         env: Option<HashMap<String, String>>,
         cwd: Option<String>,
+        limits: Option<ResourceLimits>,
+        attention: Vec<String>,
     ) -> anyhow::Result<String> {
         let name = self.allocate_name(name)?;
-        let session = Session::spawn(name.clone(), cmd, cols, rows, env, cwd)?;
-        self.sessions.insert(name.clone(), session);
+        // Forking and exec'ing happens outside the map lock, so the name
+        // is checked again before the session goes in.
+        let mut session =
+            Session::spawn(name.clone(), cmd, cols, rows, env, cwd, limits, attention)?;
+        self.relay_events(&mut session);
+        let mut sessions = self.map();
+        if sessions.contains_key(&name) {
+            if let Some(kill_tx) = session.kill_tx.take() {
+                let _ = kill_tx.send(KillRequest::default());
+            }
+            anyhow::bail!("session '{}' already exists", name);
+        }
+        sessions.insert(name.clone(), Arc::new(Mutex::new(session)));
         Ok(name)
     }
 
-    /// Build a SessionInfo from a Session.
-    fn session_info(s: &Session, now: std::time::SystemTime) -> SessionInfo {
+    /// (Re)start relaying a session's terminal events to `subscribe_events`
+    /// under its current name. The task ends when the session is dropped.
+    fn relay_events(&self, session: &mut Session) {
+        if let Some(old) = session.events_relay.take() {
+            old.abort();
+        }
+        let mut rx = session.events_tx.subscribe();
+        let events = self.events.clone();
+        let session_name = session.name.clone();
+        session.events_relay = Some(tokio::spawn(async move {
+            loop {
+                match rx.recv().await {
+                    Ok(event) => {
+                        let _ = events.send((session_name.clone(), event));
+                    }
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => return,
+                }
+            }
+        }));
+    }
+
+    /// Give a session a new name. The program keeps the `AMUX_SESSION`
+    /// it was started with until it is respawned.
+    pub async fn rename(&self, name: &str, new_name: &str) -> anyhow::Result<()> {
+        let handle = {
+            let mut sessions = self.map();
+            if !sessions.contains_key(name) {
+                anyhow::bail!("session '{}' not found", name);
+            }
+            if name == new_name {
+                return Ok(());
+            }
+            Self::allocate_in(&sessions, Some(new_name.to_string()))?;
+            let handle = sessions.remove(name).expect("checked above");
+            sessions.insert(new_name.to_string(), handle.clone());
+            handle
+        };
+        let mut session = handle.lock().await;
+        session.name = new_name.to_string();
+        self.relay_events(&mut session);
+        Ok(())
+    }
+
+    /// Build the SessionInfo of session `name`.
+    fn session_info(name: &str, s: &Session, now: std::time::SystemTime) -> SessionInfo {
         let uptime_secs = now
             .duration_since(s.created_at)
             .unwrap_or_default()
@@ -90,8 +192,18 @@ impl Registry {
         let (rows, cols) = s.current_size.lock().map(|sz| *sz).unwrap_or((24, 80));
         let attach_count = s.attach_count.load(std::sync::atomic::Ordering::Relaxed);
         let respawn_count = s.respawn_count.load(std::sync::atomic::Ordering::Relaxed);
+        let (title, notifications) = s
+            .vterm
+            .lock()
+            .map(|vt| (vt.title().to_string(), vt.notifications()))
+            .unwrap_or_default();
+        let (usage, cpu_recent) = s
+            .usage
+            .lock()
+            .map(|h| (h.latest(), h.recent_cpu(telemetry::RECENT_CPU_SAMPLES)))
+            .unwrap_or_default();
         SessionInfo {
-            name: s.name.clone(),
+            name: name.to_string(),
             command: s.command.clone(),
             pid: s.child_pid.as_raw() as u32,
             alive: s.is_alive(),
@@ -105,58 +217,162 @@ impl Registry {
             cols,
             attach_count,
             respawn_count,
+            limits: s
+                .limits
+                .as_ref()
+                .map(|l| Box::new(limits::status(l, s.cgroup.as_ref(), s.child_pid.as_raw() as u32))),
+            usage,
+            cpu_recent,
+            paused: s.paused,
+            title,
+            notifications,
+            needs_attention: s.attention.needed(),
+            attention_count: s.attention.count(),
         }
     }
 
-    /// List all sessions.
-    pub fn list(&self) -> Vec<SessionInfo> {
-        let now = std::time::SystemTime::now();
-        self.sessions
-            .values()
-            .map(|s| Self::session_info(s, now))
+    /// Every session with its name, as of now.
+    pub fn handles(&self) -> Vec<(String, SessionHandle)> {
+        self.map()
+            .iter()
+            .map(|(name, handle)| (name.clone(), handle.clone()))
             .collect()
     }
 
+    /// Root pid and usage history of every live session, for the
+    /// telemetry sampler.
+    pub async fn usage_targets(&self) -> Vec<(u32, Arc<StdMutex<UsageHistory>>)> {
+        let mut targets = Vec::new();
+        for (_, handle) in self.handles() {
+            let s = handle.lock().await;
+            if s.is_alive() {
+                targets.push((s.child_pid.as_raw() as u32, s.usage.clone()));
+            }
+        }
+        targets
+    }
+
+    /// Full usage history of a session, oldest sample first.
+    pub async fn usage_history(&self, name: &str) -> Option<Vec<ResourceSample>> {
+        let handle = self.get(name)?;
+        let usage = handle.lock().await.usage.clone();
+        let samples = usage.lock().map(|h| h.samples()).unwrap_or_default();
+        Some(samples)
+    }
+
+    /// List all sessions. Each is locked in turn, so a session that is busy
+    /// (respawning) only delays the list by as long as it stays busy.
+    pub async fn list(&self) -> Vec<SessionInfo> {
+        let now = std::time::SystemTime::now();
+        let mut list = Vec::new();
+        for (name, handle) in self.handles() {
+            list.push(Self::session_info(&name, &*handle.lock().await, now));
+        }
+        list
+    }
+
     /// Get detailed info for a single session.
-    pub fn info(&self, name: &str) -> Option<SessionInfo> {
+    pub async fn info(&self, name: &str) -> Option<SessionInfo> {
         let now = std::time::SystemTime::now();
-        self.sessions.get(name).map(|s| Self::session_info(s, now))
+        let handle = self.get(name)?;
+        let session = handle.lock().await;
+        Some(Self::session_info(name, &session, now))
     }
 
     /// Kill a session by name.
-    pub fn kill(&mut self, name: &str) -> anyhow::Result<()> {
-        let mut session = self
-            .sessions
+    pub async fn kill(&self, name: &str, req: KillRequest) -> anyhow::Result<()> {
+        let handle = self
+            .map()
             .remove(name)
             .ok_or_else(|| anyhow::anyhow!("session '{}' not found", name))?;
-        if let Some(kill_tx) = session.kill_tx.take() {
-            let _ = kill_tx.send(());
+        if let Some(kill_tx) = handle.lock().await.kill_tx.take() {
+            let _ = kill_tx.send(req);
         }
         Ok(())
     }
 
+    /// Send `sig` to a live session, tracking pause state for stop/continue
+    /// signals. Returns the pids signalled.
+    pub async fn signal(
+        &self,
+        name: &str,
+        sig: Signal,
+        target: SignalTarget,
+    ) -> anyhow::Result<Vec<u32>> {
+        let handle = self
+            .get(name)
+            .ok_or_else(|| anyhow::anyhow!("session '{}' not found", name))?;
+        let mut session = handle.lock().await;
+        if !session.is_alive() {
+            anyhow::bail!("session '{}' has exited", name);
+        }
+        let pids = proctree::signal_session(session.child_pid.as_raw() as u32, sig, target);
+        match sig {
+            Signal::SIGSTOP | Signal::SIGTSTP => session.paused = true,
+            Signal::SIGCONT => session.paused = false,
+            _ => {}
+        }
+        Ok(pids)
+    }
+
     /// Kill all sessions. Returns the number killed.
-    pub fn kill_all(&mut self) -> usize {
-        let names: Vec<String> = self.sessions.keys().cloned().collect();
-        let count = names.len();
-        for name in &names {
-            if let Some(mut session) = self.sessions.remove(name) {
-                if let Some(kill_tx) = session.kill_tx.take() {
-                    let _ = kill_tx.send(());
-                }
+    pub async fn kill_all(&self) -> usize {
+        let handles: Vec<SessionHandle> = self.map().drain().map(|(_, handle)| handle).collect();
+        let count = handles.len();
+        for handle in handles {
+            if let Some(kill_tx) = handle.lock().await.kill_tx.take() {
+                let _ = kill_tx.send(KillRequest::default());
             }
         }
         count
     }
 
+    /// Resolve a selector to session names, sorted. `All` and `Pattern`
+    /// only yield live sessions; `Names` is returned as given (deduplicated)
+    /// so callers can report missing or dead sessions individually.
+    pub async fn select(&self, selector: &SessionSelector) -> Vec<String> {
+        let mut names: Vec<String> = match selector {
+            SessionSelector::Names(names) => names.clone(),
+            SessionSelector::All | SessionSelector::Pattern(_) => {
+                let mut names = Vec::new();
+                for (name, handle) in self.handles() {
+                    let wanted = match selector {
+                        SessionSelector::Pattern(pattern) => glob_match(pattern, &name),
+                        _ => true,
+                    };
+                    if wanted && handle.lock().await.is_alive() {
+                        names.push(name);
+                    }
+                }
+                names
+            }
+        };
+        names.sort();
+        names.dedup();
+        names
+    }
+
     /// Get a session by name.
-    pub fn get(&self, name: &str) -> Option<&Session> {
-        self.sessions.get(name)
+    pub fn get(&self, name: &str) -> Option<SessionHandle> {
+        self.map().get(name).cloned()
+    }
+
+    /// Run `f` on session `name` under its lock, or `None` if there is no
+    /// such session. Anything slow belongs after `f` returns, working on
+    /// what `f` cloned out.
+    pub async fn with_session<T>(
+        &self,
+        name: &str,
+        f: impl FnOnce(&mut Session) -> T,
+    ) -> Option<T> {
+        let handle = self.get(name)?;
+        let mut session = handle.lock().await;
+        Some(f(&mut session))
     }
 
-    /// Get a mutable session by name.
-    pub fn get_mut(&mut self, name: &str) -> Option<&mut Session> {
-        self.sessions.get_mut(name)
+    /// Whether a session called `name` exists.
+    pub fn contains(&self, name: &str) -> bool {
+        self.map().contains_key(name)
     }
 
     /// Re-probe each session's child via `waitpid(WNOHANG)` and reap any
@@ -169,11 +385,12 @@ impl Registry {
     /// (only if not already set, to avoid clobbering values written by the
     /// session's own io_loop). Returns the names of sessions whose
     /// children were reaped here.
-    pub fn probe_after_resume(&self) -> Vec<String> {
+    pub async fn probe_after_resume(&self) -> Vec<String> {
         use nix::sys::wait::{waitpid, WaitPidFlag, WaitStatus};
         let now = std::time::SystemTime::now();
         let mut reaped = Vec::new();
-        for (name, session) in &self.sessions {
+        for (name, handle) in self.handles() {
+            let session = handle.lock().await;
             let code = match waitpid(session.child_pid, Some(WaitPidFlag::WNOHANG)) {
                 Ok(WaitStatus::Exited(_, code)) => Some(code),
                 Ok(WaitStatus::Signaled(_, sig, _)) => Some(128 + sig as i32),
@@ -189,38 +406,74 @@ impl Registry {
                     *da = Some(now);
                 }
             }
-            reaped.push(name.clone());
+            reaped.push(name);
         }
         reaped
     }
 
     /// Remove dead sessions that have been dead for longer than the retention period.
     /// Dead sessions are kept for 5 minutes so their exit codes remain visible in `ls`.
-    pub fn reap_dead(&mut self) -> Vec<String> {
+    pub async fn reap_dead(&self) -> Vec<String> {
         let now = std::time::SystemTime::now();
         let retention = std::time::Duration::from_secs(300); // 5 minutes
-        let dead: Vec<String> = self
-            .sessions
-            .iter()
-            .filter(|(_, s)| {
-                if s.is_alive() {
-                    return false;
-                }
-                // Only reap if died_at is set and older than retention period.
-                match s.died_at.lock().ok().and_then(|da| *da) {
-                    Some(died) => now.duration_since(died).unwrap_or_default() > retention,
-                    None => false, // Keep if died_at not yet set (race with io_loop).
-                }
-            })
-            .map(|(k, _)| k.clone())
-            .collect();
-        for name in &dead {
-            self.sessions.remove(name);
+        let mut dead = Vec::new();
+        for (name, handle) in self.handles() {
+            // Decide and remove under the session's lock, so a respawn
+            // can't revive it in between.
+            let session = handle.lock().await;
+            if session.is_alive() {
+                continue;
+            }
+            // Only reap if died_at is set and older than retention period.
+            let expired = match session.died_at.lock().ok().and_then(|da| *da) {
+                Some(died) => now.duration_since(died).unwrap_or_default() > retention,
+                None => false, // Keep if died_at not yet set (race with io_loop).
+            };
+            if !expired {
+                continue;
+            }
+            let mut sessions = self.map();
+            // Unless it was renamed or killed meanwhile.
+            if sessions
+                .get(&name)
+                .is_some_and(|current| Arc::ptr_eq(current, &handle))
+            {
+                sessions.remove(&name);
+                dead.push(name);
+            }
         }
         dead
     }
 }
 
+/// Match `name` against a shell-style glob supporting `*` (any run of
+/// characters) and `?` (exactly one). Session names are restricted to
+/// `[A-Za-z0-9_-]`, so there is no escaping or character-class syntax.
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let p: Vec<char> = pattern.chars().collect();
+    let n: Vec<char> = name.chars().collect();
+    let (mut pi, mut ni) = (0, 0);
+    // Position of the last `*` and the name index it was tried against,
+    // for backtracking.
+    let mut star: Option<(usize, usize)> = None;
+    while ni < n.len() {
+        if pi < p.len() && (p[pi] == '?' || p[pi] == n[ni]) {
+            pi += 1;
+            ni += 1;
+        } else if pi < p.len() && p[pi] == '*' {
+            star = Some((pi, ni));
+            pi += 1;
+        } else if let Some((sp, sn)) = star {
+            pi = sp + 1;
+            ni = sn + 1;
+            star = Some((sp, sn + 1));
+        } else {
+            return false;
+        }
+    }
+    p[pi..].iter().all(|&c| c == '*')
+}
+
 /// Format a SystemTime as an ISO 8601 UTC string (no external deps).
 fn format_system_time(t: std::time::SystemTime) -> String {
     let dur = t
@@ -326,7 +579,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_create_named_session() {
-        let mut reg = Registry::new();
+        let reg = Registry::new();
         let name = reg
             .create(
                 Some("test-named".to_string()),
@@ -335,6 +588,8 @@ mod tests {
                 24,
                 None,
                 None,
+                None,
+                Vec::new(),
             )
             .unwrap();
         assert_eq!(name, "test-named");
@@ -346,7 +601,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_create_duplicate_name_fails() {
-        let mut reg = Registry::new();
+        let reg = Registry::new();
         reg.create(
             Some("dup-test".to_string()),
             &["echo".to_string()],
@@ -354,6 +609,8 @@ mod tests {
             24,
             None,
             None,
+            None,
+            Vec::new(),
         )
         .unwrap();
 
@@ -365,6 +622,8 @@ mod tests {
                 24,
                 None,
                 None,
+                None,
+                Vec::new(),
             )
             .unwrap_err();
         assert!(err.to_string().contains("already exists"));
@@ -372,7 +631,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_named_session_appears_in_list() {
-        let mut reg = Registry::new();
+        let reg = Registry::new();
         reg.create(
             Some("listed-session".to_string()),
             &["echo".to_string()],
@@ -380,16 +639,18 @@ mod tests {
             24,
             None,
             None,
+            None,
+            Vec::new(),
         )
         .unwrap();
 
-        let list = reg.list();
+        let list = reg.list().await;
         assert!(list.iter().any(|s| s.name == "listed-session"));
     }
 
     #[tokio::test]
     async fn test_named_session_info_lookup() {
-        let mut reg = Registry::new();
+        let reg = Registry::new();
         reg.create(
             Some("info-test".to_string()),
             &["echo".to_string()],
@@ -397,17 +658,19 @@ mod tests {
             24,
             None,
             None,
+            None,
+            Vec::new(),
         )
         .unwrap();
 
-        let info = reg.info("info-test").unwrap();
+        let info = reg.info("info-test").await.unwrap();
         assert_eq!(info.name, "info-test");
         assert!(info.alive);
     }
 
     #[tokio::test]
     async fn test_probe_after_resume_reaps_exited_child() {
-        let mut reg = Registry::new();
+        let reg = Registry::new();
         reg.create(
             Some("probe-test".to_string()),
             // `true` exits with status 0 immediately.
@@ -416,6 +679,8 @@ mod tests {
             24,
             None,
             None,
+            None,
+            Vec::new(),
         )
         .unwrap();
 
@@ -431,10 +696,11 @@ mod tests {
         // Give the child time to exit and become a zombie.
         tokio::time::sleep(std::time::Duration::from_millis(200)).await;
 
-        let reaped = reg.probe_after_resume();
+        let reaped = reg.probe_after_resume().await;
         // Either we reaped it here, or the io_loop already did — both are
         // legal outcomes. What matters is that exit_code is populated.
-        let session = reg.get("probe-test").expect("session still present");
+        let handle = reg.get("probe-test").expect("session still present");
+        let session = handle.lock().await;
         // Wait briefly for either path to populate exit_code.
         let mut got_code = None;
         for _ in 0..50 {
@@ -461,7 +727,7 @@ mod tests {
         // watchdog would race io_loop and either could overwrite the
         // other with stale data — see the matching `if ec.is_none()`
         // checks in registry::probe_after_resume and session::io_loop.
-        let mut reg = Registry::new();
+        let reg = Registry::new();
         reg.create(
             Some("preserve".to_string()),
             &["true".to_string()],
@@ -469,6 +735,8 @@ mod tests {
             24,
             None,
             None,
+            None,
+            Vec::new(),
         )
         .unwrap();
 
@@ -478,14 +746,16 @@ mod tests {
         // Inject a sentinel value as if io_loop had already recorded it.
         let sentinel = std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(42);
         {
-            let session = reg.get("preserve").expect("session present");
+            let handle = reg.get("preserve").expect("session present");
+            let session = handle.lock().await;
             *session.exit_code.lock().unwrap() = Some(99);
             *session.died_at.lock().unwrap() = Some(sentinel);
         }
 
-        let _ = reg.probe_after_resume();
+        let _ = reg.probe_after_resume().await;
 
-        let session = reg.get("preserve").expect("session still present");
+        let handle = reg.get("preserve").expect("session still present");
+        let session = handle.lock().await;
         assert_eq!(
             *session.exit_code.lock().unwrap(),
             Some(99),
@@ -500,7 +770,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_probe_after_resume_skips_alive_child() {
-        let mut reg = Registry::new();
+        let reg = Registry::new();
         reg.create(
             Some("probe-alive".to_string()),
             &["sleep".to_string(), "60".to_string()],
@@ -508,21 +778,23 @@ mod tests {
             24,
             None,
             None,
+            None,
+            Vec::new(),
         )
         .unwrap();
-        let reaped = reg.probe_after_resume();
+        let reaped = reg.probe_after_resume().await;
         assert!(
             reaped.is_empty(),
             "alive child must not be reaped: {:?}",
             reaped
         );
         // Cleanup.
-        let _ = reg.kill("probe-alive");
+        let _ = reg.kill("probe-alive", KillRequest::default()).await;
     }
 
     #[tokio::test]
     async fn test_kill_named_session() {
-        let mut reg = Registry::new();
+        let reg = Registry::new();
         reg.create(
             Some("kill-me".to_string()),
             &["sleep".to_string(), "60".to_string()],
@@ -530,11 +802,115 @@ mod tests {
             24,
             None,
             None,
+            None,
+            Vec::new(),
         )
         .unwrap();
 
         assert!(reg.get("kill-me").is_some());
-        reg.kill("kill-me").unwrap();
+        reg.kill("kill-me", KillRequest::default()).await.unwrap();
         assert!(reg.get("kill-me").is_none());
     }
+
+    #[tokio::test]
+    async fn test_rename_session_moves_events() {
+        let reg = Registry::new();
+        for name in ["old", "taken"] {
+            reg.create(
+                Some(name.to_string()),
+                &[
+                    "sh".to_string(),
+                    "-c".to_string(),
+                    r"sleep 0.5; printf '\033]2;hello\007'; sleep 30".to_string(),
+                ],
+                80,
+                24,
+                None,
+                None,
+                None,
+                Vec::new(),
+            )
+            .unwrap();
+        }
+        let mut events = reg.subscribe_events();
+
+        assert!(reg.rename("old", "taken").await.is_err());
+        assert!(reg.rename("old", "bad name").await.is_err());
+        assert!(reg.rename("missing", "x").await.is_err());
+        reg.rename("old", "new").await.unwrap();
+        assert!(reg.get("old").is_none());
+        assert_eq!(reg.get("new").unwrap().lock().await.name, "new");
+
+        let mut names = Vec::new();
+        while names.len() < 2 {
+            let (name, event) = tokio::time::timeout(std::time::Duration::from_secs(5), events.recv())
+                .await
+                .expect("no event within 5s")
+                .unwrap();
+            assert_eq!(
+                event,
+                TerminalEvent::TitleChanged {
+                    title: "hello".to_string()
+                }
+            );
+            names.push(name);
+        }
+        names.sort();
+        assert_eq!(names, vec!["new", "taken"]);
+        reg.kill_all().await;
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("worker-*", "worker-1"));
+        assert!(glob_match("worker-*", "worker-"));
+        assert!(glob_match("*", "anything"));
+        assert!(glob_match("w?rker", "worker"));
+        assert!(glob_match("*-build-*", "api-build-2"));
+        assert!(glob_match("exact", "exact"));
+        assert!(!glob_match("worker-*", "builder-1"));
+        assert!(!glob_match("w?rker", "wrker"));
+        assert!(!glob_match("exact", "exactly"));
+        assert!(!glob_match("*-build", "api-build-2"));
+    }
+
+    #[tokio::test]
+    async fn test_select_sessions() {
+        let reg = Registry::new();
+        for name in ["sel-worker-b", "sel-worker-a", "sel-other"] {
+            reg.create(
+                Some(name.to_string()),
+                &["sleep".to_string(), "60".to_string()],
+                80,
+                24,
+                None,
+                None,
+                None,
+                Vec::new(),
+            )
+            .unwrap();
+        }
+
+        assert_eq!(
+            reg.select(&SessionSelector::Pattern("sel-worker-*".to_string())).await,
+            vec!["sel-worker-a", "sel-worker-b"]
+        );
+        assert_eq!(
+            reg.select(&SessionSelector::All).await,
+            vec!["sel-other", "sel-worker-a", "sel-worker-b"]
+        );
+        // Explicit names pass through (deduplicated) even when unknown, so
+        // the caller can report them.
+        assert_eq!(
+            reg.select(&SessionSelector::Names(vec![
+                "nope".to_string(),
+                "sel-other".to_string(),
+                "nope".to_string(),
+            ]))
+            .await,
+            vec!["nope", "sel-other"]
+        );
+
+        reg.kill_all().await;
+    }
 }
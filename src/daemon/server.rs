@@ -1,11 +1,44 @@
-use std::sync::Arc;
+use std::time::Duration;
 
+use bytes::Bytes;
 use tokio::net::{UnixListener, UnixStream};
-use tokio::sync::{broadcast, Mutex};
+use tokio::sync::{broadcast, mpsc};
 
+use nix::sys::signal::Signal;
+
+use crate::daemon::session::{KillRequest, PtyInput};
+use crate::daemon::fanout::{self, ClientFeed, Feed};
+use crate::daemon::{keys, metrics, mux, pacing, paste};
 use crate::daemon::registry::Registry;
-use crate::protocol::codec::{try_read_frame_async, write_frame_async};
-use crate::protocol::messages::{CaptureMode, ClientMessage, DaemonMessage};
+use crate::protocol::codec::{
+    split_chunks, try_read_frame_async, write_frame_async, write_frame_compressed_async,
+    write_output_frame_async, Reassembly, CHUNK_SIZE,
+};
+use crate::protocol::messages::{
+    CaptureMode, ClientMessage, Compression, DaemonMessage, InputDelivery, InputPacing,
+    KillSurvivor, SessionInfo,
+};
+use crate::protocol::session_list::{PreviewUpdate, SessionListDiff};
+
+/// How often a `SubscribeSessionList` client is sent what drifted on its
+/// own (uptimes, idle times, usage). Terminal events and output from the
+/// previewed session push an update sooner.
+const SESSION_LIST_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Minimum gap between session-list updates, so a session flooding its
+/// terminal doesn't turn into a flood of updates.
+const SESSION_LIST_MIN_GAP: Duration = Duration::from_millis(30);
+
+/// Minimum gap between preview renders woken by the previewed session's
+/// output. Each render walks the session's vterm under its lock.
+const PREVIEW_MIN_GAP: Duration = Duration::from_millis(100);
+
+/// Most preview lines a subscriber can ask for; a preview is a screenful.
+const MAX_PREVIEW_LINES: u32 = 200;
+
+/// Most bytes of preview lines per update, keeping every
+/// `SessionListUpdate` well inside a frame however wide the session is.
+const MAX_PREVIEW_BYTES: usize = 256 * 1024;
 
 /// Strip CSI escape sequences (ESC `[` ... final-byte) from `bytes`. The
 /// final byte of a CSI sequence is in the range 0x40..=0x7E. Used for
@@ -31,8 +64,14 @@ fn strip_csi_escapes(bytes: &[u8]) -> Vec<u8> {
     out
 }
 
-pub async fn run_server(listener: UnixListener, shutdown_tx: broadcast::Sender<()>) {
-    let registry = Arc::new(Mutex::new(Registry::new()));
+/// Serve `listener` until shutdown, with the metrics exporter on
+/// `metrics_addr` if given (`start-server --metrics`).
+pub async fn run_server(
+    listener: UnixListener,
+    shutdown_tx: broadcast::Sender<()>,
+    metrics_addr: Option<String>,
+) {
+    let registry = Registry::new();
     let mut shutdown_rx = shutdown_tx.subscribe();
 
     // Spawn the suspension-aware watchdog. It detects macOS App Nap /
@@ -44,6 +83,20 @@ pub async fn run_server(listener: UnixListener, shutdown_tx: broadcast::Sender<(
         crate::daemon::watchdog::run(registry_watchdog).await;
     });
 
+    // Sample each session's process tree for `info`/`top`/`stats`.
+    let registry_telemetry = registry.clone();
+    tokio::spawn(async move {
+        crate::daemon::telemetry::run(registry_telemetry).await;
+    });
+
+    // Optional metrics exporter (`start-server --metrics`).
+    if let Some(addr) = metrics_addr {
+        let registry_metrics = registry.clone();
+        tokio::spawn(async move {
+            crate::daemon::metrics::run(addr, registry_metrics).await;
+        });
+    }
+
     loop {
         tokio::select! {
             accept_result = listener.accept() => {
@@ -52,7 +105,7 @@ pub async fn run_server(listener: UnixListener, shutdown_tx: broadcast::Sender<(
                         let registry = registry.clone();
                         let shutdown = shutdown_tx.clone();
                         tokio::spawn(async move {
-                            handle_connection(stream, registry, shutdown).await;
+                            handle_connection(stream, registry, shutdown, false).await;
                         });
                     }
                     Err(e) => {
@@ -63,26 +116,41 @@ pub async fn run_server(listener: UnixListener, shutdown_tx: broadcast::Sender<(
             _ = shutdown_rx.recv() => {
                 tracing::info!("shutdown signal received");
                 // Kill all sessions.
-                let mut reg = registry.lock().await;
-                let sessions = reg.list();
-                for s in &sessions {
-                    let _ = reg.kill(&s.name);
-                }
+                registry.kill_all().await;
                 break;
             }
         }
     }
 }
 
+/// `handle_connection` as a named `Send` future, for multiplexed channels
+/// (`mux`), which are themselves served from inside `handle_connection`.
+pub(crate) fn serve_channel(
+    stream: UnixStream,
+    registry: Registry,
+    shutdown: broadcast::Sender<()>,
+) -> std::pin::Pin<Box<dyn std::future::Future<Output = ()> + Send>> {
+    Box::pin(handle_connection(stream, registry, shutdown, true))
+}
+
+/// Serve one client connection, or one channel of a multiplexed one
+/// (`channel`). A channel declines compression: its frames are re-framed
+/// by `mux` before they reach the socket.
 async fn handle_connection(
     stream: UnixStream,
-    registry: Arc<Mutex<Registry>>,
+    registry: Registry,
     shutdown: broadcast::Sender<()>,
+    channel: bool,
 ) {
     let (mut reader, mut writer) = stream.into_split();
+    let _connection = metrics::global().connection();
+    // Agreed by `Negotiate`, for the rest of the connection.
+    let mut compression: Option<Compression> = None;
+    // `InputChunk`s received so far, for the input request that follows.
+    let mut upload: Option<Reassembly> = None;
 
     loop {
-        let msg = match try_read_frame_async::<ClientMessage>(&mut reader).await {
+        let mut msg = match try_read_frame_async::<ClientMessage>(&mut reader).await {
             Some(Ok(msg)) => msg,
             Some(Err(e)) => {
                 tracing::debug!("read error: {}", e);
@@ -90,19 +158,65 @@ async fn handle_connection(
             }
             None => return, // Client disconnected.
         };
+        metrics::global().record_message(msg.kind());
+
+        if !matches!(msg, ClientMessage::InputChunk(_)) {
+            if let Some(chunks) = upload.take() {
+                let kind = msg.kind();
+                let failure = match (chunks.finish(), msg.input_mut()) {
+                    (Ok(mut uploaded), Some(data)) => {
+                        uploaded.append(data);
+                        *data = uploaded;
+                        None
+                    }
+                    (Err(e), _) => Some(format!("input upload failed: {}", e)),
+                    (Ok(_), None) => Some(format!("{} takes no uploaded input", kind)),
+                };
+                if let Some(e) = failure {
+                    let _ = write_frame_async(&mut writer, &DaemonMessage::Error(e)).await;
+                    continue;
+                }
+            }
+        }
 
         match msg {
             ClientMessage::Ping => {
                 let _ = write_frame_async(&mut writer, &DaemonMessage::Pong).await;
             }
+            ClientMessage::Negotiate { compression: offered } => {
+                // LZ4 is all there is; take it if offered.
+                compression = offered
+                    .into_iter()
+                    .find(|c| matches!(c, Compression::Lz4))
+                    .filter(|_| !channel);
+                let _ = write_frame_async(&mut writer, &DaemonMessage::Negotiated { compression })
+                    .await;
+            }
             ClientMessage::KillServer => {
                 let _ = write_frame_async(&mut writer, &DaemonMessage::Ok).await;
                 let _ = shutdown.send(());
                 return;
             }
-            ClientMessage::CreateSession { name, command, env, cwd, cols, rows } => {
-                let mut reg = registry.lock().await;
-                match reg.create(name, &command, cols.unwrap_or(80), rows.unwrap_or(24), env, cwd) {
+            ClientMessage::CreateSession {
+                name,
+                command,
+                env,
+                cwd,
+                cols,
+                rows,
+                limits,
+                attention,
+            } => {
+                match registry.create(
+                    name,
+                    &command,
+                    cols.unwrap_or(80),
+                    rows.unwrap_or(24),
+                    env,
+                    cwd,
+                    limits,
+                    attention,
+                ) {
                     Ok(name) => {
                         let _ = write_frame_async(
                             &mut writer,
@@ -120,14 +234,12 @@ async fn handle_connection(
                 }
             }
             ClientMessage::ListSessions => {
-                let reg = registry.lock().await;
-                let list = reg.list();
+                let list = registry.list().await;
                 let _ =
                     write_frame_async(&mut writer, &DaemonMessage::SessionList(list)).await;
             }
             ClientMessage::GetSessionInfo { name } => {
-                let reg = registry.lock().await;
-                match reg.info(&name) {
+                match registry.info(&name).await {
                     Some(info) => {
                         let _ = write_frame_async(
                             &mut writer,
@@ -144,52 +256,138 @@ async fn handle_connection(
                     }
                 }
             }
-            ClientMessage::KillSession { name } => {
-                let mut reg = registry.lock().await;
-                match reg.kill(&name) {
-                    Ok(()) => {
-                        let _ = write_frame_async(&mut writer, &DaemonMessage::Ok).await;
-                    }
-                    Err(e) => {
+            ClientMessage::GetSessionStats { name } => {
+                let resp = match registry.usage_history(&name).await {
+                    Some(samples) => DaemonMessage::SessionStats(samples),
+                    None => DaemonMessage::Error(format!("session '{}' not found", name)),
+                };
+                let _ = write_frame_async(&mut writer, &resp).await;
+            }
+            ClientMessage::SignalSession {
+                name,
+                signal,
+                target,
+            } => {
+                let resp = match Signal::try_from(signal) {
+                    Ok(sig) => match registry.signal(&name, sig, target).await {
+                        Ok(pids) => DaemonMessage::SignalSent { pids },
+                        Err(e) => DaemonMessage::Error(e.to_string()),
+                    },
+                    Err(_) => DaemonMessage::Error(format!("invalid signal number {}", signal)),
+                };
+                let _ = write_frame_async(&mut writer, &resp).await;
+            }
+            ClientMessage::KillSession {
+                name,
+                signal,
+                grace_ms,
+            } => {
+                let signal = match signal.map(Signal::try_from).transpose() {
+                    Ok(sig) => sig.unwrap_or(KillRequest::DEFAULT_SIGNAL),
+                    Err(_) => {
                         let _ = write_frame_async(
                             &mut writer,
-                            &DaemonMessage::Error(e.to_string()),
+                            &DaemonMessage::Error(format!(
+                                "invalid signal number {}",
+                                signal.unwrap_or_default()
+                            )),
                         )
                         .await;
+                        continue;
                     }
-                }
+                };
+                let (done_tx, done_rx) = tokio::sync::oneshot::channel();
+                let req = KillRequest {
+                    signal,
+                    grace: grace_ms
+                        .map(std::time::Duration::from_millis)
+                        .unwrap_or(KillRequest::DEFAULT_GRACE),
+                    done: Some(done_tx),
+                };
+                let result = registry.kill(&name, req).await;
+                let resp = match result {
+                    // The session is already out of the registry; wait for
+                    // its io_loop to finish the kill. A session whose child had already exited drops
+                    // the request, which reads as "nothing survived".
+                    Ok(()) => DaemonMessage::SessionKilled {
+                        survivors: done_rx
+                            .await
+                            .unwrap_or_default()
+                            .into_iter()
+                            .map(|(pid, command)| KillSurvivor { pid, command })
+                            .collect(),
+                    },
+                    Err(e) => DaemonMessage::Error(e.to_string()),
+                };
+                let _ = write_frame_async(&mut writer, &resp).await;
             }
             ClientMessage::KillAllSessions => {
-                let mut reg = registry.lock().await;
-                let count = reg.kill_all();
+                let count = registry.kill_all().await;
                 let _ = write_frame_async(
                     &mut writer,
                     &DaemonMessage::KilledSessions { count },
                 )
                 .await;
             }
-            ClientMessage::Attach { name, cols, rows } => {
+            ClientMessage::Attach { name, cols, rows, buffer } => {
                 // Attach takes ownership of reader/writer (connection is consumed).
-                handle_attach(reader, writer, registry.clone(), &name, cols, rows)
-                    .await;
+                handle_attach(
+                    reader,
+                    writer,
+                    registry.clone(),
+                    &name,
+                    cols,
+                    rows,
+                    buffer,
+                    compression,
+                )
+                .await;
                 return;
             }
-            ClientMessage::Follow { name } => {
+            ClientMessage::Follow { name, buffer } => {
                 // Follow takes ownership of the connection (read-only streaming).
-                handle_follow(reader, writer, registry.clone(), &name).await;
+                handle_follow(reader, writer, registry.clone(), &name, buffer, compression).await;
                 return;
             }
             ClientMessage::SendInput {
                 name,
-                data,
+                mut data,
                 newline,
+                pacing,
+                wait_written,
             } => {
-                let reg = registry.lock().await;
-                if let Some(session) = reg.get(&name) {
-                    let _ = session.input_tx.send(data).await;
-                    if newline {
-                        let _ = session.input_tx.send(vec![b'\r']).await;
+                // Clone the sender and drop the session lock before
+                // queueing: paced sends sleep between chunks and
+                // `wait_written` blocks on the PTY, neither of which may
+                // stall other clients.
+                let input_tx = registry
+                    .with_session(&name, |session| session.input_tx.clone())
+                    .await;
+                let resp = match input_tx {
+                    Some(input_tx) => {
+                        if newline {
+                            data.push(b'\r');
+                        }
+                        send_input(&input_tx, data, pacing, wait_written).await
                     }
+                    None => DaemonMessage::Error(format!("session '{}' not found", name)),
+                };
+                let _ = write_frame_async(&mut writer, &resp).await;
+            }
+            ClientMessage::SendKeys { name, keys } => {
+                let target = registry
+                    .with_session(&name, |session| {
+                        let app_cursor = session
+                            .vterm
+                            .lock()
+                            .map(|vt| vt.application_cursor())
+                            .unwrap_or(false);
+                        (session.input_tx.clone(), app_cursor)
+                    })
+                    .await;
+                if let Some((input_tx, app_cursor)) = target {
+                    let data = keys::encode_keys(&keys, app_cursor);
+                    let _ = input_tx.send(data.into()).await;
                     let _ =
                         write_frame_async(&mut writer, &DaemonMessage::InputSent).await;
                 } else {
@@ -200,60 +398,177 @@ async fn handle_connection(
                     .await;
                 }
             }
+            ClientMessage::PasteInput {
+                name,
+                data,
+                line_delay_ms,
+            } => {
+                // Grab what we need and release the session lock before
+                // pacing the fallback path — holding it across sleeps would
+                // stall every other client of the session.
+                let target = registry
+                    .with_session(&name, |session| {
+                        let bracketed = session
+                            .vterm
+                            .lock()
+                            .map(|vt| vt.bracketed_paste())
+                            .unwrap_or(false);
+                        (session.input_tx.clone(), bracketed)
+                    })
+                    .await;
+                match target {
+                    Some((input_tx, true)) => {
+                        let _ = input_tx.send(paste::bracketed(&data).into()).await;
+                        let _ =
+                            write_frame_async(&mut writer, &DaemonMessage::InputSent).await;
+                    }
+                    Some((input_tx, false)) => {
+                        let delay = std::time::Duration::from_millis(line_delay_ms);
+                        for (i, line) in paste::lines(&data).into_iter().enumerate() {
+                            if i > 0 && !delay.is_zero() {
+                                tokio::time::sleep(delay).await;
+                            }
+                            let _ = input_tx.send(line.into()).await;
+                        }
+                        let _ =
+                            write_frame_async(&mut writer, &DaemonMessage::InputSent).await;
+                    }
+                    None => {
+                        let _ = write_frame_async(
+                            &mut writer,
+                            &DaemonMessage::Error(format!("session '{}' not found", name)),
+                        )
+                        .await;
+                    }
+                }
+            }
+            ClientMessage::BroadcastInput {
+                target,
+                data,
+                newline,
+                keys,
+            } => {
+                // Resolve targets under the session locks, deliver outside
+                // them.
+                let mut targets: Vec<(String, Result<_, String>)> = Vec::new();
+                for name in registry.select(&target).await {
+                    let tx = registry
+                        .with_session(&name, |session| match session.is_alive() {
+                            true => {
+                                let app_cursor = session
+                                    .vterm
+                                    .lock()
+                                    .map(|vt| vt.application_cursor())
+                                    .unwrap_or(false);
+                                Ok((session.input_tx.clone(), app_cursor))
+                            }
+                            false => Err("session has exited".to_string()),
+                        })
+                        .await
+                        .unwrap_or_else(|| Err(format!("session '{}' not found", name)));
+                    targets.push((name, tx));
+                }
+                let mut results = Vec::with_capacity(targets.len());
+                for (session, tx) in targets {
+                    let error = match tx {
+                        Ok((tx, app_cursor)) => {
+                            let mut input = data.clone();
+                            input.extend(keys::encode_keys(&keys, app_cursor));
+                            if newline {
+                                input.push(b'\r');
+                            }
+                            tx.send(input.into())
+                                .await
+                                .err()
+                                .map(|_| "session is no longer accepting input".to_string())
+                        }
+                        Err(e) => Some(e),
+                    };
+                    results.push(InputDelivery { session, error });
+                }
+                let _ = write_frame_async(&mut writer, &DaemonMessage::BroadcastResult(results))
+                    .await;
+            }
             ClientMessage::HasSession { name } => {
-                let reg = registry.lock().await;
-                let exists = reg.get(&name).is_some();
+                let exists = registry.contains(&name);
                 let _ =
                     write_frame_async(&mut writer, &DaemonMessage::SessionExists(exists)).await;
             }
             ClientMessage::CaptureScrollback { name, lines, mode } => {
-                let reg = registry.lock().await;
-                if let Some(session) = reg.get(&name) {
-                    let data = match mode {
-                        CaptureMode::Raw => session
-                            .scrollback
-                            .lock()
-                            .map(|sb| sb.last_lines(lines))
-                            .unwrap_or_default(),
-                        // Plain and Formatted snapshot the LIVE vt100 parser
-                        // (which has tracked every byte the agent has written)
-                        // rather than replaying the 64KB scrollback ring
-                        // through a fresh parser. The replay path produced
-                        // jitter against cursor-addressed redraws like claude:
-                        // once the ring evicted the most recent full UI
-                        // redraw, partial updates left most rows blank in the
-                        // fresh parser, and the captured screen shifted as
-                        // bytes flowed (bd-8w7). The live parser also keeps
-                        // its own scrollback for streaming output that has
-                        // scrolled past the screen (bd-pmk).
-                        CaptureMode::Plain => {
-                            let formatted = session
-                                .vterm
+                let buffers = registry
+                    .with_session(&name, |session| {
+                        (session.scrollback.clone(), session.vterm.clone())
+                    })
+                    .await;
+                let resp = match buffers {
+                    // A deep capture takes a while to render; do it off the
+                    // async workers and without the session's lock.
+                    Some((scrollback, vterm)) => {
+                        let render = tokio::task::spawn_blocking(move || match mode {
+                            CaptureMode::Raw => scrollback
+                                .lock()
+                                .map(|sb| sb.last_lines(lines))
+                                .unwrap_or_default(),
+                            // Plain and Formatted snapshot the LIVE vt100 parser
+                            // (which has tracked every byte the agent has written)
+                            // rather than replaying the 64KB scrollback ring
+                            // through a fresh parser. The replay path produced
+                            // jitter against cursor-addressed redraws like claude:
+                            // once the ring evicted the most recent full UI
+                            // redraw, partial updates left most rows blank in the
+                            // fresh parser, and the captured screen shifted as
+                            // bytes flowed (bd-8w7). The live parser also keeps
+                            // its own scrollback for streaming output that has
+                            // scrolled past the screen (bd-pmk).
+                            CaptureMode::Plain => {
+                                let formatted = vterm
+                                    .lock()
+                                    .map(|mut vt| vt.rendered_recent_formatted(lines))
+                                    .unwrap_or_default();
+                                strip_csi_escapes(&formatted)
+                            }
+                            CaptureMode::Formatted => vterm
                                 .lock()
                                 .map(|mut vt| vt.rendered_recent_formatted(lines))
-                                .unwrap_or_default();
-                            strip_csi_escapes(&formatted)
+                                .unwrap_or_default(),
+                            CaptureMode::Structured => vterm
+                                .lock()
+                                .ok()
+                                .and_then(|mut vt| serde_json::to_vec(&vt.structured(lines)).ok())
+                                .unwrap_or_default(),
+                        });
+                        DaemonMessage::CaptureOutput(render.await.unwrap_or_default())
+                    }
+                    None => DaemonMessage::Error(format!("session '{}' not found", name)),
+                };
+                match resp {
+                    DaemonMessage::CaptureOutput(data) if data.len() > CHUNK_SIZE => {
+                        for chunk in split_chunks(&data) {
+                            let msg = DaemonMessage::CaptureChunk(chunk);
+                            if write_frame_compressed_async(&mut writer, &msg, compression)
+                                .await
+                                .is_err()
+                            {
+                                break;
+                            }
                         }
-                        CaptureMode::Formatted => session
-                            .vterm
-                            .lock()
-                            .map(|mut vt| vt.rendered_recent_formatted(lines))
-                            .unwrap_or_default(),
-                    };
-                    let _ =
-                        write_frame_async(&mut writer, &DaemonMessage::CaptureOutput(data)).await;
-                } else {
-                    let _ = write_frame_async(
-                        &mut writer,
-                        &DaemonMessage::Error(format!("session '{}' not found", name)),
-                    )
-                    .await;
+                    }
+                    resp => {
+                        let _ = write_frame_compressed_async(&mut writer, &resp, compression).await;
+                    }
                 }
             }
+            ClientMessage::InputChunk(chunk) => {
+                // Errors surface when the upload's request arrives.
+                let _ = upload.get_or_insert_with(Reassembly::default).push(chunk);
+            }
             ClientMessage::SetEnv { name, key, value } => {
-                let mut reg = registry.lock().await;
-                if let Some(session) = reg.get_mut(&name) {
-                    session.env_vars.insert(key, value);
+                let found = registry
+                    .with_session(&name, |session| {
+                        session.env_vars.insert(key, value);
+                    })
+                    .await;
+                if found.is_some() {
                     let _ = write_frame_async(&mut writer, &DaemonMessage::Ok).await;
                 } else {
                     let _ = write_frame_async(
@@ -264,9 +579,10 @@ async fn handle_connection(
                 }
             }
             ClientMessage::GetEnv { name, key } => {
-                let reg = registry.lock().await;
-                if let Some(session) = reg.get(&name) {
-                    let value = session.env_vars.get(&key).cloned();
+                let value = registry
+                    .with_session(&name, |session| session.env_vars.get(&key).cloned())
+                    .await;
+                if let Some(value) = value {
                     let _ =
                         write_frame_async(&mut writer, &DaemonMessage::EnvValue(value)).await;
                 } else {
@@ -278,9 +594,10 @@ async fn handle_connection(
                 }
             }
             ClientMessage::GetAllEnv { name } => {
-                let reg = registry.lock().await;
-                if let Some(session) = reg.get(&name) {
-                    let vars = session.env_vars.clone();
+                let vars = registry
+                    .with_session(&name, |session| session.env_vars.clone())
+                    .await;
+                if let Some(vars) = vars {
                     let _ =
                         write_frame_async(&mut writer, &DaemonMessage::EnvVars(vars)).await;
                 } else {
@@ -293,29 +610,31 @@ async fn handle_connection(
             }
             ClientMessage::WaitSession { name, timeout_secs } => {
                 // Subscribe to session's exit watch to detect when io_loop exits.
-                let mut exit_rx = {
-                    let reg = registry.lock().await;
-                    match reg.get(&name) {
-                        Some(session) => {
-                            // If already dead, return immediately.
-                            if !session.is_alive() {
-                                let _ = write_frame_async(
-                                    &mut writer,
-                                    &DaemonMessage::SessionExited,
-                                )
-                                .await;
-                                continue;
-                            }
-                            session.exit_watch.clone()
-                        }
-                        None => {
+                let watched = registry
+                    .with_session(&name, |session| {
+                        (session.is_alive(), session.exit_watch.clone())
+                    })
+                    .await;
+                let mut exit_rx = match watched {
+                    Some((alive, exit_watch)) => {
+                        // If already dead, return immediately.
+                        if !alive {
                             let _ = write_frame_async(
                                 &mut writer,
-                                &DaemonMessage::Error(format!("session '{}' not found", name)),
+                                &DaemonMessage::SessionExited,
                             )
                             .await;
                             continue;
                         }
+                        exit_watch
+                    }
+                    None => {
+                        let _ = write_frame_async(
+                            &mut writer,
+                            &DaemonMessage::Error(format!("session '{}' not found", name)),
+                        )
+                        .await;
+                        continue;
                     }
                 };
 
@@ -363,9 +682,12 @@ async fn handle_connection(
                 }
             }
             ClientMessage::GetExitCode { name } => {
-                let reg = registry.lock().await;
-                if let Some(session) = reg.get(&name) {
-                    let code = session.exit_code.lock().ok().and_then(|ec| *ec);
+                let code = registry
+                    .with_session(&name, |session| {
+                        session.exit_code.lock().ok().and_then(|ec| *ec)
+                    })
+                    .await;
+                if let Some(code) = code {
                     let _ =
                         write_frame_async(&mut writer, &DaemonMessage::ExitCode(code)).await;
                 } else {
@@ -381,6 +703,53 @@ async fn handle_connection(
                 handle_watch(writer, registry.clone(), sessions).await;
                 return;
             }
+            ClientMessage::RenameSession { name, new_name } => {
+                let result = registry.rename(&name, &new_name).await;
+                let resp = match result {
+                    Ok(()) => DaemonMessage::Ok,
+                    Err(e) => DaemonMessage::Error(e.to_string()),
+                };
+                let _ = write_frame_async(&mut writer, &resp).await;
+            }
+            ClientMessage::WaitAttention {
+                sessions,
+                timeout_secs,
+            } => {
+                handle_wait_attention(&mut writer, registry.clone(), sessions, timeout_secs).await;
+            }
+            ClientMessage::WatchEvents { sessions } => {
+                // Takes ownership of the connection (streaming).
+                handle_watch_events(writer, registry.clone(), sessions).await;
+                return;
+            }
+            ClientMessage::SubscribeSessionList {
+                preview,
+                preview_lines,
+            } => {
+                // Takes ownership of the connection (streaming); the client
+                // keeps sending SetPreview on it.
+                handle_subscribe_session_list(
+                    reader,
+                    writer,
+                    registry.clone(),
+                    preview,
+                    preview_lines,
+                )
+                .await;
+                return;
+            }
+            ClientMessage::Tagged { .. } | ClientMessage::Cancel { .. } => {
+                // The connection is multiplexed from here on.
+                mux::serve(reader, writer, registry.clone(), shutdown.clone(), msg).await;
+                return;
+            }
+            ClientMessage::SetPreview { .. } => {
+                let _ = write_frame_async(
+                    &mut writer,
+                    &DaemonMessage::Error("SetPreview needs a SubscribeSessionList connection".into()),
+                )
+                .await;
+            }
             ClientMessage::WaitAny {
                 sessions,
                 timeout_secs,
@@ -388,9 +757,11 @@ async fn handle_connection(
                 handle_wait_any(&mut writer, registry.clone(), sessions, timeout_secs).await;
             }
             ClientMessage::ResizeSession { name, cols, rows } => {
-                let reg = registry.lock().await;
-                if let Some(session) = reg.get(&name) {
-                    let _ = session.resize_tx.send((cols, rows)).await;
+                let resize_tx = registry
+                    .with_session(&name, |session| session.resize_tx.clone())
+                    .await;
+                if let Some(resize_tx) = resize_tx {
+                    let _ = resize_tx.send((cols, rows)).await;
                     let _ = write_frame_async(&mut writer, &DaemonMessage::Ok).await;
                 } else {
                     let _ = write_frame_async(
@@ -401,15 +772,15 @@ async fn handle_connection(
                 }
             }
             ClientMessage::RespawnSession { name, command, cwd, env } => {
-                // Hold the registry lock for the entire respawn so the
-                // reaper, watchdog, and other clients see a coherent
-                // mid-swap state. respawn() is bounded (a few hundred ms
-                // at most: SIGKILL → waitpid → openpty → fork+exec) and
-                // it's a rare operation, so the brief serialization is
-                // acceptable. See bd-wh4 race-conditions section.
-                let mut reg = registry.lock().await;
-                let result = match reg.get_mut(&name) {
-                    Some(session) => session.respawn(&command, env, cwd).await,
+                // Hold the session's lock for the entire respawn so the
+                // reaper, watchdog, and the session's other clients see a
+                // coherent mid-swap state. respawn() is bounded (a few
+                // hundred ms at most: SIGKILL → waitpid → openpty →
+                // fork+exec) and other sessions aren't held up at all. See
+                // bd-wh4 race-conditions section.
+                let handle = registry.get(&name);
+                let result = match handle {
+                    Some(handle) => handle.lock().await.respawn(&command, env, cwd).await,
                     None => Err(anyhow::anyhow!("session '{}' not found", name)),
                 };
                 match result {
@@ -436,54 +807,98 @@ async fn handle_connection(
     }
 }
 
+/// Queue `data` on a session's input channel, optionally paced, and build
+/// the reply: `InputSent` once queued, or `InputWritten` once the PTY has
+/// accepted the final byte when `wait_written` is set.
+async fn send_input(
+    input_tx: &tokio::sync::mpsc::Sender<PtyInput>,
+    data: Vec<u8>,
+    pacing: Option<InputPacing>,
+    wait_written: bool,
+) -> DaemonMessage {
+    let total = data.len() as u64;
+    let chunks = match pacing {
+        Some(pacing) => pacing::plan(&data, &pacing),
+        None => vec![(data, std::time::Duration::ZERO)],
+    };
+    let last = chunks.len().saturating_sub(1);
+    let mut written_rx = None;
+    for (i, (chunk, pause)) in chunks.into_iter().enumerate() {
+        let mut input = PtyInput::from(chunk);
+        if wait_written && i == last {
+            let (tx, rx) = tokio::sync::oneshot::channel();
+            input.written = Some(tx);
+            written_rx = Some(rx);
+        }
+        if input_tx.send(input).await.is_err() {
+            return DaemonMessage::Error("session is no longer accepting input".to_string());
+        }
+        // No point pausing after the final chunk.
+        if i < last && !pause.is_zero() {
+            tokio::time::sleep(pause).await;
+        }
+    }
+    match written_rx {
+        Some(rx) => match rx.await {
+            Ok(()) => DaemonMessage::InputWritten { bytes: total },
+            Err(_) => DaemonMessage::Error("session exited before input was written".to_string()),
+        },
+        None => DaemonMessage::InputSent,
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 async fn handle_attach(
     mut reader: tokio::net::unix::OwnedReadHalf,
     mut writer: tokio::net::unix::OwnedWriteHalf,
-    registry: Arc<Mutex<Registry>>,
+    registry: Registry,
     name: &str,
     cols: u16,
     rows: u16,
+    buffer: Option<u32>,
+    compression: Option<Compression>,
 ) {
     // Get session handles (brief lock, no scrollback mutation needed).
     // Increment attach_count so `amux top` defers size control to us.
-    let (input_tx, mut output_rx, resize_tx, mut exit_rx, scrollback_data, attach_count) = {
-        let reg = registry.lock().await;
-        let session = match reg.get(name) {
-            Some(s) => s,
-            None => {
-                let _ = write_frame_async(
-                    &mut writer,
-                    &DaemonMessage::Error(format!("session '{}' not found", name)),
-                )
-                .await;
-                return;
-            }
-        };
+    let handles = registry
+        .with_session(name, |session| {
+            let input_tx = session.input_tx.clone();
+            // Start the client from a repaint of the live vterm rather than a
+            // replay of the raw scrollback ring: once the ring has evicted a
+            // TUI's last full redraw, replaying its partial updates paints
+            // garbage (the capture path hit the same problem, bd-8w7).
+            let (feed, screen) = ClientFeed::subscribe(
+                &session.output_tx,
+                session.vterm.clone(),
+                fanout::client_buffer(buffer),
+                |vt| vt.repaint(),
+            );
+            let resize_tx = session.resize_tx.clone();
+            let exit_rx = session.exit_watch.clone();
+            let attach_count = session.attach_count.clone();
+
+            attach_count.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            // Someone is looking at it now.
+            session.attention.clear();
 
-        let input_tx = session.input_tx.clone();
-        let output_rx = session.output_tx.subscribe();
-        let resize_tx = session.resize_tx.clone();
-        let exit_rx = session.exit_watch.clone();
-        let attach_count = session.attach_count.clone();
-        // Read scrollback from the session's Arc (short std::sync::Mutex lock).
-        let scrollback = session
-            .scrollback
-            .lock()
-            .map(|sb| sb.contents())
-            .unwrap_or_default();
-
-        attach_count.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-
-        (input_tx, output_rx, resize_tx, exit_rx, scrollback, attach_count)
+            (input_tx, feed, resize_tx, exit_rx, screen, attach_count)
+        })
+        .await;
+    let Some((input_tx, mut feed, resize_tx, mut exit_rx, screen, attach_count)) = handles else {
+        let _ = write_frame_async(
+            &mut writer,
+            &DaemonMessage::Error(format!("session '{}' not found", name)),
+        )
+        .await;
+        return;
     };
 
-    // Resize to client's terminal size (outside registry lock).
+    // Resize to client's terminal size (outside the session lock).
     let _ = resize_tx.send((cols, rows)).await;
 
-    // Send scrollback first.
-    if !scrollback_data.is_empty() {
-        let _ = write_frame_async(&mut writer, &DaemonMessage::Output(scrollback_data)).await;
-    }
+    // Paint the current screen first.
+    let _ = write_frame_compressed_async(&mut writer, &DaemonMessage::Output(screen), compression)
+        .await;
 
     // Spawn a dedicated reader task for client messages.
     // try_read_frame_async is NOT cancel-safe (two sequential read_exact calls),
@@ -494,6 +909,7 @@ async fn handle_attach(
         loop {
             match try_read_frame_async::<ClientMessage>(&mut reader).await {
                 Some(Ok(msg)) => {
+                    metrics::global().record_message(msg.kind());
                     if client_msg_tx.send(msg).await.is_err() {
                         break; // Receiver dropped, attach ended.
                     }
@@ -511,22 +927,11 @@ async fn handle_attach(
     loop {
         tokio::select! {
             // Output from PTY → client.
-            output = output_rx.recv() => {
-                match output {
-                    Ok(data) => {
-                        // Scrollback is now stored by io_loop in session.rs,
-                        // so no registry lock needed here.
-                        if write_frame_async(&mut writer, &DaemonMessage::Output(data)).await.is_err() {
-                            break; // Client disconnected.
-                        }
-                    }
-                    Err(broadcast::error::RecvError::Lagged(n)) => {
-                        tracing::warn!("output lagged by {} messages", n);
-                    }
-                    Err(broadcast::error::RecvError::Closed) => {
-                        let _ = write_frame_async(&mut writer, &DaemonMessage::SessionEnded).await;
-                        break;
-                    }
+            output = feed.recv() => {
+                // Scrollback is now stored by io_loop in session.rs,
+                // so no registry lock needed here.
+                if !forward_feed(&mut writer, &mut feed, output, compression).await {
+                    break; // Client disconnected or session ended.
                 }
             }
             // Session io_loop exited → notify client immediately.
@@ -543,7 +948,7 @@ async fn handle_attach(
                 match msg {
                     Some(ClientMessage::AttachInput(data)) => {
                         tracing::trace!("attach input: {} bytes", data.len());
-                        let _ = input_tx.send(data).await;
+                        let _ = input_tx.send(data.into()).await;
                     }
                     Some(ClientMessage::AttachResize { cols, rows }) => {
                         let _ = resize_tx.send((cols, rows)).await;
@@ -564,41 +969,73 @@ async fn handle_attach(
     attach_count.fetch_sub(1, std::sync::atomic::Ordering::Relaxed);
 }
 
+/// Send one `Feed` item to an attached/following client, along with any
+/// output queued behind it. Returns false once the stream is over (client
+/// gone or session ended).
+async fn forward_feed(
+    writer: &mut tokio::net::unix::OwnedWriteHalf,
+    feed: &mut ClientFeed,
+    item: Feed,
+    compression: Option<Compression>,
+) -> bool {
+    let msg = match item {
+        Feed::Output(first) => {
+            let batch = feed.batch(first);
+            return write_output_frame_async(writer, &batch, compression).await.is_ok();
+        }
+        Feed::Resync { skipped, screen } => DaemonMessage::Resync { skipped, screen },
+        Feed::Closed => {
+            let _ = write_frame_async(writer, &DaemonMessage::SessionEnded).await;
+            return false;
+        }
+    };
+    write_frame_async(writer, &msg).await.is_ok()
+}
+
 async fn handle_follow(
     reader: tokio::net::unix::OwnedReadHalf,
     mut writer: tokio::net::unix::OwnedWriteHalf,
-    registry: Arc<Mutex<Registry>>,
+    registry: Registry,
     name: &str,
+    buffer: Option<u32>,
+    compression: Option<Compression>,
 ) {
     // Get session output channel and exit watch (brief lock).
-    let (mut output_rx, mut exit_rx, scrollback_data) = {
-        let reg = registry.lock().await;
-        let session = match reg.get(name) {
-            Some(s) => s,
-            None => {
-                let _ = write_frame_async(
-                    &mut writer,
-                    &DaemonMessage::Error(format!("session '{}' not found", name)),
-                )
-                .await;
-                return;
-            }
-        };
-
-        let output_rx = session.output_tx.subscribe();
-        let exit_rx = session.exit_watch.clone();
-        let scrollback = session
-            .scrollback
-            .lock()
-            .map(|sb| sb.contents())
-            .unwrap_or_default();
+    let handles = registry
+        .with_session(name, |session| {
+            // Follow is a log view: start it with the screen and the parser's
+            // own scrollback as formatted lines, rendered from the live vterm
+            // for the same reason as attach.
+            let (feed, history) = ClientFeed::subscribe(
+                &session.output_tx,
+                session.vterm.clone(),
+                fanout::client_buffer(buffer),
+                |vt| {
+                    let mut lines = vt.rendered_recent_formatted(usize::MAX);
+                    if !lines.is_empty() {
+                        lines.push(b'\n');
+                    }
+                    lines
+                },
+            );
+            let exit_rx = session.exit_watch.clone();
 
-        (output_rx, exit_rx, scrollback)
+            (feed, exit_rx, history)
+        })
+        .await;
+    let Some((mut feed, mut exit_rx, history)) = handles else {
+        let _ = write_frame_async(
+            &mut writer,
+            &DaemonMessage::Error(format!("session '{}' not found", name)),
+        )
+        .await;
+        return;
     };
 
-    // Send scrollback first.
-    if !scrollback_data.is_empty() {
-        if write_frame_async(&mut writer, &DaemonMessage::Output(scrollback_data))
+    // Send the history first, in frames small enough for any reader.
+    for part in history.chunks(CHUNK_SIZE) {
+        let msg = DaemonMessage::Output(part.to_vec());
+        if write_frame_compressed_async(&mut writer, &msg, compression)
             .await
             .is_err()
         {
@@ -630,20 +1067,9 @@ async fn handle_follow(
     // Stream output to the client (read-only, no stdin forwarding).
     loop {
         tokio::select! {
-            output = output_rx.recv() => {
-                match output {
-                    Ok(data) => {
-                        if write_frame_async(&mut writer, &DaemonMessage::Output(data)).await.is_err() {
-                            break;
-                        }
-                    }
-                    Err(broadcast::error::RecvError::Lagged(n)) => {
-                        tracing::warn!("follow output lagged by {} messages", n);
-                    }
-                    Err(broadcast::error::RecvError::Closed) => {
-                        let _ = write_frame_async(&mut writer, &DaemonMessage::SessionEnded).await;
-                        break;
-                    }
+            output = feed.recv() => {
+                if !forward_feed(&mut writer, &mut feed, output, compression).await {
+                    break;
                 }
             }
             _ = exit_rx.changed() => {
@@ -663,7 +1089,7 @@ async fn handle_follow(
 
 async fn handle_watch(
     mut writer: tokio::net::unix::OwnedWriteHalf,
-    registry: Arc<Mutex<Registry>>,
+    registry: Registry,
     sessions: Vec<String>,
 ) {
     use std::collections::HashMap;
@@ -684,13 +1110,17 @@ async fn handle_watch(
         HashMap::new();
 
     {
-        let reg = registry.lock().await;
         for name in &sessions {
-            match reg.get(name) {
-                Some(session) => {
-                    if !session.is_alive() {
+            let watched = registry
+                .with_session(name, |session| {
+                    (session.is_alive(), session.exit_watch.clone(), session.exit_code.clone())
+                })
+                .await;
+            match watched {
+                Some((alive, exit_watch, exit_code)) => {
+                    if !alive {
                         // Already dead — send exit event immediately.
-                        let code = session.exit_code.lock().ok().and_then(|ec| *ec);
+                        let code = exit_code.lock().ok().and_then(|ec| *ec);
                         if write_frame_async(
                             &mut writer,
                             &DaemonMessage::WatchSessionExited {
@@ -704,10 +1134,7 @@ async fn handle_watch(
                             return; // Client disconnected.
                         }
                     } else {
-                        watchers.insert(
-                            name.clone(),
-                            (session.exit_watch.clone(), session.exit_code.clone()),
-                        );
+                        watchers.insert(name.clone(), (exit_watch, exit_code));
                     }
                 }
                 None => {
@@ -775,10 +1202,290 @@ async fn handle_watch(
     let _ = write_frame_async(&mut writer, &DaemonMessage::WatchDone).await;
 }
 
+/// Stream terminal events (titles, notifications) from `sessions`, or
+/// from every session when empty, until the client goes away.
+async fn handle_watch_events(
+    mut writer: tokio::net::unix::OwnedWriteHalf,
+    registry: Registry,
+    sessions: Vec<String>,
+) {
+    if let Some(missing) = sessions.iter().find(|name| !registry.contains(name)) {
+        let _ = write_frame_async(
+            &mut writer,
+            &DaemonMessage::Error(format!("session '{}' not found", missing)),
+        )
+        .await;
+        return;
+    }
+    let mut rx = registry.subscribe_events();
+
+    loop {
+        let (session, event) = match rx.recv().await {
+            Ok(item) => item,
+            Err(tokio::sync::broadcast::error::RecvError::Lagged(n)) => {
+                tracing::warn!("event watcher fell behind, dropped {} events", n);
+                continue;
+            }
+            Err(tokio::sync::broadcast::error::RecvError::Closed) => return,
+        };
+        if !sessions.is_empty() && !sessions.contains(&session) {
+            continue;
+        }
+        if write_frame_async(&mut writer, &DaemonMessage::SessionEvent { session, event })
+            .await
+            .is_err()
+        {
+            return; // Client disconnected.
+        }
+    }
+}
+
+/// Handle SubscribeSessionList: push the session list, then diffs of it,
+/// until the client disconnects.
+async fn handle_subscribe_session_list(
+    reader: tokio::net::unix::OwnedReadHalf,
+    writer: tokio::net::unix::OwnedWriteHalf,
+    registry: Registry,
+    preview: Option<String>,
+    preview_lines: u32,
+) {
+    // Reading a frame is not cancel-safe, so SetPreview messages come in
+    // on their own task rather than inside the select below.
+    let (control_tx, control_rx) = mpsc::channel(8);
+    let control = tokio::spawn(async move {
+        let mut reader = reader;
+        while let Some(Ok(msg)) = try_read_frame_async::<ClientMessage>(&mut reader).await {
+            if control_tx.send(msg).await.is_err() {
+                return;
+            }
+        }
+    });
+    push_session_list(writer, registry, control_rx, preview, preview_lines).await;
+    control.abort();
+}
+
+async fn push_session_list(
+    mut writer: tokio::net::unix::OwnedWriteHalf,
+    registry: Registry,
+    mut control: mpsc::Receiver<ClientMessage>,
+    mut preview: Option<String>,
+    mut preview_lines: u32,
+) {
+    let mut events = registry.subscribe_events();
+    // The previewed session's output, subscribed once it is found.
+    let mut output: Option<broadcast::Receiver<Bytes>> = None;
+    let mut sent: Vec<SessionInfo> = Vec::new();
+    let mut sent_preview: Option<(String, Vec<String>)> = None;
+    let mut first = true;
+    let mut tick = tokio::time::interval(SESSION_LIST_INTERVAL);
+    tick.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    // Whether the list may have changed since the last update. Output from
+    // the previewed session only changes the preview.
+    let mut list_due = true;
+
+    loop {
+        let mut lines = None;
+        let previewed = match preview.as_deref() {
+            Some(name) => {
+                registry
+                    .with_session(name, |session| {
+                        let rx = output.is_none().then(|| session.output_tx.subscribe());
+                        (rx, session.vterm.clone())
+                    })
+                    .await
+            }
+            None => None,
+        };
+        if let Some((rx, vterm)) = previewed {
+            if rx.is_some() {
+                output = rx;
+            }
+            // Rendered off the async workers, like CaptureScrollback.
+            let count = preview_lines.min(MAX_PREVIEW_LINES) as usize;
+            let render = tokio::task::spawn_blocking(move || {
+                vterm
+                    .lock()
+                    .map(|mut vt| vt.rendered_recent_formatted(count))
+                    .unwrap_or_default()
+            });
+            lines = Some(preview_text(&render.await.unwrap_or_default()));
+        }
+        let list = if list_due {
+            Some(registry.list().await)
+        } else {
+            None
+        };
+
+        let mut diff = match &list {
+            Some(list) => SessionListDiff::between(&sent, list),
+            None => SessionListDiff::default(),
+        };
+        if let (Some(name), Some(lines)) = (preview.as_ref(), lines) {
+            // A newly chosen preview is always sent, even when blank, so
+            // the client knows which session it shows.
+            let old = match &sent_preview {
+                Some((sent_name, old)) if sent_name == name => Some(old.as_slice()),
+                _ => None,
+            };
+            if old != Some(lines.as_slice()) {
+                diff.preview = Some(PreviewUpdate::between(name, old.unwrap_or(&[]), &lines));
+            }
+            sent_preview = Some((name.clone(), lines));
+        }
+        if first || !diff.is_empty() {
+            first = false;
+            if write_frame_async(&mut writer, &DaemonMessage::SessionListUpdate(diff))
+                .await
+                .is_err()
+            {
+                return; // Client disconnected.
+            }
+        }
+        if let Some(list) = list {
+            sent = list;
+        }
+
+        list_due = true;
+        tokio::select! {
+            _ = tick.tick() => {}
+            msg = control.recv() => match msg {
+                Some(ClientMessage::SetPreview { name, lines }) => {
+                    if name != preview {
+                        output = None;
+                        sent_preview = None;
+                    }
+                    preview = name;
+                    preview_lines = lines;
+                }
+                Some(other) => tracing::debug!("ignoring {} on a session-list subscription", other.kind()),
+                None => return, // Client disconnected.
+            },
+            event = events.recv() => {
+                if let Err(broadcast::error::RecvError::Closed) = event {
+                    return;
+                }
+            }
+            chunk = next_output(&mut output) => {
+                if chunk.is_none() {
+                    // Respawned or gone; resubscribe on the next update.
+                    output = None;
+                }
+                list_due = false;
+            }
+        }
+        let gap = if list_due { SESSION_LIST_MIN_GAP } else { PREVIEW_MIN_GAP };
+        tokio::time::sleep(gap).await;
+    }
+}
+
+/// Split a formatted render into preview lines, keeping the most recent
+/// ones within `MAX_PREVIEW_BYTES`.
+fn preview_text(formatted: &[u8]) -> Vec<String> {
+    if formatted.is_empty() {
+        return Vec::new();
+    }
+    let mut lines: Vec<String> = String::from_utf8_lossy(formatted)
+        .split('\n')
+        .map(str::to_string)
+        .collect();
+    let mut total = 0;
+    let keep = lines
+        .iter()
+        .rev()
+        .take_while(|line| {
+            total += line.len() + 1;
+            total <= MAX_PREVIEW_BYTES
+        })
+        .count();
+    lines.drain(..lines.len() - keep);
+    lines
+}
+
+/// Wait for the next output chunk from `output`, or forever without one.
+/// `None` once the channel closes.
+async fn next_output(output: &mut Option<broadcast::Receiver<Bytes>>) -> Option<()> {
+    let Some(rx) = output.as_mut() else {
+        return std::future::pending().await;
+    };
+    loop {
+        match rx.recv().await {
+            Ok(_) => return Some(()),
+            Err(broadcast::error::RecvError::Lagged(_)) => continue,
+            Err(broadcast::error::RecvError::Closed) => return None,
+        }
+    }
+}
+
+/// Handle WaitAttention: answer with the first of `sessions` (any live
+/// session when empty) that needs attention, waiting for one if none does.
+async fn handle_wait_attention(
+    writer: &mut tokio::net::unix::OwnedWriteHalf,
+    registry: Registry,
+    sessions: Vec<String>,
+    timeout_secs: u64,
+) {
+    // Subscribe before checking the flags: io_loop raises the flag before
+    // sending the event, so nothing slips between the two.
+    let (mut rx, already) = {
+        if let Some(missing) = sessions.iter().find(|name| !registry.contains(name)) {
+            let _ = write_frame_async(
+                writer,
+                &DaemonMessage::Error(format!("session '{}' not found", missing)),
+            )
+            .await;
+            return;
+        }
+        let rx = registry.subscribe_events();
+        let mut waiting: Vec<String> = registry
+            .list()
+            .await
+            .into_iter()
+            .filter(|s| s.alive && s.needs_attention)
+            .filter(|s| sessions.is_empty() || sessions.contains(&s.name))
+            .map(|s| s.name)
+            .collect();
+        waiting.sort();
+        (rx, waiting.into_iter().next())
+    };
+
+    let wait_fut = async {
+        if let Some(session) = already {
+            return Some(session);
+        }
+        loop {
+            match rx.recv().await {
+                Ok((session, event)) => {
+                    if event.wants_attention()
+                        && (sessions.is_empty() || sessions.contains(&session))
+                    {
+                        return Some(session);
+                    }
+                }
+                Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    };
+
+    let result = if timeout_secs > 0 {
+        tokio::time::timeout(std::time::Duration::from_secs(timeout_secs), wait_fut)
+            .await
+            .unwrap_or(None)
+    } else {
+        wait_fut.await
+    };
+
+    let resp = match result {
+        Some(session) => DaemonMessage::AttentionNeeded { session },
+        None => DaemonMessage::Error("timeout".to_string()),
+    };
+    let _ = write_frame_async(writer, &resp).await;
+}
+
 /// Handle WaitAny: block until the first of the given sessions exits, or timeout.
 async fn handle_wait_any(
     writer: &mut tokio::net::unix::OwnedWriteHalf,
-    registry: std::sync::Arc<tokio::sync::Mutex<crate::daemon::registry::Registry>>,
+    registry: Registry,
     sessions: Vec<String>,
     timeout_secs: u64,
 ) {
@@ -799,13 +1506,17 @@ async fn handle_wait_any(
         HashMap::new();
 
     {
-        let reg = registry.lock().await;
         for name in &sessions {
-            match reg.get(name) {
-                Some(session) => {
-                    if !session.is_alive() {
+            let watched = registry
+                .with_session(name, |session| {
+                    (session.is_alive(), session.exit_watch.clone(), session.exit_code.clone())
+                })
+                .await;
+            match watched {
+                Some((alive, exit_watch, exit_code)) => {
+                    if !alive {
                         // Already dead — return immediately.
-                        let code = session.exit_code.lock().ok().and_then(|ec| *ec);
+                        let code = exit_code.lock().ok().and_then(|ec| *ec);
                         let _ = write_frame_async(
                             writer,
                             &DaemonMessage::WaitAnyExited {
@@ -816,10 +1527,7 @@ async fn handle_wait_any(
                         .await;
                         return;
                     }
-                    watchers.insert(
-                        name.clone(),
-                        (session.exit_watch.clone(), session.exit_code.clone()),
-                    );
+                    watchers.insert(name.clone(), (exit_watch, exit_code));
                 }
                 None => {
                     let _ = write_frame_async(
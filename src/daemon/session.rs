@@ -5,27 +5,50 @@ use std::process::Command as StdCommand;
 use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
 use std::sync::{Arc, Mutex as StdMutex};
 
+use bytes::{Bytes, BytesMut};
 use nix::libc;
 use nix::pty::{openpty, Winsize};
 use nix::unistd::{self, ForkResult};
+use nix::sys::signal::Signal;
 use tokio::sync::{broadcast, mpsc, oneshot, watch};
 use tokio::task::JoinHandle;
 
+use super::limits::{self, SessionCgroup};
+use super::proctree;
+use super::telemetry::UsageHistory;
 use super::vterm::VirtualTerminal;
+use crate::protocol::messages::{ResourceLimits, TerminalEvent};
 
 const SCROLLBACK_SIZE: usize = 64 * 1024; // 64KB
 
+/// PTY reads are carved out of blocks this big, so a run of small reads
+/// shares one allocation (see `io_loop`).
+const READ_BLOCK_SIZE: usize = 64 * 1024;
+
+/// Room guaranteed for each PTY read.
+const MIN_READ_SIZE: usize = 4096;
+
+/// Least time between two scans of the screen for attention patterns;
+/// output arriving sooner is scanned once this has passed.
+const ATTENTION_SCAN_INTERVAL: std::time::Duration = std::time::Duration::from_millis(100);
+
 pub struct Session {
     pub name: String,
     pub command: String,
+    /// The command as spawned (program first); `command` joined for
+    /// display. A respawn with an empty command reruns it.
+    pub argv: Vec<String>,
     pub child_pid: nix::unistd::Pid,
     pub created_at: std::time::SystemTime,
     /// Timestamp of last PTY output (updated by io_loop).
     pub last_activity: Arc<StdMutex<std::time::SystemTime>>,
-    pub input_tx: mpsc::Sender<Vec<u8>>,
-    pub output_tx: broadcast::Sender<Vec<u8>>,
+    pub input_tx: mpsc::Sender<PtyInput>,
+    pub output_tx: broadcast::Sender<Bytes>,
+    /// Title changes and desktop notifications parsed out of the output
+    /// by io_loop. The registry relays them to `WatchEvents` clients.
+    pub events_tx: broadcast::Sender<TerminalEvent>,
     pub resize_tx: mpsc::Sender<(u16, u16)>,
-    pub kill_tx: Option<oneshot::Sender<()>>,
+    pub kill_tx: Option<oneshot::Sender<KillRequest>>,
     pub scrollback: Arc<StdMutex<Scrollback>>,
     /// Virtual terminal emulator maintaining rendered screen state.
     /// Used by CaptureScrollback and preview to return cursor-addressed
@@ -70,6 +93,161 @@ pub struct Session {
     /// Working directory recorded at spawn time. Used as the default for
     /// respawn() when the caller doesn't specify one.
     pub original_cwd: Option<String>,
+    /// Resource limits applied to the child at spawn (and again on each
+    /// respawn). `None` when the session is unlimited.
+    pub limits: Option<ResourceLimits>,
+    /// Per-session cgroup enforcing `limits`, when a delegated cgroup v2
+    /// subtree was available. Removed when the session is dropped.
+    pub cgroup: Option<SessionCgroup>,
+    /// Resource usage samples for the session's process tree, filled in
+    /// by the telemetry sampler. Kept across respawns.
+    pub usage: Arc<StdMutex<UsageHistory>>,
+    /// Set while the process tree is stopped via `SignalSession`
+    /// (SIGSTOP/SIGTSTP), cleared by SIGCONT or a respawn.
+    pub paused: bool,
+    /// Whether the program is waiting for a human. Shared with io_loop.
+    pub attention: Arc<Attention>,
+    /// Task relaying `events_tx` to the registry under the session's
+    /// name; replaced when the session is renamed.
+    pub events_relay: Option<JoinHandle<()>>,
+}
+
+/// Attention state of a session: raised by a bell, a desktop notification
+/// or one of the session's attention patterns appearing on screen, and
+/// cleared when a client attaches (or the session is respawned).
+pub struct Attention {
+    /// Screen substrings that raise attention (`amux new --attention`).
+    patterns: Vec<String>,
+    needed: AtomicBool,
+    count: AtomicU64,
+}
+
+impl Attention {
+    pub fn new(patterns: Vec<String>) -> Self {
+        Self {
+            patterns: patterns.into_iter().filter(|p| !p.is_empty()).collect(),
+            needed: AtomicBool::new(false),
+            count: AtomicU64::new(0),
+        }
+    }
+
+    pub fn raise(&self) {
+        self.needed.store(true, Ordering::SeqCst);
+        self.count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn clear(&self) {
+        self.needed.store(false, Ordering::SeqCst);
+    }
+
+    pub fn needed(&self) -> bool {
+        self.needed.load(Ordering::SeqCst)
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count.load(Ordering::Relaxed)
+    }
+}
+
+/// io_loop's watch for attention patterns. Rendering the screen is too
+/// costly to do under the vterm lock on every read, so output marks a scan
+/// as pending and the screen is checked at most once per
+/// `ATTENTION_SCAN_INTERVAL`.
+struct PatternScan {
+    /// Which patterns were on screen at the previous scan.
+    visible: Vec<bool>,
+    last: std::time::Instant,
+    pending: bool,
+}
+
+impl PatternScan {
+    fn new(attention: &Attention) -> Self {
+        Self {
+            visible: vec![false; attention.patterns.len()],
+            last: std::time::Instant::now() - ATTENTION_SCAN_INTERVAL,
+            pending: false,
+        }
+    }
+
+    /// Note new output; scans right away if the last scan is old enough.
+    fn output(&mut self, attention: &Attention, vt: &VirtualTerminal) -> Vec<String> {
+        if attention.patterns.is_empty() {
+            return Vec::new();
+        }
+        self.pending = true;
+        if self.last.elapsed() < ATTENTION_SCAN_INTERVAL {
+            return Vec::new();
+        }
+        self.scan(attention, vt)
+    }
+
+    /// When the pending scan is due, if there is one.
+    fn due(&self) -> Option<tokio::time::Instant> {
+        self.pending
+            .then(|| tokio::time::Instant::from_std(self.last + ATTENTION_SCAN_INTERVAL))
+    }
+
+    /// Patterns on `vt`'s screen now that weren't at the previous scan, so
+    /// a prompt that stays up while the program redraws around it is
+    /// reported once.
+    fn scan(&mut self, attention: &Attention, vt: &VirtualTerminal) -> Vec<String> {
+        self.pending = false;
+        self.last = std::time::Instant::now();
+        let screen = vt.rendered_screen();
+        let mut found = Vec::new();
+        for (pattern, was_visible) in attention.patterns.iter().zip(self.visible.iter_mut()) {
+            let is_visible = screen.contains(pattern.as_str());
+            if is_visible && !*was_visible {
+                found.push(pattern.clone());
+            }
+            *was_visible = is_visible;
+        }
+        found
+    }
+}
+
+/// One write queued for a session's PTY. `written`, when present, fires
+/// once io_loop has handed every byte of `data` to the kernel, so a caller
+/// can report actual delivery rather than mere queueing. It is dropped
+/// without firing if the write fails or the io_loop exits first.
+pub struct PtyInput {
+    pub data: Vec<u8>,
+    pub written: Option<oneshot::Sender<()>>,
+}
+
+impl From<Vec<u8>> for PtyInput {
+    fn from(data: Vec<u8>) -> Self {
+        PtyInput {
+            data,
+            written: None,
+        }
+    }
+}
+
+/// How io_loop should take a session's process tree down. `signal` goes to
+/// every process in the child's session and process group; whatever is
+/// still running `grace` later is SIGKILLed and, if `done` is set, reported
+/// back as `(pid, comm)` pairs.
+pub struct KillRequest {
+    pub signal: Signal,
+    pub grace: std::time::Duration,
+    pub done: Option<oneshot::Sender<Vec<(u32, String)>>>,
+}
+
+impl KillRequest {
+    /// Default signal and grace period for `amux kill`.
+    pub const DEFAULT_SIGNAL: Signal = Signal::SIGTERM;
+    pub const DEFAULT_GRACE: std::time::Duration = std::time::Duration::from_secs(2);
+}
+
+impl Default for KillRequest {
+    fn default() -> Self {
+        KillRequest {
+            signal: Self::DEFAULT_SIGNAL,
+            grace: Self::DEFAULT_GRACE,
+            done: None,
+        }
+    }
 }
 
 pub struct Scrollback {
@@ -92,6 +270,8 @@ impl Scrollback {
         }
     }
 
+    /// The whole buffer.
+    #[cfg(test)]
     pub fn contents(&self) -> Vec<u8> {
         self.buf.iter().copied().collect()
     }
@@ -133,7 +313,10 @@ impl Scrollback {
 }
 
 impl Session {
-    /// Spawn a new session with the given command.
+    /// Spawn a new session with the given command. When `limits` is set
+    /// the child runs under them (see `daemon::limits`); `attention` lists
+    /// the screen strings that flag it as needing a human.
+    #[allow(clippy::too_many_arguments)]
     pub fn spawn(
         name: String,
         cmd: &[String],
@@ -141,7 +324,16 @@ impl Session {
         rows: u16,
         env: Option<std::collections::HashMap<String, String>>,
         cwd: Option<String>,
+        limits: Option<ResourceLimits>,
+        attention: Vec<String>,
     ) -> anyhow::Result<Self> {
+        if let Some(ref limits) = limits {
+            limits::validate(limits)?;
+        }
+        let cgroup = limits
+            .as_ref()
+            .and_then(SessionCgroup::create);
+
         // Validate cwd if provided.
         if let Some(ref dir) = cwd {
             let path = std::path::Path::new(dir);
@@ -212,6 +404,15 @@ impl Session {
                     drop(pty.slave);
                 }
 
+                if let Some(ref limits) = limits {
+                    if let Err(e) =
+                        limits::apply_in_child(limits, cgroup.as_ref().map(|c| c.path()))
+                    {
+                        eprintln!("amux: failed to apply resource limits: {}", e);
+                        std::process::exit(1);
+                    }
+                }
+
                 // Exec the command.
                 let program = &cmd[0];
                 let args = &cmd[1..];
@@ -249,10 +450,11 @@ impl Session {
         }
 
         // Create channels.
-        let (input_tx, input_rx) = mpsc::channel::<Vec<u8>>(256);
-        let (output_tx, _) = broadcast::channel::<Vec<u8>>(256);
+        let (input_tx, input_rx) = mpsc::channel::<PtyInput>(256);
+        let (output_tx, _) = broadcast::channel::<Bytes>(256);
+        let (events_tx, _) = broadcast::channel::<TerminalEvent>(64);
         let (resize_tx, resize_rx) = mpsc::channel::<(u16, u16)>(16);
-        let (kill_tx, kill_rx) = oneshot::channel::<()>();
+        let (kill_tx, kill_rx) = oneshot::channel::<KillRequest>();
         let (exit_tx, exit_rx) = watch::channel(false);
         let exit_tx = Arc::new(exit_tx);
 
@@ -281,6 +483,7 @@ impl Session {
         let respawn_in_progress = Arc::new(AtomicBool::new(false));
         let respawn_in_progress_clone = respawn_in_progress.clone();
         let exit_tx_clone = exit_tx.clone();
+        let attention = Arc::new(Attention::new(attention));
 
         // Spawn the I/O task (owns the master fd via OwnedFd).
         let io_handle = tokio::spawn(Self::io_loop(
@@ -288,6 +491,7 @@ impl Session {
             child_pid,
             input_rx,
             output_tx_clone,
+            events_tx.clone(),
             scrollback_clone,
             vterm_clone,
             last_activity_clone,
@@ -299,16 +503,19 @@ impl Session {
             total_output_bytes_clone,
             current_size_clone,
             respawn_in_progress_clone,
+            attention.clone(),
         ));
 
         let session = Session {
             name,
             command: command_str,
+            argv: cmd.to_vec(),
             child_pid,
             created_at: now,
             last_activity,
             input_tx,
             output_tx,
+            events_tx,
             resize_tx,
             kill_tx: Some(kill_tx),
             scrollback,
@@ -325,6 +532,12 @@ impl Session {
             io_handle: Some(io_handle),
             exit_tx,
             original_cwd: cwd,
+            limits,
+            cgroup,
+            usage: Arc::new(StdMutex::new(UsageHistory::default())),
+            paused: false,
+            attention,
+            events_relay: None,
         };
 
         Ok(session)
@@ -333,19 +546,21 @@ impl Session {
     async fn io_loop(
         master_fd: i32,
         child_pid: nix::unistd::Pid,
-        mut input_rx: mpsc::Receiver<Vec<u8>>,
-        output_tx: broadcast::Sender<Vec<u8>>,
+        mut input_rx: mpsc::Receiver<PtyInput>,
+        output_tx: broadcast::Sender<Bytes>,
+        events_tx: broadcast::Sender<TerminalEvent>,
         scrollback: Arc<StdMutex<Scrollback>>,
         vterm: Arc<StdMutex<VirtualTerminal>>,
         last_activity: Arc<StdMutex<std::time::SystemTime>>,
         mut resize_rx: mpsc::Receiver<(u16, u16)>,
-        mut kill_rx: oneshot::Receiver<()>,
+        mut kill_rx: oneshot::Receiver<KillRequest>,
         exit_tx: Arc<watch::Sender<bool>>,
         exit_code: Arc<StdMutex<Option<i32>>>,
         died_at: Arc<StdMutex<Option<std::time::SystemTime>>>,
         total_output_bytes: Arc<AtomicU64>,
         current_size: Arc<StdMutex<(u16, u16)>>,
         respawn_in_progress: Arc<AtomicBool>,
+        attention: Arc<Attention>,
     ) {
         // Wrap the master fd in async I/O (fd must already be non-blocking).
         let master_file = unsafe { OwnedFd::from_raw_fd(master_fd) };
@@ -357,44 +572,78 @@ impl Session {
             }
         };
 
-        let mut read_buf = [0u8; 4096];
+        // Each read lands in the spare room of `read_buf` and is split off
+        // as a refcounted chunk: the broadcast hands the same buffer to
+        // every client, and a new block is only allocated once the current
+        // one is used up.
+        let mut read_buf = BytesMut::with_capacity(READ_BLOCK_SIZE);
+        let mut pattern_scan = PatternScan::new(&attention);
 
         loop {
+            let scan_due = pattern_scan.due();
             tokio::select! {
                 // Read from PTY master → broadcast to clients.
                 readable = master_async.readable() => {
                     match readable {
                         Ok(mut guard) => {
+                            read_buf.reserve(MIN_READ_SIZE);
                             match guard.try_io(|fd| {
                                 let raw = fd.as_raw_fd();
+                                let spare = read_buf.spare_capacity_mut();
                                 let n = unsafe {
                                     libc::read(
                                         raw,
-                                        read_buf.as_mut_ptr() as *mut libc::c_void,
-                                        read_buf.len(),
+                                        spare.as_mut_ptr() as *mut libc::c_void,
+                                        spare.len(),
                                     )
                                 };
                                 if n < 0 {
                                     Err(std::io::Error::last_os_error())
                                 } else {
+                                    // SAFETY: read() initialized the first n bytes.
+                                    unsafe { read_buf.set_len(n as usize) };
                                     Ok(n as usize)
                                 }
                             }) {
                                 Ok(Ok(0)) => break, // EOF
                                 Ok(Ok(n)) => {
-                                    let data = read_buf[..n].to_vec();
+                                    let data = read_buf.split().freeze();
                                     // Store in scrollback, feed virtual terminal, update activity.
                                     if let Ok(mut sb) = scrollback.lock() {
                                         sb.push(&data);
                                     }
-                                    if let Ok(mut vt) = vterm.lock() {
-                                        vt.process(&data);
-                                    }
                                     if let Ok(mut ts) = last_activity.lock() {
                                         *ts = std::time::SystemTime::now();
                                     }
                                     total_output_bytes.fetch_add(n as u64, Ordering::Relaxed);
-                                    let _ = output_tx.send(data);
+                                    // Broadcast while still holding the vterm lock:
+                                    // a lagging client resyncs from the vterm under
+                                    // the same lock, so the chunks it discards are
+                                    // exactly the ones already on the screen.
+                                    {
+                                        let mut vt = vterm.lock().ok();
+                                        if let Some(vt) = vt.as_mut() {
+                                            vt.process(&data);
+                                            let mut events = vt.take_events();
+                                            events.extend(
+                                                pattern_scan
+                                                    .output(&attention, vt)
+                                                    .into_iter()
+                                                    .map(|pattern| TerminalEvent::AttentionPattern { pattern }),
+                                            );
+                                            for event in events {
+                                                if event.wants_attention() {
+                                                    attention.raise();
+                                                }
+                                                let _ = events_tx.send(event);
+                                            }
+                                        }
+                                        let _ = output_tx.send(data);
+                                    }
+                                    // Let the clients' pumps run between reads: woken from here they
+                                    // may sit on this worker until the loop yields, and meanwhile the
+                                    // broadcast overflows under a steady stream.
+                                    tokio::task::consume_budget().await;
                                 }
                                 Ok(Err(e)) => {
                                     if e.kind() != std::io::ErrorKind::WouldBlock {
@@ -412,7 +661,7 @@ impl Session {
                     }
                 }
                 // Write client input → PTY master.
-                Some(data) = input_rx.recv() => {
+                Some(PtyInput { data, written }) = input_rx.recv() => {
                     let mut offset = 0;
                     while offset < data.len() {
                         // Wait for the fd to be writable (handles non-blocking EAGAIN).
@@ -450,6 +699,11 @@ impl Session {
                             Err(_would_block) => continue, // Spurious readiness, retry
                         }
                     }
+                    if offset == data.len() {
+                        if let Some(written) = written {
+                            let _ = written.send(());
+                        }
+                    }
                 }
                 // Handle resize.
                 Some((cols, rows)) = resize_rx.recv() => {
@@ -473,12 +727,28 @@ impl Session {
                         *sz = (rows, cols);
                     }
                 }
-                // Kill signal.
-                _ = &mut kill_rx => {
-                    let _ = nix::sys::signal::kill(child_pid, nix::sys::signal::Signal::SIGTERM);
-                    // Give child 2 seconds then SIGKILL.
-                    tokio::time::sleep(std::time::Duration::from_secs(2)).await;
-                    let _ = nix::sys::signal::kill(child_pid, nix::sys::signal::Signal::SIGKILL);
+                // Output arrived too soon after the last pattern scan.
+                _ = tokio::time::sleep_until(scan_due.unwrap_or_else(tokio::time::Instant::now)),
+                    if scan_due.is_some() =>
+                {
+                    if let Ok(vt) = vterm.lock() {
+                        for pattern in pattern_scan.scan(&attention, &vt) {
+                            attention.raise();
+                            let _ = events_tx.send(TerminalEvent::AttentionPattern { pattern });
+                        }
+                    }
+                }
+                // Kill signal, or the Session was dropped without one.
+                req = &mut kill_rx => {
+                    let req = req.unwrap_or_default();
+                    // Signal the whole tree the child leads, not just the
+                    // child, so build tools and language servers it started
+                    // don't outlive the session.
+                    let survivors =
+                        proctree::kill_tree(child_pid.as_raw() as u32, req.signal, req.grace).await;
+                    if let Some(done) = req.done {
+                        let _ = done.send(survivors);
+                    }
                     break;
                 }
             }
@@ -536,16 +806,22 @@ impl Session {
     /// io_loop task, `input_tx`/`resize_tx`/`kill_tx` (recreated). The
     /// `respawn_count` is incremented for telemetry.
     ///
-    /// `cmd` is required (first element is the program). `env` and
-    /// `cwd` are optional; `env` is merged with `AMUX_SESSION=<name>`,
-    /// and a missing `cwd` falls back to the workdir recorded on the
-    /// original spawn.
+    /// `cmd` is the new command (first element is the program); empty
+    /// reruns the current one, with its environment unless `env` is
+    /// given. `env` and `cwd` are optional; `env` is merged with
+    /// `AMUX_SESSION=<name>`, and a missing `cwd` falls back to the
+    /// workdir recorded on the original spawn.
     pub async fn respawn(
         &mut self,
         cmd: &[String],
         env: Option<HashMap<String, String>>,
         cwd: Option<String>,
     ) -> anyhow::Result<()> {
+        let (cmd, env) = if cmd.is_empty() {
+            (self.argv.clone(), env.or_else(|| Some(self.env_vars.clone())))
+        } else {
+            (cmd.to_vec(), env)
+        };
         if cmd.is_empty() {
             anyhow::bail!("respawn requires a non-empty command");
         }
@@ -642,6 +918,14 @@ impl Session {
                 if slave_fd > 2 {
                     drop(pty.slave);
                 }
+                if let Some(ref limits) = self.limits {
+                    if let Err(e) =
+                        limits::apply_in_child(limits, self.cgroup.as_ref().map(|c| c.path()))
+                    {
+                        eprintln!("amux: failed to apply resource limits: {}", e);
+                        std::process::exit(1);
+                    }
+                }
                 let program = &cmd_vec[0];
                 let args = &cmd_vec[1..];
                 let mut command = StdCommand::new(program);
@@ -671,22 +955,23 @@ impl Session {
         //    output continuity is what attach cares about (preserved
         //    via the unchanged `output_tx`), input requires a
         //    re-attach.
-        let (input_tx, input_rx) = mpsc::channel::<Vec<u8>>(256);
+        let (input_tx, input_rx) = mpsc::channel::<PtyInput>(256);
         let (resize_tx, resize_rx) = mpsc::channel::<(u16, u16)>(16);
-        let (kill_tx, kill_rx) = oneshot::channel::<()>();
+        let (kill_tx, kill_rx) = oneshot::channel::<KillRequest>();
 
         self.input_tx = input_tx;
         self.resize_tx = resize_tx;
         self.kill_tx = Some(kill_tx);
         self.child_pid = new_child_pid;
         self.command = cmd.join(" ");
+        self.argv = cmd;
         self.env_vars = full_env;
         self.original_cwd = effective_cwd;
 
         // 10. Emit a clear-screen sequence so any client whose Output
         //     stream straddled the swap sees a clean visual reset
         //     before the new child's bytes start flowing.
-        let _ = self.output_tx.send(b"\x1b[2J\x1b[H".to_vec());
+        let _ = self.output_tx.send(Bytes::from_static(b"\x1b[2J\x1b[H"));
 
         // 11. Bump last_activity so idle metrics restart from now.
         if let Ok(mut ts) = self.last_activity.lock() {
@@ -700,6 +985,7 @@ impl Session {
             new_child_pid,
             input_rx,
             self.output_tx.clone(),
+            self.events_tx.clone(),
             self.scrollback.clone(),
             self.vterm.clone(),
             self.last_activity.clone(),
@@ -711,10 +997,13 @@ impl Session {
             self.total_output_bytes.clone(),
             self.current_size.clone(),
             self.respawn_in_progress.clone(),
+            self.attention.clone(),
         ));
         self.io_handle = Some(handle);
 
         self.respawn_count.fetch_add(1, Ordering::Relaxed);
+        self.paused = false;
+        self.attention.clear();
 
         Ok(())
     }
@@ -857,6 +1146,8 @@ mod tests {
             24,
             None,
             Some("/nonexistent/path/that/does/not/exist".to_string()),
+            None,
+            Vec::new(),
         );
         assert!(result.is_err());
         let err = result.err().unwrap().to_string();
@@ -874,6 +1165,8 @@ mod tests {
             24,
             None,
             Some(tmp_str),
+            None,
+            Vec::new(),
         );
         assert!(result.is_ok());
     }
@@ -887,6 +1180,8 @@ mod tests {
             24,
             None,
             None,
+            None,
+            Vec::new(),
         );
         assert!(result.is_ok());
     }
@@ -919,6 +1214,65 @@ mod tests {
         assert_eq!(*exit_code.lock().unwrap(), Some(137));
     }
 
+    #[test]
+    fn test_pattern_scan_is_rate_limited() {
+        let attention = Attention::new(vec!["want to proceed".to_string()]);
+        let mut scan = PatternScan::new(&attention);
+        let mut vt = VirtualTerminal::new(24, 80);
+
+        vt.process(b"working\r\n");
+        assert!(scan.output(&attention, &vt).is_empty());
+        assert!(scan.due().is_none(), "first output is scanned right away");
+
+        // Output right behind it waits for the next scan.
+        vt.process(b"Do you want to proceed?");
+        assert!(scan.output(&attention, &vt).is_empty());
+        assert!(scan.due().is_some());
+        assert_eq!(scan.scan(&attention, &vt), vec!["want to proceed"]);
+        assert!(scan.due().is_none());
+
+        // Still on screen at the next scan: not reported again.
+        std::thread::sleep(ATTENTION_SCAN_INTERVAL);
+        vt.process(b" ");
+        assert!(scan.output(&attention, &vt).is_empty());
+        assert!(scan.due().is_none());
+
+        // Without patterns nothing is ever scanned.
+        let none = Attention::new(Vec::new());
+        let mut idle = PatternScan::new(&none);
+        assert!(idle.output(&none, &vt).is_empty());
+        assert!(idle.due().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_dropping_session_kills_child() {
+        let session = Session::spawn(
+            "drop-test".to_string(),
+            &["sleep".to_string(), "30".to_string()],
+            80,
+            24,
+            None,
+            None,
+            None,
+            Vec::new(),
+        )
+        .expect("spawn failed");
+        let pid = session.child_pid.as_raw();
+        drop(session);
+
+        // Dead once it is gone from /proc or left only as a zombie.
+        let alive = || {
+            std::fs::read_to_string(format!("/proc/{}/stat", pid))
+                .map(|s| !s.rsplit(')').next().unwrap_or("").trim_start().starts_with('Z'))
+                .unwrap_or(false)
+        };
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+        while alive() && std::time::Instant::now() < deadline {
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        }
+        assert!(!alive(), "child {} outlived its dropped session", pid);
+    }
+
     #[tokio::test]
     async fn test_spawn_with_custom_size() {
         // Spawn a session with non-default terminal size and verify
@@ -930,6 +1284,8 @@ mod tests {
             43,
             None,
             None,
+            None,
+            Vec::new(),
         )
         .expect("spawn failed");
 
@@ -981,6 +1337,8 @@ mod tests {
                 24,
                 None,
                 None,
+                None,
+                Vec::new(),
             )
             .expect("spawn failed");
             sessions.push(s);
@@ -1020,7 +1378,7 @@ mod tests {
         // to the orphan pile if the assertion fires.
         for s in &mut sessions {
             if let Some(tx) = s.kill_tx.take() {
-                let _ = tx.send(());
+                let _ = tx.send(KillRequest::default());
             }
         }
         // Best-effort: wait briefly for io_loops to deliver SIGTERM.
@@ -1046,6 +1404,8 @@ mod tests {
             60,
             None,
             None,
+            None,
+            Vec::new(),
         )
         .expect("spawn failed");
 
@@ -1077,6 +1437,8 @@ mod tests {
             0,
             None,
             None,
+            None,
+            Vec::new(),
         )
         .expect("spawn failed");
 
@@ -1102,7 +1464,7 @@ mod tests {
     /// everything received. Used by respawn tests that need to read
     /// streaming output without ending early on a single timeout.
     async fn drain_output(
-        rx: &mut broadcast::Receiver<Vec<u8>>,
+        rx: &mut broadcast::Receiver<Bytes>,
         dur: std::time::Duration,
     ) -> Vec<u8> {
         let mut out = Vec::new();
@@ -1120,7 +1482,7 @@ mod tests {
     /// Wait until `predicate` returns true on the accumulated output,
     /// or `dur` elapses. Returns the accumulated output either way.
     async fn drain_until<F: Fn(&[u8]) -> bool>(
-        rx: &mut broadcast::Receiver<Vec<u8>>,
+        rx: &mut broadcast::Receiver<Bytes>,
         dur: std::time::Duration,
         predicate: F,
     ) -> Vec<u8> {
@@ -1156,6 +1518,8 @@ mod tests {
             24,
             None,
             None,
+            None,
+            Vec::new(),
         )
         .expect("spawn failed");
 
@@ -1238,6 +1602,8 @@ mod tests {
             40,
             None,
             None,
+            None,
+            Vec::new(),
         )
         .expect("spawn failed");
 
@@ -1278,6 +1644,8 @@ mod tests {
             24,
             None,
             None,
+            None,
+            Vec::new(),
         )
         .expect("spawn failed");
 
@@ -1299,6 +1667,39 @@ mod tests {
         let _ = nix::sys::signal::kill(session.child_pid, nix::sys::signal::Signal::SIGKILL);
     }
 
+    /// An empty respawn command reruns the session's own command and env.
+    #[tokio::test]
+    async fn test_respawn_empty_command_reruns_original() {
+        let mut env = HashMap::new();
+        env.insert("GREETING".to_string(), "hi-again".to_string());
+        let mut session = Session::spawn(
+            "respawn-rerun".to_string(),
+            &[
+                "sh".to_string(),
+                "-c".to_string(),
+                "echo $GREETING; sleep 30".to_string(),
+            ],
+            80,
+            24,
+            Some(env),
+            None,
+            None,
+            Vec::new(),
+        )
+        .expect("spawn failed");
+        let mut rx = session.output_tx.subscribe();
+        session.respawn(&[], None, None).await.expect("respawn failed");
+        assert_eq!(session.argv[2], "echo $GREETING; sleep 30");
+        let out = drain_output(&mut rx, std::time::Duration::from_millis(800)).await;
+        assert!(
+            String::from_utf8_lossy(&out).contains("hi-again"),
+            "rerun should keep the spawn env: {:?}",
+            String::from_utf8_lossy(&out)
+        );
+
+        let _ = nix::sys::signal::kill(session.child_pid, nix::sys::signal::Signal::SIGKILL);
+    }
+
     /// bd-wh4: respawn into a non-existent cwd errors and leaves the
     /// existing child running. (The validation runs before SIGKILL so
     /// callers don't lose state on a typo.)
@@ -1311,6 +1712,8 @@ mod tests {
             24,
             None,
             None,
+            None,
+            Vec::new(),
         )
         .expect("spawn failed");
 
@@ -1354,6 +1757,8 @@ mod tests {
             24,
             None,
             None,
+            None,
+            Vec::new(),
         )
         .expect("spawn failed");
 
@@ -1415,6 +1820,8 @@ mod tests {
             24,
             None,
             None,
+            None,
+            Vec::new(),
         )
         .expect("spawn failed");
 
@@ -1456,6 +1863,8 @@ mod tests {
             24,
             Some(env),
             None,
+            None,
+            Vec::new(),
         )
         .expect("spawn failed");
 
@@ -1487,6 +1896,8 @@ mod tests {
             24,
             None,
             None,
+            None,
+            Vec::new(),
         )
         .expect("spawn failed");
 
@@ -1537,6 +1948,8 @@ mod tests {
             24,
             None,
             None,
+            None,
+            Vec::new(),
         )
         .expect("spawn failed");
 
@@ -1573,6 +1986,8 @@ mod tests {
             24,
             None,
             None,
+            None,
+            Vec::new(),
         )
         .expect("spawn failed");
 
@@ -1599,8 +2014,8 @@ mod tests {
 
     /// bd-wh4: sequential respawns of the same Session work — proves
     /// the cycle (kill → wait → reset → spawn) is idempotent. The
-    /// daemon serializes concurrent client calls via the registry
-    /// mutex, so this is the worst case the runtime actually has to
+    /// daemon serializes concurrent client calls via the session's
+    /// lock, so this is the worst case the runtime actually has to
     /// handle.
     #[tokio::test]
     async fn test_respawn_repeated_cycles() {
@@ -1615,6 +2030,8 @@ mod tests {
             24,
             None,
             None,
+            None,
+            Vec::new(),
         )
         .expect("spawn failed");
 
@@ -0,0 +1,205 @@
+//! Per-session resource telemetry.
+//!
+//! A sampler task wakes every `SAMPLE_INTERVAL`, takes one pass over
+//! `/proc` (see `proctree`), and for each live session sums CPU time, RSS,
+//! threads and open fds across the session's whole process tree (the child
+//! plus every descendant that hasn't escaped via reparenting). Samples land
+//! in a bounded per-session history that `amux info`, `amux top` and
+//! `amux stats` read.
+//!
+//! Linux only: on platforms without `/proc` the sampler finds nothing and
+//! sessions simply report no usage.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::daemon::proctree;
+use crate::daemon::registry::Registry;
+use crate::protocol::messages::ResourceSample;
+
+/// How often the sampler walks `/proc`.
+pub const SAMPLE_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Samples kept per session: 10 minutes at `SAMPLE_INTERVAL`.
+pub const HISTORY_SAMPLES: usize = 300;
+
+/// CPU% values included in `SessionInfo::cpu_recent` for sparklines.
+pub const RECENT_CPU_SAMPLES: usize = 10;
+
+/// Bounded history of resource samples for one session.
+#[derive(Debug, Default)]
+pub struct UsageHistory {
+    samples: VecDeque<ResourceSample>,
+    /// Root pid, cumulative CPU ticks and time of the previous sample;
+    /// CPU% is the tick delta between consecutive samples of the same root.
+    last_cpu: Option<(u32, u64, Instant)>,
+}
+
+impl UsageHistory {
+    /// Record a snapshot of the tree rooted at `root` taken at `at`.
+    fn record(&mut self, root: u32, tree: &TreeUsage, at: Instant, ticks_per_sec: u64) {
+        let cpu_percent = match self.last_cpu {
+            // A respawn changes the root pid; its CPU counters restart, so
+            // the first sample after it has no meaningful delta.
+            Some((prev_root, prev_ticks, prev_at)) if prev_root == root => {
+                let secs = at.duration_since(prev_at).as_secs_f64();
+                if secs > 0.0 && ticks_per_sec > 0 {
+                    let used = tree.cpu_ticks.saturating_sub(prev_ticks) as f64;
+                    (used / ticks_per_sec as f64 / secs * 100.0) as f32
+                } else {
+                    0.0
+                }
+            }
+            _ => 0.0,
+        };
+        self.last_cpu = Some((root, tree.cpu_ticks, at));
+
+        if self.samples.len() == HISTORY_SAMPLES {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(ResourceSample {
+            unix_ms: SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as u64,
+            cpu_percent,
+            rss_bytes: tree.rss_bytes,
+            threads: tree.threads,
+            fds: tree.fds,
+            children: tree.processes.saturating_sub(1),
+        });
+    }
+
+    pub fn latest(&self) -> Option<ResourceSample> {
+        self.samples.back().cloned()
+    }
+
+    /// CPU% of the most recent `n` samples, oldest first.
+    pub fn recent_cpu(&self, n: usize) -> Vec<f32> {
+        let skip = self.samples.len().saturating_sub(n);
+        self.samples.iter().skip(skip).map(|s| s.cpu_percent).collect()
+    }
+
+    pub fn samples(&self) -> Vec<ResourceSample> {
+        self.samples.iter().cloned().collect()
+    }
+}
+
+/// Totals across one session's process tree.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+struct TreeUsage {
+    cpu_ticks: u64,
+    rss_bytes: u64,
+    threads: u32,
+    fds: u32,
+    processes: u32,
+}
+
+fn count_fds(pid: u32) -> u32 {
+    std::fs::read_dir(format!("/proc/{}/fd", pid))
+        .map(|dir| dir.count() as u32)
+        .unwrap_or(0)
+}
+
+/// Read every process's stat once and total up the tree under each root.
+fn sample_trees(roots: &[u32], page_size: u64) -> HashMap<u32, TreeUsage> {
+    let stats = proctree::snapshot();
+    let children = proctree::children_index(&stats);
+    let mut out = HashMap::new();
+    for &root in roots {
+        if !stats.contains_key(&root) {
+            continue;
+        }
+        let mut usage = TreeUsage::default();
+        for pid in proctree::tree_pids(root, &children) {
+            let Some(stat) = stats.get(&pid) else { continue };
+            usage.cpu_ticks += stat.cpu_ticks;
+            usage.rss_bytes += stat.rss_pages * page_size;
+            usage.threads += stat.threads;
+            usage.fds += count_fds(pid);
+            usage.processes += 1;
+        }
+        out.insert(root, usage);
+    }
+    out
+}
+
+fn sysconf_or(var: nix::unistd::SysconfVar, default: u64) -> u64 {
+    match nix::unistd::sysconf(var) {
+        Ok(Some(v)) if v > 0 => v as u64,
+        _ => default,
+    }
+}
+
+/// Run the sampler loop. Cancelled when the caller drops the spawned task.
+pub async fn run(registry: Registry) {
+    let ticks_per_sec = sysconf_or(nix::unistd::SysconfVar::CLK_TCK, 100);
+    let page_size = sysconf_or(nix::unistd::SysconfVar::PAGE_SIZE, 4096);
+    loop {
+        tokio::time::sleep(SAMPLE_INTERVAL).await;
+        // Only the pid list is collected under the locks; the /proc walk
+        // happens on a blocking thread and results go straight into each
+        // session's history.
+        let targets: Vec<(u32, Arc<StdMutex<UsageHistory>>)> =
+            registry.usage_targets().await;
+        if targets.is_empty() {
+            continue;
+        }
+        let roots: Vec<u32> = targets.iter().map(|(pid, _)| *pid).collect();
+        let Ok(trees) =
+            tokio::task::spawn_blocking(move || sample_trees(&roots, page_size)).await
+        else {
+            continue;
+        };
+        let at = Instant::now();
+        for (root, history) in targets {
+            if let Some(tree) = trees.get(&root) {
+                if let Ok(mut h) = history.lock() {
+                    h.record(root, tree, at, ticks_per_sec);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_history_cpu_percent_and_bound() {
+        let mut h = UsageHistory::default();
+        let t0 = Instant::now();
+        let tree = |ticks| TreeUsage {
+            cpu_ticks: ticks,
+            processes: 3,
+            ..Default::default()
+        };
+        h.record(7, &tree(100), t0, 100);
+        assert_eq!(h.latest().unwrap().cpu_percent, 0.0);
+        assert_eq!(h.latest().unwrap().children, 2);
+        // 50 ticks over 1s at 100 ticks/s = half a core.
+        h.record(7, &tree(150), t0 + Duration::from_secs(1), 100);
+        assert!((h.latest().unwrap().cpu_percent - 50.0).abs() < 0.01);
+        // New root (respawn): counters restart, no delta.
+        h.record(8, &tree(5), t0 + Duration::from_secs(2), 100);
+        assert_eq!(h.latest().unwrap().cpu_percent, 0.0);
+        assert_eq!(h.recent_cpu(2).len(), 2);
+
+        for i in 0..HISTORY_SAMPLES as u64 {
+            h.record(8, &tree(5 + i), t0 + Duration::from_secs(3 + i), 100);
+        }
+        assert_eq!(h.samples().len(), HISTORY_SAMPLES);
+    }
+
+    #[test]
+    fn test_sample_trees_sees_own_process() {
+        let me = std::process::id();
+        let trees = sample_trees(&[me], 4096);
+        let usage = trees.get(&me).expect("own process should be sampled");
+        assert!(usage.rss_bytes > 0);
+        assert!(usage.threads >= 1);
+        assert!(usage.fds >= 1);
+    }
+}
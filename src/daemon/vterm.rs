@@ -10,8 +10,14 @@
 //! text; `rendered_screen_formatted()` returns it with SGR color/attribute
 //! codes preserved (but no cursor positioning).
 
+use std::collections::VecDeque;
+
 use vt100::Color;
 
+use crate::protocol::messages::{
+    Notification, ScreenCursor, ScreenLine, ScreenSnapshot, StyledRun, TerminalEvent,
+};
+
 /// Number of rows the live parser keeps in its internal scrollback. When
 /// streaming output scrolls past the live screen, those rows move into this
 /// buffer and remain queryable for tall preview captures (bd-pmk). 200 rows
@@ -19,8 +25,94 @@ use vt100::Color;
 /// memory at well under a megabyte per session even at 200x200.
 const PARSER_SCROLLBACK_ROWS: usize = 200;
 
+/// Desktop notifications remembered per session (reported in
+/// `SessionInfo`); older ones are dropped.
+pub const MAX_NOTIFICATIONS: usize = 16;
+
+/// Events held for `take_events` before the oldest are dropped. io_loop
+/// drains after every read, so this only bounds vterms nobody drains.
+const MAX_PENDING_EVENTS: usize = 256;
+
+/// Parser callbacks: state set by escape sequences that don't draw on
+/// the screen, which vt100 reports but doesn't keep.
+#[derive(Default)]
+struct TermEvents {
+    /// Window title from OSC 0/2.
+    title: String,
+    /// Latest OSC 9/777 notifications, oldest first.
+    notifications: VecDeque<Notification>,
+    /// Events not yet collected by `take_events`.
+    pending: VecDeque<TerminalEvent>,
+}
+
+impl TermEvents {
+    fn push(&mut self, event: TerminalEvent) {
+        if self.pending.len() >= MAX_PENDING_EVENTS {
+            self.pending.pop_front();
+        }
+        self.pending.push_back(event);
+    }
+
+    fn set_title(&mut self, title: String) {
+        // Agents re-send an unchanged title with every redraw.
+        if title != self.title {
+            self.title = title.clone();
+            self.push(TerminalEvent::TitleChanged { title });
+        }
+    }
+
+    fn notify(&mut self, title: Option<String>, body: String) {
+        let unix_ms = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+        let notification = Notification {
+            unix_ms,
+            title,
+            body,
+        };
+        if self.notifications.len() >= MAX_NOTIFICATIONS {
+            self.notifications.pop_front();
+        }
+        self.notifications.push_back(notification.clone());
+        self.push(TerminalEvent::Notification(notification));
+    }
+}
+
+/// Join OSC parameters back together: vt100 splits on every `;`, including
+/// ones that belong to a title or message.
+fn join_params(params: &[&[u8]]) -> String {
+    String::from_utf8_lossy(&params.join(&b';')).into_owned()
+}
+
+impl vt100::Callbacks for TermEvents {
+    fn audible_bell(&mut self, _: &mut vt100::Screen) {
+        self.push(TerminalEvent::Bell);
+    }
+
+    fn set_window_title(&mut self, _: &mut vt100::Screen, title: &[u8]) {
+        self.set_title(String::from_utf8_lossy(title).into_owned());
+    }
+
+    fn unhandled_osc(&mut self, _: &mut vt100::Screen, params: &[&[u8]]) {
+        match params {
+            // A title containing `;`.
+            [b"0" | b"2", rest @ ..] if rest.len() > 1 => self.set_title(join_params(rest)),
+            // ConEmu reuses OSC 9 with a numeric subcommand (`9;4;...` is a
+            // progress bar); those aren't notifications.
+            [b"9", sub, ..] if !sub.is_empty() && sub.iter().all(u8::is_ascii_digit) => {}
+            [b"9", body @ ..] if !body.is_empty() => self.notify(None, join_params(body)),
+            [b"777", b"notify", title, body @ ..] => {
+                let title = String::from_utf8_lossy(title).into_owned();
+                self.notify((!title.is_empty()).then_some(title), join_params(body));
+            }
+            _ => {}
+        }
+    }
+}
+
 pub struct VirtualTerminal {
-    parser: vt100::Parser,
+    parser: vt100::Parser<TermEvents>,
 }
 
 impl VirtualTerminal {
@@ -31,7 +123,12 @@ impl VirtualTerminal {
     /// can still be recovered for preview / capture (bd-pmk).
     pub fn new(rows: u16, cols: u16) -> Self {
         Self {
-            parser: vt100::Parser::new(rows, cols, PARSER_SCROLLBACK_ROWS),
+            parser: vt100::Parser::new_with_callbacks(
+                rows,
+                cols,
+                PARSER_SCROLLBACK_ROWS,
+                TermEvents::default(),
+            ),
         }
     }
 
@@ -51,6 +148,82 @@ impl VirtualTerminal {
         self.parser.screen().size()
     }
 
+    /// Whether the program in the session has enabled DECCKM (application
+    /// cursor keys). `amux send --keys` consults this so `Up` is sent as
+    /// `ESC O A` to vim/less instead of `ESC [ A`.
+    pub fn application_cursor(&self) -> bool {
+        self.parser.screen().application_cursor()
+    }
+
+    /// Whether the program in the session has enabled bracketed paste
+    /// (DECSET 2004). `amux send --paste` wraps its payload in paste
+    /// markers only when this is set.
+    pub fn bracketed_paste(&self) -> bool {
+        self.parser.screen().bracketed_paste()
+    }
+
+    /// The window title last set by the program (OSC 0/2), empty if none.
+    pub fn title(&self) -> &str {
+        &self.parser.callbacks().title
+    }
+
+    /// The most recent desktop notifications (OSC 9/777), oldest first.
+    pub fn notifications(&self) -> Vec<Notification> {
+        self.parser.callbacks().notifications.iter().cloned().collect()
+    }
+
+    /// Title changes, notifications and bells seen since the last call.
+    pub fn take_events(&mut self) -> Vec<TerminalEvent> {
+        self.parser.callbacks_mut().pending.drain(..).collect()
+    }
+
+    /// The bottom `n` rows of the screen as data, for
+    /// `CaptureMode::Structured`. Only styled spans get a run; the plain
+    /// text of every row is in `ScreenLine::text`.
+    pub fn structured(&mut self, n: usize) -> ScreenSnapshot {
+        let saved_offset = self.parser.screen().scrollback();
+        self.parser.screen_mut().set_scrollback(usize::MAX);
+        let scrollback_rows = self.parser.screen().scrollback();
+        self.parser.screen_mut().set_scrollback(saved_offset);
+
+        let screen = self.parser.screen();
+        let (rows, cols) = screen.size();
+        let (cursor_row, cursor_col) = screen.cursor_position();
+        let first = rows.saturating_sub(n.min(rows as usize) as u16);
+        let lines = (first..rows)
+            .map(|row| structured_row(screen, row, cols))
+            .collect();
+        ScreenSnapshot {
+            rows,
+            cols,
+            cursor: ScreenCursor {
+                row: cursor_row,
+                col: cursor_col,
+                visible: !screen.hide_cursor(),
+            },
+            alternate_screen: screen.alternate_screen(),
+            title: self.title().to_string(),
+            scrollback_offset: saved_offset,
+            scrollback_rows,
+            lines,
+        }
+    }
+
+    /// Escape sequences that redraw the whole live screen on a real
+    /// terminal: alternate-screen state, cells with attributes, input modes
+    /// and cursor. Sent to attached clients that fell behind the output
+    /// stream, in place of the output they missed.
+    pub fn repaint(&self) -> Vec<u8> {
+        let screen = self.parser.screen();
+        let mut out = if screen.alternate_screen() {
+            b"\x1b[?1049h".to_vec()
+        } else {
+            b"\x1b[?1049l".to_vec()
+        };
+        out.extend_from_slice(&screen.state_formatted());
+        out
+    }
+
     /// Return the rendered screen as plain UTF-8 text.
     ///
     /// Trailing blank lines and per-row trailing whitespace are trimmed.
@@ -338,19 +511,19 @@ pub fn render_raw_scrollback_formatted(
 }
 
 /// SGR-relevant attributes of a cell.
-#[derive(Clone, Copy, PartialEq, Eq)]
-struct SgrAttrs {
-    fg: Color,
-    bg: Color,
-    bold: bool,
-    dim: bool,
-    italic: bool,
-    underline: bool,
-    inverse: bool,
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct SgrAttrs {
+    pub(crate) fg: Color,
+    pub(crate) bg: Color,
+    pub(crate) bold: bool,
+    pub(crate) dim: bool,
+    pub(crate) italic: bool,
+    pub(crate) underline: bool,
+    pub(crate) inverse: bool,
 }
 
 impl SgrAttrs {
-    fn default_state() -> Self {
+    pub(crate) fn default_state() -> Self {
         Self {
             fg: Color::Default,
             bg: Color::Default,
@@ -434,6 +607,87 @@ fn emit_sgr(out: &mut Vec<u8>, attrs: &SgrAttrs) {
     out.push(b'm');
 }
 
+/// Name a vt100 color for `StyledRun`; `None` is the terminal default.
+fn color_name(color: Color) -> Option<String> {
+    const NAMES: [&str; 8] = [
+        "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white",
+    ];
+    match color {
+        Color::Default => None,
+        Color::Idx(i) if i < 8 => Some(NAMES[i as usize].to_string()),
+        Color::Idx(i) if i < 16 => Some(format!("bright-{}", NAMES[i as usize - 8])),
+        Color::Idx(i) => Some(format!("color{}", i)),
+        Color::Rgb(r, g, b) => Some(format!("#{:02x}{:02x}{:02x}", r, g, b)),
+    }
+}
+
+/// Adjacent cells of one row that share attributes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct CellRun {
+    pub(crate) col: u16,
+    /// Width in cells (wide characters count twice).
+    pub(crate) width: u16,
+    pub(crate) text: String,
+    pub(crate) attrs: SgrAttrs,
+}
+
+/// Split a screen row into runs of identical attributes, covering every
+/// cell (empty cells read as spaces). Shared by structured capture and the
+/// HTML/SVG export.
+pub(crate) fn row_runs(screen: &vt100::Screen, row: u16, cols: u16) -> Vec<CellRun> {
+    let mut runs: Vec<CellRun> = Vec::new();
+    for c in 0..cols {
+        let Some(cell) = screen.cell(row, c) else { break };
+        if cell.is_wide_continuation() {
+            continue;
+        }
+        let contents = if cell.has_contents() { cell.contents() } else { " " };
+        let width = if cell.is_wide() { 2 } else { 1 };
+        let attrs = SgrAttrs::from_cell(cell);
+        match runs.last_mut() {
+            Some(run) if run.attrs == attrs => {
+                run.width += width;
+                run.text.push_str(contents);
+            }
+            _ => runs.push(CellRun {
+                col: c,
+                width,
+                text: contents.to_string(),
+                attrs,
+            }),
+        }
+    }
+    runs
+}
+
+/// One screen row as text plus its styled runs.
+fn structured_row(screen: &vt100::Screen, row: u16, cols: u16) -> ScreenLine {
+    let plain = SgrAttrs::default_state();
+    let runs = row_runs(screen, row, cols);
+    let text: String = runs.iter().map(|r| r.text.as_str()).collect();
+    ScreenLine {
+        row,
+        text: text.trim_end().to_string(),
+        wrapped: screen.row_wrapped(row),
+        runs: runs
+            .into_iter()
+            .filter(|r| r.attrs != plain)
+            .map(|r| StyledRun {
+                col: r.col,
+                width: r.width,
+                text: r.text,
+                fg: color_name(r.attrs.fg),
+                bg: color_name(r.attrs.bg),
+                bold: r.attrs.bold,
+                dim: r.attrs.dim,
+                italic: r.attrs.italic,
+                underline: r.attrs.underline,
+                inverse: r.attrs.inverse,
+            })
+            .collect(),
+    }
+}
+
 /// Write one row to `out` as SGR-formatted bytes. Trailing blank cells at
 /// the end of the row are dropped so a colored background does not extend
 /// past the last visible character.
@@ -974,4 +1228,110 @@ mod tests {
             &out[out.len().saturating_sub(8)..]
         );
     }
+
+    #[test]
+    fn repaint_reproduces_screen_modes_and_cursor() {
+        let mut vt = VirtualTerminal::new(10, 40);
+        vt.process(b"shell line\r\n");
+        // A TUI: alt screen, app cursor keys, bracketed paste, colored text.
+        vt.process(b"\x1b[?1049h\x1b[?1h\x1b[?2004h\x1b[2J\x1b[1;1H\x1b[1;32mTITLE\x1b[0m");
+        vt.process(b"\x1b[5;3Hbody\x1b[7;10H");
+
+        let mut client = VirtualTerminal::new(10, 40);
+        client.process(b"stale junk\r\n\x1b[31m");
+        client.process(&vt.repaint());
+
+        let (a, b) = (vt.parser.screen(), client.parser.screen());
+        assert!(b.alternate_screen());
+        assert_eq!(b.contents(), a.contents());
+        assert_eq!(b.contents_formatted(), a.contents_formatted());
+        assert_eq!(b.cursor_position(), (6, 9));
+        assert!(client.application_cursor());
+        assert!(client.bracketed_paste());
+
+        // Back on the main screen, the repaint leaves the alt screen.
+        vt.process(b"\x1b[?1049l");
+        client.process(&vt.repaint());
+        assert!(!client.parser.screen().alternate_screen());
+        assert!(client.rendered_screen().contains("shell line"));
+    }
+
+    #[test]
+    fn structured_reports_runs_cursor_and_title() {
+        let mut vt = VirtualTerminal::new(4, 20);
+        vt.process(b"\x1b]0;agent: idle\x07");
+        vt.process(b"> \x1b[1;31mError\x1b[0m ok \x1b[38;5;200;48;2;1;2;3mx\x1b[0m\r\n");
+        vt.process("wide: 日本\r\n".as_bytes());
+        vt.process(b"\x1b[?25l\x1b[?1049h\x1b[2;5H");
+
+        let snap = vt.structured(50);
+        assert_eq!(snap.title, "agent: idle");
+        assert!(snap.alternate_screen);
+        assert_eq!((snap.cursor.row, snap.cursor.col, snap.cursor.visible), (1, 4, false));
+        assert_eq!(snap.lines.len(), 4);
+        assert_eq!(snap.scrollback_offset, 0);
+
+        // The alternate screen is blank; the main screen had the content.
+        vt.process(b"\x1b[?1049l");
+        let snap = vt.structured(50);
+        assert_eq!(snap.lines[0].text, "> Error ok x");
+        let runs = &snap.lines[0].runs;
+        assert_eq!(runs.len(), 2, "{:?}", runs);
+        assert_eq!((runs[0].col, runs[0].width, runs[0].text.as_str()), (2, 5, "Error"));
+        assert_eq!(runs[0].fg.as_deref(), Some("red"));
+        assert!(runs[0].bold);
+        assert_eq!(runs[1].fg.as_deref(), Some("color200"));
+        assert_eq!(runs[1].bg.as_deref(), Some("#010203"));
+        assert_eq!(snap.lines[1].text, "wide: 日本");
+
+        // `n` selects the bottom rows.
+        let snap = vt.structured(2);
+        assert_eq!(snap.lines.iter().map(|l| l.row).collect::<Vec<_>>(), vec![2, 3]);
+    }
+
+    #[test]
+    fn title_and_notification_oscs_become_events() {
+        let mut vt = VirtualTerminal::new(4, 20);
+        vt.process(b"\x1b]2;claude: working\x07");
+        vt.process(b"\x1b]2;claude: working\x07");
+        vt.process(b"\x1b]0;a;b\x1b\\");
+        vt.process(b"\x1b]9;Needs input; approve?\x07");
+        vt.process(b"\x1b]9;4;1;50\x07");
+        vt.process(b"\x1b]777;notify;Agent;Done\x07");
+
+        assert_eq!(vt.title(), "a;b");
+        let events = vt.take_events();
+        assert_eq!(events.len(), 4, "{:?}", events);
+        assert_eq!(
+            events[0],
+            TerminalEvent::TitleChanged {
+                title: "claude: working".to_string()
+            }
+        );
+        assert_eq!(
+            events[1],
+            TerminalEvent::TitleChanged {
+                title: "a;b".to_string()
+            }
+        );
+        let notes = vt.notifications();
+        assert_eq!(notes.len(), 2);
+        assert_eq!((notes[0].title.as_deref(), notes[0].body.as_str()), (None, "Needs input; approve?"));
+        assert_eq!((notes[1].title.as_deref(), notes[1].body.as_str()), (Some("Agent"), "Done"));
+        assert_eq!(events[2], TerminalEvent::Notification(notes[0].clone()));
+        assert!(vt.take_events().is_empty());
+        // BEL ends an OSC above without counting as a bell; a bare one does.
+        vt.process(b"beep\x07");
+        assert_eq!(vt.take_events(), vec![TerminalEvent::Bell]);
+        vt.process(b"\r\x1b[K");
+        // Nothing drew on the screen.
+        assert_eq!(vt.rendered_screen().trim(), "");
+
+        for i in 0..MAX_NOTIFICATIONS + 3 {
+            vt.process(format!("\x1b]9;n{}\x07", i).as_bytes());
+        }
+        let notes = vt.notifications();
+        assert_eq!(notes.len(), MAX_NOTIFICATIONS);
+        assert_eq!(notes.last().unwrap().body, format!("n{}", MAX_NOTIFICATIONS + 2));
+    }
 }
@@ -8,11 +8,8 @@
 //! exited during the suspension are reaped promptly instead of waiting
 //! for natural EOF detection on the PTY master.
 
-use std::sync::Arc;
 use std::time::{Duration, Instant};
 
-use tokio::sync::Mutex;
-
 use crate::daemon::registry::Registry;
 
 /// How often the watchdog wakes up.
@@ -45,7 +42,7 @@ pub fn detect_suspension(
 }
 
 /// Run the watchdog loop. Cancelled when the caller drops the spawned task.
-pub async fn run(registry: Arc<Mutex<Registry>>) {
+pub async fn run(registry: Registry) {
     let mut last_tick = Instant::now();
     let mut ticks_since_reap: u32 = 0;
     loop {
@@ -55,12 +52,13 @@ pub async fn run(registry: Arc<Mutex<Registry>>) {
         last_tick = now;
 
         if let Some(secs) = detect_suspension(elapsed, TICK_INTERVAL, SUSPENSION_THRESHOLD) {
+            crate::daemon::metrics::global().record_suspension();
             tracing::warn!(
                 "resumed after {} seconds suspension (expected ~{}s tick)",
                 secs,
                 TICK_INTERVAL.as_secs()
             );
-            let reaped = registry.lock().await.probe_after_resume();
+            let reaped = registry.probe_after_resume().await;
             for name in &reaped {
                 tracing::info!("reaped session '{}' after suspension", name);
             }
@@ -69,7 +67,7 @@ pub async fn run(registry: Arc<Mutex<Registry>>) {
         ticks_since_reap += 1;
         if ticks_since_reap >= REAP_EVERY_N_TICKS {
             ticks_since_reap = 0;
-            let dead = registry.lock().await.reap_dead();
+            let dead = registry.reap_dead().await;
             for name in &dead {
                 tracing::info!("reaped dead session: {}", name);
             }
@@ -0,0 +1,411 @@
+//! HTML and SVG snapshots of a session's screen (`amux capture --format`).
+//!
+//! The input is a `CaptureMode::Formatted` capture: the last N rendered
+//! lines with SGR codes and no cursor movement. It is replayed through a
+//! fresh parser as wide as the session, split into attribute runs with
+//! `vterm::row_runs`, and drawn with a color theme. The output is a single
+//! self-contained file suitable for bug reports and PR comments.
+
+use std::fmt::Write as _;
+
+use anyhow::Context;
+use serde::Deserialize;
+use vt100::Color;
+
+use crate::daemon::vterm::{row_runs, CellRun, SgrAttrs};
+
+/// Output format of `amux capture --format`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Html,
+    Svg,
+}
+
+/// Terminal colors: default foreground/background plus the 16-color
+/// palette (normal 0-7, bright 8-15) as `#rrggbb` colors. Palette entries
+/// 16-255 use the standard xterm cube and grayscale ramp.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Theme {
+    pub foreground: String,
+    pub background: String,
+    pub palette: [String; 16],
+}
+
+/// Names accepted by `--theme`, first is the default.
+pub const BUILTIN_THEMES: &[&str] = &["dark", "light", "solarized-dark"];
+
+impl Theme {
+    pub fn builtin(name: &str) -> Option<Theme> {
+        let (foreground, background, palette): (&str, &str, [&str; 16]) = match name {
+            "dark" => (
+                "#d4d4d4",
+                "#1e1e1e",
+                [
+                    "#000000", "#cd3131", "#0dbc79", "#e5e510", "#2472c8", "#bc3fbc", "#11a8cd",
+                    "#e5e5e5", "#666666", "#f14c4c", "#23d18b", "#f5f543", "#3b8eea", "#d670d6",
+                    "#29b8db", "#ffffff",
+                ],
+            ),
+            "light" => (
+                "#333333",
+                "#ffffff",
+                [
+                    "#000000", "#cd3131", "#00bc00", "#949800", "#0451a5", "#bc05bc", "#0598bc",
+                    "#555555", "#666666", "#cd3131", "#14ce14", "#b5ba00", "#0451a5", "#bc05bc",
+                    "#0598bc", "#a5a5a5",
+                ],
+            ),
+            "solarized-dark" => (
+                "#839496",
+                "#002b36",
+                [
+                    "#073642", "#dc322f", "#859900", "#b58900", "#268bd2", "#d33682", "#2aa198",
+                    "#eee8d5", "#002b36", "#cb4b16", "#586e75", "#657b83", "#839496", "#6c71c4",
+                    "#93a1a1", "#fdf6e3",
+                ],
+            ),
+            _ => return None,
+        };
+        Some(Theme {
+            foreground: foreground.to_string(),
+            background: background.to_string(),
+            palette: palette.map(str::to_string),
+        })
+    }
+
+    /// A built-in theme name, or the path of a JSON file with
+    /// `foreground`, `background` and a 16-entry `palette`.
+    pub fn load(spec: &str) -> anyhow::Result<Theme> {
+        if let Some(theme) = Theme::builtin(spec) {
+            return Ok(theme);
+        }
+        let text = std::fs::read_to_string(spec).with_context(|| {
+            format!(
+                "'{}' is neither a theme file nor one of: {}",
+                spec,
+                BUILTIN_THEMES.join(", ")
+            )
+        })?;
+        let theme: Theme =
+            serde_json::from_str(&text).with_context(|| format!("invalid theme file {}", spec))?;
+        // The colors go verbatim into style and fill attributes.
+        let colors = [&theme.foreground, &theme.background]
+            .into_iter()
+            .chain(&theme.palette);
+        for color in colors {
+            if !is_hex_color(color) {
+                anyhow::bail!("invalid theme file {}: '{}' is not a #rrggbb color", spec, color);
+            }
+        }
+        Ok(theme)
+    }
+
+    /// CSS color for a vt100 color; `Color::Default` maps to `default`.
+    fn color(&self, color: Color, default: &str) -> String {
+        match color {
+            Color::Default => default.to_string(),
+            Color::Idx(i) if i < 16 => self.palette[i as usize].clone(),
+            Color::Idx(i) if i < 232 => {
+                let level = |v: u8| if v == 0 { 0 } else { 55 + v * 40 };
+                let i = i - 16;
+                format!("#{:02x}{:02x}{:02x}", level(i / 36), level(i / 6 % 6), level(i % 6))
+            }
+            Color::Idx(i) => {
+                let v = 8 + (i - 232) * 10;
+                format!("#{:02x}{:02x}{:02x}", v, v, v)
+            }
+            Color::Rgb(r, g, b) => format!("#{:02x}{:02x}{:02x}", r, g, b),
+        }
+    }
+
+    /// Foreground and background of a run, with inverse applied.
+    fn colors(&self, attrs: &SgrAttrs) -> (String, String) {
+        let fg = self.color(attrs.fg, &self.foreground);
+        let bg = self.color(attrs.bg, &self.background);
+        if attrs.inverse {
+            (bg, fg)
+        } else {
+            (fg, bg)
+        }
+    }
+}
+
+fn is_hex_color(s: &str) -> bool {
+    s.len() == 7 && s.starts_with('#') && s[1..].bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+fn escape_xml(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for ch in s.chars() {
+        match ch {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            _ => out.push(ch),
+        }
+    }
+    out
+}
+
+/// Replay a Formatted capture and split it into rows of runs. Trailing
+/// unstyled blanks are dropped.
+fn styled_rows(formatted: &[u8], cols: u16) -> Vec<Vec<CellRun>> {
+    if formatted.is_empty() {
+        return Vec::new();
+    }
+    let rows = formatted.iter().filter(|&&b| b == b'\n').count() + 1;
+    let rows = rows.min(u16::MAX as usize) as u16;
+    let cols = cols.max(1);
+    let mut parser = vt100::Parser::new(rows, cols, 0);
+    // Capture lines are joined with bare LFs.
+    let mut bytes = Vec::with_capacity(formatted.len() + rows as usize);
+    for &b in formatted {
+        if b == b'\n' {
+            bytes.push(b'\r');
+        }
+        bytes.push(b);
+    }
+    parser.process(&bytes);
+
+    let plain = SgrAttrs::default_state();
+    (0..rows)
+        .map(|r| {
+            let mut runs = row_runs(parser.screen(), r, cols);
+            while runs
+                .last()
+                .is_some_and(|run| run.attrs == plain && run.text.trim().is_empty())
+            {
+                runs.pop();
+            }
+            if let Some(last) = runs.last_mut().filter(|run| run.attrs == plain) {
+                let trimmed = last.text.trim_end().len();
+                last.width -= (last.text.len() - trimmed) as u16;
+                last.text.truncate(trimmed);
+            }
+            runs
+        })
+        .collect()
+}
+
+/// Render a Formatted capture of a `cols`-wide session.
+pub fn render(
+    formatted: &[u8],
+    cols: u16,
+    format: ExportFormat,
+    theme: &Theme,
+    title: &str,
+) -> String {
+    let rows = styled_rows(formatted, cols);
+    match format {
+        ExportFormat::Html => render_html(&rows, theme, title),
+        ExportFormat::Svg => render_svg(&rows, cols, theme, title),
+    }
+}
+
+fn render_html(rows: &[Vec<CellRun>], theme: &Theme, title: &str) -> String {
+    let mut out = String::new();
+    let _ = write!(
+        out,
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n</head>\n\
+         <body style=\"margin:0;background:{bg}\">\n\
+         <pre style=\"margin:0;padding:12px;background:{bg};color:{fg};\
+         font-family:ui-monospace,Menlo,Consolas,monospace;font-size:13px;line-height:1.25\">",
+        escape_xml(title),
+        bg = theme.background,
+        fg = theme.foreground,
+    );
+    let plain = SgrAttrs::default_state();
+    for (i, runs) in rows.iter().enumerate() {
+        if i > 0 {
+            out.push('\n');
+        }
+        for run in runs {
+            let text = escape_xml(&run.text);
+            if run.attrs == plain {
+                out.push_str(&text);
+                continue;
+            }
+            let (fg, bg) = theme.colors(&run.attrs);
+            let mut style = format!("color:{}", fg);
+            if bg != theme.background {
+                let _ = write!(style, ";background:{}", bg);
+            }
+            if run.attrs.bold {
+                style.push_str(";font-weight:bold");
+            }
+            if run.attrs.dim {
+                style.push_str(";opacity:0.6");
+            }
+            if run.attrs.italic {
+                style.push_str(";font-style:italic");
+            }
+            if run.attrs.underline {
+                style.push_str(";text-decoration:underline");
+            }
+            let _ = write!(out, "<span style=\"{}\">{}</span>", style, text);
+        }
+    }
+    out.push_str("</pre>\n</body>\n</html>\n");
+    out
+}
+
+/// Cell geometry of the SVG, in pixels at 14px monospace.
+const SVG_CELL_W: f32 = 8.4;
+const SVG_CELL_H: f32 = 17.0;
+const SVG_PAD: f32 = 12.0;
+
+/// An SVG coordinate, to one decimal place.
+fn px(v: f32) -> String {
+    let s = format!("{:.1}", v);
+    match s.strip_suffix(".0") {
+        Some(whole) => whole.to_string(),
+        None => s,
+    }
+}
+
+fn render_svg(rows: &[Vec<CellRun>], cols: u16, theme: &Theme, title: &str) -> String {
+    let width = SVG_PAD * 2.0 + cols.max(1) as f32 * SVG_CELL_W;
+    let height = SVG_PAD * 2.0 + rows.len() as f32 * SVG_CELL_H;
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\">",
+        w = px(width),
+        h = px(height)
+    );
+    let _ = writeln!(out, "<title>{}</title>", escape_xml(title));
+    let _ = writeln!(
+        out,
+        "<rect width=\"100%\" height=\"100%\" fill=\"{}\"/>",
+        theme.background
+    );
+    let _ = writeln!(
+        out,
+        "<g font-family=\"ui-monospace,Menlo,Consolas,monospace\" font-size=\"14\" xml:space=\"preserve\">"
+    );
+    for (r, runs) in rows.iter().enumerate() {
+        let top = SVG_PAD + r as f32 * SVG_CELL_H;
+        // Backgrounds first so text draws over them.
+        for run in runs {
+            let (_, bg) = theme.colors(&run.attrs);
+            if bg != theme.background {
+                let _ = writeln!(
+                    out,
+                    "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\"/>",
+                    px(SVG_PAD + run.col as f32 * SVG_CELL_W),
+                    px(top),
+                    px(run.width as f32 * SVG_CELL_W),
+                    px(SVG_CELL_H),
+                    bg
+                );
+            }
+        }
+        for run in runs.iter().filter(|run| !run.text.trim().is_empty()) {
+            let (fg, _) = theme.colors(&run.attrs);
+            let mut attrs = format!("fill=\"{}\"", fg);
+            if run.attrs.bold {
+                attrs.push_str(" font-weight=\"bold\"");
+            }
+            if run.attrs.dim {
+                attrs.push_str(" fill-opacity=\"0.6\"");
+            }
+            if run.attrs.italic {
+                attrs.push_str(" font-style=\"italic\"");
+            }
+            if run.attrs.underline {
+                attrs.push_str(" text-decoration=\"underline\"");
+            }
+            let _ = writeln!(
+                out,
+                "<text x=\"{}\" y=\"{}\" textLength=\"{}\" {}>{}</text>",
+                px(SVG_PAD + run.col as f32 * SVG_CELL_W),
+                px(top + SVG_CELL_H * 0.8),
+                px(run.width as f32 * SVG_CELL_W),
+                attrs,
+                escape_xml(&run.text)
+            );
+        }
+    }
+    out.push_str("</g>\n</svg>\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &[u8] = b"\x1b[0m$ ls <dir>\n\x1b[0m\x1b[1;34mbin\x1b[0m  \x1b[7m sel \x1b[0m\x1b[0m";
+
+    #[test]
+    fn test_styled_rows_splits_runs() {
+        let rows = styled_rows(SAMPLE, 20);
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].len(), 1);
+        assert_eq!(rows[0][0].text, "$ ls <dir>");
+        let texts: Vec<&str> = rows[1].iter().map(|r| r.text.as_str()).collect();
+        assert_eq!(texts, vec!["bin", "  ", " sel "]);
+        assert!(rows[1][0].attrs.bold);
+        assert_eq!(rows[1][0].attrs.fg, Color::Idx(4));
+        assert_eq!(rows[1][2].col, 5);
+        assert!(styled_rows(b"", 20).is_empty());
+    }
+
+    #[test]
+    fn test_render_html_and_svg() {
+        let theme = Theme::builtin("dark").unwrap();
+        let html = render(SAMPLE, 20, ExportFormat::Html, &theme, "w1 & co");
+        assert!(html.contains("<title>w1 &amp; co</title>"));
+        assert!(html.contains("$ ls &lt;dir&gt;"));
+        assert!(html.contains("<span style=\"color:#2472c8;font-weight:bold\">bin</span>"));
+        // Inverse swaps the theme's default colors.
+        assert!(html.contains("<span style=\"color:#1e1e1e;background:#d4d4d4\"> sel </span>"));
+
+        let svg = render(SAMPLE, 20, ExportFormat::Svg, &theme, "w1");
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+        assert!(svg.contains("fill=\"#1e1e1e\"/>"));
+        // The inverse run gets a background rect at col 5, row 1.
+        assert!(svg.contains(&format!(
+            "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"17\" fill=\"#d4d4d4\"/>",
+            px(SVG_PAD + 5.0 * SVG_CELL_W),
+            px(SVG_PAD + SVG_CELL_H),
+            px(5.0 * SVG_CELL_W)
+        )));
+        assert!(svg.contains("textLength=\"25.2\""), "{}", svg);
+        assert!(svg.contains("font-weight=\"bold\">bin</text>"));
+        assert!(svg.trim_end().ends_with("</svg>"));
+    }
+
+    #[test]
+    fn test_theme_colors_and_loading() {
+        let theme = Theme::builtin("light").unwrap();
+        assert_eq!(theme.color(Color::Idx(1), "x"), "#cd3131");
+        assert_eq!(theme.color(Color::Idx(196), "x"), "#ff0000");
+        assert_eq!(theme.color(Color::Idx(232), "x"), "#080808");
+        assert_eq!(theme.color(Color::Rgb(1, 2, 3), "x"), "#010203");
+        assert_eq!(theme.color(Color::Default, "x"), "x");
+        for name in BUILTIN_THEMES {
+            assert!(Theme::builtin(name).is_some(), "{}", name);
+        }
+
+        let path = std::env::temp_dir().join(format!("amux-theme-{}.json", std::process::id()));
+        let mut custom = serde_json::json!({
+            "foreground": "#eeeeee",
+            "background": "#101010",
+            "palette": vec!["#123456"; 16],
+        });
+        std::fs::write(&path, custom.to_string()).unwrap();
+        let loaded = Theme::load(path.to_str().unwrap()).unwrap();
+        assert_eq!(loaded.background, "#101010");
+        custom["palette"] = serde_json::json!(["#000000"]);
+        std::fs::write(&path, custom.to_string()).unwrap();
+        assert!(Theme::load(path.to_str().unwrap()).is_err());
+        custom["palette"] = serde_json::json!(vec!["#123456"; 16]);
+        custom["background"] = serde_json::json!("red\"><script>alert(1)</script>");
+        std::fs::write(&path, custom.to_string()).unwrap();
+        let err = Theme::load(path.to_str().unwrap()).unwrap_err();
+        assert!(err.to_string().contains("not a #rrggbb color"), "{}", err);
+        assert!(Theme::load("no-such-theme").is_err());
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -3,6 +3,7 @@ mod client;
 mod commands;
 mod common;
 mod daemon;
+mod export;
 mod protocol;
 mod util;
 
@@ -32,6 +33,11 @@ fn main() -> anyhow::Result<()> {
             worktree: None,
             init_message: None,
             rows: None,
+            memory: None,
+            cpus: None,
+            pids: None,
+            nofile: None,
+            attention: Vec::new(),
             cmd: vec![shell],
         }
     });
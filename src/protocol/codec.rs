@@ -1,9 +1,232 @@
-use std::io::{self, Read, Write};
+use std::io::{self, IoSlice, Read, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
 
+use bytes::Bytes;
 use serde::{Deserialize, Serialize};
 
+use super::messages::{Chunk, Compression, DaemonMessage};
+
 const MAX_FRAME_SIZE: usize = 1024 * 1024; // 1MB
 
+/// Payload bytes per `Chunk`. Payloads bigger than this travel as a run
+/// of chunks, each in a frame well under `MAX_FRAME_SIZE`.
+pub const CHUNK_SIZE: usize = 512 * 1024;
+
+/// Largest payload a `Reassembly` puts back together, so a peer that never
+/// sends the final chunk can't make the other side buffer without bound.
+const MAX_TRANSFER_SIZE: usize = 256 * 1024 * 1024; // 256MB
+
+/// Set in a frame's length prefix when its payload is LZ4-compressed
+/// (with its uncompressed size prepended). Only sent on a connection that
+/// negotiated compression, so a peer that predates it never sees one.
+const COMPRESSED_FLAG: u32 = 1 << 31;
+
+/// Payloads smaller than this are sent as they are: compressing them
+/// saves too little to be worth it.
+const MIN_COMPRESS_SIZE: usize = 256;
+
+/// What frame compression did in the daemon: the frames it compressed,
+/// with their payload size before and after. For a client's view of a
+/// whole connection, see `TransferStats`.
+pub struct CompressionStats {
+    frames: AtomicU64,
+    raw_bytes: AtomicU64,
+    wire_bytes: AtomicU64,
+}
+
+static COMPRESSION_STATS: CompressionStats = CompressionStats {
+    frames: AtomicU64::new(0),
+    raw_bytes: AtomicU64::new(0),
+    wire_bytes: AtomicU64::new(0),
+};
+
+/// This process's compression counters.
+pub fn compression_stats() -> &'static CompressionStats {
+    &COMPRESSION_STATS
+}
+
+impl CompressionStats {
+    fn record(&self, raw: usize, wire: usize) {
+        self.frames.fetch_add(1, Ordering::Relaxed);
+        self.raw_bytes.fetch_add(raw as u64, Ordering::Relaxed);
+        self.wire_bytes.fetch_add(wire as u64, Ordering::Relaxed);
+    }
+
+    pub fn frames(&self) -> u64 {
+        self.frames.load(Ordering::Relaxed)
+    }
+
+    pub fn raw_bytes(&self) -> u64 {
+        self.raw_bytes.load(Ordering::Relaxed)
+    }
+
+    pub fn wire_bytes(&self) -> u64 {
+        self.wire_bytes.load(Ordering::Relaxed)
+    }
+}
+
+/// Everything a reader received on one connection, compressed or not:
+/// frames, their payload bytes once decompressed, and the bytes that
+/// crossed the wire (length prefixes included).
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TransferStats {
+    pub frames: u64,
+    pub payload_bytes: u64,
+    pub wire_bytes: u64,
+}
+
+impl TransferStats {
+    /// Payload over wire size, once anything arrived.
+    pub fn ratio(&self) -> Option<f64> {
+        match self.wire_bytes {
+            0 => None,
+            wire => Some(self.payload_bytes as f64 / wire as f64),
+        }
+    }
+}
+
+/// Compress a serialized payload, or `None` when that doesn't pay.
+fn compress(payload: &[u8], compression: Compression) -> Option<Vec<u8>> {
+    if payload.len() < MIN_COMPRESS_SIZE {
+        return None;
+    }
+    let packed = match compression {
+        Compression::Lz4 => lz4_flex::compress_prepend_size(payload),
+    };
+    if packed.len() >= payload.len() {
+        return None;
+    }
+    COMPRESSION_STATS.record(payload.len(), packed.len());
+    Some(packed)
+}
+
+/// Undo `compress`, refusing payloads that would inflate past the frame
+/// size limit.
+fn decompress(packed: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let Some(size) = packed.get(..4) else {
+        anyhow::bail!("truncated compressed frame");
+    };
+    let size = u32::from_le_bytes([size[0], size[1], size[2], size[3]]) as usize;
+    if size > MAX_FRAME_SIZE {
+        anyhow::bail!("frame too large: {} bytes", size);
+    }
+    lz4_flex::decompress_size_prepended(packed)
+        .map_err(|e| anyhow::anyhow!("corrupt compressed frame: {}", e))
+}
+
+/// Cut `data` into `CHUNK_SIZE` pieces, numbered and with the final one
+/// marked. Empty data still makes one (empty, final) chunk.
+pub fn split_chunks(data: &[u8]) -> Vec<Chunk> {
+    let pieces: Vec<&[u8]> = if data.is_empty() {
+        vec![data]
+    } else {
+        data.chunks(CHUNK_SIZE).collect()
+    };
+    let count = pieces.len();
+    pieces
+        .into_iter()
+        .enumerate()
+        .map(|(i, piece)| Chunk {
+            seq: i as u32,
+            data: piece.to_vec(),
+            last: i + 1 == count,
+        })
+        .collect()
+}
+
+/// Puts a chunked payload back together, checking that the pieces arrive
+/// in order, end with the final one and stay within `MAX_TRANSFER_SIZE`.
+#[derive(Debug)]
+pub struct Reassembly {
+    data: Vec<u8>,
+    next_seq: u32,
+    done: bool,
+    limit: usize,
+    /// The first thing that went wrong; the rest of the upload is ignored.
+    error: Option<String>,
+}
+
+impl Default for Reassembly {
+    fn default() -> Self {
+        Reassembly {
+            data: Vec::new(),
+            next_seq: 0,
+            done: false,
+            limit: MAX_TRANSFER_SIZE,
+            error: None,
+        }
+    }
+}
+
+impl Reassembly {
+    /// Add the next piece. Returns whether that was the final one, or what
+    /// is wrong with the transfer (which `finish` reports again).
+    pub fn push(&mut self, chunk: Chunk) -> Result<bool, String> {
+        if self.error.is_none() {
+            if self.done {
+                self.error = Some(format!("chunk {} after the final chunk", chunk.seq));
+            } else if chunk.seq != self.next_seq {
+                self.error = Some(format!(
+                    "chunk {} out of order, expected {}",
+                    chunk.seq, self.next_seq
+                ));
+            } else if self.data.len() + chunk.data.len() > self.limit {
+                self.error = Some(format!(
+                    "chunked transfer exceeds the {} byte limit",
+                    self.limit
+                ));
+                self.data = Vec::new();
+            } else {
+                self.data.extend_from_slice(&chunk.data);
+                self.next_seq += 1;
+                self.done = chunk.last;
+            }
+        }
+        match &self.error {
+            Some(e) => Err(e.clone()),
+            None => Ok(self.done),
+        }
+    }
+
+    /// The whole payload, if it arrived intact.
+    pub fn finish(self) -> Result<Vec<u8>, String> {
+        match self.error {
+            Some(e) => Err(e),
+            None if !self.done => Err(format!(
+                "chunked transfer ended after {} chunks without the final one",
+                self.next_seq
+            )),
+            None => Ok(self.data),
+        }
+    }
+}
+
+/// Split a length prefix into the payload length and whether the payload
+/// is compressed.
+fn parse_len(len_buf: [u8; 4]) -> anyhow::Result<(usize, bool)> {
+    let word = u32::from_be_bytes(len_buf);
+    let len = (word & !COMPRESSED_FLAG) as usize;
+    if len > MAX_FRAME_SIZE {
+        anyhow::bail!("frame too large: {} bytes", len);
+    }
+    Ok((len, word & COMPRESSED_FLAG != 0))
+}
+
+/// Deserialize a frame's payload, decompressing it first if need be.
+fn decode<T: for<'de> Deserialize<'de>>(buf: Vec<u8>, compressed: bool) -> anyhow::Result<T> {
+    let buf = if compressed { decompress(&buf)? } else { buf };
+    deserialize(&buf)
+}
+
+fn deserialize<T: for<'de> Deserialize<'de>>(buf: &[u8]) -> anyhow::Result<T> {
+    bincode::deserialize(buf).map_err(|e| {
+        anyhow::anyhow!(
+            "{}: daemon may be running an older version — try: amux kill-server && amux start-server",
+            e
+        )
+    })
+}
+
 /// Write a length-prefixed bincode frame to a sync writer.
 pub fn write_frame<W: Write, T: Serialize>(w: &mut W, msg: &T) -> anyhow::Result<()> {
     let data = bincode::serialize(msg)?;
@@ -18,19 +241,10 @@ pub fn write_frame<W: Write, T: Serialize>(w: &mut W, msg: &T) -> anyhow::Result
 pub fn read_frame<R: Read, T: for<'de> Deserialize<'de>>(r: &mut R) -> anyhow::Result<T> {
     let mut len_buf = [0u8; 4];
     r.read_exact(&mut len_buf)?;
-    let len = u32::from_be_bytes(len_buf) as usize;
-    if len > MAX_FRAME_SIZE {
-        anyhow::bail!("frame too large: {} bytes", len);
-    }
+    let (len, compressed) = parse_len(len_buf)?;
     let mut buf = vec![0u8; len];
     r.read_exact(&mut buf)?;
-    let msg = bincode::deserialize(&buf).map_err(|e| {
-        anyhow::anyhow!(
-            "{}: daemon may be running an older version — try: amux kill-server && amux start-server",
-            e
-        )
-    })?;
-    Ok(msg)
+    decode(buf, compressed)
 }
 
 /// Write a length-prefixed bincode frame to an async writer.
@@ -46,6 +260,87 @@ pub async fn write_frame_async<T: Serialize>(
     Ok(())
 }
 
+/// Write `payload` as one frame, compressed with `compression` if that
+/// makes it smaller.
+async fn write_payload_async(
+    w: &mut (impl tokio::io::AsyncWriteExt + Unpin),
+    payload: &[u8],
+    compression: Option<Compression>,
+) -> anyhow::Result<()> {
+    let packed = compression.and_then(|c| compress(payload, c));
+    let (len, body) = match &packed {
+        Some(packed) => (packed.len() as u32 | COMPRESSED_FLAG, packed.as_slice()),
+        None => (payload.len() as u32, payload),
+    };
+    w.write_all(&len.to_be_bytes()).await?;
+    w.write_all(body).await?;
+    w.flush().await?;
+    Ok(())
+}
+
+/// Like `write_frame_async`, but `Output` and capture frames are
+/// compressed when the connection negotiated `compression`. Readers
+/// decompress transparently.
+pub async fn write_frame_compressed_async(
+    w: &mut (impl tokio::io::AsyncWriteExt + Unpin),
+    msg: &DaemonMessage,
+    compression: Option<Compression>,
+) -> anyhow::Result<()> {
+    match msg {
+        DaemonMessage::Output(_)
+        | DaemonMessage::CaptureOutput(_)
+        | DaemonMessage::CaptureChunk(_) => {
+            write_payload_async(w, &bincode::serialize(msg)?, compression).await
+        }
+        _ => write_frame_async(w, msg).await,
+    }
+}
+
+/// Write one `DaemonMessage::Output` frame carrying `chunks` back to back,
+/// straight from the session's buffers: the header is built by hand and
+/// everything goes out in a single vectored write. The bytes on the wire
+/// are those `write_frame_async` produces for `Output(chunks.concat())`.
+/// With `compression`, the frame is assembled and compressed instead.
+pub async fn write_output_frame_async(
+    w: &mut (impl tokio::io::AsyncWriteExt + Unpin),
+    chunks: &[Bytes],
+    compression: Option<Compression>,
+) -> anyhow::Result<()> {
+    // bincode's fixed-int layout: the variant index, then the Vec's length
+    // as a u64. Taking the index from an empty `Output` keeps this in step
+    // with the enum.
+    let empty = bincode::serialize(&DaemonMessage::Output(Vec::new()))?;
+    let payload: usize = chunks.iter().map(Bytes::len).sum();
+    let frame_len = u32::try_from(empty.len() + payload)?;
+    let mut header = Vec::with_capacity(4 + empty.len());
+    header.extend_from_slice(&frame_len.to_be_bytes());
+    header.extend_from_slice(&empty[..empty.len() - 8]);
+    header.extend_from_slice(&(payload as u64).to_le_bytes());
+
+    if compression.is_some() {
+        let mut frame = header.split_off(4);
+        frame.reserve(payload);
+        for chunk in chunks {
+            frame.extend_from_slice(chunk);
+        }
+        return write_payload_async(w, &frame, compression).await;
+    }
+
+    let mut slices: Vec<IoSlice<'_>> = std::iter::once(IoSlice::new(&header))
+        .chain(chunks.iter().map(|chunk| IoSlice::new(chunk)))
+        .collect();
+    let mut remaining = &mut slices[..];
+    while !remaining.is_empty() {
+        let n = w.write_vectored(remaining).await?;
+        if n == 0 {
+            return Err(io::Error::from(io::ErrorKind::WriteZero).into());
+        }
+        IoSlice::advance_slices(&mut remaining, n);
+    }
+    w.flush().await?;
+    Ok(())
+}
+
 /// Read a length-prefixed bincode frame from an async reader.
 #[allow(dead_code)]
 pub async fn read_frame_async<T: for<'de> Deserialize<'de>>(
@@ -53,54 +348,73 @@ pub async fn read_frame_async<T: for<'de> Deserialize<'de>>(
 ) -> anyhow::Result<T> {
     let mut len_buf = [0u8; 4];
     r.read_exact(&mut len_buf).await?;
-    let len = u32::from_be_bytes(len_buf) as usize;
-    if len > MAX_FRAME_SIZE {
-        anyhow::bail!("frame too large: {} bytes", len);
-    }
+    let (len, compressed) = parse_len(len_buf)?;
     let mut buf = vec![0u8; len];
     r.read_exact(&mut buf).await?;
-    let msg = bincode::deserialize(&buf).map_err(|e| {
-        anyhow::anyhow!(
-            "{}: daemon may be running an older version — try: amux kill-server && amux start-server",
-            e
-        )
-    })?;
-    Ok(msg)
+    decode(buf, compressed)
 }
 
 /// Try to read a frame, returning None on EOF/disconnect.
 pub async fn try_read_frame_async<T: for<'de> Deserialize<'de>>(
     r: &mut (impl tokio::io::AsyncReadExt + Unpin),
 ) -> Option<anyhow::Result<T>> {
+    let (buf, compressed) = match try_read_raw_async(r).await? {
+        Ok(raw) => raw,
+        Err(e) => return Some(Err(e)),
+    };
+    Some(decode(buf, compressed))
+}
+
+/// Like `try_read_frame_async`, adding the frame to `stats`.
+pub async fn try_read_frame_counted_async<T: for<'de> Deserialize<'de>>(
+    r: &mut (impl tokio::io::AsyncReadExt + Unpin),
+    stats: &mut TransferStats,
+) -> Option<anyhow::Result<T>> {
+    let (buf, compressed) = match try_read_raw_async(r).await? {
+        Ok(raw) => raw,
+        Err(e) => return Some(Err(e)),
+    };
+    stats.frames += 1;
+    stats.wire_bytes += 4 + buf.len() as u64;
+    let payload = if compressed {
+        match decompress(&buf) {
+            Ok(payload) => payload,
+            Err(e) => return Some(Err(e)),
+        }
+    } else {
+        buf
+    };
+    stats.payload_bytes += payload.len() as u64;
+    Some(deserialize(&payload))
+}
+
+/// A frame's payload as read, and whether it is compressed.
+async fn try_read_raw_async(
+    r: &mut (impl tokio::io::AsyncReadExt + Unpin),
+) -> Option<anyhow::Result<(Vec<u8>, bool)>> {
     let mut len_buf = [0u8; 4];
     match r.read_exact(&mut len_buf).await {
         Ok(_) => {}
         Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return None,
         Err(e) => return Some(Err(e.into())),
     }
-    let len = u32::from_be_bytes(len_buf) as usize;
-    if len > MAX_FRAME_SIZE {
-        return Some(Err(anyhow::anyhow!("frame too large: {} bytes", len)));
-    }
+    let (len, compressed) = match parse_len(len_buf) {
+        Ok(parsed) => parsed,
+        Err(e) => return Some(Err(e)),
+    };
     let mut buf = vec![0u8; len];
     match r.read_exact(&mut buf).await {
         Ok(_) => {}
         Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return None,
         Err(e) => return Some(Err(e.into())),
     }
-    match bincode::deserialize(&buf) {
-        Ok(msg) => Some(Ok(msg)),
-        Err(e) => Some(Err(anyhow::anyhow!(
-            "{}: daemon may be running an older version — try: amux kill-server && amux start-server",
-            e
-        ))),
-    }
+    Some(Ok((buf, compressed)))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::protocol::messages::{ClientMessage, DaemonMessage, SessionInfo};
+    use crate::protocol::messages::{ClientMessage, DaemonMessage, InputPacing, SessionInfo};
 
     #[test]
     fn test_roundtrip_ping() {
@@ -120,6 +434,8 @@ mod tests {
             cwd: None,
             cols: None,
             rows: None,
+            limits: None,
+            attention: Vec::new(),
         };
         let mut buf = Vec::new();
         write_frame(&mut buf, &msg).unwrap();
@@ -147,6 +463,8 @@ mod tests {
             cwd: None,
             cols: None,
             rows: None,
+            limits: None,
+            attention: Vec::new(),
         };
         let mut buf = Vec::new();
         write_frame(&mut buf, &msg).unwrap();
@@ -172,6 +490,8 @@ mod tests {
             cwd: Some("/tmp".to_string()),
             cols: None,
             rows: None,
+            limits: None,
+            attention: Vec::new(),
         };
         let mut buf = Vec::new();
         write_frame(&mut buf, &msg).unwrap();
@@ -194,6 +514,8 @@ mod tests {
             cwd: None,
             cols: Some(200),
             rows: Some(50),
+            limits: None,
+            attention: Vec::new(),
         };
         let mut buf = Vec::new();
         write_frame(&mut buf, &msg).unwrap();
@@ -217,6 +539,8 @@ mod tests {
             cwd: None,
             cols: None,
             rows: None,
+            limits: None,
+            attention: Vec::new(),
         };
         let mut buf = Vec::new();
         write_frame(&mut buf, &msg).unwrap();
@@ -248,6 +572,14 @@ mod tests {
                 cols: 80,
                 attach_count: 0,
                 respawn_count: 0,
+                limits: None,
+                usage: None,
+                cpu_recent: Vec::new(),
+                paused: false,
+                title: String::new(),
+                notifications: Vec::new(),
+                needs_attention: false,
+                attention_count: 0,
             },
             SessionInfo {
                 name: "s2".to_string(),
@@ -264,6 +596,14 @@ mod tests {
                 cols: 80,
                 attach_count: 0,
                 respawn_count: 0,
+                limits: None,
+                usage: None,
+                cpu_recent: Vec::new(),
+                paused: false,
+                title: String::new(),
+                notifications: Vec::new(),
+                needs_attention: false,
+                attention_count: 0,
             },
         ]);
         let mut buf = Vec::new();
@@ -302,6 +642,8 @@ mod tests {
             name: "mysession".to_string(),
             data: b"ls -la".to_vec(),
             newline: true,
+            pacing: None,
+            wait_written: false,
         };
         let mut buf = Vec::new();
         write_frame(&mut buf, &msg).unwrap();
@@ -311,6 +653,7 @@ mod tests {
                 name,
                 data,
                 newline,
+                ..
             } => {
                 assert_eq!(name, "mysession");
                 assert_eq!(data, b"ls -la");
@@ -326,6 +669,8 @@ mod tests {
             name: "sess".to_string(),
             data: b"partial text".to_vec(),
             newline: false,
+            pacing: None,
+            wait_written: false,
         };
         let mut buf = Vec::new();
         write_frame(&mut buf, &msg).unwrap();
@@ -335,6 +680,7 @@ mod tests {
                 name,
                 data,
                 newline,
+                ..
             } => {
                 assert_eq!(name, "sess");
                 assert_eq!(data, b"partial text");
@@ -344,6 +690,36 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_roundtrip_send_input_paced() {
+        let pacing = InputPacing {
+            chars_per_sec: 40,
+            chunk_size: 8,
+            newline_delay_ms: 300,
+        };
+        let msg = ClientMessage::SendInput {
+            name: "sess".to_string(),
+            data: b"slow".to_vec(),
+            newline: false,
+            pacing: Some(pacing.clone()),
+            wait_written: true,
+        };
+        let mut buf = Vec::new();
+        write_frame(&mut buf, &msg).unwrap();
+        let decoded: ClientMessage = read_frame(&mut &buf[..]).unwrap();
+        match decoded {
+            ClientMessage::SendInput {
+                pacing: decoded_pacing,
+                wait_written,
+                ..
+            } => {
+                assert_eq!(decoded_pacing, Some(pacing));
+                assert!(wait_written);
+            }
+            _ => panic!("expected SendInput"),
+        }
+    }
+
     #[test]
     fn test_roundtrip_input_sent() {
         let msg = DaemonMessage::InputSent;
@@ -369,21 +745,189 @@ mod tests {
             name: "test".to_string(),
             cols: 80,
             rows: 24,
+            buffer: Some(512),
         };
         let mut buf = Vec::new();
         write_frame_async(&mut buf, &msg).await.unwrap();
         let mut cursor = &buf[..];
         let decoded: ClientMessage = read_frame_async(&mut cursor).await.unwrap();
         match decoded {
-            ClientMessage::Attach { name, cols, rows } => {
+            ClientMessage::Attach { name, cols, rows, buffer } => {
                 assert_eq!(name, "test");
                 assert_eq!(cols, 80);
                 assert_eq!(rows, 24);
+                assert_eq!(buffer, Some(512));
             }
             _ => panic!("expected Attach"),
         }
     }
 
+    #[tokio::test]
+    async fn test_output_frame_matches_bincode() {
+        let chunks = [
+            Bytes::from_static(b"hello "),
+            Bytes::new(),
+            Bytes::from(vec![b'x'; 5000]),
+            Bytes::from_static(b"\x1b[0m"),
+        ];
+        let mut ours = Vec::new();
+        write_output_frame_async(&mut ours, &chunks, None).await.unwrap();
+        let mut theirs = Vec::new();
+        write_frame_async(&mut theirs, &DaemonMessage::Output(chunks.concat()))
+            .await
+            .unwrap();
+        assert_eq!(ours, theirs);
+
+        let mut empty = Vec::new();
+        write_output_frame_async(&mut empty, &[], None).await.unwrap();
+        let decoded: DaemonMessage = read_frame(&mut &empty[..]).unwrap();
+        assert!(matches!(decoded, DaemonMessage::Output(data) if data.is_empty()));
+    }
+
+    fn is_compressed(frame: &[u8]) -> bool {
+        u32::from_be_bytes([frame[0], frame[1], frame[2], frame[3]]) & COMPRESSED_FLAG != 0
+    }
+
+    #[tokio::test]
+    async fn test_compressed_frames_read_transparently() {
+        let log = b"   Compiling amux v0.1.0 (/src/amux)\r\n".repeat(200);
+        let chunks: Vec<Bytes> = log.chunks(1000).map(Bytes::copy_from_slice).collect();
+        let mut buf = Vec::new();
+        write_output_frame_async(&mut buf, &chunks, Some(Compression::Lz4))
+            .await
+            .unwrap();
+        assert!(is_compressed(&buf));
+        assert!(buf.len() * 4 < log.len(), "{} -> {}", log.len(), buf.len());
+        let decoded: DaemonMessage = read_frame(&mut &buf[..]).unwrap();
+        assert!(matches!(decoded, DaemonMessage::Output(data) if data == log));
+
+        let capture = DaemonMessage::CaptureOutput(log.clone());
+        let mut buf = Vec::new();
+        write_frame_compressed_async(&mut buf, &capture, Some(Compression::Lz4))
+            .await
+            .unwrap();
+        assert!(is_compressed(&buf));
+        let mut cursor = &buf[..];
+        let decoded: Option<anyhow::Result<DaemonMessage>> =
+            try_read_frame_async(&mut cursor).await;
+        assert!(matches!(decoded, Some(Ok(DaemonMessage::CaptureOutput(data))) if data == log));
+
+        // Small frames, other messages and unnegotiated connections are
+        // left alone.
+        let uncompressed = [
+            (DaemonMessage::Output(b"$ ".to_vec()), Some(Compression::Lz4)),
+            (DaemonMessage::Error("x".repeat(1000)), Some(Compression::Lz4)),
+            (DaemonMessage::CaptureOutput(log.clone()), None),
+        ];
+        for (msg, compression) in uncompressed {
+            let mut buf = Vec::new();
+            write_frame_compressed_async(&mut buf, &msg, compression)
+                .await
+                .unwrap();
+            assert!(!is_compressed(&buf), "{:?}", compression);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_transfer_stats_count_every_frame() {
+        let log = b"   Compiling amux v0.1.0 (/src/amux)\r\n".repeat(200);
+        let mut buf = Vec::new();
+        let big = DaemonMessage::Output(log.clone());
+        write_frame_compressed_async(&mut buf, &big, Some(Compression::Lz4))
+            .await
+            .unwrap();
+        let compressed_len = buf.len();
+        // Keystroke echoes are too small to compress, yet cost wire bytes.
+        for _ in 0..100 {
+            let echo = DaemonMessage::Output(b"x".to_vec());
+            write_frame_compressed_async(&mut buf, &echo, Some(Compression::Lz4))
+                .await
+                .unwrap();
+        }
+
+        let mut stats = TransferStats::default();
+        let mut cursor = &buf[..];
+        let mut frames = 0;
+        while let Some(msg) =
+            try_read_frame_counted_async::<DaemonMessage>(&mut cursor, &mut stats).await
+        {
+            assert!(matches!(msg, Ok(DaemonMessage::Output(_))));
+            frames += 1;
+        }
+        assert_eq!((frames, stats.frames), (101, 101));
+        assert_eq!(stats.wire_bytes, buf.len() as u64);
+        let echo_len = bincode::serialize(&DaemonMessage::Output(b"x".to_vec())).unwrap().len();
+        let big_len = bincode::serialize(&big).unwrap().len();
+        assert_eq!(stats.payload_bytes, (big_len + 100 * echo_len) as u64);
+        let compressed_only = big_len as f64 / compressed_len as f64;
+        assert!(stats.ratio().unwrap() < compressed_only);
+    }
+
+    #[test]
+    fn test_compressed_frame_cannot_inflate_past_limit() {
+        let packed = lz4_flex::compress_prepend_size(&vec![0u8; MAX_FRAME_SIZE + 1]);
+        let mut frame = ((packed.len() as u32) | COMPRESSED_FLAG).to_be_bytes().to_vec();
+        frame.extend_from_slice(&packed);
+        let err = read_frame::<_, DaemonMessage>(&mut &frame[..]).unwrap_err();
+        assert!(err.to_string().contains("frame too large"), "{}", err);
+    }
+
+    #[test]
+    fn test_chunks_reassemble_in_order_only() {
+        let data: Vec<u8> = (0..2 * CHUNK_SIZE + 1).map(|i| i as u8).collect();
+        let chunks = split_chunks(&data);
+        assert_eq!(chunks.len(), 3);
+        assert!(chunks.iter().all(|c| c.data.len() <= CHUNK_SIZE));
+        assert!(chunks[2].last && !chunks[1].last);
+
+        // Every chunk fits a frame with room to spare.
+        let mut buf = Vec::new();
+        write_frame(&mut buf, &DaemonMessage::CaptureChunk(chunks[0].clone())).unwrap();
+        assert!(buf.len() < MAX_FRAME_SIZE);
+
+        let mut whole = Reassembly::default();
+        for chunk in chunks.clone() {
+            let last = chunk.last;
+            assert_eq!(whole.push(chunk), Ok(last));
+        }
+        assert_eq!(whole.finish().unwrap(), data);
+
+        let mut swapped = Reassembly::default();
+        assert_eq!(
+            swapped.push(chunks[1].clone()),
+            Err("chunk 1 out of order, expected 0".into())
+        );
+        assert!(swapped.push(chunks[0].clone()).is_err(), "a broken transfer stays broken");
+        assert!(swapped.finish().is_err());
+
+        let mut cut_short = Reassembly::default();
+        cut_short.push(chunks[0].clone()).unwrap();
+        let err = cut_short.finish().unwrap_err();
+        assert!(err.contains("without the final one"), "{}", err);
+
+        let mut overlong = Reassembly::default();
+        for chunk in chunks.iter().chain(&chunks[..1]) {
+            let _ = overlong.push(chunk.clone());
+        }
+        assert!(overlong.finish().unwrap_err().contains("after the final chunk"));
+
+        let mut oversized = Reassembly {
+            limit: CHUNK_SIZE,
+            ..Default::default()
+        };
+        assert_eq!(oversized.push(chunks[0].clone()), Ok(false));
+        let err = oversized.push(chunks[1].clone()).unwrap_err();
+        assert!(err.contains("byte limit"), "{}", err);
+        assert!(oversized.data.is_empty(), "nothing more is buffered");
+        assert!(oversized.finish().is_err());
+
+        let empty = split_chunks(&[]);
+        assert_eq!(empty.len(), 1);
+        let mut nothing = Reassembly::default();
+        assert_eq!(nothing.push(empty[0].clone()), Ok(true));
+        assert!(nothing.finish().unwrap().is_empty());
+    }
+
     #[test]
     fn test_deserialization_mismatch_gives_helpful_error() {
         // Simulate an old daemon sending SessionInfo without the `output_bytes` field.
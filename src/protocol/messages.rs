@@ -2,8 +2,10 @@ use std::collections::HashMap;
 
 use serde::{Deserialize, Serialize};
 
+use super::session_list::SessionListDiff;
+
 /// Requests from client to daemon.
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum ClientMessage {
     Ping,
     KillServer,
@@ -14,20 +16,47 @@ pub enum ClientMessage {
         cwd: Option<String>,
         cols: Option<u16>,
         rows: Option<u16>,
+        /// Resource limits for the session's process tree (`amux new
+        /// --memory/--cpus/--pids/--nofile`). `None` runs it unlimited.
+        limits: Option<ResourceLimits>,
+        /// Output substrings that mark the session as needing attention,
+        /// like a bell does (`amux new --attention`).
+        #[serde(default)]
+        attention: Vec<String>,
     },
     ListSessions,
     /// Get detailed info for a single session.
     GetSessionInfo {
         name: String,
     },
+    /// Get the resource usage history of a single session (`amux stats`).
+    GetSessionStats {
+        name: String,
+    },
+    /// Send `signal` to a session's child, process group or whole tree.
+    /// SIGSTOP/SIGTSTP mark the session paused and SIGCONT clears it.
+    SignalSession {
+        name: String,
+        signal: i32,
+        target: SignalTarget,
+    },
+    /// Kill a session's whole process tree: send `signal` (SIGTERM when
+    /// `None`) to the child's session and process group, then SIGKILL
+    /// whatever survives `grace_ms` (2000 when `None`). Answered with
+    /// `SessionKilled` once the tree is down.
     KillSession {
         name: String,
+        signal: Option<i32>,
+        grace_ms: Option<u64>,
     },
     KillAllSessions,
     Attach {
         name: String,
         cols: u16,
         rows: u16,
+        /// Output chunks the daemon may queue for this client before it
+        /// counts as lagging and gets a `Resync` instead. `None` = default.
+        buffer: Option<u32>,
     },
     AttachInput(Vec<u8>),
     AttachResize {
@@ -39,15 +68,51 @@ pub enum ClientMessage {
         name: String,
         data: Vec<u8>,
         newline: bool,
+        /// Deliver the payload in timed chunks instead of one write.
+        /// `None` queues it all at once.
+        pacing: Option<InputPacing>,
+        /// Reply with `InputWritten` once the final byte has actually been
+        /// written to the PTY, instead of `InputSent` as soon as it is queued.
+        wait_written: bool,
+    },
+    /// Send a sequence of key names (`C-c`, `Escape`, `Up`, `Enter`, ...)
+    /// to a session. The daemon translates them to bytes using the
+    /// session's live terminal modes; tokens that are not key names are
+    /// sent as literal text. Answered with `InputSent`.
+    SendKeys {
+        name: String,
+        keys: Vec<String>,
+    },
+    /// Paste text into a session (`amux send --paste`). Wrapped in
+    /// bracketed-paste markers when the session's program has enabled that
+    /// mode; otherwise delivered one line at a time, pausing
+    /// `line_delay_ms` between lines. Answered with `InputSent` once every
+    /// line has been queued.
+    PasteInput {
+        name: String,
+        data: Vec<u8>,
+        line_delay_ms: u64,
+    },
+    /// Deliver the same input to several sessions at once (`amux send
+    /// --all` / `--any` / `--match`, top's synchronized input). `keys` are
+    /// key names as for `SendKeys`, sent after `data` and encoded for each
+    /// session's own terminal modes. Answered with `BroadcastResult`, one
+    /// entry per targeted session.
+    BroadcastInput {
+        target: SessionSelector,
+        data: Vec<u8>,
+        newline: bool,
+        keys: Vec<String>,
     },
     HasSession {
         name: String,
     },
-    /// Capture session scrollback in one of three modes (see `CaptureMode`).
+    /// Capture session scrollback in one of four modes (see `CaptureMode`).
     /// Plain and Formatted both return the rendered virtual-terminal screen
     /// (correct for TUI apps that use cursor movement to redraw in place);
     /// Formatted additionally preserves SGR color/attribute codes, while
-    /// Plain strips them. Raw returns the raw PTY byte stream.
+    /// Plain strips them. Raw returns the raw PTY byte stream. Structured
+    /// returns the screen as JSON.
     CaptureScrollback {
         name: String,
         lines: usize,
@@ -68,6 +133,8 @@ pub enum ClientMessage {
     /// Subscribe to session output without interactive attach (read-only streaming).
     Follow {
         name: String,
+        /// Per-client output queue size, as for `Attach`.
+        buffer: Option<u32>,
     },
     /// Block until a session exits or a timeout elapses.
     WaitSession {
@@ -101,13 +168,161 @@ pub enum ClientMessage {
     /// Atomically replace a session's child process with a new command,
     /// preserving the session name, registry slot, attached clients'
     /// output stream, and current PTY size. Equivalent to
-    /// `tmux respawn-pane -k`. See bd-wh4.
+    /// `tmux respawn-pane -k`. See bd-wh4. An empty `command` reruns the
+    /// session's current one.
     RespawnSession {
         name: String,
         command: Vec<String>,
         cwd: Option<String>,
         env: Option<HashMap<String, String>>,
     },
+    /// Stream title changes and desktop notifications from the given
+    /// sessions (all sessions, including ones created later, when empty)
+    /// until the client disconnects.
+    WatchEvents {
+        sessions: Vec<String>,
+    },
+    /// Give a session a new name. Answered with `Ok`.
+    RenameSession {
+        name: String,
+        new_name: String,
+    },
+    /// Block until one of `sessions` (any live session when empty) needs
+    /// attention, or timeout. Answered at once if one already does.
+    WaitAttention {
+        sessions: Vec<String>,
+        /// Timeout in seconds (0 = wait forever).
+        timeout_secs: u64,
+    },
+    /// Stream `SessionListUpdate`s until the client disconnects: the whole
+    /// list first, then only what changed. `preview` names a session whose
+    /// last `preview_lines` formatted screen lines ride along.
+    SubscribeSessionList {
+        preview: Option<String>,
+        preview_lines: u32,
+    },
+    /// Sent on a `SubscribeSessionList` connection to change the previewed
+    /// session. Not answered; the next update carries the new preview.
+    SetPreview {
+        name: Option<String>,
+        lines: u32,
+    },
+    /// Multiplexing: `msg` travels on channel `id` of this connection.
+    /// The first message on an id opens the channel, which behaves like a
+    /// connection of its own (answers come back as `DaemonMessage::Tagged`
+    /// with the same id); later ones are its follow-ups, such as
+    /// `AttachInput` or `SetPreview`. Once a connection sends this, every
+    /// message on it must be `Tagged` or `Cancel`.
+    Tagged {
+        id: u64,
+        msg: Box<ClientMessage>,
+    },
+    /// Close channel `id`, ending its stream (or abandoning its request).
+    Cancel {
+        id: u64,
+    },
+    /// Offer frame compression for the rest of this connection, in order
+    /// of preference. Answered with `Negotiated`; from then on the daemon
+    /// may compress `Output` and `CaptureOutput` frames (see
+    /// `codec::write_frame_compressed_async`). A daemon that predates this
+    /// drops the connection instead.
+    Negotiate {
+        compression: Vec<Compression>,
+    },
+    /// Part of the input for the next `SendInput`, `PasteInput` or
+    /// `BroadcastInput` on this connection, for payloads too big for one
+    /// frame: that request delivers the uploaded bytes ahead of its own
+    /// `data`. Not answered; a broken upload (a chunk out of order, no
+    /// final chunk) fails the request that follows it.
+    InputChunk(Chunk),
+}
+
+impl ClientMessage {
+    /// A `CreateSession` running `command` with every option left at its
+    /// default.
+    #[cfg(test)]
+    pub fn create_session(name: &str, command: Vec<String>) -> Self {
+        ClientMessage::CreateSession {
+            name: Some(name.to_string()),
+            command,
+            env: None,
+            cwd: None,
+            cols: None,
+            rows: None,
+            limits: None,
+            attention: Vec::new(),
+        }
+    }
+
+    /// Requests that keep reading the connection after they are answered:
+    /// attach input, follow's detach, session-list preview changes, the
+    /// rest of an upload and the request it belongs to. Other
+    /// requests get exactly their answer, so a channel carrying one can be
+    /// closed for input right away.
+    pub fn takes_follow_ups(&self) -> bool {
+        matches!(
+            self,
+            ClientMessage::Attach { .. }
+                | ClientMessage::Follow { .. }
+                | ClientMessage::SubscribeSessionList { .. }
+                | ClientMessage::InputChunk(_)
+        )
+    }
+
+    /// The bytes a request delivers to sessions, where an `InputChunk`
+    /// upload ends up.
+    pub fn input_mut(&mut self) -> Option<&mut Vec<u8>> {
+        match self {
+            ClientMessage::SendInput { data, .. }
+            | ClientMessage::PasteInput { data, .. }
+            | ClientMessage::BroadcastInput { data, .. } => Some(data),
+            _ => None,
+        }
+    }
+
+    /// The variant name, used as a metrics label.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ClientMessage::Ping => "Ping",
+            ClientMessage::KillServer => "KillServer",
+            ClientMessage::CreateSession { .. } => "CreateSession",
+            ClientMessage::ListSessions => "ListSessions",
+            ClientMessage::GetSessionInfo { .. } => "GetSessionInfo",
+            ClientMessage::GetSessionStats { .. } => "GetSessionStats",
+            ClientMessage::SignalSession { .. } => "SignalSession",
+            ClientMessage::KillSession { .. } => "KillSession",
+            ClientMessage::KillAllSessions => "KillAllSessions",
+            ClientMessage::Attach { .. } => "Attach",
+            ClientMessage::AttachInput { .. } => "AttachInput",
+            ClientMessage::AttachResize { .. } => "AttachResize",
+            ClientMessage::Detach => "Detach",
+            ClientMessage::SendInput { .. } => "SendInput",
+            ClientMessage::SendKeys { .. } => "SendKeys",
+            ClientMessage::PasteInput { .. } => "PasteInput",
+            ClientMessage::BroadcastInput { .. } => "BroadcastInput",
+            ClientMessage::HasSession { .. } => "HasSession",
+            ClientMessage::CaptureScrollback { .. } => "CaptureScrollback",
+            ClientMessage::SetEnv { .. } => "SetEnv",
+            ClientMessage::GetEnv { .. } => "GetEnv",
+            ClientMessage::GetAllEnv { .. } => "GetAllEnv",
+            ClientMessage::Follow { .. } => "Follow",
+            ClientMessage::WaitSession { .. } => "WaitSession",
+            ClientMessage::GetExitCode { .. } => "GetExitCode",
+            ClientMessage::WatchSessions { .. } => "WatchSessions",
+            ClientMessage::WaitAny { .. } => "WaitAny",
+            ClientMessage::ResizeSession { .. } => "ResizeSession",
+            ClientMessage::RespawnSession { .. } => "RespawnSession",
+            ClientMessage::WatchEvents { .. } => "WatchEvents",
+            ClientMessage::WaitAttention { .. } => "WaitAttention",
+            ClientMessage::RenameSession { .. } => "RenameSession",
+            ClientMessage::SubscribeSessionList { .. } => "SubscribeSessionList",
+            ClientMessage::SetPreview { .. } => "SetPreview",
+            ClientMessage::Tagged { .. } => "Tagged",
+            ClientMessage::Cancel { .. } => "Cancel",
+            ClientMessage::Negotiate { .. } => "Negotiate",
+            ClientMessage::InputChunk(_) => "InputChunk",
+        }
+    }
 }
 
 /// Responses from daemon to client.
@@ -122,12 +337,30 @@ pub enum DaemonMessage {
     SessionList(Vec<SessionInfo>),
     /// Detailed info for a single session.
     SessionDetail(SessionInfo),
+    /// Resource usage history for one session, oldest sample first.
+    SessionStats(Vec<ResourceSample>),
     /// Output data streamed during attach.
     Output(Vec<u8>),
     /// Session ended while attached.
     SessionEnded,
+    /// The attached or following client fell behind: `skipped` output
+    /// chunks were dropped, and `screen` redraws the session's current
+    /// screen in their place.
+    Resync {
+        skipped: u64,
+        screen: Vec<u8>,
+    },
     /// Whether a session exists.
     SessionExists(bool),
+    /// Reply to `SignalSession`: the pids that were signalled.
+    SignalSent {
+        pids: Vec<u32>,
+    },
+    /// Reply to `KillSession`: processes that were still running when the
+    /// grace period ran out and had to be SIGKILLed.
+    SessionKilled {
+        survivors: Vec<KillSurvivor>,
+    },
     /// Count of sessions killed in a bulk operation.
     KilledSessions {
         count: usize,
@@ -136,6 +369,13 @@ pub enum DaemonMessage {
     CaptureOutput(Vec<u8>),
     /// Acknowledgement that input was sent to a session.
     InputSent,
+    /// Acknowledgement that every byte of a `SendInput` with
+    /// `wait_written` set has been written to the session's PTY.
+    InputWritten {
+        bytes: u64,
+    },
+    /// Per-session outcome of a `BroadcastInput`, sorted by session name.
+    BroadcastResult(Vec<InputDelivery>),
     /// Value of a single environment variable (None if not set).
     EnvValue(Option<String>),
     /// All environment variables for a session.
@@ -156,6 +396,102 @@ pub enum DaemonMessage {
         session: String,
         exit_code: Option<i32>,
     },
+    /// Something a session's program signalled out of band (streamed
+    /// during WatchEvents).
+    SessionEvent {
+        session: String,
+        event: TerminalEvent,
+    },
+    /// A session needs attention (response to WaitAttention).
+    AttentionNeeded {
+        session: String,
+    },
+    /// What changed in the session list (streamed during
+    /// SubscribeSessionList).
+    SessionListUpdate(SessionListDiff),
+    /// A message on multiplexed channel `id` (see `ClientMessage::Tagged`).
+    Tagged {
+        id: u64,
+        msg: Box<DaemonMessage>,
+    },
+    /// Channel `id` is done: its request was answered or its stream ended.
+    Closed {
+        id: u64,
+    },
+    /// Reply to `Negotiate`: the compression the daemon picked, if any.
+    Negotiated {
+        compression: Option<Compression>,
+    },
+    /// Part of a capture too big for one frame, sent in place of
+    /// `CaptureOutput`; the chunks up to the final one make up the data.
+    CaptureChunk(Chunk),
+}
+
+/// Daemon-side pacing for `SendInput`, for agents and REPLs that drop
+/// characters or mistake a large single write for a paste. All fields are
+/// optional; an all-default value behaves like an unpaced send.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct InputPacing {
+    /// Typing rate in characters per second. 0 means unlimited.
+    pub chars_per_sec: u32,
+    /// Maximum characters per write. 0 picks a default: one character when
+    /// `chars_per_sec` is set, otherwise the whole payload. Writes always
+    /// break after a newline when `newline_delay_ms` is set.
+    pub chunk_size: usize,
+    /// Extra pause after each newline, in milliseconds.
+    pub newline_delay_ms: u64,
+}
+
+/// An escape sequence that carries no screen content but tells the
+/// outside world something: agents set the window title to their status
+/// and raise desktop notifications when they need input.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum TerminalEvent {
+    /// OSC 0/2 set the window title.
+    TitleChanged { title: String },
+    /// OSC 9 or OSC 777 raised a desktop notification.
+    Notification(Notification),
+    /// The program rang the bell (BEL outside an escape sequence).
+    Bell,
+    /// Output matched one of the session's attention patterns.
+    AttentionPattern { pattern: String },
+}
+
+impl TerminalEvent {
+    /// Whether this event marks the session as waiting for a human.
+    pub fn wants_attention(&self) -> bool {
+        !matches!(self, TerminalEvent::TitleChanged { .. })
+    }
+}
+
+/// A desktop notification from OSC 9 (`ESC ] 9 ; body BEL`) or OSC 777
+/// (`ESC ] 777 ; notify ; title ; body BEL`).
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Notification {
+    /// Milliseconds since the Unix epoch when the daemon saw it.
+    pub unix_ms: u64,
+    /// Title, for OSC 777 notifications that carry one.
+    pub title: Option<String>,
+    pub body: String,
+}
+
+/// Which sessions a multi-session request applies to.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum SessionSelector {
+    /// Every live session.
+    All,
+    /// Exactly these sessions; missing or dead ones are reported per name.
+    Names(Vec<String>),
+    /// Live sessions whose name matches a glob (`*` and `?` wildcards).
+    Pattern(String),
+}
+
+/// Result of delivering broadcast input to one session.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct InputDelivery {
+    pub session: String,
+    /// `None` on success, otherwise why the input was not delivered.
+    pub error: Option<String>,
 }
 
 /// Scrollback capture mode. See `ClientMessage::CaptureScrollback`.
@@ -169,6 +505,84 @@ pub enum CaptureMode {
     /// preserved but cursor positioning stripped. Intended for callers that
     /// want colored output but place the cursor themselves.
     Formatted,
+    /// The screen grid as data: a JSON-encoded `ScreenSnapshot` with
+    /// per-row text, styled runs, cursor, modes and window title. For
+    /// tools that detect UI states without parsing escape codes.
+    Structured,
+}
+
+/// One piece of a payload too big for a single frame (see
+/// `codec::CHUNK_SIZE`). Pieces are numbered from 0; the final one is
+/// marked `last`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Chunk {
+    pub seq: u32,
+    pub data: Vec<u8>,
+    pub last: bool,
+}
+
+/// Frame compression a connection can negotiate. See
+/// `ClientMessage::Negotiate`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    /// LZ4 block format: fast enough for a live stream, and terminal
+    /// output (repeated escape sequences, log lines) compresses well.
+    Lz4,
+}
+
+/// The live virtual-terminal screen (`CaptureMode::Structured`). Rows and
+/// columns are 0-based cell coordinates.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ScreenSnapshot {
+    pub rows: u16,
+    pub cols: u16,
+    pub cursor: ScreenCursor,
+    pub alternate_screen: bool,
+    /// Last title set with OSC 0/2, empty if none.
+    pub title: String,
+    /// How far the view is scrolled back into history (0 = live screen),
+    /// and how many history rows exist above the screen.
+    pub scrollback_offset: usize,
+    pub scrollback_rows: usize,
+    /// The bottom `lines` rows of the screen, top to bottom.
+    pub lines: Vec<ScreenLine>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ScreenCursor {
+    pub row: u16,
+    pub col: u16,
+    pub visible: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ScreenLine {
+    pub row: u16,
+    /// Row text with trailing blanks trimmed.
+    pub text: String,
+    /// Whether the row soft-wraps into the next one.
+    pub wrapped: bool,
+    /// Spans whose colors or attributes differ from the default; unstyled
+    /// text appears only in `text`.
+    pub runs: Vec<StyledRun>,
+}
+
+/// A span of adjacent cells sharing colors and attributes.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct StyledRun {
+    pub col: u16,
+    /// Width in cells (wide characters count twice).
+    pub width: u16,
+    pub text: String,
+    /// `None` for the terminal default, otherwise a color name (`red`,
+    /// `bright-blue`), `color<N>` for the 256-color palette, or `#rrggbb`.
+    pub fg: Option<String>,
+    pub bg: Option<String>,
+    pub bold: bool,
+    pub dim: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub inverse: bool,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -201,4 +615,90 @@ pub struct SessionInfo {
     /// `RespawnSession`. Bumped each time `amux respawn` swaps the
     /// child in place; surfaced for telemetry (bd-wh4).
     pub respawn_count: u32,
+    /// Resource limits and current usage, if the session was created
+    /// with any limits.
+    pub limits: Option<Box<LimitStatus>>,
+    /// Latest resource usage of the session's process tree, once the
+    /// daemon's sampler has seen it.
+    pub usage: Option<ResourceSample>,
+    /// CPU% of the last few samples, oldest first (for sparklines).
+    pub cpu_recent: Vec<f32>,
+    /// Whether the session was stopped with `amux pause` (or a SIGSTOP via
+    /// `amux signal`) and not yet resumed.
+    pub paused: bool,
+    /// Window title last set by the program (OSC 0/2); empty if never set.
+    pub title: String,
+    /// Most recent desktop notifications (OSC 9/777), oldest first.
+    pub notifications: Vec<Notification>,
+    /// Set by a bell, a notification or an attention pattern; cleared
+    /// when a client attaches.
+    pub needs_attention: bool,
+    /// Bells, notifications and pattern matches since the session started.
+    pub attention_count: u64,
+}
+
+/// Which processes of a session `SignalSession` reaches.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SignalTarget {
+    /// Only the session's main process.
+    #[default]
+    Child,
+    /// The process group the main process leads.
+    Pgrp,
+    /// Every process in the session: its group, its session, and all
+    /// descendants (including ones that moved to their own group).
+    Tree,
+}
+
+/// A process that outlived a kill's grace period.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct KillSurvivor {
+    pub pid: u32,
+    /// Command name from `/proc/<pid>/stat`.
+    pub command: String,
+}
+
+/// One telemetry sample of a session's process tree.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ResourceSample {
+    /// Sample time, milliseconds since the Unix epoch.
+    pub unix_ms: u64,
+    /// CPU used since the previous sample, in percent of one core.
+    pub cpu_percent: f32,
+    /// Summed resident memory of every process in the tree.
+    pub rss_bytes: u64,
+    pub threads: u32,
+    /// Open file descriptors across the tree.
+    pub fds: u32,
+    /// Descendant processes of the session's main process.
+    pub children: u32,
+}
+
+/// Resource limits requested for a session. Unset fields are unlimited.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ResourceLimits {
+    /// Memory cap in bytes.
+    pub memory_bytes: Option<u64>,
+    /// CPU bandwidth in cores (e.g. 1.5). Only enforceable via cgroup.
+    pub cpus: Option<f64>,
+    /// Maximum number of processes/threads.
+    pub pids: Option<u64>,
+    /// Maximum open file descriptors per process.
+    pub nofile: Option<u64>,
+}
+
+/// A session's limits together with how they are enforced and what the
+/// session currently uses.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LimitStatus {
+    pub limits: ResourceLimits,
+    /// Path of the session's cgroup, or `None` when only rlimits apply.
+    pub cgroup: Option<String>,
+    /// Current memory use in bytes: the whole cgroup when there is one,
+    /// otherwise the main process's RSS.
+    pub memory_bytes: Option<u64>,
+    /// Current process count (cgroup only).
+    pub pids: Option<u64>,
+    /// Cumulative CPU time in microseconds (cgroup only).
+    pub cpu_usage_usec: Option<u64>,
 }
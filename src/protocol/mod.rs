@@ -1,4 +1,5 @@
 pub mod codec;
 pub mod messages;
+pub mod session_list;
 
 pub use messages::*;
@@ -0,0 +1,332 @@
+//! Incremental session-list updates for `SubscribeSessionList`.
+//!
+//! A subscribed dashboard keeps its own copy of the session list and the
+//! previewed session's screen. The daemon sends only what changed since
+//! its last update: sessions added or removed, the individual `SessionInfo`
+//! fields that differ, and a preview delta that reuses the lines the client
+//! already has (a scrolling tail usually shifts up by a few lines).
+
+use serde::{Deserialize, Serialize};
+
+use super::messages::{LimitStatus, Notification, ResourceSample, SessionInfo};
+
+/// One changed `SessionInfo` field, carrying its new value. The session
+/// name is the key and never changes here; a rename arrives as a removal
+/// plus an addition.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum SessionField {
+    Command(String),
+    Pid(u32),
+    Alive(bool),
+    CreatedAt(String),
+    UptimeSecs(u64),
+    LastActivity(String),
+    IdleSecs(u64),
+    ExitCode(Option<i32>),
+    OutputBytes(u64),
+    Size { rows: u16, cols: u16 },
+    AttachCount(u32),
+    RespawnCount(u32),
+    Limits(Option<Box<LimitStatus>>),
+    Usage(Option<ResourceSample>),
+    CpuRecent(Vec<f32>),
+    Paused(bool),
+    Title(String),
+    Notifications(Vec<Notification>),
+    NeedsAttention(bool),
+    AttentionCount(u64),
+}
+
+impl SessionInfo {
+    /// The fields of `newer` that differ from `self`. Applying them to
+    /// `self` with `apply` makes it equal to `newer`.
+    pub fn changes(&self, newer: &SessionInfo) -> Vec<SessionField> {
+        let mut out = Vec::new();
+        if self.command != newer.command {
+            out.push(SessionField::Command(newer.command.clone()));
+        }
+        if self.pid != newer.pid {
+            out.push(SessionField::Pid(newer.pid));
+        }
+        if self.alive != newer.alive {
+            out.push(SessionField::Alive(newer.alive));
+        }
+        if self.created_at != newer.created_at {
+            out.push(SessionField::CreatedAt(newer.created_at.clone()));
+        }
+        if self.uptime_secs != newer.uptime_secs {
+            out.push(SessionField::UptimeSecs(newer.uptime_secs));
+        }
+        if self.last_activity != newer.last_activity {
+            out.push(SessionField::LastActivity(newer.last_activity.clone()));
+        }
+        if self.idle_secs != newer.idle_secs {
+            out.push(SessionField::IdleSecs(newer.idle_secs));
+        }
+        if self.exit_code != newer.exit_code {
+            out.push(SessionField::ExitCode(newer.exit_code));
+        }
+        if self.output_bytes != newer.output_bytes {
+            out.push(SessionField::OutputBytes(newer.output_bytes));
+        }
+        if (self.rows, self.cols) != (newer.rows, newer.cols) {
+            out.push(SessionField::Size {
+                rows: newer.rows,
+                cols: newer.cols,
+            });
+        }
+        if self.attach_count != newer.attach_count {
+            out.push(SessionField::AttachCount(newer.attach_count));
+        }
+        if self.respawn_count != newer.respawn_count {
+            out.push(SessionField::RespawnCount(newer.respawn_count));
+        }
+        if self.limits != newer.limits {
+            out.push(SessionField::Limits(newer.limits.clone()));
+        }
+        if self.usage != newer.usage {
+            out.push(SessionField::Usage(newer.usage.clone()));
+        }
+        if self.cpu_recent != newer.cpu_recent {
+            out.push(SessionField::CpuRecent(newer.cpu_recent.clone()));
+        }
+        if self.paused != newer.paused {
+            out.push(SessionField::Paused(newer.paused));
+        }
+        if self.title != newer.title {
+            out.push(SessionField::Title(newer.title.clone()));
+        }
+        if self.notifications != newer.notifications {
+            out.push(SessionField::Notifications(newer.notifications.clone()));
+        }
+        if self.needs_attention != newer.needs_attention {
+            out.push(SessionField::NeedsAttention(newer.needs_attention));
+        }
+        if self.attention_count != newer.attention_count {
+            out.push(SessionField::AttentionCount(newer.attention_count));
+        }
+        out
+    }
+
+    pub fn apply(&mut self, field: SessionField) {
+        match field {
+            SessionField::Command(v) => self.command = v,
+            SessionField::Pid(v) => self.pid = v,
+            SessionField::Alive(v) => self.alive = v,
+            SessionField::CreatedAt(v) => self.created_at = v,
+            SessionField::UptimeSecs(v) => self.uptime_secs = v,
+            SessionField::LastActivity(v) => self.last_activity = v,
+            SessionField::IdleSecs(v) => self.idle_secs = v,
+            SessionField::ExitCode(v) => self.exit_code = v,
+            SessionField::OutputBytes(v) => self.output_bytes = v,
+            SessionField::Size { rows, cols } => {
+                self.rows = rows;
+                self.cols = cols;
+            }
+            SessionField::AttachCount(v) => self.attach_count = v,
+            SessionField::RespawnCount(v) => self.respawn_count = v,
+            SessionField::Limits(v) => self.limits = v,
+            SessionField::Usage(v) => self.usage = v,
+            SessionField::CpuRecent(v) => self.cpu_recent = v,
+            SessionField::Paused(v) => self.paused = v,
+            SessionField::Title(v) => self.title = v,
+            SessionField::Notifications(v) => self.notifications = v,
+            SessionField::NeedsAttention(v) => self.needs_attention = v,
+            SessionField::AttentionCount(v) => self.attention_count = v,
+        }
+    }
+}
+
+/// New preview lines for `name`, expressed against the lines the client
+/// last received for it: `old[drop..drop + keep]` followed by `append`.
+/// A preview of a different session always starts from nothing.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PreviewUpdate {
+    pub name: String,
+    pub drop: u32,
+    pub keep: u32,
+    pub append: Vec<String>,
+}
+
+impl PreviewUpdate {
+    /// The cheapest update turning `old` into `new`: the longest run of
+    /// `old` that reappears at the start of `new` is reused. Previews are
+    /// a screenful of lines, so the quadratic search is cheap.
+    pub fn between(name: &str, old: &[String], new: &[String]) -> Self {
+        let (mut drop, mut keep) = (0, 0);
+        for start in 0..old.len() {
+            let run = old[start..]
+                .iter()
+                .zip(new)
+                .take_while(|(a, b)| a == b)
+                .count();
+            if run > keep {
+                drop = start;
+                keep = run;
+            }
+        }
+        Self {
+            name: name.to_string(),
+            drop: drop as u32,
+            keep: keep as u32,
+            append: new[keep..].to_vec(),
+        }
+    }
+
+    /// Rebuild the preview from the client's previous `lines` for the same
+    /// session (empty if it had none).
+    pub fn apply(self, lines: &mut Vec<String>) {
+        let drop = (self.drop as usize).min(lines.len());
+        let keep = (self.keep as usize).min(lines.len() - drop);
+        lines.drain(..drop);
+        lines.truncate(keep);
+        lines.extend(self.append);
+    }
+}
+
+/// What changed in the session list since the previous update. The first
+/// update after subscribing lists every session under `added`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct SessionListDiff {
+    pub added: Vec<SessionInfo>,
+    pub removed: Vec<String>,
+    pub changed: Vec<(String, Vec<SessionField>)>,
+    pub preview: Option<PreviewUpdate>,
+}
+
+impl SessionListDiff {
+    /// Diff two snapshots of the list (in any order).
+    pub fn between(old: &[SessionInfo], new: &[SessionInfo]) -> Self {
+        let mut diff = Self::default();
+        for s in new {
+            match old.iter().find(|o| o.name == s.name) {
+                Some(o) => {
+                    let fields = o.changes(s);
+                    if !fields.is_empty() {
+                        diff.changed.push((s.name.clone(), fields));
+                    }
+                }
+                None => diff.added.push(s.clone()),
+            }
+        }
+        diff.removed = old
+            .iter()
+            .filter(|o| !new.iter().any(|s| s.name == o.name))
+            .map(|o| o.name.clone())
+            .collect();
+        diff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.changed.is_empty()
+            && self.preview.is_none()
+    }
+
+    /// Apply the list part of this diff to the client's copy. The preview
+    /// is left for the caller, which knows which session it shows.
+    pub fn apply_sessions(&mut self, sessions: &mut Vec<SessionInfo>) {
+        sessions.retain(|s| !self.removed.contains(&s.name));
+        for (name, fields) in self.changed.drain(..) {
+            if let Some(s) = sessions.iter_mut().find(|s| s.name == name) {
+                for field in fields {
+                    s.apply(field);
+                }
+            }
+        }
+        for added in self.added.drain(..) {
+            sessions.retain(|s| s.name != added.name);
+            sessions.push(added);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(name: &str) -> SessionInfo {
+        SessionInfo {
+            name: name.to_string(),
+            command: "bash".to_string(),
+            pid: 1,
+            alive: true,
+            created_at: "2026-01-01T00:00:00Z".to_string(),
+            uptime_secs: 0,
+            last_activity: "2026-01-01T00:00:00Z".to_string(),
+            idle_secs: 0,
+            exit_code: None,
+            output_bytes: 0,
+            rows: 24,
+            cols: 80,
+            attach_count: 0,
+            respawn_count: 0,
+            limits: None,
+            usage: None,
+            cpu_recent: Vec::new(),
+            paused: false,
+            title: String::new(),
+            notifications: Vec::new(),
+            needs_attention: false,
+            attention_count: 0,
+        }
+    }
+
+    fn lines(v: &[&str]) -> Vec<String> {
+        v.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_list_diff_round_trips() {
+        let old = vec![info("a"), info("b")];
+        let mut a = info("a");
+        a.uptime_secs = 5;
+        a.alive = false;
+        a.exit_code = Some(1);
+        a.title = "done".to_string();
+        let new = vec![a.clone(), info("c")];
+
+        let mut diff = SessionListDiff::between(&old, &new);
+        assert_eq!(diff.removed, ["b"]);
+        assert_eq!(diff.added.len(), 1);
+        assert_eq!(diff.changed.len(), 1);
+        assert_eq!(diff.changed[0].1.len(), 4, "only changed fields are sent");
+
+        let mut client = old.clone();
+        diff.apply_sessions(&mut client);
+        let names: Vec<_> = client.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["a", "c"]);
+        assert!(client[0].changes(&a).is_empty());
+
+        assert!(SessionListDiff::between(&new, &new).is_empty());
+    }
+
+    #[test]
+    fn test_preview_update_reuses_scrolled_lines() {
+        let old = lines(&["1", "2", "3", "4"]);
+        // Output scrolled two lines.
+        let new = lines(&["3", "4", "5", "6"]);
+        let update = PreviewUpdate::between("a", &old, &new);
+        assert_eq!((update.drop, update.keep), (2, 2));
+        assert_eq!(update.append, lines(&["5", "6"]));
+        let mut client = old.clone();
+        update.apply(&mut client);
+        assert_eq!(client, new);
+
+        // The last line was redrawn in place.
+        let new = lines(&["1", "2", "3", "4!"]);
+        let update = PreviewUpdate::between("a", &old, &new);
+        let mut client = old.clone();
+        update.clone().apply(&mut client);
+        assert_eq!(client, new);
+        assert_eq!((update.drop, update.keep), (0, 3));
+        assert_eq!(update.append, lines(&["4!"]));
+
+        // Unrelated content, and a client with nothing yet.
+        let new = lines(&["x"]);
+        let mut client = Vec::new();
+        PreviewUpdate::between("a", &[], &new).apply(&mut client);
+        assert_eq!(client, new);
+    }
+}
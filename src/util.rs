@@ -247,7 +247,7 @@ pub(crate) fn remove_git_worktree(worktree_path: &str) -> anyhow::Result<()> {
 /// Ensure the daemon is running, starting it if needed.
 pub(crate) fn ensure_daemon_running() -> anyhow::Result<()> {
     if !crate::common::server_running() {
-        crate::daemon::fork_daemon()?;
+        crate::daemon::fork_daemon(None)?;
         std::thread::sleep(std::time::Duration::from_millis(200));
         if !crate::common::server_running() {
             anyhow::bail!("failed to start daemon");
@@ -357,9 +357,58 @@ pub(crate) fn truncate_preserving_ansi(s: &str, max_width: usize) -> String {
     }
 }
 
+/// Human-readable byte count using binary units (`512.0M`, `4.0G`).
+pub(crate) fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["K", "M", "G", "T"];
+    if bytes < 1024 {
+        return format!("{}B", bytes);
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1}{}", value, UNITS[unit])
+}
+
+/// Sparkline characters ordered by intensity (lowest to highest).
+pub(crate) const SPARKLINE_CHARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Sparkline of CPU% values. Scaled to at least one full core so an idle
+/// session doesn't render as pegged just because its tiny peak is the max.
+pub(crate) fn cpu_sparkline(values: &[f32]) -> String {
+    let max = values.iter().copied().fold(100.0f32, f32::max);
+    values
+        .iter()
+        .map(|&v| {
+            let idx = ((v.max(0.0) / max) * 7.0).round() as usize;
+            SPARKLINE_CHARS[idx.min(7)]
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{strip_ansi, clean_control_chars, truncate, truncate_preserving_ansi};
+    use super::{
+        clean_control_chars, cpu_sparkline, format_size, strip_ansi, truncate,
+        truncate_preserving_ansi,
+    };
+
+    #[test]
+    fn test_cpu_sparkline_scales_to_a_core() {
+        assert_eq!(cpu_sparkline(&[0.0, 1.0, 50.0, 100.0]), "▁▁▅█");
+        // Multi-core usage rescales to the peak.
+        assert_eq!(cpu_sparkline(&[100.0, 200.0]), "▅█");
+        assert_eq!(cpu_sparkline(&[]), "");
+    }
+
+    #[test]
+    fn test_format_size_units() {
+        assert_eq!(format_size(0), "0B");
+        assert_eq!(format_size(1536), "1.5K");
+        assert_eq!(format_size(5 << 40), "5.0T");
+    }
 
     #[test]
     fn test_strip_ansi_plain_text() {